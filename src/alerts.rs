@@ -0,0 +1,300 @@
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+
+use crate::user_config::NotificationConfig;
+
+/// Events that can trigger an alert, used as keys in the notifications config.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertEvent {
+    /// Any chat message from someone else. This only rings bells, sounds
+    /// and hooks would go off far too often.
+    Message,
+    Mention,
+    DirectMessage,
+    Disconnect,
+    Keyword,
+}
+
+/// How the terminal gets attention for an alert.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Bell {
+    /// The terminal bell, BEL.
+    Audible,
+    /// A flashing indicator in the status bar.
+    Visual,
+    Both,
+}
+
+/// An external action run whenever `event` happens. The command is run with
+/// `sh -c` and gets the alert details in `MARAIN_EVENT`, `MARAIN_SENDER` and
+/// `MARAIN_MESSAGE`, the webhook receives them as a JSON POST body.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Hook {
+    pub event: AlertEvent,
+    pub command: Option<String>,
+    pub webhook: Option<String>,
+}
+
+/// How a message is matched as mentioning the user.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct MentionConfig {
+    /// Only match the username as a whole word, so `tom` doesn't match `tomato`.
+    pub whole_word: bool,
+    pub case_sensitive: bool,
+    /// Other words treated like the username, e.g. nicknames.
+    pub keywords: Vec<String>,
+}
+
+impl Default for MentionConfig {
+    fn default() -> Self {
+        Self {
+            whole_word: true,
+            case_sensitive: false,
+            keywords: vec![],
+        }
+    }
+}
+
+impl MentionConfig {
+    /// Checks `content` for the username or any of the extra keywords.
+    pub fn matches(&self, username: &str, content: &str) -> bool {
+        std::iter::once(username)
+            .chain(self.keywords.iter().map(String::as_str))
+            .any(|word| self.contains(content, word))
+    }
+
+    fn contains(&self, content: &str, word: &str) -> bool {
+        if word.is_empty() {
+            return false;
+        }
+        let (content, word) = match self.case_sensitive {
+            true => (content.to_string(), word.to_string()),
+            false => (content.to_lowercase(), word.to_lowercase()),
+        };
+        if !self.whole_word {
+            return content.contains(&word);
+        }
+        let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+        content.match_indices(&word).any(|(i, _)| {
+            let before = content[..i].chars().next_back();
+            let after = content[i + word.len()..].chars().next();
+            !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char)
+        })
+    }
+}
+
+/// Something a notification rule lets a message do.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleAction {
+    /// Ring the configured bells and play sounds and hooks.
+    Bell,
+    /// Show a desktop notification.
+    Desktop,
+    /// Count the message as unread.
+    Badge,
+    /// None of the above, the message only shows in the log.
+    Silent,
+}
+
+/// Decides how a chat message gets attention, e.g.
+/// `{"sender": "ci-bot", "actions": ["badge"]}`. Every condition given has
+/// to hold.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct NotificationRule {
+    pub room: Option<String>,
+    pub sender: Option<String>,
+    /// Found anywhere in the message, ignoring case.
+    pub keyword: Option<String>,
+    /// Only while the terminal has focus, or only while it hasn't.
+    pub focused: Option<bool>,
+    pub actions: Vec<RuleAction>,
+}
+
+impl NotificationRule {
+    fn matches(&self, room: &str, sender: &str, message: &str, focused: bool) -> bool {
+        self.room.as_ref().is_none_or(|r| r == room)
+            && self.sender.as_ref().is_none_or(|s| s == sender)
+            && self.focused.is_none_or(|f| f == focused)
+            && self
+                .keyword
+                .as_ref()
+                .is_none_or(|k| message.to_lowercase().contains(&k.to_lowercase()))
+    }
+}
+
+/// The ways a chat message may get attention.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Attention {
+    pub bell: bool,
+    pub desktop: bool,
+    pub badge: bool,
+}
+
+impl Attention {
+    /// Decided by the first rule matching the message, without one the bells
+    /// ring as configured and the rest only happens in the background.
+    pub fn of(
+        rules: &[NotificationRule],
+        room: &str,
+        sender: &str,
+        message: &str,
+        focused: bool,
+    ) -> Self {
+        match rules
+            .iter()
+            .find(|r| r.matches(room, sender, message, focused))
+        {
+            Some(rule) => Self {
+                bell: rule.actions.contains(&RuleAction::Bell),
+                desktop: rule.actions.contains(&RuleAction::Desktop),
+                badge: rule.actions.contains(&RuleAction::Badge),
+            },
+            None => Self {
+                bell: true,
+                desktop: !focused,
+                badge: !focused,
+            },
+        }
+    }
+}
+
+/// Desktop notifications for chat messages received while the terminal
+/// doesn't have focus.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct DesktopConfig {
+    pub enabled: bool,
+    /// Rooms that never raise a desktop notification.
+    pub muted_rooms: Vec<String>,
+    /// Messages longer than this many characters are cut short.
+    pub max_len: usize,
+}
+
+impl Default for DesktopConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            muted_rooms: vec![],
+            max_len: 120,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Alert {
+    pub event: AlertEvent,
+    pub sender: String,
+    pub message: String,
+}
+
+impl Alert {
+    pub fn new(event: AlertEvent, sender: &str, message: &str) -> Self {
+        Self {
+            event,
+            sender: sender.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+/// Plays the configured sound and runs the configured hooks for an alert.
+pub fn raise(conf: &NotificationConfig, alert: Alert) {
+    play_sound(conf, alert.event);
+    for hook in conf.hooks.iter().filter(|h| h.event == alert.event) {
+        run_hook(hook.clone(), alert.clone());
+    }
+}
+
+/// Shows a desktop notification for a message in `room`, unless they are
+/// disabled or the room is muted. Sending blocks on the notification daemon,
+/// so it's done off the async runtime.
+pub fn notify_desktop(conf: &DesktopConfig, room: &str, sender: &str, message: &str) {
+    if !conf.enabled || conf.muted_rooms.iter().any(|r| r == room) {
+        return;
+    }
+    let mut body: String = message.chars().take(conf.max_len).collect();
+    if body.len() < message.len() {
+        body.push('…');
+    }
+    let summary = format!("{sender} in {room}");
+
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = notify_rust::Notification::new()
+            .appname("marain")
+            .summary(&summary)
+            .body(&body)
+            .show()
+        {
+            tracing::error!("Failed to show desktop notification: {e}");
+        }
+    });
+}
+
+/// Plays the sound configured for `event` by spawning the configured sound
+/// command in the background. Does nothing if either is not configured.
+pub fn play_sound(conf: &NotificationConfig, event: AlertEvent) {
+    let (Some(cmd), Some(sound)) = (conf.sound_command.as_ref(), conf.sounds.get(&event)) else {
+        return;
+    };
+    let cmd = cmd.replace("{sound}", sound);
+
+    tokio::spawn(async move {
+        match Command::new("sh").arg("-c").arg(&cmd).output().await {
+            Ok(out) if !out.status.success() => {
+                tracing::error!("Sound command `{cmd}` exited with {}", out.status);
+            }
+            Err(e) => tracing::error!("Failed to run sound command `{cmd}`: {e}"),
+            _ => {}
+        }
+    });
+}
+
+fn run_hook(hook: Hook, alert: Alert) {
+    tokio::spawn(async move {
+        if let Some(ref cmd) = hook.command {
+            let result = Command::new("sh")
+                .arg("-c")
+                .arg(cmd)
+                .env("MARAIN_EVENT", format!("{:?}", alert.event))
+                .env("MARAIN_SENDER", &alert.sender)
+                .env("MARAIN_MESSAGE", &alert.message)
+                .output()
+                .await;
+            match result {
+                Ok(out) if !out.status.success() => {
+                    tracing::error!("Hook command `{cmd}` exited with {}", out.status);
+                }
+                Err(e) => tracing::error!("Failed to run hook command `{cmd}`: {e}"),
+                _ => {}
+            }
+        }
+
+        if let Some(ref url) = hook.webhook {
+            let body = match serde_json::to_string(&alert) {
+                Ok(body) => body,
+                Err(e) => {
+                    tracing::error!("Could not serialize alert for webhook: {e}");
+                    return;
+                }
+            };
+            let result = reqwest::Client::new()
+                .post(url)
+                .header("Content-Type", "application/json")
+                .body(body)
+                .send()
+                .await;
+            match result {
+                Ok(resp) if !resp.status().is_success() => {
+                    tracing::error!("Webhook {url} returned {}", resp.status());
+                }
+                Err(e) => tracing::error!("Failed to call webhook {url}: {e}"),
+                _ => {}
+            }
+        }
+    });
+}
@@ -1,30 +1,93 @@
 use chrono::prelude::*;
-use crossterm::event::KeyCode;
-use log2 as log;
-use marain_api::prelude::ClientMsgBody;
+use crossterm::event::{KeyCode, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+use marain_api::prelude::{ClientMsg, ClientMsgBody, Timestamp};
 use ratatui::{
-    style::{Color, Modifier, Style},
+    layout::{Margin, Rect},
+    style::{Modifier, Style},
     text::{Line, Span, Text},
 };
+use serde::{Deserialize, Serialize};
 use std::{
-    collections::{HashMap, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
     fmt::{Debug, Display},
+    ops::Range,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
-use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::mpsc::Sender;
+use unicode_segmentation::UnicodeSegmentation;
 
 use crate::{
-    chat_log::{Log, LogStyle},
-    default_keybinds,
+    autosave::{Autosave, DraftStore},
+    capabilities::{self, Glyphs},
+    chat_log::{Delivery, Log, LogKind, LogStyle, MESSAGE_SPAN},
+    completion::{self, Completion},
+    copy_mode::{self, CopySelection},
+    default_keybinds::{self, KeyPreset},
+    directory::{Directory, DirectoryEntry},
+    e2e::RoomKeys,
+    edits::{self, Change},
+    emoji, ephemeral,
+    event_log::{kind_of, EventKind, EventLog},
+    file_transfer::{self, Chunk, Download, Upload},
+    fragments::{self, Reassembly},
+    graphemes,
+    graphics::{self, Protocol, Thumbnails, THUMBNAIL_ROWS},
+    highlights::Highlights,
+    history::HistoryStore,
+    html_export,
+    i18n::{tr, trf},
+    ime,
+    input_history::InputHistory,
+    keymap_file::{
+        chord_name, key_name, parse_chord, read_keymap, write_cheat_sheet, write_keymap,
+        KeyBindEntry, KeymapFile,
+    },
+    latency::Latency,
+    links::{self, Hyperlink, MAX_PICKER_LINKS},
+    modal::{Choice, Modal},
+    param_history::ParamHistory,
+    paste,
+    perf::Perf,
+    plugins::{Action, Plugins},
+    protocol, qr,
+    search::Search,
+    shell,
+    signing::{Identity, KnownServers, KnownSigners, ServerKeyCheck},
+    slash_commands,
+    spelling::{self, Dictionary},
+    stats::{self, RoomCounts},
+    theme::{AccessibilityConfig, Theme},
+    toasts::{Severity, Toasts},
+    traffic::Traffic,
+    transcripts::Transcripts,
+    transforms::TransformChain,
     tui_framework::Event,
-    user_config::UserConfig,
+    ui::{self, Panes},
+    user_config::{self, Density, RetentionConfig, UserConfig},
+    welcome::Welcome,
 };
 
-#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Mode {
     Navigate,
     Insert,
     InsertCommand,
     Disconnected,
+    /// Picking a room from the room list.
+    RoomList,
+    /// Typing a query to search the chat log with.
+    Search,
+    /// Selecting chat log lines to copy.
+    Copy,
+}
+
+impl Mode {
+    /// Modes where the buffer is a message being written, rather than a
+    /// command parameter or search query.
+    pub fn holds_draft(&self) -> bool {
+        !matches!(self, Mode::InsertCommand | Mode::Search)
+    }
 }
 
 impl Display for Mode {
@@ -33,49 +96,265 @@ impl Display for Mode {
     }
 }
 
-#[derive(Hash, Debug, Clone)]
+#[derive(Hash, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum CaretMotion {
     Character,
     Line,
+    Word,
+    /// To the start (negative) or end of the line.
+    LineBoundary,
+    /// To the start (negative) or end of the whole buffer.
+    BufferBoundary,
 }
 
-#[derive(Debug, Clone, Hash)]
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Command {
     Reset,
+    /// Quit, asking first if there's anything unsent.
     Quit,
+    QuitNow,
     Capture(char),
     Del(isize),
     MoveCaret(CaretMotion, isize),
     Enter(Mode),
     SendBuffer,
     GetServerTime,
+    /// Ask the server for the current room's occupants again.
+    RefreshRoom,
     MoveRooms(Option<String>),
+    CreateRoom(Option<String>),
     SendStagedCommand,
     AbortStagedCommand,
     ToggleDebug,
+    ExportKeymap(Option<String>),
+    ImportKeymap(Option<String>),
+    /// Write every mode's bindings to a file to print, Markdown if it ends
+    /// in `.md`.
+    ExportKeys(String),
+    /// Write the current room's logs to a file, Markdown if it ends in `.md`.
+    ExportLogs(Option<String>),
+    /// Write the current room's logs to a styled HTML page.
+    ExportHtml(Option<String>),
+    Upload(Option<String>),
+    CancelUpload,
+    /// Save or throw away the oldest finished download.
+    SaveDownload,
+    DiscardDownload,
+    Run(String),
+    SetBio(String),
+    Whois(String),
+    Ignore(String),
+    Unignore(String),
+    ShowQr(QrKind),
+    /// Puts the `marain://` link to the current room on the clipboard.
+    CopyJoinLink,
+    /// Offers fixes for the misspelled word before the caret, cycled
+    /// through like completions.
+    SuggestSpelling,
+    PasteImage,
+    SendEphemeral(String),
+    ShowStats,
+    /// Full-screen list of every binding and slash command.
+    ShowHelp,
+    /// Re-read the config file and apply it to the running session.
+    ReloadConfig,
+    /// Send a direct message to a user.
+    SendDirect(String, String),
+    ToggleDms,
+    /// Pick a recent link to open in the browser.
+    OpenLink,
+    /// Pick one of my recent messages to edit or delete.
+    SelectOwnMessage,
+    /// Pick one of my messages that failed to send, to retry or discard.
+    SelectFailed,
+    /// Pick a message in the chat log to show all there is to know about.
+    SelectLog,
+    /// Pin the message at the bottom of the view, or under the copy
+    /// cursor, or unpin it if it already is.
+    PinMessage,
+    TogglePins,
+    /// Show or hide message rates, queue depths and connection trouble.
+    ToggleTraffic,
+    /// Open the room directory.
+    RoomDirectory,
+    /// Scroll the chat log by a number of messages, positive is back in time.
+    Scroll(isize),
+    ScrollTop,
+    ScrollBottom,
+    HistoryPrev,
+    HistoryNext,
+    /// Delete to the start (negative) or end of a word.
+    DelWord(isize),
+    /// Move the room list selection.
+    SelectRoom(isize),
+    JoinSelectedRoom,
+    SubmitSearch,
+    ClearSearch,
+    /// Pin the server key presented at login in place of the old one.
+    TrustServerKey,
+    ShowServerKeys,
+    /// Show the host, protocol, server key, session start and latency.
+    ShowServerInfo,
+    /// Ask for a passphrase to encrypt the current room's messages with.
+    SetRoomKey,
+    ForgetRoomKey,
+    /// Forget the current room's messages, asking first.
+    ClearHistory,
+    ClearHistoryNow,
+    /// Complete the command or room name being typed, again to cycle.
+    Complete,
+    /// Jump to the next older search match.
+    SearchNext,
+    SearchPrev,
+    /// Log in again after losing the connection.
+    Reconnect,
+    /// Delete the line under the caret.
+    DeleteLine,
+    /// Move the copy mode cursor, positive is back in time.
+    CopyMove(isize),
+    /// Start or drop the selection at the copy mode cursor.
+    CopyMark,
+    /// Copy the selected lines to the clipboard and the paste buffer.
+    CopyYank,
+    /// Insert the paste buffer at the caret.
+    PasteYanked,
+    /// Cut to the start (negative) or end of the line or word onto the kill
+    /// ring.
+    Kill(CaretMotion, isize),
+    /// Insert the latest kill at the caret.
+    Yank,
+    /// Scroll the debug pane, positive is back in time.
+    ScrollDebug(isize),
+    /// Show or hide notifications and the client's own messages.
+    ToggleSystem,
+    /// Switch message times between when and how long ago.
+    ToggleRelativeTime,
+    /// Switch the chat log between compact and comfortable, and save it.
+    ToggleDensity,
+    /// Bring the tab at this index into view.
+    SwitchTab(usize),
+    /// Move along the tabs, positive is to the right.
+    CycleTab(isize),
+    /// Close the tab in view, unless it's the last one.
+    CloseTab,
+    /// Put the buffer back as it was before the last paste, cut or line
+    /// deletion.
+    Undo,
+    /// Go back to the room last left.
+    PreviousRoom,
+    /// Pick one of the rooms recently left, most recent first.
+    RecentRooms,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QrKind {
+    /// The signing identity, for verifying it in person.
+    Fingerprint,
+    /// A link to join the current room.
+    Invite,
 }
 
 impl Display for Command {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         use Command::*;
-        let s = match self {
-            Reset => "Reset",
-            Quit => "Quit",
-            Capture(_) => "",
-            MoveCaret(_, _) => "Move Cursor",
-            Del(_) => "Delete",
-            Enter(Mode::Navigate) => "Enter Navigation Mode",
-            Enter(Mode::Insert) => "Enter Insert Mode",
-            Enter(Mode::InsertCommand) => "Enter command params mode",
-            Enter(Mode::Disconnected) => "Disconnect from server",
-            SendBuffer => "Send Message",
-            GetServerTime => "Get Server Time",
-            MoveRooms(..) => "Move rooms",
-            SendStagedCommand => "Send Staged Command",
-            AbortStagedCommand => "Abort Command Staging",
-            ToggleDebug => "Toggle debug output",
-        };
-        write!(f, "{s}")
+        let key = match self {
+            Reset => "cmd.reset",
+            Quit => "cmd.quit",
+            QuitNow => "cmd.quit_now",
+            Capture(_) => return write!(f, ""),
+            MoveCaret(_, _) => "cmd.move_caret",
+            Del(_) => "cmd.delete",
+            Enter(Mode::Navigate) => "cmd.enter_navigate",
+            Enter(Mode::Insert) => "cmd.enter_insert",
+            Enter(Mode::InsertCommand) => "cmd.enter_insert_command",
+            Enter(Mode::Disconnected) => "cmd.enter_disconnected",
+            Enter(Mode::RoomList) => "cmd.enter_room_list",
+            Enter(Mode::Search) => "cmd.enter_search",
+            SendBuffer => "cmd.send_buffer",
+            GetServerTime => "cmd.get_server_time",
+            RefreshRoom => "cmd.refresh_room",
+            MoveRooms(..) => "cmd.move_rooms",
+            CreateRoom(..) => "cmd.create_room",
+            SendStagedCommand => "cmd.send_staged",
+            AbortStagedCommand => "cmd.abort_staged",
+            ToggleDebug => "cmd.toggle_debug",
+            SendDirect(..) => "cmd.send_direct",
+            ToggleDms => "cmd.toggle_dms",
+            OpenLink => "cmd.open_link",
+            SelectOwnMessage => "cmd.select_own",
+            PinMessage => "cmd.pin_message",
+            TogglePins => "cmd.toggle_pins",
+            ToggleTraffic => "cmd.toggle_traffic",
+            SelectFailed => "cmd.select_failed",
+            SelectLog => "cmd.select_log",
+            ExportKeymap(..) => "cmd.export_keymap",
+            ImportKeymap(..) => "cmd.import_keymap",
+            ExportKeys(..) => "cmd.export_keys",
+            ExportLogs(..) => "cmd.export_logs",
+            ExportHtml(..) => "cmd.export_html",
+            Upload(..) => "cmd.upload",
+            CancelUpload => "cmd.cancel_upload",
+            SaveDownload => "cmd.save_download",
+            DiscardDownload => "cmd.discard_download",
+            Run(..) => "cmd.run",
+            SetBio(..) => "cmd.bio",
+            Whois(..) => "cmd.whois",
+            Ignore(..) => "cmd.ignore",
+            Unignore(..) => "cmd.unignore",
+            ShowQr(_) => "cmd.qr",
+            CopyJoinLink => "cmd.copy_join_link",
+            SuggestSpelling => "cmd.suggest_spelling",
+            PasteImage => "cmd.paste_image",
+            SendEphemeral(..) => "cmd.send_ephemeral",
+            ShowStats => "cmd.stats",
+            ShowHelp => "cmd.help",
+            ReloadConfig => "cmd.reload",
+            RoomDirectory => "cmd.room_directory",
+            Scroll(n) if *n > 0 => "cmd.scroll_up",
+            Scroll(_) => "cmd.scroll_down",
+            ScrollTop => "cmd.scroll_top",
+            ScrollBottom => "cmd.scroll_bottom",
+            HistoryPrev => "cmd.history_prev",
+            HistoryNext => "cmd.history_next",
+            DelWord(_) => "cmd.delete_word",
+            SelectRoom(_) => "cmd.select_room",
+            JoinSelectedRoom => "cmd.join_room",
+            SubmitSearch => "cmd.submit_search",
+            TrustServerKey => "cmd.trust_server_key",
+            ShowServerKeys => "cmd.server_keys",
+            ShowServerInfo => "cmd.server_info",
+            SetRoomKey => "cmd.set_room_key",
+            ForgetRoomKey => "cmd.forget_room_key",
+            ClearHistory => "cmd.clear_history",
+            ClearHistoryNow => "cmd.clear_history_now",
+            Complete => "cmd.complete",
+            ClearSearch => "cmd.clear_search",
+            SearchNext => "cmd.search_next",
+            SearchPrev => "cmd.search_prev",
+            Reconnect => "cmd.reconnect",
+            DeleteLine => "cmd.delete_line",
+            Enter(Mode::Copy) => "cmd.enter_copy",
+            CopyMove(n) if *n > 0 => "cmd.copy_up",
+            CopyMove(_) => "cmd.copy_down",
+            CopyMark => "cmd.copy_mark",
+            CopyYank => "cmd.copy_yank",
+            PasteYanked => "cmd.paste_yanked",
+            Kill(..) => "cmd.kill",
+            Yank => "cmd.yank",
+            ScrollDebug(n) if *n > 0 => "cmd.scroll_debug_up",
+            ScrollDebug(_) => "cmd.scroll_debug_down",
+            ToggleSystem => "cmd.toggle_system",
+            ToggleRelativeTime => "cmd.toggle_relative_time",
+            ToggleDensity => "cmd.toggle_density",
+            SwitchTab(_) => "cmd.switch_tab",
+            CycleTab(n) if *n > 0 => "cmd.next_tab",
+            CycleTab(_) => "cmd.prev_tab",
+            CloseTab => "cmd.close_tab",
+            Undo => "cmd.undo",
+            PreviousRoom => "cmd.previous_room",
+            RecentRooms => "cmd.recent_rooms",
+        };
+        write!(f, "{}", tr(key))
     }
 }
 
@@ -83,11 +362,278 @@ impl Command {
     fn parse_params(&self, params: String) -> Option<Self> {
         match self {
             Command::MoveRooms(None) => Some(Command::MoveRooms(Some(params))),
+            Command::CreateRoom(None) => Some(Command::CreateRoom(Some(params))),
+            Command::ExportKeymap(None) => Some(Command::ExportKeymap(Some(params))),
+            Command::ImportKeymap(None) => Some(Command::ImportKeymap(Some(params))),
+            Command::ExportLogs(None) => Some(Command::ExportLogs(Some(params))),
+            Command::ExportHtml(None) => Some(Command::ExportHtml(Some(params))),
+            Command::Upload(None) => Some(Command::Upload(Some(params))),
             _ => None,
         }
     }
 }
 
+/// The server has no profile fields, so status messages are sent to the room
+/// as chat messages starting with this marker.
+const BIO_PREFIX: &str = "[status] ";
+
+/// Rows rendered past the top of the log pane, for lines that wrap.
+const LOG_MARGIN: usize = 2;
+
+/// Seconds a sent message waits for the server to echo it back before it's
+/// shown as not delivered.
+const DELIVERY_TIMEOUT_SECS: i64 = 10;
+
+/// Number of raw messages kept for the debug pane.
+const MAX_DEBUG_LOGS: usize = 200;
+
+/// Longest time between redraws while nothing changes, keeps the clock and
+/// other time based parts of the UI current.
+const IDLE_REDRAW: Duration = Duration::from_secs(1);
+
+/// Splits a rendered log into a heading of sender, markers and time and an
+/// indented body of the message and what follows it, for the comfortable
+/// density.
+fn split_heading(line: Line, ephemeral: bool) -> (Line, Line) {
+    // the countdown of an ephemeral log sits in the header
+    let header_len = MESSAGE_SPAN + ephemeral as usize;
+    let mut spans = line.spans;
+    if spans.len() < header_len {
+        return (Line::raw(""), Line::from(spans));
+    }
+    let body = spans.split_off(header_len);
+    let time = spans[1].clone();
+    let mut heading: Vec<Span> = spans.drain(3..header_len - 1).collect();
+    heading.extend([Span::raw(" "), time]);
+    let mut indented = vec![Span::raw("  ")];
+    indented.extend(body);
+
+    (Line::from(heading), Line::from(indented))
+}
+
+/// Kills kept on the kill ring.
+const MAX_KILLS: usize = 20;
+
+/// Rooms kept in the recent rooms list, one per digit to pick them with.
+const MAX_RECENT_ROOMS: usize = 9;
+
+/// Buffer states kept for undoing.
+const MAX_UNDO: usize = 50;
+
+/// Dates on the separators between days in the chat log.
+const DATE_FORMAT: &str = "%Y-%m-%d";
+/// How long the visual bell flashes for.
+const FLASH_DURATION: Duration = Duration::from_secs(2);
+/// Time the indicator spends on, then off, while flashing.
+const FLASH_PHASE: Duration = Duration::from_millis(250);
+
+/// Modes in the order the help overlay lists them.
+const HELP_MODES: [Mode; 7] = [
+    Mode::Navigate,
+    Mode::Insert,
+    Mode::InsertCommand,
+    Mode::Search,
+    Mode::Copy,
+    Mode::RoomList,
+    Mode::Disconnected,
+];
+/// Columns the key names and slash command usages are padded to.
+const HELP_KEY_WIDTH: usize = 14;
+const HELP_USAGE_WIDTH: usize = 28;
+/// Rows a page up or down scrolls the help overlay by.
+const HELP_PAGE: usize = 10;
+
+/// Share of the size limit from which the message counter warns.
+const SIZE_WARNING: f64 = 0.9;
+
+/// Messages scrolled per mouse wheel notch.
+const WHEEL_LINES: isize = 3;
+
+/// Names an image under its log entry, the thumbnail goes beneath it.
+pub fn image_placeholder(path: &Path) -> String {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    trf!("log.image", name)
+}
+
+/// The theme as configured, with the log's time and formatting settings,
+/// and the highlight rules that couldn't be used.
+fn theme_for(config: &UserConfig) -> (Theme, Vec<String>) {
+    let accessibility = AccessibilityConfig {
+        high_contrast: config.accessibility.high_contrast
+            || config.accessible
+            || crate::cli::args().accessible,
+        ascii_borders: config.accessibility.ascii_borders
+            || capabilities::get().glyphs == Glyphs::Ascii,
+        ..config.accessibility
+    };
+    let mut theme = Theme::from_config(&config.theme, &accessibility);
+    let (highlights, problems) = Highlights::from_config(&config.highlights);
+    theme.log = theme
+        .log
+        .with_time(&config.time_format, config.timezone)
+        .with_markdown(config.format_messages)
+        .with_relative(config.relative_times)
+        .with_highlights(highlights);
+
+    (theme, problems)
+}
+
+/// The bindings of the preset chosen on the command line or in the config
+/// with the user's own bindings on top, and the ones that couldn't be
+/// understood.
+pub fn keymaps_for(config: &UserConfig) -> (ModalKeyMaps, Vec<String>) {
+    // nothing that writes or sends, whatever the config binds
+    if crate::cli::args().monitor.is_some() {
+        return (ModalKeyMaps::monitor(), vec![]);
+    }
+    let mut keymaps = ModalKeyMaps::preset(crate::cli::args().keys.unwrap_or(config.key_preset));
+    let mut problems = keymaps.overlay(&favorite_keys(&config.favorite_rooms));
+    problems.extend(keymaps.overlay(&config.keybindings));
+    (keymaps, problems)
+}
+
+/// Navigate mode bindings of the number keys to the favorite rooms.
+fn favorite_keys(favorites: &[String]) -> KeymapFile {
+    let entries = favorites
+        .iter()
+        .take(9)
+        .enumerate()
+        .map(|(i, room)| KeyBindEntry {
+            key: (i + 1).to_string(),
+            command: Command::MoveRooms(Some(room.clone())),
+        })
+        .collect();
+    KeymapFile::from([(Mode::Navigate, entries)])
+}
+
+/// The newest `max` of `logs` after skipping `skip`, oldest at the top.
+fn render_newest<'a>(
+    logs: &'a VecDeque<Log>,
+    skip: usize,
+    max: usize,
+    log_style: &LogStyle,
+) -> Text<'a> {
+    let mut lines: Vec<Line> = logs
+        .iter()
+        .skip(skip)
+        .take(max)
+        .map(|l| l.render(log_style))
+        .collect();
+    lines.reverse();
+
+    lines.into()
+}
+
+/// Text shown over the rest of the UI until the next key press.
+#[derive(Debug, Clone)]
+pub struct Popup {
+    pub title: String,
+    pub body: String,
+}
+
+/// What to do with the text typed into a prompt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PromptAction {
+    UnlockHistory,
+    /// The passphrase to encrypt the named room with.
+    RoomKey(String),
+}
+
+/// A line of text asked for over the rest of the UI, nothing else can be
+/// done until it's given or skipped with Esc.
+#[derive(Debug, Clone)]
+pub struct Prompt {
+    pub title: String,
+    pub body: String,
+    pub input: String,
+    /// Show the input as dots, for passphrases.
+    pub masked: bool,
+    /// Why the last input wasn't accepted.
+    pub error: Option<String>,
+    pub action: PromptAction,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Presence {
+    Online,
+    Away(String),
+}
+
+/// The server has no presence updates, so they are sent to the room as chat
+/// messages starting with these markers.
+const AWAY_MARKER: &str = "[away]";
+const BACK_MARKER: &str = "[back]";
+
+impl Presence {
+    fn marker(&self) -> String {
+        match self {
+            Presence::Online => BACK_MARKER.to_string(),
+            Presence::Away(msg) => format!("{AWAY_MARKER} {msg}"),
+        }
+    }
+
+    fn parse(content: &str) -> Option<Self> {
+        if content.trim() == BACK_MARKER {
+            return Some(Presence::Online);
+        }
+        let msg = content.strip_prefix(AWAY_MARKER)?;
+        Some(Presence::Away(msg.trim().to_string()))
+    }
+
+    /// Whether a message is a presence update rather than chat.
+    pub fn is_update(content: &str) -> bool {
+        Self::parse(content).is_some()
+    }
+}
+
+impl Display for Presence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Presence::Online => write!(f, "{}", tr("presence.online")),
+            Presence::Away(msg) => write!(f, "{}", trf!("presence.away", msg)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    Connecting,
+    Connected,
+    /// The server closed the connection.
+    Closed,
+    /// The connection died without the server closing it.
+    Lost,
+}
+
+impl Display for ConnectionState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let key = match self {
+            ConnectionState::Connecting => "conn.connecting",
+            ConnectionState::Connected => "conn.connected",
+            ConnectionState::Closed => "conn.closed",
+            ConnectionState::Lost => "conn.lost",
+        };
+        write!(f, "{}", tr(key))
+    }
+}
+
+/// A room seen this session, for the room list.
+#[derive(Debug, Clone)]
+pub struct RoomSummary {
+    pub name: String,
+    pub occupants: usize,
+    /// Messages that arrived in the room since it was last read.
+    pub unread: usize,
+}
+
+/// A room open in a tab, with what it showed when it was last in view.
+#[derive(Debug, Default)]
+pub struct Tab {
+    pub name: String,
+    logs: VecDeque<Log>,
+    occupants: Vec<String>,
+}
+
 #[derive(Debug)]
 pub struct RoomData {
     pub timestamp: DateTime<Utc>,
@@ -100,7 +646,7 @@ impl Default for RoomData {
         Self {
             timestamp: Utc::now(),
             occupants: vec![],
-            room_name: "None".into(),
+            room_name: tr("room.none"),
         }
     }
 }
@@ -108,7 +654,27 @@ impl Default for RoomData {
 #[derive(Debug)]
 pub struct App {
     pub should_quit: bool,
+    /// Something changed since the last draw.
+    pub dirty: bool,
+    drawn_at: Instant,
+    /// Whether the direct message pane is shown.
+    pub show_dms: bool,
+    /// Messages pinned in the current room, oldest pinned first.
+    pub pins: Vec<Log>,
+    pub show_pins: bool,
+    /// Direct messages sent and received, newest first.
+    pub dm_logs: VecDeque<Log>,
+    /// Whether the debug pane is shown.
     pub show_debug: bool,
+    /// Whether notifications and the client's own messages are shown in the
+    /// chat log.
+    pub show_system: bool,
+    /// Raw server messages for the debug pane, newest first.
+    pub debug_logs: VecDeque<Log>,
+    /// Number of entries the debug pane is scrolled back from the newest.
+    pub debug_scroll: usize,
+    /// Oldest debug entries dropped to keep the buffer bounded.
+    pub dropped_debug_logs: u64,
     pub buffer: Vec<String>,
     pub caret_offset: (usize, usize),
     pub logs: VecDeque<Log>,
@@ -117,56 +683,605 @@ pub struct App {
     pub keymaps: ModalKeyMaps,
     pub username: String,
     pub token: Option<String>,
-    pub command_sink: Option<UnboundedSender<Event>>,
+    pub command_sink: Option<Sender<Event>>,
     pub room_state: RoomData,
+    pub focused: bool,
+    pub unread_count: usize,
+    /// Messages mentioning the user among the unread ones.
+    pub unread_mentions: usize,
+    pub config: UserConfig,
+    pub presence: Presence,
+    /// Presence others have reported, only those that are away.
+    pub presences: HashMap<String, Presence>,
+    /// When each occupant of the current room was first seen in it.
+    pub occupants_since: HashMap<String, DateTime<Utc>>,
+    /// When the terminal lost focus, if it doesn't have it.
+    unfocused_since: Option<DateTime<Utc>>,
+    /// The newest message shown when the terminal lost focus, the unread
+    /// ones came after it.
+    last_read: Option<DateTime<Utc>>,
+    pub last_input: DateTime<Utc>,
+    pub server_time_offset: Option<chrono::Duration>,
+    pub upload: Option<Upload>,
+    /// Files still arriving.
+    pub downloads: Vec<Download>,
+    /// Files that arrived, waiting to be saved or thrown away.
+    pub finished_downloads: VecDeque<Download>,
+    /// How thumbnails are drawn, none when the terminal can't or they're off.
+    pub graphics: Option<Protocol>,
+    pub thumbnails: Thumbnails,
+    /// When the visual bell started flashing.
+    flash_started: Option<Instant>,
+    /// Emit OSC 8 escapes so links can be clicked.
+    pub hyperlinks: bool,
+    /// Messages the rate limit is holding back.
+    pub throttled: usize,
+    /// Whether the traffic overlay is shown.
+    pub show_traffic: bool,
+    /// Only watching a room with `--monitor`: no input and nothing sent.
+    pub monitor: bool,
+    /// Copied from the terminal side each tick while the overlay is shown.
+    pub traffic: Traffic,
+    /// Rows the help overlay is scrolled by, `None` while it's closed.
+    pub help_scroll: Option<usize>,
+    /// Timestamp of my message picked for editing or deleting.
+    pub selected_own: Option<DateTime<Utc>>,
+    /// Timestamp of my message being edited in the buffer.
+    pub editing: Option<DateTime<Utc>>,
+    /// Timestamp of the failed message picked for retrying or discarding.
+    pub selected_failed: Option<DateTime<Utc>>,
+    /// Position of the message picked for its details, counted back from
+    /// the newest rendered like the scroll offset.
+    pub selected_log: Option<usize>,
+    /// Links on screen, found while rendering.
+    pub link_placements: Vec<Hyperlink>,
+    /// Links offered by the open picker, newest first.
+    pub link_picker: Option<Vec<String>>,
+    /// Rooms left this session, the one left last first.
+    pub recent_rooms: VecDeque<String>,
+    /// The recent rooms picker is open.
+    pub recent_picker: bool,
+    /// Where thumbnails go on screen, found while rendering.
+    pub image_placements: Vec<(Rect, PathBuf)>,
+    pub identity: Option<Identity>,
+    pub known_signers: KnownSigners,
+    pub theme: Theme,
+    pub transforms: TransformChain,
+    /// Scripts hooked into sending and receiving.
+    pub plugins: Plugins,
+    /// Status messages seen from other users, by username.
+    pub bios: HashMap<String, String>,
+    pub event_log: Option<EventLog>,
+    pub transcripts: Option<Transcripts>,
+    pub popup: Option<Popup>,
+    /// Gathering the server's welcome, for a little while after login.
+    pub welcome: Option<Welcome>,
+    /// Parts of long messages waiting for the rest.
+    pub fragments: Reassembly,
+    /// Why the app can't go on, shown over everything until a key quits.
+    pub fatal: Option<String>,
+    pub room_counts: RoomCounts,
+    /// Number of messages the chat log is scrolled back from the newest.
+    pub scroll_offset: usize,
+    /// Messages that arrived below the view while scrolled back.
+    pub new_below: usize,
+    /// Sent messages, for recalling into the buffer.
+    pub send_history: InputHistory,
+    /// Parameters given to staged commands.
+    pub param_history: ParamHistory,
+    pub rooms: Vec<RoomSummary>,
+    /// Room asked to be created, until the server moves us into it.
+    pub creating_room: Option<String>,
+    /// Reconnected, the next room data is merged into the logs rather than
+    /// replacing them.
+    pub resyncing: bool,
+    /// Index into `rooms` highlighted in the room list.
+    pub selected_room: usize,
+    /// Pane positions from the last draw.
+    pub panes: Panes,
+    pub search: Option<Search>,
+    /// Lines picked in copy mode.
+    pub copy: Option<CopySelection>,
+    /// Text last copied from the chat log, for pasting into the buffer.
+    pub yanked: Option<String>,
+    /// Text cut from the buffer, newest first.
+    pub kill_ring: VecDeque<String>,
+    /// The buffer and caret before each paste, cut or line deletion,
+    /// newest last.
+    pub undo: Vec<(Vec<String>, (usize, usize))>,
+    pub history: Option<HistoryStore>,
+    pub room_keys: RoomKeys,
+    pub prompt: Option<Prompt>,
+    /// First key of a key sequence such as `gg`, waiting for the second.
+    pub pending_key: Option<KeyCode>,
+    /// Unsent buffers and carets of rooms other than the one being typed in.
+    pub drafts: HashMap<String, (Vec<String>, (usize, usize))>,
+    /// Rooms joined this session, in the order their tabs are shown.
+    pub tabs: Vec<Tab>,
+    pub completion: Option<Completion>,
+    /// Words of the configured language, none if spell checking is off.
+    pub dictionary: Option<Dictionary>,
+    /// A dead key waiting for the letter it goes on. It's in the buffer
+    /// before the caret until then.
+    composing: Option<char>,
+    /// Where unsent text is kept in case the client doesn't exit cleanly.
+    draft_store: DraftStore,
+    pub connection: ConnectionState,
+    /// `host:port` of the server, for the status bar.
+    pub server: String,
+    pub latency: Latency,
+    /// Draw times and tick jitter, shown under the debug pane.
+    pub perf: Perf,
+    pub modal: Option<Modal>,
+    /// The room directory popup, while it's open.
+    pub directory: Option<Directory>,
+    pub known_servers: KnownServers,
+    /// Fingerprint of the key the server presented at login.
+    pub server_key: Option<String>,
+    /// When the current login went through.
+    pub session_started: Option<DateTime<Utc>>,
+    pub toasts: Toasts,
+    /// Where plugins and pinned keys are read from and saved to.
+    config_dir: PathBuf,
 }
 
 impl App {
     pub fn new(config: UserConfig) -> Self {
-        Self {
+        Self::new_in(config, user_config::config_dir())
+    }
+
+    /// An app keeping its plugins and pinned keys in `config_dir`.
+    fn new_in(config: UserConfig, config_dir: PathBuf) -> Self {
+        let identity = match config.signing {
+            true => Identity::load_or_create(&config.identity_path())
+                .map_err(|e| tracing::error!("Could not load signing identity: {e}"))
+                .ok(),
+            false => None,
+        };
+        let graphics = config.images.protocol();
+        let hyperlinks = config.hyperlinks.unwrap_or_else(links::detect_hyperlinks);
+        let (theme, highlight_problems) = theme_for(&config);
+        let transforms = TransformChain::from_config(&config.transforms);
+        let dictionary = Dictionary::load(&config.spelling);
+        let event_log = config.event_log.as_ref().and_then(|path| {
+            EventLog::open(path)
+                .map_err(|e| tracing::error!("Could not open event log {path}: {e}"))
+                .ok()
+        });
+        let transcripts = config
+            .transcripts
+            .enabled
+            .then(|| Transcripts::open(&config.transcripts));
+        let param_history = match config.persist_history {
+            true => ParamHistory::load(),
+            false => ParamHistory::default(),
+        };
+        // an encrypted store waits for the passphrase prompt
+        let history = match config.persist_history && !config.encrypt_history {
+            true => HistoryStore::open()
+                .map_err(|e| tracing::error!("Could not open the history store: {e}"))
+                .ok(),
+            false => None,
+        };
+        let prompt = match config.persist_history && config.encrypt_history {
+            true => Some(Prompt {
+                title: tr("prompt.history_title"),
+                body: tr("prompt.history_body"),
+                input: String::new(),
+                masked: true,
+                error: None,
+                action: PromptAction::UnlockHistory,
+            }),
+            false => None,
+        };
+        let (keymaps, mut problems) = keymaps_for(&config);
+        let (plugins, plugin_problems) = Plugins::load(&config_dir.join("plugins"));
+        problems.extend(plugin_problems);
+        problems.extend(highlight_problems);
+        let mut app = Self {
             should_quit: false,
+            dirty: true,
+            drawn_at: Instant::now(),
+            show_dms: false,
+            pins: vec![],
+            show_pins: false,
+            dm_logs: VecDeque::new(),
             show_debug: false,
+            show_system: config.show_system_messages,
+            debug_logs: VecDeque::new(),
+            debug_scroll: 0,
+            dropped_debug_logs: 0,
             buffer: vec!["".into()],
             caret_offset: (1, 1),
             logs: VecDeque::new(),
-            mode: Mode::Navigate,
+            mode: match keymaps.modeless {
+                true => Mode::Insert,
+                false => Mode::Navigate,
+            },
             staged_command: None,
-            keymaps: ModalKeyMaps::default(),
+            keymaps,
             username: config.get_username(),
             token: None,
             command_sink: None,
             room_state: RoomData::default(),
+            focused: true,
+            unread_count: 0,
+            unread_mentions: 0,
+            config,
+            presence: Presence::Online,
+            presences: HashMap::new(),
+            occupants_since: HashMap::new(),
+            unfocused_since: None,
+            last_read: None,
+            last_input: Utc::now(),
+            server_time_offset: None,
+            upload: None,
+            downloads: vec![],
+            finished_downloads: VecDeque::new(),
+            graphics,
+            thumbnails: Thumbnails::default(),
+            image_placements: vec![],
+            flash_started: None,
+            hyperlinks,
+            link_picker: None,
+            recent_rooms: VecDeque::new(),
+            recent_picker: false,
+            throttled: 0,
+            show_traffic: false,
+            monitor: crate::cli::args().monitor.is_some(),
+            traffic: Traffic::default(),
+            help_scroll: None,
+            selected_own: None,
+            selected_failed: None,
+            selected_log: None,
+            editing: None,
+            link_placements: vec![],
+            identity,
+            known_signers: KnownSigners::load(&config_dir),
+            theme,
+            transforms,
+            plugins,
+            bios: HashMap::new(),
+            event_log,
+            transcripts,
+            popup: None,
+            welcome: None,
+            fragments: Reassembly::default(),
+            fatal: None,
+            room_counts: RoomCounts::default(),
+            scroll_offset: 0,
+            new_below: 0,
+            send_history: InputHistory::default(),
+            param_history,
+            rooms: vec![],
+            creating_room: None,
+            resyncing: false,
+            selected_room: 0,
+            panes: Panes::default(),
+            search: None,
+            copy: None,
+            yanked: None,
+            kill_ring: VecDeque::new(),
+            undo: vec![],
+            history,
+            room_keys: RoomKeys::default(),
+            prompt,
+            pending_key: None,
+            drafts: HashMap::new(),
+            tabs: vec![],
+            completion: None,
+            dictionary,
+            composing: None,
+            draft_store: DraftStore::default(),
+            connection: ConnectionState::Connecting,
+            server: String::new(),
+            latency: Latency::default(),
+            perf: Perf::default(),
+            modal: None,
+            directory: None,
+            known_servers: KnownServers::load(&config_dir),
+            server_key: None,
+            session_started: None,
+            toasts: Toasts::default(),
+            config_dir,
+        };
+        for problem in problems {
+            app.push_log(Log::new("CLIENT".into(), problem));
+        }
+
+        app
+    }
+
+    /// An app for tests, logged in as `me` with nothing kept between
+    /// sessions. Its settings live in a directory of the test run's own, so
+    /// none of the user's plugins or keys are loaded.
+    #[cfg(test)]
+    pub fn for_test(mut config: UserConfig) -> Self {
+        config.persist_history = false;
+        let dir = std::env::temp_dir().join(format!("marain-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("Could not make the test directory");
+        let mut app = Self::new_in(config, dir);
+        app.username = "me".into();
+        app
+    }
+
+    /// Applies the config file as it is now: theme, keybindings,
+    /// notifications and the rest take effect straight away. The username
+    /// and connection settings stay as they were until a restart.
+    fn reload_config(&mut self) {
+        let config = match user_config::reload_config() {
+            Ok(config) => config.with_host_overrides(),
+            Err(e) => {
+                self.notify(Severity::Error, trf!("sys.reload_failed", e));
+                return;
+            }
+        };
+        let (keymaps, mut problems) = keymaps_for(&config);
+        self.keymaps = keymaps;
+        let (plugins, plugin_problems) = Plugins::load(&self.config_dir.join("plugins"));
+        problems.extend(plugin_problems);
+        self.plugins = plugins;
+        let (theme, highlight_problems) = theme_for(&config);
+        problems.extend(highlight_problems);
+        self.theme = theme;
+        self.transforms = TransformChain::from_config(&config.transforms);
+        self.graphics = config.images.protocol();
+        self.transcripts = config
+            .transcripts
+            .enabled
+            .then(|| Transcripts::open(&config.transcripts));
+        self.hyperlinks = config.hyperlinks.unwrap_or_else(links::detect_hyperlinks);
+        self.dictionary = Dictionary::load(&config.spelling);
+        self.config = config;
+        self.logs
+            .iter_mut()
+            .chain(self.dm_logs.iter_mut())
+            .chain(self.debug_logs.iter_mut())
+            .for_each(Log::restyle);
+        self.push_log(Log::new("CLIENT".into(), tr("sys.config_reloaded")));
+        for problem in problems {
+            self.push_log(Log::new("CLIENT".into(), problem));
+        }
+    }
+
+    /// Switches the log density and saves it to the config file, on top of
+    /// whatever else the file says by now.
+    fn toggle_density(&mut self) {
+        let density = self.config.layout.density.toggled();
+        self.config.layout.density = density;
+        let saved = user_config::reload_config().and_then(|mut config| {
+            config.layout.density = density;
+            user_config::save_config(&config)
+        });
+        if let Err(e) = saved {
+            self.notify(Severity::Error, trf!("sys.save_config_failed", e));
+        }
+    }
+
+    /// Whether a render tick should draw, only if something changed or the
+    /// last draw is getting stale.
+    pub fn needs_redraw(&self) -> bool {
+        self.dirty || self.drawn_at.elapsed() >= IDLE_REDRAW || self.bell_lit().is_some()
+    }
+
+    /// Starts the visual bell.
+    pub fn flash(&mut self) {
+        self.flash_started = Some(Instant::now());
+    }
+
+    /// Whether the visual bell indicator is lit right now, `None` once it
+    /// has stopped flashing.
+    pub fn bell_lit(&self) -> Option<bool> {
+        let elapsed = self.flash_started?.elapsed();
+        if elapsed >= FLASH_DURATION {
+            return None;
         }
+        Some((elapsed.as_millis() / FLASH_PHASE.as_millis()).is_multiple_of(2))
     }
 
-    pub fn set_send_chan(&mut self, chan: UnboundedSender<Event>) {
+    pub fn drawn(&mut self) {
+        self.dirty = false;
+        self.drawn_at = Instant::now();
+    }
+
+    pub fn set_send_chan(&mut self, chan: Sender<Event>) {
         self.command_sink = Some(chan);
     }
 
-    pub fn map_key(&self, code: KeyCode) -> Option<Command> {
-        log::info!("App mapping key {code:?}");
-        self.keymaps.get_cmd(&self.mode, code)
+    pub fn map_key(&mut self, code: KeyCode, modifiers: KeyModifiers) -> Option<Command> {
+        tracing::info!("App mapping key {code:?} {modifiers:?}");
+        // the second key of a sequence, anything else drops the first
+        if let Some(first) = self.pending_key.take() {
+            return self.keymaps.get_sequence(&self.mode, first, code);
+        }
+        if modifiers.is_empty() && self.keymaps.starts_sequence(&self.mode, code) {
+            self.pending_key = Some(code);
+            return None;
+        }
+        self.keymaps.get_cmd(&self.mode, code, modifiers)
+    }
+
+    pub fn render_debug_logs(&self, max_messages: usize, log_style: &LogStyle) -> Text {
+        render_newest(&self.debug_logs, self.debug_scroll, max_messages, log_style)
     }
 
-    pub fn render_logs(&self, max_messages: usize, log_style: &LogStyle) -> Text {
-        self.logs
-            .iter()
-            .filter(|l| l.should_render(self.show_debug))
-            .collect::<Vec<_>>()
+    pub fn render_dm_logs(&self, max_messages: usize, log_style: &LogStyle) -> Text {
+        render_newest(&self.dm_logs, 0, max_messages, log_style)
+    }
+
+    /// Shows a direct message in the DM pane, opening it.
+    pub fn push_dm(&mut self, log: Log) {
+        self.dm_logs.push_front(log);
+        self.dm_logs.truncate(self.config.retention.max_messages);
+        self.show_dms = true;
+    }
+
+    /// The server delivers direct messages but has no way to send one, so
+    /// rather than going to the whole room `/msg` is turned down.
+    fn send_direct(&mut self, to: String, _text: String) {
+        self.push_log(Log::new("CLIENT".into(), trf!("sys.no_direct_send", to)));
+    }
+
+    fn scroll_debug(&mut self, by: isize) {
+        let max = self.debug_logs.len().saturating_sub(1);
+        self.debug_scroll = self.debug_scroll.saturating_add_signed(by).min(max);
+    }
+
+    /// The logs that fill a pane `rows` high and `width` wide at the current
+    /// scroll offset, oldest first. Only those, plus a few rows of margin,
+    /// are rendered: rows are counted as if lines wrapped at any character,
+    /// which word wrapping only ever adds to, so the window can run over
+    /// the top but never falls short.
+    pub fn render_logs(&self, rows: usize, width: usize, log_style: &LogStyle) -> Text {
+        let mut lines: Vec<Line> = vec![];
+        // rows taken by the first `counted` lines
+        let mut filled = 0;
+        let mut counted = 0;
+        let row_count = |line: &Line| line.width().div_ceil(width.max(1)).max(1);
+        // day of the log shown below the current one
+        let mut newer_day: Option<String> = None;
+        let comfortable = self.config.layout.density == Density::Comfortable;
+        // messages from ignored users since the last one shown
+        let mut hidden = 0;
+        // peeked one past the page, to know where the oldest run shown starts
+        let mut shown = self
+            .logs
             .iter()
-            .take(max_messages)
-            .rev()
-            .map(|l| l.render(log_style))
-            .collect::<Vec<Line>>()
-            .into()
+            .filter(|l| l.should_render(self.show_debug, self.show_system))
+            .enumerate()
+            .skip(self.scroll_offset)
+            .peekable();
+        while let Some((i, l)) = shown.next() {
+            filled += lines[counted..].iter().map(row_count).sum::<usize>();
+            counted = lines.len();
+            if filled >= rows + LOG_MARGIN {
+                break;
+            }
+            if l.kind == LogKind::Chat && self.is_ignored(&l.from) {
+                hidden += 1;
+                continue;
+            }
+            if hidden > 0 && self.config.collapse_ignored {
+                lines.push(Line::styled(
+                    trf!("log.ignored", hidden),
+                    log_style.delims(),
+                ));
+            }
+            hidden = 0;
+            // a day starts between this log and the one below it
+            let day = log_style.format_time_with(l.ts, DATE_FORMAT);
+            if let Some(newer) = newer_day.replace(day.clone()) {
+                if newer != day {
+                    lines.push(Line::styled(
+                        trf!("log.date_separator", newer),
+                        log_style.delims(),
+                    ));
+                }
+            }
+            // logs are stored newest first, so the unread ones are at the front
+            if i == self.unread_count && i > 0 {
+                let divider = match self.last_read {
+                    Some(ts) => trf!("log.catch_up", self.unread_count, log_style.format_time(ts)),
+                    None => trf!("log.unread_divider", self.unread_count),
+                };
+                lines.push(Line::styled(divider, log_style.delims()));
+            }
+            let mut line = l.render(log_style);
+            let selected = [self.selected_own, self.selected_failed].contains(&Some(l.ts))
+                && l.from == self.username;
+            if selected
+                || self.selected_log == Some(i)
+                || self.copy.as_ref().is_some_and(|c| c.contains(i))
+            {
+                line = line.patch_style(Modifier::REVERSED);
+            }
+            if let Some(ref search) = self.search {
+                // swap the message spans for the raw text with the matches
+                // picked out
+                if let Some(msg) = line.spans.get(MESSAGE_SPAN).cloned() {
+                    line.spans.truncate(MESSAGE_SPAN);
+                    let found = match search.current == Some(i) {
+                        true => log_style.highlight().add_modifier(Modifier::REVERSED),
+                        false => log_style.highlight(),
+                    };
+                    line.spans
+                        .extend(search.highlight(&l.msg, msg.style, found));
+                }
+            }
+            match l.delivery {
+                Some(Delivery::Failed) => line.spans.push(Span::styled(
+                    Delivery::Failed.indicator(),
+                    log_style.highlight(),
+                )),
+                Some(delivery) => line
+                    .spans
+                    .push(Span::styled(delivery.indicator(), log_style.time())),
+                None => {}
+            }
+            if let Some(ref path) = l.image {
+                // blank rows for the thumbnail to be drawn over
+                if self.shows_thumbnail(path) {
+                    lines.extend((0..THUMBNAIL_ROWS).map(|_| Line::raw("")));
+                }
+                lines.push(Line::styled(image_placeholder(path), log_style.delims()));
+            }
+            if !comfortable {
+                lines.push(line);
+                continue;
+            }
+            let (heading, body) = split_heading(line, l.ttl.is_some());
+            lines.push(body);
+            let older = shown.peek().map(|&(_, o)| o);
+            let starts_run = !older.is_some_and(|o| {
+                o.from == l.from
+                    && i + 1 != self.unread_count
+                    && log_style.format_time_with(o.ts, DATE_FORMAT) == day
+            });
+            if starts_run {
+                lines.push(heading);
+                if older.is_some() {
+                    lines.push(Line::raw(""));
+                }
+            }
+        }
+        if hidden > 0 && self.config.collapse_ignored {
+            lines.push(Line::styled(
+                trf!("log.ignored", hidden),
+                log_style.delims(),
+            ));
+        }
+        lines.reverse();
+
+        lines.into()
+    }
+
+    pub fn logs_title(&self) -> String {
+        let title = match self.unread_count {
+            0 => tr("pane.logs"),
+            n => trf!("pane.logs_unread", n),
+        };
+        let title = match self.scroll_offset {
+            0 => title,
+            n => title + trf!("pane.logs_scrolled", n).as_str(),
+        };
+        match self.search {
+            Some(ref search) if search.current.is_none() => {
+                title + trf!("pane.logs_search_none", search.query).as_str()
+            }
+            Some(ref search) => title + trf!("pane.logs_search", search.query).as_str(),
+            None => title,
+        }
     }
 
     pub fn show_current_mode(&self) -> String {
-        format!("{}", self.mode)
+        match self.presence {
+            Presence::Online => format!("{}", self.mode),
+            ref away => format!("{} [{away}]", self.mode),
+        }
     }
 
     pub fn render_keymap(&self) -> Text {
-        self.keymaps.render(&self.mode)
+        self.keymaps.render(&self.mode, &self.theme.log)
     }
 
     pub fn get_caret_2d(&self) -> (usize, usize) {
@@ -175,92 +1290,188 @@ impl App {
 
     pub fn set_caret_2d(&mut self, row: usize, col: usize) {
         self.caret_offset.0 = row.clamp(1, self.buffer.len());
-        self.caret_offset.1 = col.clamp(
-            1,
-            self.buffer[row.checked_sub(1).unwrap_or(0) % self.buffer.len()].len() + 1,
-        );
+        self.caret_offset.1 =
+            col.clamp(1, graphemes::len(&self.buffer[self.caret_offset.0 - 1]) + 1);
     }
 
     pub fn render_buf(&self) -> String {
-        self.buffer.iter().fold("".to_string(), |acc, el| acc + &el)
+        self.buffer.join("\n")
+    }
+
+    /// Replaces the edit buffer with `text`, one buffer line per text line,
+    /// leaving the caret at the end.
+    pub fn load_buffer(&mut self, text: &str) {
+        self.buffer = text.lines().map(String::from).collect();
+        if self.buffer.is_empty() {
+            self.buffer.push("".into());
+        }
+        let last = self.buffer.len();
+        self.set_caret_2d(last, graphemes::len(&self.buffer[last - 1]) + 1);
     }
 
-    pub fn split_current_at_caret(&self) -> (String, String) {
+    /// The buffer wrapped to the input area, one line per row on screen.
+    /// Misspelled words are underlined, but not the one being typed.
+    pub fn render_buf_styled(&self) -> Text {
         let (row, col) = self.get_caret_2d();
+        let width = self.input_width();
+        let mut lines: Vec<Line> = vec![];
+        for (i, buf_line) in self.buffer.iter().enumerate() {
+            let caret = (i + 1 == row).then_some(col - 1);
+            let misspelled: Vec<Range<usize>> = match self.dictionary {
+                Some(ref dictionary) if self.mode == Mode::Insert => dictionary
+                    .misspelled(buf_line)
+                    .into_iter()
+                    .filter(|word| caret.is_none_or(|c| !(word.start..=word.end).contains(&c)))
+                    .collect(),
+                _ => vec![],
+            };
+            let starts = graphemes::wrap_starts(buf_line, width);
+            for (j, &start) in starts.iter().enumerate() {
+                let end = starts.get(j + 1).copied().unwrap_or(usize::MAX);
+                let (_, from) = graphemes::split_at(buf_line, start);
+                let (text, _) = graphemes::split_at(from, end - start);
+                let caret = caret.filter(|c| (start..end).contains(c));
+                // only rows with something to style are split up, the others
+                // borrow the buffer
+                let marked = misspelled.iter().any(|w| w.start < end && w.end > start);
+                if caret.is_none() && !marked {
+                    lines.push(Line::raw(text));
+                    continue;
+                }
+                lines.push(self.styled_row(text, start, caret, &misspelled));
+            }
+        }
 
-        let buf_line = self.buffer[row.checked_sub(1).unwrap_or(0)].clone();
-        let (pre, post) = if buf_line.len() > 0 {
-            buf_line.split_at(col - 1)
-        } else {
-            (buf_line.as_str(), "")
+        lines.into()
+    }
+
+    /// One row of the buffer starting at grapheme `start` of its line, with
+    /// the caret and misspelled words picked out.
+    fn styled_row<'a>(
+        &self,
+        text: &'a str,
+        start: usize,
+        caret: Option<usize>,
+        misspelled: &[Range<usize>],
+    ) -> Line<'a> {
+        let style_at = |idx: usize| match idx {
+            _ if caret == Some(idx) => self.theme.caret,
+            _ if misspelled.iter().any(|w| w.contains(&idx)) => spelling::style(),
+            _ => Style::new(),
         };
-        let pre = pre.to_string();
+        let mut spans: Vec<Span> = vec![];
+        let (mut from, mut offset) = (0, 0);
+        let mut current: Option<Style> = None;
+        for (n, grapheme) in text.graphemes(true).enumerate() {
+            let style = style_at(start + n);
+            if let Some(previous) = current.filter(|&previous| previous != style) {
+                spans.push(Span::styled(&text[from..offset], previous));
+                from = offset;
+            }
+            current = Some(style);
+            offset += grapheme.len();
+        }
+        if let Some(style) = current {
+            spans.push(Span::styled(&text[from..], style));
+        }
+        if caret.is_some_and(|c| c - start == graphemes::len(text)) {
+            spans.push(Span::styled(" ", self.theme.caret));
+        }
+        Line::from(spans)
+    }
 
-        let (mut up_to, mut caret_and_beyond) = ("".to_string(), "".to_string());
-        up_to = up_to + &pre;
+    /// Columns the input area wraps the buffer at, unbounded before the
+    /// first draw has laid it out.
+    fn input_width(&self) -> usize {
+        match self.panes.input.width.saturating_sub(2) {
+            0 => usize::MAX,
+            width => width as usize,
+        }
+    }
 
-        let post = post.to_string();
-        caret_and_beyond = caret_and_beyond + &post;
+    /// Rows the buffer takes up wrapped at `width`.
+    pub fn wrapped_rows(&self, width: usize) -> usize {
+        self.buffer
+            .iter()
+            .map(|line| graphemes::wrap_starts(line, width).len())
+            .sum()
+    }
 
-        (up_to, caret_and_beyond)
+    /// The buffer's rows on screen, as the buffer row and the index of the
+    /// grapheme each one starts at.
+    fn visual_rows(&self) -> Vec<(usize, usize)> {
+        let width = self.input_width();
+        self.buffer
+            .iter()
+            .enumerate()
+            .flat_map(|(i, line)| {
+                graphemes::wrap_starts(line, width)
+                    .into_iter()
+                    .map(move |start| (i + 1, start))
+            })
+            .collect()
     }
 
-    pub fn render_buf_styled(&self) -> Line {
-        let mut line_vec: Vec<Span> = vec![];
+    /// The row and cell column of the caret among the rows on screen, from
+    /// 0 and before scrolling.
+    pub fn caret_on_screen(&self) -> (usize, usize) {
         let (row, col) = self.get_caret_2d();
-        let preceding_chunk = self
-            .buffer
+        let rows = self.visual_rows();
+        let at = rows
             .iter()
-            .take(row - 1)
-            .fold(String::from(""), |acc, el| acc + &el);
-
-        if preceding_chunk.len() > 0 {
-            line_vec.push(Span::raw(preceding_chunk));
-        }
+            .rposition(|&(r, start)| r == row && start < col)
+            .unwrap_or(0);
+        let (_, from) = graphemes::split_at(&self.buffer[row - 1], rows[at].1);
 
-        let buf_line = self.buffer[row.checked_sub(1).unwrap_or(0)].clone();
-        let (pre, post) = if buf_line.len() > 0 {
-            buf_line.split_at(col - 1)
-        } else {
-            (buf_line.as_str(), "")
-        };
-        let post = post.to_string();
-        let pre = pre.to_string();
-        line_vec.push(Span::raw(pre));
-
-        let blinkin = Style::default()
-            .bg(Color::Green)
-            .fg(Color::Black)
-            .add_modifier(Modifier::SLOW_BLINK);
-        let highlighted = match post.len() {
-            0 => Span::styled(" ".to_string(), blinkin),
-            _ => Span::styled(post.clone().chars().take(1).collect::<String>(), blinkin),
-        };
-
-        line_vec.push(highlighted);
-
-        let rest_of_line = Span::raw(post.clone().chars().skip(1).collect::<String>());
-        line_vec.push(rest_of_line);
+        (at, graphemes::column(from, col - 1 - rows[at].1))
+    }
 
-        let subsequent_lines = self
-            .buffer
-            .iter()
-            .skip(row)
-            .fold("".to_string(), |acc, el| acc + &el);
-        line_vec.push(Span::raw(subsequent_lines));
+    /// Rows the input area is scrolled by to keep the caret in view.
+    pub fn input_scroll(&self) -> u16 {
+        let height = self.panes.input.height.saturating_sub(2).max(1) as usize;
+        let (at, _) = self.caret_on_screen();
+        at.saturating_sub(height - 1) as u16
+    }
 
-        Line::from(line_vec)
+    /// Puts the caret `col` cells into row `at` on screen, or at the end of
+    /// the row if it's shorter.
+    fn set_caret_on_screen(&mut self, rows: &[(usize, usize)], at: usize, col: usize) {
+        let at = at.min(rows.len() - 1);
+        let (row, start) = rows[at];
+        let (_, from) = graphemes::split_at(&self.buffer[row - 1], start);
+        let mut idx = start + graphemes::index_at_column(from, col);
+        // the end of a wrapped row is the start of the next one
+        if let Some(&(next_row, next)) = rows.get(at + 1) {
+            if next_row == row {
+                idx = idx.min(next - 1);
+            }
+        }
+        self.set_caret_2d(row, idx + 1);
     }
 
     pub fn handle_toggle_debug(&mut self) {
         self.show_debug = !self.show_debug;
     }
 
+    fn toggle_system(&mut self) {
+        self.show_system = !self.show_system;
+        self.scroll_offset = 0;
+    }
+
     pub fn handle(&mut self, cmd: Command) {
+        // anything but another Tab accepts the completion
+        if cmd != Command::Complete {
+            self.completion = None;
+        }
+        // anything but more typing leaves a dead key as it is
+        if !matches!(cmd, Command::Capture(_)) {
+            self.composing = None;
+        }
         match cmd {
-            Command::Quit => {
-                self.should_quit = true;
-            }
+            Command::Quit => self.quit(),
+            Command::QuitNow => self.should_quit = true,
+            Command::ClearHistory => self.ask_clear_history(),
+            Command::ClearHistoryNow => self.clear_history(),
             Command::Reset => {
                 self.buffer = vec!["".into()];
                 self.caret_offset = (1, 1);
@@ -274,15 +1485,83 @@ impl App {
             Command::SendBuffer => {
                 self.handle_send();
             }
+            Command::MoveCaret(CaretMotion::Line, amount) if self.recalls_history() => {
+                self.recall_history(amount < 0);
+            }
             Command::MoveCaret(motion, amount) => {
                 self.handle_caret_move(motion, amount);
             }
-            Command::Del(offset) => self.handle_deletion(offset),
-            Command::GetServerTime => self.send_server_command(cmd),
+            Command::HistoryPrev => self.recall_history(true),
+            Command::HistoryNext => self.recall_history(false),
+            Command::Del(offset) => self.handle_deletion(CaretMotion::Character, offset),
+            Command::DelWord(offset) => self.handle_deletion(CaretMotion::Word, offset),
+            Command::RefreshRoom => self.send_server_command(cmd),
+            Command::GetServerTime => {
+                self.latency.start(true);
+                self.send_server_command(cmd);
+            }
             Command::ToggleDebug => self.handle_toggle_debug(),
+            Command::SendDirect(to, text) => self.send_direct(to, text),
+            Command::ToggleDms => self.show_dms = !self.show_dms,
+            Command::OpenLink => self.open_link_picker(),
+            Command::SelectOwnMessage => self.select_own(0),
+            Command::SelectLog => self.select_log(0),
+            Command::PinMessage => self.pin_message(),
+            Command::TogglePins => self.show_pins = !self.show_pins,
+            Command::ToggleTraffic => self.show_traffic = !self.show_traffic,
+
+            Command::ExportKeymap(Some(path)) => self.handle_export_keymap(path),
+            Command::ImportKeymap(Some(path)) => self.handle_import_keymap(path),
+            Command::ExportKeys(path) => self.export_keys(path),
+            Command::ExportLogs(Some(path)) => self.handle_export_logs(path),
+            Command::ExportHtml(Some(path)) => self.export_html(path),
+            Command::Upload(Some(path)) => self.handle_upload(path),
+            Command::CancelUpload => self.handle_cancel_upload(),
+            Command::SaveDownload => self.save_download(),
+            Command::DiscardDownload => self.discard_download(),
+            Command::Run(cmd) => self.handle_run(cmd),
+            Command::SetBio(bio) => self.handle_set_bio(bio),
+            Command::Whois(name) => self.handle_whois(name),
+            Command::Ignore(name) => self.set_ignored(name, true),
+            Command::Unignore(name) => self.set_ignored(name, false),
+            Command::ShowQr(kind) => self.handle_show_qr(kind),
+            Command::CopyJoinLink => self.copy_join_link(),
+            Command::SuggestSpelling => self.suggest_spelling(),
+            Command::PasteImage => self.handle_paste_image(),
+            Command::Scroll(n) => self.scroll(n),
+            Command::ScrollTop => self.scroll(isize::MAX),
+            Command::ScrollBottom => self.scroll_offset = 0,
+            Command::ShowStats => {
+                self.popup = Some(Popup {
+                    title: tr("popup.stats"),
+                    body: stats::render(self.logs.iter(), &self.room_counts),
+                })
+            }
+            Command::RoomDirectory => self.open_directory(),
+            Command::ToggleSystem => self.toggle_system(),
+            Command::ToggleRelativeTime => self.theme.log.toggle_relative(),
+            Command::ToggleDensity => self.toggle_density(),
+            Command::SelectFailed => self.select_failed(0),
+            Command::SwitchTab(index) => self.switch_tab(index),
+            Command::CycleTab(by) => self.cycle_tab(by),
+            Command::CloseTab => self.close_tab(),
+            Command::Undo => self.undo(),
+            Command::PreviousRoom => self.previous_room(),
+            Command::RecentRooms => self.open_recent_rooms(),
+            Command::ShowHelp => self.help_scroll = Some(0),
+            Command::ReloadConfig => self.reload_config(),
+            Command::SendEphemeral(contents) => {
+                self.send_chat(ephemeral::mark(self.config.ephemeral_ttl_secs, &contents));
+            }
 
             // Any commands requiring user input should go here
-            Command::MoveRooms(None) => {
+            Command::MoveRooms(None)
+            | Command::CreateRoom(None)
+            | Command::ExportKeymap(None)
+            | Command::ImportKeymap(None)
+            | Command::ExportLogs(None)
+            | Command::ExportHtml(None)
+            | Command::Upload(None) => {
                 self.stage_command(cmd);
                 self.switch_mode(Mode::InsertCommand);
             }
@@ -295,148 +1574,2274 @@ impl App {
                 self.handle_abort_staged_command();
             }
 
-            // ignored patterns
-            Command::MoveRooms(Some(_)) => {}
+            Command::MoveRooms(Some(_)) => self.send_server_command(cmd),
+            Command::CreateRoom(Some(name)) => self.create_room(name),
+            Command::SelectRoom(by) => self.select_room(by),
+            Command::JoinSelectedRoom => self.join_selected_room(),
+            Command::SubmitSearch => self.submit_search(),
+            Command::TrustServerKey => self.trust_server_key(),
+            Command::ShowServerKeys => self.show_server_keys(),
+            Command::ShowServerInfo => self.show_server_info(),
+            Command::SetRoomKey => self.ask_room_key(),
+            Command::ForgetRoomKey => self.forget_room_key(),
+            Command::Complete => self.handle_complete(),
+            Command::ClearSearch => self.clear_search(),
+            Command::SearchNext => self.step_search(true),
+            Command::SearchPrev => self.step_search(false),
+            Command::Reconnect => self.handle_reconnect(),
+            Command::DeleteLine => self.delete_line(),
+            Command::CopyMove(by) => self.move_copy_cursor(by),
+            Command::CopyMark => {
+                if let Some(ref mut selection) = self.copy {
+                    selection.toggle_anchor();
+                }
+            }
+            Command::CopyYank => self.yank_selection(),
+            Command::PasteYanked => self.paste_yanked(),
+            Command::Kill(motion, direction) => self.kill(motion, direction),
+            Command::Yank => self.yank(),
+            Command::ScrollDebug(by) => self.scroll_debug(by),
         };
-        log::info!("Caret: {:?}", self.caret_offset);
+        tracing::info!("Caret: {:?}", self.caret_offset);
     }
 
     fn send_server_command(&self, cmd: Command) {
         let body = match cmd {
             Command::GetServerTime => ClientMsgBody::GetTime,
+            // moving into the room we're in answers with its room data
+            Command::RefreshRoom => ClientMsgBody::Move {
+                target: self.room_state.room_name.clone(),
+            },
             Command::MoveRooms(Some(target)) => ClientMsgBody::Move { target },
+            // there is no separate create, moving into a room that doesn't
+            // exist yet makes it
+            Command::CreateRoom(Some(target)) => ClientMsgBody::Move { target },
             _ => todo!(),
         };
         if let (Some(ref chan), Some(tok)) = (self.command_sink.clone(), self.token.clone()) {
-            match chan.send(Event::ServerCommand {
+            match chan.try_send(Event::ServerCommand {
                 token: tok.clone(),
                 username: self.username.clone(),
                 timestamp: Utc::now(),
                 message_body: body,
             }) {
                 Err(e) => {
-                    log::error!("Failed to send server: {e}");
+                    tracing::error!("Failed to send server: {e}");
                 }
                 _ => {}
             };
         }
     }
 
-    fn handle_deletion(&mut self, offset: isize) {
-        let (pre, post) = self.split_current_at_caret();
+    fn handle_deletion(&mut self, motion: CaretMotion, offset: isize) {
         let (row, col) = self.get_caret_2d();
-        let line_with_removal = match offset.signum() < 0 {
-            false => {
-                if post.len() >= 1 {
-                    pre + &String::from(post.chars().skip(1).collect::<String>())
-                } else {
-                    pre + &post
-                }
+        let idx = row.checked_sub(1).unwrap_or(0);
+        let line = &self.buffer[idx];
+        match (motion, offset.signum() < 0) {
+            (CaretMotion::Word, false) => {
+                let end = graphemes::word_right(line, col - 1);
+                self.buffer[idx] = graphemes::delete_range(line, col - 1, end);
             }
-            true => {
-                if pre.len() >= 1 {
-                    self.set_caret_2d(row, col - 1);
-                    String::from(pre.chars().take(pre.len() - 1).collect::<String>() + &post)
-                } else {
-                    pre + &post
-                }
+            (CaretMotion::Word, true) => {
+                let start = graphemes::word_left(line, col - 1);
+                self.buffer[idx] = graphemes::delete_range(line, start, col - 1);
+                self.set_caret_2d(row, start + 1);
             }
-        };
-        self.buffer[row.checked_sub(1).unwrap_or(0)] = line_with_removal;
+            (_, false) => self.buffer[idx] = graphemes::delete_at(line, col - 1),
+            (_, true) => {
+                let (line, new_col) = graphemes::delete_before(&self.buffer[idx], col - 1);
+                self.buffer[idx] = line;
+                self.set_caret_2d(row, new_col + 1);
+            }
+        }
     }
 
     fn handle_caret_move(&mut self, motion: CaretMotion, amount: isize) {
         let (row, col) = self.get_caret_2d();
         let new_caret = match motion {
             CaretMotion::Character => (row, (col as isize + amount).max(0) as usize),
-            CaretMotion::Line => ((row as isize + amount).max(0) as usize, col),
+            // by rows on screen, so wrapped lines can be moved through
+            CaretMotion::Line => {
+                let rows = self.visual_rows();
+                let (at, col) = self.caret_on_screen();
+                let to = (at as isize + amount).max(0) as usize;
+                self.set_caret_on_screen(&rows, to, col);
+                return;
+            }
+            CaretMotion::Word => {
+                let line = &self.buffer[row.saturating_sub(1)];
+                let mut idx = col - 1;
+                for _ in 0..amount.unsigned_abs() {
+                    idx = match amount < 0 {
+                        true => graphemes::word_left(line, idx),
+                        false => graphemes::word_right(line, idx),
+                    };
+                }
+                (row, idx + 1)
+            }
+            // set_caret_2d clamps these to the end of the line or buffer
+            CaretMotion::LineBoundary if amount < 0 => (row, 1),
+            CaretMotion::LineBoundary => (row, usize::MAX),
+            CaretMotion::BufferBoundary if amount < 0 => (1, 1),
+            CaretMotion::BufferBoundary => (usize::MAX, usize::MAX),
         };
         self.set_caret_2d(new_caret.0, new_caret.1);
     }
 
+    /// Removes the caret's line, or empties it if it's the only one.
+    fn delete_line(&mut self) {
+        self.checkpoint();
+        let (row, _) = self.get_caret_2d();
+        match self.buffer.len() {
+            1 => self.buffer[0].clear(),
+            _ => _ = self.buffer.remove(row - 1),
+        }
+        self.set_caret_2d(row, 1);
+    }
+
+    fn create_room(&mut self, name: String) {
+        let name = name.trim().to_string();
+        let msg = if name.is_empty() {
+            tr("sys.room_name_empty")
+        } else if self.rooms.iter().any(|r| r.name == name) {
+            trf!("sys.room_exists", name)
+        } else {
+            self.creating_room = Some(name.clone());
+            self.send_server_command(Command::CreateRoom(Some(name.clone())));
+            trf!("sys.creating_room", name)
+        };
+        self.push_log(Log::new("CLIENT".into(), msg));
+    }
+
     fn stage_command(&mut self, command: Command) {
         self.staged_command = Some(command);
     }
 
-    pub fn input_area_name(&self) -> String {
-        match (self.mode.clone(), self.staged_command.clone()) {
-            (Mode::InsertCommand, Some(command)) => {
-                format!("CMD: {command}")
-            }
-            _ => "MSG".to_string(),
-        }
+    /// Size of `contents` as it would go to the server: serialized, signed
+    /// if signing is on and with the session token.
+    pub fn outgoing_size(&self, contents: &str) -> usize {
+        let contents = match self.identity {
+            Some(ref identity) => identity.sign(contents),
+            None => contents.to_string(),
+        };
+        self.serialized_size(contents)
     }
 
-    fn handle_send_staged_command(&mut self) {
-        let Some(cmd) = self.staged_command.clone() else {
-            log::error!("Called handler for sending staged command with no staged command");
-            return;
+    fn serialized_size(&self, contents: String) -> usize {
+        let msg = ClientMsg {
+            token: self.token.clone(),
+            body: ClientMsgBody::SendToRoom { contents },
+            timestamp: Timestamp::from(Utc::now()),
         };
-        let param_string = self.render_buf();
-        if let Some(command_with_params) = cmd.parse_params(param_string) {
-            self.send_server_command(command_with_params);
-        }
-        self.buffer = vec!["".into()];
-        self.caret_offset = (1, 1);
-        self.staged_command = None;
-        self.switch_mode(Mode::Navigate);
+        bincode::serialized_size(&msg).map_or(usize::MAX, |size| size as usize)
     }
 
-    fn handle_abort_staged_command(&mut self) {
-        if let Some(_) = self.staged_command.clone() {
-            self.staged_command = None;
+    /// Characters and bytes in the buffer for the input area's title, and
+    /// whether the message is getting close to the size limit. Only while
+    /// writing a message.
+    pub fn message_counter(&self) -> Option<(String, bool)> {
+        let contents = self.render_buf();
+        if !self.mode.holds_draft() || contents.starts_with('/') {
+            return None;
         }
-
-        self.buffer = vec!["".into()];
-        self.caret_offset = (1, 1);
-        self.switch_mode(Mode::Navigate);
+        let size = self.outgoing_size(&contents);
+        let warn = size as f64 >= self.size_limit() as f64 * SIZE_WARNING;
+        let counter = trf!(
+            "pane.counter",
+            graphemes::len(&contents),
+            contents.len(),
+            self.size_limit()
+        );
+        Some((counter, warn))
     }
 
-    pub fn switch_mode(&mut self, mode: Mode) {
-        self.mode = mode;
-        match self.mode {
-            Mode::Insert => {
-                let (r, c) = self.get_caret_2d();
-                self.set_caret_2d(r, c);
-            }
-            Mode::Navigate => {}
-            Mode::InsertCommand => {}
-            Mode::Disconnected => {}
+    pub fn input_area_name(&self) -> String {
+        match (self.mode.clone(), self.staged_command.clone()) {
+            (Mode::InsertCommand, Some(command)) => trf!("pane.cmd", command),
+            (Mode::Search, _) => tr("pane.search"),
+            _ if self.editing.is_some() => tr("pane.edit"),
+            _ => tr("pane.msg"),
         }
     }
 
-    pub fn handle_send(&mut self) {
-        let chat_log = Log::new(self.username.clone(), self.render_buf());
-        if let (Some(ref chan), Some(tok)) = (self.command_sink.clone(), self.token.clone()) {
-            let Ok(_) = chan.send(Event::Send {
-                token: tok.clone(),
-                username: self.username.clone(),
-                timestamp: chat_log.get_ts(),
-                contents: chat_log.get_msg_body(),
-            }) else {
-                return;
-            };
+    /// Label and percentage for the transfer progress bar, the upload
+    /// before any downloads.
+    pub fn transfer_progress(&self) -> Option<(String, usize)> {
+        if let Some(ref upload) = self.upload {
+            return Some((trf!("pane.upload", upload.name), upload.progress_percent()));
         }
-        self.buffer = vec!["".into()];
-        self.caret_offset = (1, 1);
+        let download = self.downloads.first()?;
+        let label = trf!("pane.download", download.name, download.from);
+        Some((label, download.progress_percent()))
     }
 
-    pub fn push_debug_log(&mut self, data: impl Debug) {
-        self.logs.push_front(Log::new_debug(data));
+    /// Only one upload runs at a time, returns false after telling the user
+    /// if one is already in progress.
+    fn can_upload(&mut self) -> bool {
+        if let Some(ref upload) = self.upload {
+            let msg = trf!("sys.upload_in_progress", upload.name);
+            self.push_log(Log::new("CLIENT".into(), msg));
+            return false;
+        }
+        true
     }
 
-    fn log_count(&self) -> usize {
+    fn handle_upload(&mut self, path: String) {
+        if !self.can_upload() {
+            return;
+        }
+        match Upload::from_path(&path) {
+            Ok(upload) => self.upload = Some(upload),
+            Err(e) => self.notify(Severity::Error, trf!("sys.read_failed", path, e)),
+        }
+    }
+
+    fn handle_paste_image(&mut self) {
+        if !self.can_upload() {
+            return;
+        }
+        match Upload::from_clipboard_image() {
+            Ok(upload) => self.upload = Some(upload),
+            Err(e) => self.notify(Severity::Error, trf!("sys.clipboard_image_failed", e)),
+        }
+    }
+
+    fn handle_run(&mut self, cmd: String) {
+        if !self.config.allow_run {
+            self.push_log(Log::new("CLIENT".into(), tr("sys.run_disabled")));
+            return;
+        }
+        if let Some(sink) = self.command_sink.clone() {
+            shell::spawn_run(cmd, sink);
+        }
+    }
+
+    fn handle_set_bio(&mut self, bio: String) {
+        self.config.bio = Some(bio.clone()).filter(|b| !b.is_empty());
+        if self.send_chat(format!("{BIO_PREFIX}{bio}")) {
+            self.bios.insert(self.username.clone(), bio);
+        }
+    }
+
+    /// Whether messages from `name` are hidden. We can't ignore ourselves.
+    pub fn is_ignored(&self, name: &str) -> bool {
+        name != self.username && self.config.ignored.iter().any(|n| n == name)
+    }
+
+    /// Adds `name` to or removes it from the ignore list, saving it to the
+    /// config file.
+    fn set_ignored(&mut self, name: String, ignore: bool) {
+        let msg = match (ignore, self.is_ignored(&name)) {
+            (true, true) => trf!("sys.already_ignored", name),
+            (false, false) => trf!("sys.not_ignored", name),
+            (true, false) if name == self.username => tr("sys.ignore_self"),
+            (true, false) => trf!("sys.ignored", name),
+            (false, true) => trf!("sys.unignored", name),
+        };
+        let ignored = &mut self.config.ignored;
+        ignored.retain(|n| *n != name);
+        if ignore && name != self.username {
+            ignored.push(name);
+        }
+        let ignored = ignored.clone();
+        let saved = user_config::reload_config().and_then(|mut config| {
+            config.ignored = ignored;
+            user_config::save_config(&config)
+        });
+        self.push_log(Log::new("CLIENT".into(), msg));
+        if let Err(e) = saved {
+            self.notify(Severity::Error, trf!("sys.save_config_failed", e));
+        }
+    }
+
+    fn handle_whois(&mut self, name: String) {
+        let msg = match self.bios.get(&name) {
+            Some(bio) if !bio.is_empty() => trf!("sys.whois", name, bio),
+            _ => trf!("sys.whois_unknown", name),
+        };
+        self.push_log(Log::new("CLIENT".into(), msg));
+    }
+
+    fn handle_show_qr(&mut self, kind: QrKind) {
+        let (title, data) = match kind {
+            QrKind::Fingerprint => match self.identity {
+                Some(ref identity) => (tr("popup.fingerprint"), identity.fingerprint()),
+                None => {
+                    self.push_log(Log::new("CLIENT".into(), tr("sys.no_identity")));
+                    return;
+                }
+            },
+            QrKind::Invite => (
+                trf!("popup.invite", self.room_state.room_name),
+                qr::invite_url(&self.server, &self.room_state.room_name),
+            ),
+        };
+        match qr::render(&data) {
+            Ok(code) => {
+                self.popup = Some(Popup {
+                    title,
+                    body: format!("{code}\n{data}"),
+                })
+            }
+            Err(e) => self.notify(Severity::Error, trf!("sys.qr_failed", e)),
+        }
+    }
+
+    /// Copies the link others can start the client with to join the room,
+    /// showing it instead if the clipboard can't be reached.
+    fn copy_join_link(&mut self) {
+        let link = qr::invite_url(&self.server, &self.room_state.room_name);
+        let msg = match copy_mode::copy_to_clipboard(&link) {
+            Ok(()) => trf!("sys.link_copied", link),
+            Err(e) => {
+                tracing::error!("Could not copy to the clipboard: {e}");
+                trf!("sys.link_not_copied", link)
+            }
+        };
+        self.push_log(Log::new("CLIENT".into(), msg));
+    }
+
+    /// Checks the key the server presented against the one pinned for it,
+    /// asking before trusting a key that changed.
+    pub fn verify_server_key(&mut self, key: &[u8; 32]) {
+        let fingerprint = KnownServers::fingerprint(key);
+        self.server_key = Some(fingerprint.clone());
+        if let ServerKeyCheck::Changed(old) = self.known_servers.check(&self.server, &fingerprint) {
+            let body = trf!(
+                "popup.server_key_changed_body",
+                self.server,
+                old,
+                fingerprint
+            );
+            self.modal = Some(
+                Modal::new(
+                    tr("popup.server_key_changed"),
+                    body,
+                    Command::TrustServerKey,
+                )
+                .with_no(Command::QuitNow)
+                .with_cancel(),
+            );
+        }
+    }
+
+    fn trust_server_key(&mut self) {
+        if let Some(fingerprint) = self.server_key.clone() {
+            self.known_servers.pin(&self.server, &fingerprint);
+            let msg = trf!("sys.server_key_trusted", self.server);
+            self.push_log(Log::new("CLIENT".into(), msg));
+        }
+    }
+
+    fn show_server_keys(&mut self) {
+        let mut servers: Vec<_> = self.known_servers.iter().collect();
+        servers.sort();
+        let body = servers
+            .into_iter()
+            .map(|(server, fingerprint)| match *server == self.server {
+                true => format!("* {server}  {fingerprint}"),
+                false => format!("  {server}  {fingerprint}"),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        self.popup = Some(Popup {
+            title: tr("popup.server_keys"),
+            body,
+        });
+    }
+
+    fn show_server_info(&mut self) {
+        self.popup = Some(Popup {
+            title: tr("popup.server_info"),
+            body: self.server_info(),
+        });
+    }
+
+    /// What is known about the connection, a line each.
+    pub fn server_info(&self) -> String {
+        let unknown = || tr("info.unknown");
+        let started = self
+            .session_started
+            .map(|ts| {
+                let log_style = &self.theme.log;
+                format!(
+                    "{} {}",
+                    log_style.format_time_with(ts, DATE_FORMAT),
+                    log_style.format_time(ts)
+                )
+            })
+            .unwrap_or_else(unknown);
+        let latency = self
+            .latency
+            .average()
+            .map(|rtt| format!("{} ms", rtt.as_millis()))
+            .unwrap_or_else(|| tr("info.not_measured"));
+        [
+            trf!("info.host", self.server),
+            trf!("info.protocol", protocol::VERSION),
+            trf!(
+                "info.server_key",
+                self.server_key.clone().unwrap_or_else(unknown)
+            ),
+            trf!("info.session_started", started),
+            trf!("info.latency", latency),
+            trf!("info.connection", self.connection),
+        ]
+        .join("\n")
+    }
+
+    /// Passes the key to the open modal, which takes every key until it's
+    /// answered. Returns false if there is no modal open.
+    pub fn answer_modal(&mut self, key: KeyCode) -> bool {
+        let Some(ref mut modal) = self.modal else {
+            return false;
+        };
+        let Some(choice) = modal.key(key) else {
+            return true;
+        };
+        let cmd = modal.command(choice);
+        self.modal = None;
+        if let Some(cmd) = cmd {
+            self.handle(cmd);
+        }
+        true
+    }
+
+    /// Quits, unless there's a draft or messages waiting for the connection,
+    /// in which case it asks first.
+    fn quit(&mut self) {
+        let drafts = self.buffer.iter().any(|line| !line.is_empty()) as usize
+            + self
+                .drafts
+                .values()
+                .filter(|(buffer, _)| buffer.iter().any(|line| !line.is_empty()))
+                .count();
+        let queued = self
+            .logs
+            .iter()
+            .filter(|l| l.delivery == Some(Delivery::Queued))
+            .count();
+        if drafts == 0 && queued == 0 {
+            self.should_quit = true;
+            return;
+        }
+        let mut body = vec![];
+        if drafts > 0 {
+            body.push(trf!("popup.quit_drafts", drafts));
+        }
+        if queued > 0 {
+            body.push(trf!("popup.quit_queued", queued));
+        }
+        body.push(tr("popup.quit_body"));
+        self.modal = Some(Modal::new(
+            tr("popup.quit"),
+            body.join("\n"),
+            Command::QuitNow,
+        ));
+    }
+
+    fn ask_clear_history(&mut self) {
+        let room = &self.room_state.room_name;
+        self.modal = Some(Modal::new(
+            tr("popup.clear_history"),
+            trf!("popup.clear_history_body", room),
+            Command::ClearHistoryNow,
+        ));
+    }
+
+    /// Forgets the current room's messages, here and on disk.
+    fn clear_history(&mut self) {
+        let room = self.room_state.room_name.clone();
+        if let Some(ref history) = self.history {
+            if let Err(e) = history.clear(&room) {
+                self.notify(Severity::Error, trf!("sys.clear_history_failed", room, e));
+                return;
+            }
+        }
+        self.logs.clear();
+        self.scroll_offset = 0;
+        self.new_below = 0;
+        self.toasts
+            .push(Severity::Success, trf!("sys.history_cleared", room));
+    }
+
+    fn open_directory(&mut self) {
+        let known = self.rooms.iter().map(|r| DirectoryEntry {
+            name: r.name.clone(),
+            occupants: Some(r.occupants),
+        });
+        let stored = self
+            .history
+            .iter()
+            .flat_map(HistoryStore::rooms)
+            .map(|name| DirectoryEntry {
+                name,
+                occupants: None,
+            });
+        self.directory = Some(Directory::new(known.chain(stored)));
+    }
+
+    /// Feeds a key to the room directory, returns false if it isn't open.
+    pub fn answer_directory(&mut self, key: KeyCode) -> bool {
+        let Some(ref mut directory) = self.directory else {
+            return false;
+        };
+        match key {
+            KeyCode::Up | KeyCode::Char('k') => directory.move_by(-1),
+            KeyCode::Down | KeyCode::Char('j') => directory.move_by(1),
+            KeyCode::PageUp => directory.move_by(-10),
+            KeyCode::PageDown => directory.move_by(10),
+            KeyCode::Enter => {
+                let room = directory.selected_room().map(str::to_string);
+                self.directory = None;
+                if let Some(room) = room {
+                    self.handle(Command::MoveRooms(Some(room)));
+                }
+            }
+            KeyCode::Esc | KeyCode::Char('q') => self.directory = None,
+            _ => {}
+        }
+        true
+    }
+
+    fn open_link_picker(&mut self) {
+        let mut found: Vec<String> = vec![];
+        let logs = self.logs.iter().filter(|l| l.kind != LogKind::Debug);
+        for link in logs.flat_map(|l| {
+            links::find(&l.msg)
+                .into_iter()
+                .map(|r| l.msg[r].to_string())
+        }) {
+            if !found.contains(&link) {
+                found.push(link);
+            }
+            if found.len() == MAX_PICKER_LINKS {
+                break;
+            }
+        }
+        match found.is_empty() {
+            true => self.push_log(Log::new("CLIENT".into(), tr("sys.no_links"))),
+            false => self.link_picker = Some(found),
+        }
+    }
+
+    /// The help overlay's contents: every mode's bindings, then the slash
+    /// commands.
+    pub fn help_text(&self) -> Text<'static> {
+        let style = &self.theme.log;
+        let mut lines = vec![];
+        for (mode, binds) in self.keymaps.sheet() {
+            lines.push(Line::styled(mode.to_string(), self.theme.title));
+            lines.extend(binds.into_iter().map(|(key, command)| {
+                Line::from(vec![
+                    Span::styled(format!("  {key:<HELP_KEY_WIDTH$}"), style.uname()),
+                    Span::styled(command, style.msg()),
+                ])
+            }));
+            lines.push(Line::default());
+        }
+        lines.push(Line::styled(tr("help.slash_commands"), self.theme.title));
+        for (usage, description) in slash_commands::help() {
+            lines.push(Line::from(vec![
+                Span::styled(format!("  {usage:<HELP_USAGE_WIDTH$}"), style.uname()),
+                Span::styled(description, style.msg()),
+            ]));
+        }
+
+        lines.into()
+    }
+
+    /// Scrolls the help overlay, or closes it. Returns false if it isn't
+    /// open.
+    pub fn answer_help(&mut self, key: KeyCode) -> bool {
+        let Some(scroll) = self.help_scroll else {
+            return false;
+        };
+        let last = self.help_text().height().saturating_sub(1);
+        let scroll = match key {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('?') => {
+                self.help_scroll = None;
+                return true;
+            }
+            KeyCode::Down | KeyCode::Char('j') => scroll + 1,
+            KeyCode::Up | KeyCode::Char('k') => scroll.saturating_sub(1),
+            KeyCode::PageDown | KeyCode::Char(' ') => scroll + HELP_PAGE,
+            KeyCode::PageUp => scroll.saturating_sub(HELP_PAGE),
+            KeyCode::Home | KeyCode::Char('g') => 0,
+            KeyCode::End | KeyCode::Char('G') => last,
+            _ => scroll,
+        };
+        self.help_scroll = Some(scroll.min(last));
+        true
+    }
+
+    /// My messages the server has, which are the ones that can be changed,
+    /// newest first.
+    fn own_messages(&self) -> impl Iterator<Item = &Log> {
+        self.logs.iter().filter(|l| {
+            l.from == self.username
+                && l.kind != LogKind::Debug
+                && !l.deleted
+                && matches!(l.delivery, None | Some(Delivery::Sent))
+        })
+    }
+
+    /// Moves the selection `by` messages older, starting at the newest.
+    fn select_own(&mut self, by: isize) {
+        let own: Vec<DateTime<Utc>> = self.own_messages().map(|l| l.ts).collect();
+        if own.is_empty() {
+            self.push_log(Log::new("CLIENT".into(), tr("sys.no_own_messages")));
+            return;
+        }
+        let at = self
+            .selected_own
+            .and_then(|ts| own.iter().position(|t| *t == ts))
+            .unwrap_or(0);
+        let at = at.saturating_add_signed(by).min(own.len() - 1);
+        self.selected_own = Some(own[at]);
+    }
+
+    /// Keys while one of my messages is selected: up and down pick another,
+    /// enter edits it, d deletes it. Returns false if nothing is selected.
+    pub fn answer_own_selection(&mut self, key: KeyCode) -> bool {
+        let Some(ts) = self.selected_own else {
+            return false;
+        };
+        match key {
+            KeyCode::Up | KeyCode::Char('k') => self.select_own(1),
+            KeyCode::Down | KeyCode::Char('j') => self.select_own(-1),
+            KeyCode::Enter | KeyCode::Char('e') => {
+                self.selected_own = None;
+                self.start_edit(ts);
+            }
+            KeyCode::Delete | KeyCode::Char('d') => {
+                self.selected_own = None;
+                self.delete_own(ts);
+            }
+            KeyCode::Esc | KeyCode::Char('q') => self.selected_own = None,
+            _ => {}
+        }
+        true
+    }
+
+    /// My messages that didn't get through, newest first. They stay in the
+    /// log until retried or discarded.
+    pub fn failed_outbox(&self) -> impl Iterator<Item = &Log> {
+        self.logs
+            .iter()
+            .filter(|l| l.from == self.username && l.delivery == Some(Delivery::Failed))
+    }
+
+    /// Moves the failed selection `by` messages older, starting at the
+    /// newest.
+    fn select_failed(&mut self, by: isize) {
+        let failed: Vec<DateTime<Utc>> = self.failed_outbox().map(|l| l.ts).collect();
+        if failed.is_empty() {
+            self.selected_failed = None;
+            self.push_log(Log::new("CLIENT".into(), tr("sys.no_failed")));
+            return;
+        }
+        let at = self
+            .selected_failed
+            .and_then(|ts| failed.iter().position(|t| *t == ts))
+            .unwrap_or(0);
+        let at = at.saturating_add_signed(by).min(failed.len() - 1);
+        self.selected_failed = Some(failed[at]);
+    }
+
+    /// Keys while a failed message is selected: up and down pick another,
+    /// enter or r sends it again, d throws it away. Returns false if
+    /// nothing is selected.
+    pub fn answer_failed_selection(&mut self, key: KeyCode) -> bool {
+        let Some(ts) = self.selected_failed else {
+            return false;
+        };
+        match key {
+            KeyCode::Up | KeyCode::Char('k') => self.select_failed(1),
+            KeyCode::Down | KeyCode::Char('j') => self.select_failed(-1),
+            KeyCode::Enter | KeyCode::Char('r') => {
+                if let Some(log) = self.take_failed(ts) {
+                    self.send_own(log.msg);
+                }
+                self.select_next_failed();
+            }
+            KeyCode::Delete | KeyCode::Char('d') => {
+                self.take_failed(ts);
+                self.select_next_failed();
+            }
+            KeyCode::Esc | KeyCode::Char('q') => self.selected_failed = None,
+            _ => {}
+        }
+        true
+    }
+
+    /// Moves the details selection `by` messages older, starting at the
+    /// newest in view.
+    fn select_log(&mut self, by: isize) {
+        let count = self.log_count();
+        if count == 0 {
+            return;
+        }
+        let at = self.selected_log.unwrap_or(self.scroll_offset);
+        let at = at.saturating_add_signed(by).min(count - 1);
+        self.selected_log = Some(at);
+        self.scroll_to_log(at);
+    }
+
+    /// Keys while a message is picked for its details: up and down pick
+    /// another, enter shows them. Returns false if nothing is picked.
+    pub fn answer_log_selection(&mut self, key: KeyCode) -> bool {
+        let Some(pos) = self.selected_log else {
+            return false;
+        };
+        match key {
+            KeyCode::Up | KeyCode::Char('k') => self.select_log(1),
+            KeyCode::Down | KeyCode::Char('j') => self.select_log(-1),
+            KeyCode::Enter => {
+                self.selected_log = None;
+                self.show_log_details(pos);
+            }
+            KeyCode::Esc | KeyCode::Char('q') => self.selected_log = None,
+            _ => {}
+        }
+        true
+    }
+
+    /// Opens a popup with everything about the log at `pos`, for when the
+    /// log's compact format leaves things out.
+    fn show_log_details(&mut self, pos: usize) {
+        let Some(log) = self
+            .logs
+            .iter()
+            .filter(|l| l.should_render(self.show_debug, self.show_system))
+            .nth(pos)
+        else {
+            return;
+        };
+        let mut body = vec![trf!("detail.from", log.from)];
+        if let Some(ref to) = log.to {
+            body.push(trf!("detail.to", to));
+        }
+        body.push(trf!(
+            "detail.utc",
+            log.ts.format("%Y-%m-%d %H:%M:%S%.3f UTC")
+        ));
+        let local = log.ts.with_timezone(&Local);
+        body.push(trf!(
+            "detail.local",
+            local.format("%Y-%m-%d %H:%M:%S%.3f %:z")
+        ));
+        if let Some(ref delivery) = log.delivery {
+            body.push(trf!("detail.delivery", delivery.name()));
+        }
+        body.push(trf!(
+            "detail.length",
+            log.msg.chars().count(),
+            log.msg.len()
+        ));
+        if log.edited {
+            body.push(tr("detail.edited"));
+        }
+        if log.deleted {
+            body.push(tr("detail.deleted"));
+        }
+        body.push(String::new());
+        body.push(log.msg.clone());
+        self.popup = Some(Popup {
+            title: tr("popup.log_details"),
+            body: body.join("\n"),
+        });
+    }
+
+    /// Keeps the selection going while failed messages are left.
+    fn select_next_failed(&mut self) {
+        let next = self.failed_outbox().next().map(|l| l.ts);
+        self.selected_failed = next;
+    }
+
+    /// Removes a failed message from the log.
+    fn take_failed(&mut self, ts: DateTime<Utc>) -> Option<Log> {
+        let at = self.logs.iter().position(|l| {
+            l.ts == ts && l.from == self.username && l.delivery == Some(Delivery::Failed)
+        })?;
+        self.logs.remove(at)
+    }
+
+    /// Puts the message in the buffer, the draft waits aside until the edit
+    /// is sent or abandoned.
+    fn start_edit(&mut self, ts: DateTime<Utc>) {
+        let Some(msg) = self
+            .own_messages()
+            .find(|l| l.ts == ts)
+            .map(|l| l.msg.clone())
+        else {
+            return;
+        };
+        self.stash_draft();
+        self.buffer = msg.split('\n').map(str::to_string).collect();
+        self.editing = Some(ts);
+        self.switch_mode(Mode::Insert);
+        self.handle_caret_move(CaretMotion::BufferBoundary, 1);
+    }
+
+    fn send_edit(&mut self, ts: DateTime<Utc>, text: String) {
+        if self.send_chat(edits::edit(ts, &text)) {
+            let change = Change::Edit {
+                ts: ts.timestamp_millis(),
+                text,
+            };
+            self.apply_change(&self.username.clone(), change);
+        }
+        self.restore_draft();
+    }
+
+    fn delete_own(&mut self, ts: DateTime<Utc>) {
+        if self.send_chat(edits::delete(ts)) {
+            let change = Change::Delete {
+                ts: ts.timestamp_millis(),
+            };
+            self.apply_change(&self.username.clone(), change);
+        }
+    }
+
+    /// Applies an edit or deletion to the sender's message, if it's here.
+    pub fn apply_change(&mut self, sender: &str, change: Change) {
+        let original = self
+            .logs
+            .iter_mut()
+            .find(|l| l.from == sender && l.ts.timestamp_millis() == change.ts());
+        match (original, change) {
+            (Some(log), Change::Edit { text, .. }) => log.edit(text),
+            (Some(log), Change::Delete { .. }) => log.delete(),
+            (None, _) => {}
+        }
+    }
+
+    /// Opens the link picked by number, any other key closes the picker.
+    /// Returns false if the picker isn't open.
+    pub fn answer_link_picker(&mut self, key: KeyCode) -> bool {
+        let Some(picker) = self.link_picker.take() else {
+            return false;
+        };
+        let picked = match key {
+            KeyCode::Char(c) => c
+                .to_digit(10)
+                .and_then(|n| picker.get((n as usize).checked_sub(1)?)),
+            _ => None,
+        };
+        if let Some(link) = picked {
+            match links::open(link) {
+                Ok(()) => self.push_log(Log::new("CLIENT".into(), trf!("sys.link_opened", link))),
+                Err(e) => self.notify(Severity::Error, trf!("sys.link_failed", link, e)),
+            }
+        }
+        true
+    }
+
+    /// The picker's lines, numbered for the keys that open them.
+    pub fn link_picker_body(&self) -> Option<String> {
+        let picker = self.link_picker.as_ref()?;
+        let lines: Vec<String> = picker
+            .iter()
+            .enumerate()
+            .map(|(i, link)| format!("{}  {link}", i + 1))
+            .collect();
+        Some(lines.join("\n"))
+    }
+
+    fn previous_room(&mut self) {
+        match self.recent_rooms.front().cloned() {
+            Some(room) => self.handle(Command::MoveRooms(Some(room))),
+            None => self.push_log(Log::new("CLIENT".into(), tr("sys.no_recent_rooms"))),
+        }
+    }
+
+    fn open_recent_rooms(&mut self) {
+        match self.recent_rooms.is_empty() {
+            true => self.push_log(Log::new("CLIENT".into(), tr("sys.no_recent_rooms"))),
+            false => self.recent_picker = true,
+        }
+    }
+
+    /// Moves to the recent room under the digit pressed, any key closes
+    /// the picker.
+    pub fn answer_recent_rooms(&mut self, key: KeyCode) -> bool {
+        if !std::mem::take(&mut self.recent_picker) {
+            return false;
+        }
+        let picked = match key {
+            KeyCode::Char(c) => c
+                .to_digit(10)
+                .and_then(|n| self.recent_rooms.get((n as usize).checked_sub(1)?)),
+            _ => None,
+        };
+        if let Some(room) = picked.cloned() {
+            self.handle(Command::MoveRooms(Some(room)));
+        }
+        true
+    }
+
+    /// The recent rooms picker's lines, numbered for the keys that pick them.
+    pub fn recent_rooms_body(&self) -> Option<String> {
+        if !self.recent_picker {
+            return None;
+        }
+        let lines: Vec<String> = self
+            .recent_rooms
+            .iter()
+            .enumerate()
+            .map(|(i, room)| format!("{}  {room}", i + 1))
+            .collect();
+        Some(lines.join("\n"))
+    }
+
+    /// Feeds a key to the open prompt, returns false if there isn't one.
+    pub fn answer_prompt(&mut self, key: KeyCode) -> bool {
+        let Some(ref mut prompt) = self.prompt else {
+            return false;
+        };
+        match key {
+            KeyCode::Char(c) => prompt.input.push(c),
+            KeyCode::Backspace => _ = prompt.input.pop(),
+            KeyCode::Enter => self.submit_prompt(),
+            KeyCode::Esc => self.skip_prompt(),
+            _ => {}
+        }
+        true
+    }
+
+    fn submit_prompt(&mut self) {
+        let Some(prompt) = self.prompt.take() else {
+            return;
+        };
+        match prompt.action {
+            PromptAction::UnlockHistory => match HistoryStore::open_encrypted(&prompt.input) {
+                Ok(store) => {
+                    self.history = Some(store);
+                    self.restore_history();
+                    self.load_pins();
+                }
+                Err(e) => {
+                    self.prompt = Some(Prompt {
+                        input: String::new(),
+                        error: Some(trf!("prompt.history_error", e)),
+                        ..prompt
+                    })
+                }
+            },
+            PromptAction::RoomKey(ref room) => match self.room_keys.set(room, &prompt.input) {
+                Ok(()) => self.push_log(Log::new("CLIENT".into(), trf!("sys.e2e_on", room))),
+                Err(e) => {
+                    self.prompt = Some(Prompt {
+                        input: String::new(),
+                        error: Some(e),
+                        ..prompt
+                    })
+                }
+            },
+        }
+    }
+
+    fn skip_prompt(&mut self) {
+        let Some(prompt) = self.prompt.take() else {
+            return;
+        };
+        match prompt.action {
+            PromptAction::UnlockHistory => {
+                self.push_log(Log::new("CLIENT".into(), tr("sys.history_locked")))
+            }
+            PromptAction::RoomKey(_) => {}
+        }
+    }
+
+    /// Asks for the passphrase the room's members agreed on. Everyone
+    /// who gives the same one can read what the others send.
+    fn ask_room_key(&mut self) {
+        let room = self.room_state.room_name.clone();
+        self.prompt = Some(Prompt {
+            title: tr("prompt.e2e_title"),
+            body: trf!("prompt.e2e_body", room),
+            input: String::new(),
+            masked: true,
+            error: None,
+            action: PromptAction::RoomKey(room),
+        });
+    }
+
+    fn forget_room_key(&mut self) {
+        let room = self.room_state.room_name.clone();
+        let msg = match self.room_keys.remove(&room) {
+            true => trf!("sys.e2e_off", room),
+            false => trf!("sys.e2e_not_on", room),
+        };
+        self.push_log(Log::new("CLIENT".into(), msg));
+    }
+
+    pub fn close_popup(&mut self) -> bool {
+        self.popup.take().is_some()
+    }
+
+    /// The status message to show next to an occupant in the room pane.
+    pub fn bio_of(&self, name: &str) -> Option<&str> {
+        self.bios
+            .get(name)
+            .map(String::as_str)
+            .filter(|b| !b.is_empty())
+    }
+
+    fn handle_cancel_upload(&mut self) {
+        if let Some(upload) = self.upload.take() {
+            let msg = trf!("sys.upload_cancelled", upload.name);
+            self.push_log(Log::new("CLIENT".into(), msg));
+        }
+    }
+
+    /// Adds a chunk of a file someone is sending, asking whether to keep the
+    /// file once it's complete.
+    pub fn receive_chunk(&mut self, from: String, chunk: Chunk) {
+        let i = match self.downloads.iter().position(|d| d.matches(&from, &chunk)) {
+            Some(i) => i,
+            None => {
+                file_transfer::make_room(&mut self.downloads, &from);
+                let Some(download) = Download::new(from, &chunk) else {
+                    let msg = trf!("sys.download_too_large", chunk.name);
+                    self.push_log(Log::new("CLIENT".into(), msg));
+                    return;
+                };
+                self.downloads.push(download);
+                self.downloads.len() - 1
+            }
+        };
+        self.downloads[i].add(chunk);
+        if self.downloads[i].is_done() {
+            let download = self.downloads.remove(i);
+            // nobody has said what to do with the others yet
+            if self.finished_downloads.len() >= file_transfer::MAX_FINISHED {
+                let msg = trf!("sys.download_dropped", download.name, download.from);
+                self.push_log(Log::new("CLIENT".into(), msg));
+                return;
+            }
+            self.finished_downloads.push_back(download);
+            self.offer_download();
+        }
+    }
+
+    /// Asks about the oldest finished download, unless something else is
+    /// being asked already.
+    pub fn offer_download(&mut self) {
+        if self.modal.is_some() {
+            return;
+        }
+        let Some(download) = self.finished_downloads.front() else {
+            return;
+        };
+        let body = trf!(
+            "popup.download_body",
+            download.from,
+            download.name,
+            download.size(),
+            self.config.download_dir().display()
+        );
+        self.modal = Some(
+            Modal::new(tr("popup.download"), body, Command::SaveDownload)
+                .with_no(Command::DiscardDownload)
+                .with_focus(Choice::No),
+        );
+    }
+
+    fn save_download(&mut self) {
+        let Some(download) = self.finished_downloads.pop_front() else {
+            return;
+        };
+        let log = match download.save(&self.config.download_dir()) {
+            Ok(path) => {
+                let msg = trf!("sys.download_saved", path.display());
+                self.toasts.push(Severity::Success, msg.clone());
+                self.with_image(Log::new("CLIENT".into(), msg), path)
+            }
+            Err(e) => {
+                let msg = trf!("sys.download_failed", download.name, e);
+                self.toasts.push(Severity::Error, msg.clone());
+                Log::new("CLIENT".into(), msg)
+            }
+        };
+        self.push_log(log);
+        self.offer_download();
+    }
+
+    fn discard_download(&mut self) {
+        if let Some(download) = self.finished_downloads.pop_front() {
+            let msg = trf!("sys.download_discarded", download.name);
+            self.push_log(Log::new("CLIENT".into(), msg));
+        }
+        self.offer_download();
+    }
+
+    /// Sends the next chunk of the in-progress upload, called every tick.
+    pub fn progress_upload(&mut self) {
+        let Some(chunk) = self.upload.as_mut().and_then(Upload::next_chunk) else {
+            return;
+        };
+        if !self.send_chat(chunk) {
+            self.handle_cancel_upload();
+            return;
+        }
+        if self.upload.as_ref().is_some_and(Upload::is_done) {
+            let upload = self.upload.take().unwrap();
+            let mut log = Log::new("CLIENT".into(), trf!("sys.upload_done", upload.name));
+            if let Some(path) = upload.path {
+                log = self.with_image(log, path);
+            }
+            self.push_log(log);
+        }
+    }
+
+    /// Attaches the file to the log if it's an image, loading its thumbnail.
+    fn with_image(&mut self, log: Log, path: PathBuf) -> Log {
+        if !graphics::is_image(&path) {
+            return log;
+        }
+        if self.graphics.is_some() {
+            self.thumbnails.load(&path);
+        }
+        log.image(path)
+    }
+
+    /// Whether a thumbnail will be drawn for `path`.
+    pub fn shows_thumbnail(&self, path: &Path) -> bool {
+        self.graphics.is_some() && self.thumbnails.get(path).is_some()
+    }
+
+    fn handle_send_staged_command(&mut self) {
+        let Some(cmd) = self.staged_command.clone() else {
+            tracing::error!("Called handler for sending staged command with no staged command");
+            return;
+        };
+        let param_string = self.render_buf();
+        self.param_history.push(&cmd, param_string.clone());
+        if let Some(cmd) = cmd.parse_params(param_string) {
+            self.handle(cmd);
+        }
+        self.buffer = vec!["".into()];
+        self.caret_offset = (1, 1);
+        self.staged_command = None;
+        self.switch_mode(Mode::Navigate);
+    }
+
+    fn handle_export_keymap(&mut self, path: String) {
+        match write_keymap(Path::new(&path), &self.keymaps.export()) {
+            Ok(_) => self.notify(Severity::Success, trf!("sys.keymap_exported", path)),
+            Err(e) => self.notify(Severity::Error, trf!("sys.keymap_export_failed", path, e)),
+        }
+    }
+
+    fn export_keys(&mut self, path: String) {
+        match write_cheat_sheet(Path::new(&path), &self.keymaps.sheet()) {
+            Ok(_) => self.notify(Severity::Success, trf!("sys.keys_exported", path)),
+            Err(e) => self.notify(Severity::Error, trf!("sys.keys_export_failed", path, e)),
+        }
+    }
+
+    fn handle_import_keymap(&mut self, path: String) {
+        let keymap = match read_keymap(Path::new(&path)) {
+            Ok(keymap) => keymap,
+            Err(e) => {
+                self.notify(Severity::Error, trf!("sys.keymap_import_failed", path, e));
+                return;
+            }
+        };
+        for problem in self.keymaps.import(keymap) {
+            self.push_log(Log::new("CLIENT".into(), problem));
+        }
+        self.notify(Severity::Success, trf!("sys.keymap_imported", path));
+    }
+
+    fn handle_export_logs(&mut self, path: String) {
+        let markdown = Path::new(&path).extension().is_some_and(|ext| ext == "md");
+        let mut out = match markdown {
+            true => format!("# {}\n\n", self.room_state.room_name),
+            false => String::new(),
+        };
+        // logs are stored newest first
+        for log in self
+            .logs
+            .iter()
+            .rev()
+            .filter(|l| l.should_render(self.show_debug, self.show_system))
+        {
+            out += &match markdown {
+                true => format!("- {log}\n"),
+                false => format!("{log}\n"),
+            };
+        }
+        self.report_export(std::fs::write(&path, out), path);
+    }
+
+    fn export_html(&mut self, path: String) {
+        // logs are stored newest first
+        let logs: Vec<&Log> = self
+            .logs
+            .iter()
+            .rev()
+            .filter(|l| l.should_render(self.show_debug, self.show_system))
+            .collect();
+        let page = html_export::render(&self.room_state.room_name, &logs, &self.theme);
+        self.report_export(std::fs::write(&path, page), path);
+    }
+
+    fn report_export(&mut self, written: std::io::Result<()>, path: String) {
+        match written {
+            Ok(_) => self.notify(Severity::Success, trf!("sys.logs_exported", path)),
+            Err(e) => self.notify(Severity::Error, trf!("sys.logs_export_failed", path, e)),
+        }
+    }
+
+    fn handle_abort_staged_command(&mut self) {
+        if let Some(_) = self.staged_command.clone() {
+            self.staged_command = None;
+        }
+
+        self.buffer = vec!["".into()];
+        self.caret_offset = (1, 1);
+        self.switch_mode(Mode::Navigate);
+    }
+
+    pub fn switch_mode(&mut self, mode: Mode) {
+        // leaving insert mode abandons an edit
+        if mode == Mode::Navigate && self.editing.take().is_some() {
+            self.restore_draft();
+        }
+        let mode = match mode {
+            Mode::Navigate if self.keymaps.modeless => Mode::Insert,
+            mode => mode,
+        };
+        let had_draft = self.mode.holds_draft();
+        if self.mode == Mode::Copy {
+            self.copy = None;
+        }
+        self.mode = mode;
+        // commands and searches borrow the buffer, the draft waits aside
+        match (had_draft, self.mode.holds_draft()) {
+            (true, false) => self.stash_draft(),
+            (false, true) => self.restore_draft(),
+            _ => {}
+        }
+        match self.mode {
+            Mode::Insert => {
+                let (r, c) = self.get_caret_2d();
+                self.set_caret_2d(r, c);
+            }
+            Mode::Navigate => {}
+            Mode::InsertCommand => {}
+            Mode::Search => {}
+            Mode::Copy => {
+                // start on the newest line in view
+                self.copy = Some(CopySelection::new(self.scroll_offset));
+            }
+            Mode::Disconnected => {}
+            Mode::RoomList => {
+                // start from the current room
+                if let Some(i) = self.current_room_index() {
+                    self.selected_room = i;
+                }
+            }
+        }
+    }
+
+    fn current_room_index(&self) -> Option<usize> {
+        self.rooms
+            .iter()
+            .position(|r| r.name == self.room_state.room_name)
+    }
+
+    fn select_room(&mut self, by: isize) {
+        let max = self.rooms.len().saturating_sub(1);
+        self.selected_room = self.selected_room.saturating_add_signed(by).min(max);
+    }
+
+    /// Wheel scrolling over the chat log scrolls it, and over the room list
+    /// moves the selection, picking rooms if that wasn't already happening.
+    pub fn handle_mouse(&mut self, event: MouseEvent) {
+        let over = |area| ui::contains(area, event.column, event.row);
+        match event.kind {
+            MouseEventKind::ScrollUp if over(self.panes.logs) => self.scroll(WHEEL_LINES),
+            MouseEventKind::ScrollDown if over(self.panes.logs) => self.scroll(-WHEEL_LINES),
+            MouseEventKind::ScrollUp if over(self.panes.debug) => self.scroll_debug(WHEEL_LINES),
+            MouseEventKind::ScrollDown if over(self.panes.debug) => self.scroll_debug(-WHEEL_LINES),
+            MouseEventKind::Down(MouseButton::Left) if over(self.panes.input) => {
+                self.click_caret(event.column, event.row)
+            }
+            MouseEventKind::ScrollUp | MouseEventKind::ScrollDown if over(self.panes.rooms) => {
+                if self.mode != Mode::RoomList {
+                    self.switch_mode(Mode::RoomList);
+                }
+                match event.kind {
+                    MouseEventKind::ScrollUp => self.select_room(-1),
+                    _ => self.select_room(1),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Moves the caret to the clicked cell of the input area, starting to
+    /// type if the app was navigating. The input isn't soft wrapped, so each
+    /// row inside the border is a buffer line and columns are graphemes.
+    fn click_caret(&mut self, column: u16, row: u16) {
+        let inner = self.panes.input.inner(&Margin::new(1, 1));
+        if !ui::contains(inner, column, row) {
+            return;
+        }
+        if matches!(self.mode, Mode::Navigate | Mode::RoomList) {
+            self.switch_mode(Mode::Insert);
+        }
+        let rows = self.visual_rows();
+        let at = (row - inner.y + self.input_scroll()) as usize;
+        self.set_caret_on_screen(&rows, at, (column - inner.x) as usize);
+    }
+
+    fn join_selected_room(&mut self) {
+        if let Some(room) = self.rooms.get(self.selected_room) {
+            self.send_server_command(Command::MoveRooms(Some(room.name.clone())));
+        }
+        self.switch_mode(Mode::Navigate);
+    }
+
+    /// What the buffer could be completed to: emoji shortcodes, then
+    /// occupants' names, then slash commands while writing a message, and
+    /// a staged command's earlier parameters, then room names when moving
+    /// rooms.
+    pub fn completions(&self) -> Vec<String> {
+        self.completions_for_input().0
+    }
+
+    /// Completions for the buffer, and whether they complete just the word
+    /// before the caret.
+    fn completions_for_input(&self) -> (Vec<String>, bool) {
+        let buf = self.render_buf();
+        match (&self.mode, &self.staged_command) {
+            (Mode::InsertCommand, Some(cmd)) => {
+                let mut candidates = completion::params(&buf, self.param_history.entries(cmd));
+                if let Command::MoveRooms(None) = cmd {
+                    for room in completion::rooms(&buf, self.rooms.iter().map(|r| r.name.as_str()))
+                    {
+                        if !candidates.contains(&room) {
+                            candidates.push(room);
+                        }
+                    }
+                }
+                (candidates, false)
+            }
+            (Mode::Insert, _) => {
+                let word = self.word_before_caret();
+                let shortcodes = match self.config.emoji_shortcodes {
+                    true => emoji::completions(word),
+                    false => vec![],
+                };
+                if !shortcodes.is_empty() {
+                    return (shortcodes, true);
+                }
+                let others = self
+                    .room_state
+                    .occupants
+                    .iter()
+                    .filter(|o| **o != self.username)
+                    .map(String::as_str);
+                match completion::usernames(word, others) {
+                    names if !names.is_empty() => (names, true),
+                    _ => (completion::commands(&buf), false),
+                }
+            }
+            _ => (vec![], false),
+        }
+    }
+
+    /// The text between the last space and the caret on the caret's line.
+    fn word_before_caret(&self) -> &str {
+        let (row, col) = self.get_caret_2d();
+        let (pre, _) = graphemes::split_at(&self.buffer[row - 1], col - 1);
+        pre.rsplit(' ').next().unwrap_or(pre)
+    }
+
+    /// Swaps the word before the caret for `replacement`, leaving the caret
+    /// after it.
+    fn replace_word_before_caret(&mut self, replacement: &str) {
+        let (row, col) = self.get_caret_2d();
+        let line = &self.buffer[row - 1];
+        let (pre, post) = graphemes::split_at(line, col - 1);
+        let word_len = pre.rsplit(' ').next().unwrap_or(pre).len();
+        let pre = format!("{}{replacement}", &pre[..pre.len() - word_len]);
+        let caret = graphemes::len(&pre) + 1;
+        self.buffer[row - 1] = pre + post;
+        self.set_caret_2d(row, caret);
+    }
+
+    fn handle_complete(&mut self) {
+        match self.completion {
+            Some(ref mut completion) => completion.next(),
+            None => {
+                let (candidates, word) = self.completions_for_input();
+                self.completion = Completion::start(candidates, word);
+            }
+        }
+        let Some(ref completion) = self.completion else {
+            return;
+        };
+        let text = completion.current().to_string();
+        match completion.word {
+            true => self.replace_word_before_caret(&text),
+            false => self.load_buffer(&text),
+        }
+    }
+
+    /// Swaps the word before the caret for the first fix the dictionary
+    /// has for it, Tab then cycles through the others.
+    fn suggest_spelling(&mut self) {
+        let Some(ref dictionary) = self.dictionary else {
+            self.notify(Severity::Info, tr("sys.spelling_off"));
+            return;
+        };
+        let word = self.word_before_caret();
+        if dictionary.check(word) {
+            return;
+        }
+        let suggestions = dictionary.suggest(word);
+        self.completion = Completion::start(suggestions, true);
+        match self.completion {
+            Some(ref completion) => {
+                let text = completion.current().to_string();
+                self.replace_word_before_caret(&text);
+            }
+            None => self.notify(Severity::Info, tr("sys.no_suggestions")),
+        }
+    }
+
+    fn submit_search(&mut self) {
+        let query = self.render_buf();
+        self.buffer = vec!["".into()];
+        self.caret_offset = (1, 1);
+        self.switch_mode(Mode::Navigate);
+        if query.is_empty() {
+            self.search = None;
+            return;
+        }
+        match Search::new(&query) {
+            Ok(search) => {
+                self.search = Some(search);
+                self.step_search(true);
+            }
+            Err(e) => {
+                self.search = None;
+                self.push_log(Log::new("CLIENT".into(), trf!("sys.bad_search", e)));
+            }
+        }
+    }
+
+    fn clear_search(&mut self) {
+        self.search = None;
+        if self.mode == Mode::Search {
+            self.buffer = vec!["".into()];
+            self.caret_offset = (1, 1);
+        }
+        self.switch_mode(Mode::Navigate);
+    }
+
+    /// Moves to the next match older or newer than the current one and
+    /// scrolls it into view. Stays put when there are no more matches.
+    fn step_search(&mut self, older: bool) {
+        let Some(ref search) = self.search else {
+            return;
+        };
+        let mut matches = self
+            .logs
+            .iter()
+            .filter(|l| l.should_render(self.show_debug, self.show_system))
+            .enumerate()
+            .filter(|(_, l)| search.is_match(&l.msg))
+            .map(|(pos, _)| pos);
+        let found = match (search.current, older) {
+            (None, _) => matches.next(),
+            (Some(current), true) => matches.find(|pos| *pos > current),
+            (Some(current), false) => matches.take_while(|pos| *pos < current).last(),
+        };
+        if let Some(pos) = found {
+            self.scroll_offset = pos;
+            if let Some(ref mut search) = self.search {
+                search.current = Some(pos);
+            }
+        }
+    }
+
+    /// Moves the copy cursor, scrolling to keep it in view.
+    fn move_copy_cursor(&mut self, by: isize) {
+        let count = self.log_count();
+        let Some(ref mut selection) = self.copy else {
+            return;
+        };
+        selection.move_by(by, count);
+        let cursor = selection.cursor;
+        self.scroll_to_log(cursor);
+    }
+
+    /// Scrolls just enough for the log at `pos` to be in view.
+    fn scroll_to_log(&mut self, pos: usize) {
+        let visible = (self.panes.logs.height as usize).saturating_sub(2).max(1);
+        if pos < self.scroll_offset {
+            self.scroll_offset = pos;
+        } else if pos >= self.scroll_offset + visible {
+            self.scroll_offset = pos + 1 - visible;
+        }
+    }
+
+    /// Copies the selected lines, oldest first, and leaves copy mode.
+    fn pin_message(&mut self) {
+        let pos = match self.copy {
+            Some(ref selection) => selection.cursor,
+            None => self.scroll_offset,
+        };
+        let Some(log) = self
+            .logs
+            .iter()
+            .filter(|l| l.should_render(self.show_debug, self.show_system))
+            .nth(pos)
+            .cloned()
+        else {
+            return;
+        };
+        let pinned = self
+            .pins
+            .iter()
+            .position(|p| p.ts == log.ts && p.from == log.from && p.msg == log.msg);
+        let msg = match pinned {
+            Some(i) => {
+                self.pins.remove(i);
+                tr("sys.unpinned")
+            }
+            None => {
+                self.pins.push(Log::new(log.from, log.msg).at(log.ts));
+                self.show_pins = true;
+                tr("sys.pinned")
+            }
+        };
+        if let Some(ref history) = self.history {
+            history.save_pins(&self.room_state.room_name, &self.pins);
+        }
+        self.push_log(Log::new("CLIENT".into(), msg));
+    }
+
+    /// The pins stored for the current room, none if history isn't kept.
+    fn load_pins(&mut self) {
+        self.pins = match self.history {
+            Some(ref history) => history.load_pins(&self.room_state.room_name),
+            None => vec![],
+        };
+    }
+
+    fn yank_selection(&mut self) {
+        let Some(selection) = self.copy.take() else {
+            return;
+        };
+        let mut lines: Vec<String> = self
+            .logs
+            .iter()
+            .filter(|l| l.should_render(self.show_debug, self.show_system))
+            .enumerate()
+            .filter(|(pos, _)| selection.contains(*pos))
+            .map(|(_, l)| l.to_string())
+            .collect();
+        lines.reverse();
+        let text = lines.join("\n");
+        let msg = match copy_mode::copy_to_clipboard(&text) {
+            Ok(()) => trf!("sys.copied", lines.len()),
+            Err(e) => {
+                tracing::error!("Could not copy to the clipboard: {e}");
+                trf!("sys.copied_to_buffer", lines.len())
+            }
+        };
+        // Ctrl+Y pastes copied lines as well as cut text
+        self.kill_ring.push_front(text.clone());
+        self.kill_ring.truncate(MAX_KILLS);
+        self.yanked = Some(text);
+        self.switch_mode(Mode::Navigate);
+        self.push_log(Log::new("CLIENT".into(), msg));
+    }
+
+    /// Inserts the copied text at the caret, line breaks and all.
+    fn paste_yanked(&mut self) {
+        if let Some(text) = self.yanked.clone() {
+            self.insert_at_caret(&text);
+        }
+    }
+
+    /// Cuts from the caret to the start or end of the line, or back to the
+    /// start of the word, onto the kill ring.
+    fn kill(&mut self, motion: CaretMotion, direction: isize) {
+        self.checkpoint();
+        let (row, col) = self.get_caret_2d();
+        let line = &self.buffer[row - 1];
+        let (from, to) = match (motion, direction < 0) {
+            (CaretMotion::Word, true) => (graphemes::word_left(line, col - 1), col - 1),
+            (CaretMotion::Word, false) => (col - 1, graphemes::word_right(line, col - 1)),
+            (_, true) => (0, col - 1),
+            (_, false) => (col - 1, graphemes::len(line)),
+        };
+        if from == to {
+            return;
+        }
+        let (pre, _) = graphemes::split_at(line, to);
+        let (_, killed) = graphemes::split_at(pre, from);
+        self.kill_ring.push_front(killed.to_string());
+        self.kill_ring.truncate(MAX_KILLS);
+        self.buffer[row - 1] = graphemes::delete_range(line, from, to);
+        self.set_caret_2d(row, from + 1);
+    }
+
+    /// Inserts the latest kill at the caret.
+    fn yank(&mut self) {
+        if let Some(text) = self.kill_ring.front().cloned() {
+            self.insert_at_caret(&text);
+        }
+    }
+
+    /// Inserts `text` at the caret, leaving the caret after it. Each line
+    /// break in it starts a new buffer line.
+    fn insert_at_caret(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        self.checkpoint();
+        let (row, col) = self.get_caret_2d();
+        let (pre, post) = graphemes::split_at(&self.buffer[row - 1], col - 1);
+        let (pre, post) = (pre.to_string(), post.to_string());
+        let mut pasted: Vec<String> = text.split('\n').map(String::from).collect();
+        let last = pasted.len() - 1;
+        let caret_col = graphemes::len(&pasted[last]) + 1;
+        let caret_col = match last {
+            0 => caret_col + graphemes::len(&pre),
+            _ => caret_col,
+        };
+        pasted[0] = pre + pasted[0].as_str();
+        pasted[last] += &post;
+        self.buffer.splice(row - 1..row, pasted);
+        self.set_caret_2d(row + last, caret_col);
+    }
+
+    /// Takes text pasted into the terminal in one go, rather than as a key
+    /// press per character, so that a paste is a single edit to undo.
+    pub fn paste(&mut self, text: &str) {
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        if let Some(ref mut prompt) = self.prompt {
+            prompt.input += text.lines().next().unwrap_or_default();
+            return;
+        }
+        if self.monitor {
+            return;
+        }
+        match self.mode {
+            Mode::Insert | Mode::InsertCommand | Mode::Search => {}
+            Mode::Navigate => self.switch_mode(Mode::Insert),
+            _ => return,
+        }
+        self.insert_at_caret(&text);
+    }
+
+    /// Remembers the buffer as it is, for [`Command::Undo`].
+    fn checkpoint(&mut self) {
+        self.undo.push((self.buffer.clone(), self.caret_offset));
+        if self.undo.len() > MAX_UNDO {
+            self.undo.remove(0);
+        }
+    }
+
+    fn undo(&mut self) {
+        if let Some((buffer, caret)) = self.undo.pop() {
+            self.buffer = buffer;
+            self.caret_offset = caret;
+        }
+    }
+
+    fn history(&mut self) -> &mut InputHistory {
+        match (&self.mode, &self.staged_command) {
+            (Mode::InsertCommand, Some(cmd)) => self.param_history.for_command(cmd),
+            _ => &mut self.send_history,
+        }
+    }
+
+    /// Up and down browse the history instead of moving the caret while
+    /// the buffer is empty or still holds the entry last recalled.
+    fn recalls_history(&mut self) -> bool {
+        let buf = self.render_buf();
+        buf.is_empty() || self.history().current() == Some(buf.as_str())
+    }
+
+    fn recall_history(&mut self, older: bool) {
+        let buf = self.render_buf();
+        let entry = match older {
+            true => self.history().older(&buf),
+            false => self.history().newer(),
+        };
+        if let Some(entry) = entry {
+            self.load_buffer(&entry);
+        }
+    }
+
+    pub fn handle_send(&mut self) {
+        self.undo.clear();
+        let contents = self.render_buf();
+        self.send_history.push(contents.clone());
+        if let Some(ts) = self.editing.take() {
+            return self.send_edit(ts, contents);
+        }
+        match slash_commands::parse(&contents) {
+            Some(Ok(cmd)) => self.handle(cmd),
+            Some(Err(_)) if self.plugin_command(&contents) => {}
+            Some(Err(e)) => self.push_log(Log::new("CLIENT".into(), e)),
+            // a paste service would get what an encrypted room keeps from the server
+            None if paste::should_paste(&self.config.paste, &contents)
+                && !self.room_keys.is_encrypted(&self.room_state.room_name) =>
+            {
+                let Some(sink) = self.command_sink.clone() else {
+                    return;
+                };
+                self.push_log(Log::new("CLIENT".into(), tr("sys.pasting")));
+                paste::spawn_paste(&self.config.paste, contents, sink);
+            }
+            None => {
+                let contents = match self.config.emoji_shortcodes {
+                    true => emoji::expand(&contents),
+                    false => contents,
+                };
+                let contents = self.plugins.before_send(contents);
+                self.run_plugin_actions();
+                if let Some(ref contents) = contents {
+                    // refused here rather than by the server, the text stays
+                    let size = self.outgoing_size(contents);
+                    if size > self.size_limit() {
+                        let max = self.size_limit();
+                        self.push_log(Log::new(
+                            "CLIENT".into(),
+                            trf!("sys.message_too_long", size, max),
+                        ));
+                        return;
+                    }
+                }
+                // a failed send stays in the buffer to try again
+                if contents.is_some_and(|contents| !self.send_own(contents)) {
+                    return;
+                }
+            }
+        }
+        self.buffer = vec!["".into()];
+        self.caret_offset = (1, 1);
+    }
+
+    /// Sends a message of mine and shows it in the log until the server
+    /// echoes it, returns false if it could not be sent.
+    fn send_own(&mut self, contents: String) -> bool {
+        if !self.send_chat(contents.clone()) {
+            self.push_own_log(contents, Delivery::Failed);
+            return false;
+        }
+        self.push_own_log(contents, Delivery::Pending);
+        true
+    }
+
+    /// Offers a slash command the client doesn't know to the plugins,
+    /// returns true if one of them handled it.
+    fn plugin_command(&mut self, input: &str) -> bool {
+        let Some(input) = input.strip_prefix('/') else {
+            return false;
+        };
+        let (name, args) = input.split_once(' ').unwrap_or((input, ""));
+        let handled =
+            !slash_commands::NAMES.contains(&name) && self.plugins.command(name, args.trim());
+        self.run_plugin_actions();
+        handled
+    }
+
+    /// Carries out what the plugins asked for during their last hooks.
+    pub fn run_plugin_actions(&mut self) {
+        for action in self.plugins.take_actions() {
+            match action {
+                Action::Send(contents) => {
+                    self.send_own(contents);
+                }
+                Action::Echo(text) => self.push_log(Log::new("PLUGIN".into(), text)),
+            }
+        }
+    }
+
+    /// Sends a chat message to the current room, returns false if the
+    /// message could not be handed over to the event loop.
+    pub fn send_chat(&self, contents: String) -> bool {
+        let contents = match self.identity {
+            Some(ref identity) => identity.sign(&contents),
+            None => contents,
+        };
+        self.send_unsigned(contents)
+    }
+
+    fn send_unsigned(&self, contents: String) -> bool {
+        // status messages included
+        if self.monitor {
+            return true;
+        }
+        let chat_log = Log::new(self.username.clone(), contents);
+        let Some((chan, tok)) = self.command_sink.as_ref().zip(self.token.as_ref()) else {
+            return true;
+        };
+        let Some(contents) = self.room_keys.seal(
+            &self.room_state.room_name,
+            &self.username,
+            chat_log.get_msg_body(),
+        ) else {
+            return false;
+        };
+        // too big for one message, it goes in parts put back together at
+        // the other end, as does one that would be mistaken for a part
+        let parts = match self.serialized_size(contents.clone()) > self.config.max_message_bytes
+            || fragments::looks_like_part(&contents)
+        {
+            true => fragments::split(&contents, self.part_size()),
+            false => vec![contents],
+        };
+        parts.into_iter().all(|contents| {
+            chan.try_send(Event::Send {
+                token: tok.clone(),
+                username: self.username.clone(),
+                timestamp: chat_log.get_ts(),
+                contents,
+            })
+            .is_ok()
+        })
+    }
+
+    /// Bytes of a long message that fit in each part.
+    fn part_size(&self) -> usize {
+        let overhead = self.serialized_size(fragments::HEADER_MAX.into());
+        self.config.max_message_bytes.saturating_sub(overhead)
+    }
+
+    /// Largest message that can be sent, in as many parts as it takes.
+    fn size_limit(&self) -> usize {
+        self.part_size() * fragments::MAX_PARTS
+    }
+
+    /// Shows a message we sent without waiting for the server to echo it.
+    fn push_own_log(&mut self, contents: String, delivery: Delivery) {
+        let mut log = self.chat_log(self.username.clone(), contents);
+        log.msg = self.transforms.apply(&log.from, log.msg);
+        self.push_log(log.delivery(delivery));
+    }
+
+    /// Marks the oldest pending copy of an echoed message as sent, returns
+    /// false if there is none and the echo should be logged as usual.
+    pub fn confirm_delivery(&mut self, echo: &Log) -> bool {
+        let pending = self.logs.iter_mut().rev().find(|l| {
+            l.delivery == Some(Delivery::Pending) && l.from == echo.from && l.msg == echo.msg
+        });
+        match pending {
+            Some(log) => {
+                log.delivery = Some(Delivery::Sent);
+                // edits name the message by the server's timestamp
+                log.retime(echo.ts);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Gives up on pending messages that haven't been echoed in time.
+    pub fn expire_pending(&mut self) {
+        let cutoff = Utc::now() - chrono::Duration::seconds(DELIVERY_TIMEOUT_SECS);
+        self.logs
+            .iter_mut()
+            .filter(|l| l.delivery == Some(Delivery::Pending) && l.ts < cutoff)
+            .for_each(|l| l.delivery = Some(Delivery::Failed));
+    }
+
+    /// Messages that were pending when the socket turned out to be gone
+    /// are queued with them for the next reconnect.
+    pub fn mark_queued(&mut self) {
+        self.logs
+            .iter_mut()
+            .filter(|l| l.delivery == Some(Delivery::Pending))
+            .for_each(|l| l.delivery = Some(Delivery::Queued));
+    }
+
+    /// Called once a reconnect has sent the queue, the queued messages are
+    /// now waiting on the server like any other.
+    pub fn reconnected(&mut self, flushed: usize) {
+        let now = Utc::now();
+        self.logs
+            .iter_mut()
+            .filter(|l| l.delivery == Some(Delivery::Queued))
+            .for_each(|l| {
+                l.delivery = Some(Delivery::Pending);
+                l.retime(now);
+            });
+        self.switch_mode(Mode::Navigate);
+        self.notify(Severity::Success, trf!("sys.reconnected", flushed));
+        self.resyncing = true;
+    }
+
+    /// Adds the logs only this client has to the room's history from the
+    /// server, returning how many of the server's it didn't have. Our own
+    /// messages still in flight are matched on their text, the server's
+    /// copy has its own time.
+    fn resync_logs(&self, chat_logs: &mut Vec<Log>) -> usize {
+        let same = |a: &Log, b: &Log| a.ts == b.ts && a.from == b.from && a.msg == b.msg;
+        let in_flight = |l: &Log| {
+            matches!(l.delivery, Some(Delivery::Pending | Delivery::Queued))
+                && chat_logs.iter().any(|s| s.from == l.from && s.msg == l.msg)
+        };
+        let missed = chat_logs
+            .iter()
+            .filter(|s| s.kind == LogKind::Chat && !self.logs.iter().any(|l| same(l, s)))
+            .count();
+        let local: Vec<Log> = self
+            .logs
+            .iter()
+            .filter(|l| !chat_logs.iter().any(|s| same(s, l)) && !in_flight(l))
+            .cloned()
+            .collect();
+        chat_logs.extend(local);
+        missed
+    }
+
+    /// Marks everything still pending as failed, e.g. when the socket is gone.
+    pub fn fail_newest_pending(&mut self) {
+        if let Some(log) = self
+            .logs
+            .iter_mut()
+            .rev()
+            .find(|l| l.delivery == Some(Delivery::Pending))
+        {
+            log.delivery = Some(Delivery::Failed);
+        }
+    }
+
+    pub fn fail_pending(&mut self) {
+        self.logs
+            .iter_mut()
+            .filter(|l| l.delivery == Some(Delivery::Pending))
+            .for_each(|l| l.delivery = Some(Delivery::Failed));
+    }
+
+    /// Debug entries go to their own buffer so they neither clutter the
+    /// chat log nor push chat out of it.
+    pub fn push_debug_log(&mut self, data: impl Debug) {
+        if self.debug_scroll > 0 {
+            self.debug_scroll += 1;
+        }
+        self.debug_logs.push_front(Log::new_debug(data));
+        if self.debug_logs.len() > MAX_DEBUG_LOGS {
+            self.debug_logs.truncate(MAX_DEBUG_LOGS);
+            self.dropped_debug_logs += 1;
+        }
+    }
+
+    /// Where the view is in the chat log for the scrollbar: the number of
+    /// messages shown, and how many the view is from the oldest.
+    pub fn log_position(&self) -> (usize, usize) {
+        let count = self.log_count();
+        (count, count.saturating_sub(self.scroll_offset + 1))
+    }
+
+    fn log_count(&self) -> usize {
         self.logs
             .iter()
-            .filter(|l| l.should_render(self.show_debug))
+            .filter(|l| l.should_render(self.show_debug, self.show_system))
             .count()
     }
 
+    /// Pushes a log that arrived from the server. Logs that arrive while the
+    /// terminal is unfocused are counted as unread until the user is back.
+    pub fn push_incoming_log(&mut self, log: Log) {
+        if !self.focused && log.should_render(false, true) {
+            self.unread_count += 1;
+            let room = self.room_state.room_name.clone();
+            self.count_unread_in(&room);
+        }
+        self.push_log(log);
+    }
+
+    /// Builds the log for a chat message, checking its signature if it has one.
+    pub fn chat_log(&mut self, sender: String, content: String) -> Log {
+        let (msg, signature) = self.known_signers.verify(&sender, &content);
+        if let Some(bio) = msg.strip_prefix(BIO_PREFIX) {
+            self.bios.insert(sender.clone(), bio.trim().to_string());
+        }
+        match ephemeral::parse(&msg) {
+            Some((ttl, contents)) => Log::new(sender, contents.to_string())
+                .signed(signature)
+                .ephemeral(Some(ttl)),
+            None => Log::new(sender, msg).signed(signature),
+        }
+    }
+
+    /// A message mentions the user if it contains their username, ignoring case.
+    pub fn is_mention(&self, sender: &str, content: &str) -> bool {
+        sender != self.username
+            && self
+                .config
+                .notifications
+                .mentions
+                .matches(&self.username, content)
+    }
+
+    /// Records keyboard activity, returning from away if necessary.
+    pub fn register_input(&mut self) {
+        self.last_input = Utc::now();
+        if self.presence != Presence::Online {
+            self.set_presence(Presence::Online);
+        }
+    }
+
+    /// Goes away once there has been no input, or the terminal has been in
+    /// the background, for as long as configured.
+    pub fn check_idle(&mut self) {
+        if self.presence != Presence::Online {
+            return;
+        }
+        let away = &self.config.away;
+        let now = Utc::now();
+        let idle = away
+            .after_secs
+            .is_some_and(|secs| (now - self.last_input).num_seconds() >= secs as i64);
+        let unfocused = match (away.unfocused_secs, self.unfocused_since) {
+            (Some(secs), Some(since)) => (now - since).num_seconds() >= secs as i64,
+            _ => false,
+        };
+        if idle || unfocused {
+            self.set_presence(Presence::Away(away.message.clone()));
+        }
+    }
+
+    fn set_presence(&mut self, presence: Presence) {
+        self.presence = presence;
+        if self.config.away.announce {
+            self.send_chat(trf!("sys.presence_changed", self.presence));
+        }
+        if self.config.away.notify {
+            self.send_chat(self.presence.marker());
+        }
+    }
+
+    /// Records someone's presence update, returns false if the message
+    /// isn't one.
+    pub fn take_presence(&mut self, sender: &str, content: &str) -> bool {
+        let Some(presence) = Presence::parse(content) else {
+            return false;
+        };
+        match presence {
+            Presence::Online => self.presences.remove(sender),
+            away => self.presences.insert(sender.to_string(), away),
+        };
+        true
+    }
+
+    /// Someone's presence, if they've said they're away.
+    pub fn presence_of(&self, name: &str) -> Option<&Presence> {
+        match name == self.username {
+            true => Some(&self.presence).filter(|p| **p != Presence::Online),
+            false => self.presences.get(name),
+        }
+    }
+
+    pub fn matches_keyword(&self, sender: &str, content: &str) -> bool {
+        let content = content.to_lowercase();
+        sender != self.username
+            && self
+                .config
+                .notifications
+                .keywords
+                .iter()
+                .any(|k| content.contains(&k.to_lowercase()))
+    }
+
+    /// Checks the extra highlight words configured for the current room,
+    /// these are treated like a mention of the user.
+    pub fn matches_room_keyword(&self, sender: &str, content: &str) -> bool {
+        let Some(keywords) = self
+            .config
+            .notifications
+            .room_keywords
+            .get(&self.room_state.room_name)
+        else {
+            return false;
+        };
+        let content = content.to_lowercase();
+        sender != self.username && keywords.iter().any(|k| content.contains(&k.to_lowercase()))
+    }
+
+    pub fn set_focus(&mut self, focused: bool) {
+        self.focused = focused;
+        match focused {
+            // looking at the terminal again counts as being back
+            true => {
+                self.unfocused_since = None;
+                self.unread_mentions = 0;
+                self.register_input();
+                self.catch_up();
+            }
+            false => {
+                self.unfocused_since = Some(Utc::now());
+                if self.unread_count == 0 {
+                    self.last_read = self.logs.front().map(|l| l.ts);
+                }
+            }
+        }
+    }
+
+    /// Scrolls back to the first message that came while the terminal was
+    /// in the background, just under the unread divider.
+    fn catch_up(&mut self) {
+        if self.unread_count == 0 {
+            return;
+        }
+        let first_unread = self.unread_count - 1;
+        self.scroll_offset = first_unread.min(self.log_count().saturating_sub(1));
+        self.new_below = self.scroll_offset;
+    }
+
+    /// Called on user input, which is the only reliable sign that the unread
+    /// messages have actually been seen.
+    pub fn mark_read(&mut self) {
+        if self.focused {
+            self.unread_count = 0;
+            self.last_read = None;
+            let room = self.room_state.room_name.clone();
+            self.reset_unread_in(&room);
+        }
+    }
+
+    /// Chat messages only carry a room implicitly, the one we're in, so
+    /// this is counted for the active room while the terminal is unfocused.
+    pub fn count_unread_in(&mut self, room: &str) {
+        if let Some(room) = self.rooms.iter_mut().find(|r| r.name == room) {
+            room.unread += 1;
+        }
+    }
+
+    fn reset_unread_in(&mut self, room: &str) {
+        if let Some(room) = self.rooms.iter_mut().find(|r| r.name == room) {
+            room.unread = 0;
+        }
+    }
+
+    /// Title for the terminal window or tab: the room, with a badge
+    /// counting what came while the terminal was in the background.
+    pub fn window_title(&self) -> String {
+        let room = &self.room_state.room_name;
+        if room.is_empty() {
+            return tr("title.app");
+        }
+        let badge = match (self.focused, self.unread_count, self.unread_mentions) {
+            (true, ..) | (false, 0, _) => String::new(),
+            (false, unread, 0) => trf!("title.unread", unread),
+            (false, unread, mentions) => trf!("title.unread_mentions", unread, mentions),
+        };
+        trf!("title.room", badge, room)
+    }
+
+    pub fn current_room_unread(&self) -> usize {
+        self.current_room_index()
+            .map(|i| self.rooms[i].unread)
+            .unwrap_or(0)
+    }
+
     pub fn push_log(&mut self, log: Log) {
-        self.logs.push_front(log);
-        if self.log_count() > 100 {
-            self.logs.pop_back();
+        if log.should_render(false, true) && kind_of(&log) == EventKind::Chat {
+            self.room_counts.count(&self.room_state.room_name);
         }
+        if let Some(ref mut event_log) = self.event_log {
+            if log.should_render(false, true) {
+                event_log.write(kind_of(&log), &self.room_state.room_name, &log);
+            }
+        }
+        if let Some(ref mut transcripts) = self.transcripts {
+            if log.kind == LogKind::Chat && log.delivery != Some(Delivery::Failed) {
+                transcripts.write(&self.room_state.room_name, &log);
+            }
+        }
+        self.store_log(log);
+    }
+
+    /// Tells the user something in a toast as well as in the log, for what
+    /// shouldn't go unnoticed.
+    pub fn notify(&mut self, severity: Severity, msg: String) {
+        self.toasts.push(severity, msg.clone());
+        self.push_log(Log::new("CLIENT".into(), msg));
+    }
+
+    pub fn expire_toasts(&mut self) {
+        if self.toasts.expire() {
+            self.dirty = true;
+        }
+    }
+
+    /// Drops ephemeral messages whose time to live has passed.
+    pub fn expire_logs(&mut self) {
+        let now = Utc::now();
+        self.logs.retain(|l| !l.is_expired(now));
+    }
+
+    fn scroll(&mut self, by: isize) {
+        if self.scroll_offset == 0 {
+            self.new_below = 0;
+        }
+        let max = self.log_count().saturating_sub(1);
+        self.scroll_offset = self.scroll_offset.saturating_add_signed(by).min(max);
+    }
+
+    fn store_log(&mut self, log: Log) {
+        // keep the view still while scrolled back
+        if log.should_render(self.show_debug, self.show_system) {
+            if self.monitor && self.config.monitor.auto_scroll {
+                self.scroll_offset = 0;
+                self.new_below = 0;
+            } else if self.scroll_offset > 0 {
+                self.scroll_offset += 1;
+                self.new_below += 1;
+            }
+            if let Some(current) = self.search.as_mut().and_then(|s| s.current.as_mut()) {
+                *current += 1;
+            }
+            if let Some(ref mut selection) = self.copy {
+                selection.cursor += 1;
+                if let Some(ref mut anchor) = selection.anchor {
+                    *anchor += 1;
+                }
+            }
+        }
+        self.logs.push_front(log);
+        self.trim_logs();
+    }
+
+    /// Drops the oldest logs past the retention limits, counting every log
+    /// whether or not it's shown. The newest always stays.
+    fn trim_logs(&mut self) {
+        let RetentionConfig {
+            max_messages,
+            max_bytes,
+        } = self.config.retention;
+        let mut bytes = 0;
+        let keep = self
+            .logs
+            .iter()
+            .take(max_messages)
+            .take_while(|l| {
+                bytes += l.approx_size();
+                bytes <= max_bytes
+            })
+            .count();
+        self.logs.truncate(keep.max(1));
+    }
+
+    /// Approximate bytes the chat log takes up.
+    pub fn logs_size(&self) -> usize {
+        self.logs.iter().map(Log::approx_size).sum()
     }
 
     pub fn update_room(
@@ -447,39 +3852,408 @@ impl App {
         dt: DateTime<Utc>,
         room_name: String,
     ) {
+        let switching = room_name != self.room_state.room_name;
+        if switching && self.mode.holds_draft() {
+            self.stash_draft();
+        }
+        if switching {
+            self.leave_room(&room_name);
+            self.park_tab();
+            if !self.tabs.iter().any(|t| t.name == room_name) {
+                self.tabs.push(Tab {
+                    name: room_name.clone(),
+                    ..Default::default()
+                });
+            }
+        }
+        if let Some(ref mut directory) = self.directory {
+            directory.update(&room_name, occupants.len());
+        }
+        match self.rooms.iter_mut().find(|r| r.name == room_name) {
+            Some(room) => {
+                room.occupants = occupants.len();
+                room.unread = 0;
+            }
+            None => self.rooms.push(RoomSummary {
+                name: room_name.clone(),
+                occupants: occupants.len(),
+                unread: 0,
+            }),
+        }
+        if switching {
+            self.occupants_since.clear();
+        }
+        self.occupants_since
+            .retain(|name, _| occupants.contains(name));
+        for name in &occupants {
+            self.occupants_since.entry(name.clone()).or_insert(dt);
+        }
         self.room_state = RoomData {
             timestamp: dt,
             occupants,
             room_name,
         };
+        if switching && self.mode.holds_draft() {
+            self.restore_draft();
+        }
+        if switching {
+            self.load_pins();
+        }
+        if let Some(ref mut event_log) = self.event_log {
+            let joined = Log::new("CLIENT".into(), self.room_state.room_name.clone()).at(dt);
+            event_log.write(EventKind::Room, &self.room_state.room_name, &joined);
+        }
+        self.merge_history(&mut chat_logs, true);
+        // the server never had these, they wait in the log for a retry
+        if !switching {
+            chat_logs.extend(self.failed_outbox().cloned());
+        }
         chat_logs.extend(notifications);
+        let resynced = match std::mem::take(&mut self.resyncing) && !switching {
+            true => Some(self.resync_logs(&mut chat_logs)),
+            false => None,
+        };
         chat_logs.sort_by(|a, b| a.ts.cmp(&b.ts));
         self.replace_logs(chat_logs);
+        if let Some(missed) = resynced {
+            self.push_log(Log::new("CLIENT".into(), trf!("sys.resynced", missed)));
+        }
+        if self.creating_room.as_ref() == Some(&self.room_state.room_name) {
+            self.creating_room = None;
+            let msg = trf!("sys.room_created", self.room_state.room_name);
+            self.push_log(Log::new("CLIENT".into(), msg));
+        }
+    }
+
+    /// Index of the tab in view.
+    pub fn active_tab(&self) -> Option<usize> {
+        self.tabs
+            .iter()
+            .position(|t| t.name == self.room_state.room_name)
+    }
+
+    /// Keeps the logs and occupants in view with the room's tab, for
+    /// showing again straight away when the tab comes back.
+    fn park_tab(&mut self) {
+        let room = &self.room_state.room_name;
+        if let Some(tab) = self.tabs.iter_mut().find(|t| &t.name == room) {
+            tab.logs = std::mem::take(&mut self.logs);
+            tab.occupants = self.room_state.occupants.clone();
+        }
+    }
+
+    /// Shows the welcome screen once the room landed in is known, or once
+    /// the wait for it is over if the server said anything.
+    pub fn check_welcome(&mut self, room_known: bool) {
+        let Some(ref welcome) = self.welcome else {
+            return;
+        };
+        let room = (
+            self.room_state.room_name.as_str(),
+            &self.room_state.occupants[..],
+        );
+        let body = match (room_known, welcome.open()) {
+            (true, true) => welcome.body(Some(room)),
+            (false, false) if welcome.has_motd() => welcome.body(None),
+            (false, true) => return,
+            (_, false) => {
+                self.welcome = None;
+                return;
+            }
+        };
+        self.popup = Some(Popup {
+            title: trf!("welcome.title", self.server),
+            body,
+        });
+        self.welcome = None;
+    }
+
+    /// Puts the room being left at the top of the recent rooms, and takes
+    /// the one being entered off them. Before the first room there's only
+    /// a placeholder to leave.
+    fn leave_room(&mut self, entering: &str) {
+        let left = self.room_state.room_name.clone();
+        self.recent_rooms.retain(|r| *r != left && r != entering);
+        if self.rooms.iter().any(|r| r.name == left) {
+            self.recent_rooms.push_front(left);
+        }
+        self.recent_rooms.truncate(MAX_RECENT_ROOMS);
+    }
+
+    /// Shows the tab's room as it was last seen, and moves there on the
+    /// server, which only sends for the room a session is in. The room data
+    /// that comes back brings the logs up to date.
+    fn switch_tab(&mut self, index: usize) {
+        let Some(name) = self.tabs.get(index).map(|t| t.name.clone()) else {
+            return;
+        };
+        if name == self.room_state.room_name {
+            return;
+        }
+        if self.mode.holds_draft() {
+            self.stash_draft();
+        }
+        self.leave_room(&name);
+        self.park_tab();
+        let tab = &mut self.tabs[index];
+        self.logs = std::mem::take(&mut tab.logs);
+        self.room_state.occupants = std::mem::take(&mut tab.occupants);
+        self.room_state.room_name = name.clone();
+        self.scroll_offset = 0;
+        if self.mode.holds_draft() {
+            self.restore_draft();
+        }
+        self.send_server_command(Command::MoveRooms(Some(name)));
+    }
+
+    fn cycle_tab(&mut self, by: isize) {
+        if self.tabs.is_empty() {
+            return;
+        }
+        let from = self.active_tab().unwrap_or(0) as isize;
+        let index = (from + by).rem_euclid(self.tabs.len() as isize);
+        self.switch_tab(index as usize);
+    }
+
+    fn close_tab(&mut self) {
+        let Some(index) = self.active_tab() else {
+            return;
+        };
+        if self.tabs.len() < 2 {
+            return;
+        }
+        self.switch_tab(index.checked_sub(1).unwrap_or(1));
+        self.tabs.remove(index);
+    }
+
+    /// Adds stored messages the server no longer sends to `chat_logs`, and
+    /// with `save` stores what was said while we were away.
+    fn merge_history(&self, chat_logs: &mut Vec<Log>, save: bool) {
+        let Some(ref history) = self.history else {
+            return;
+        };
+        let room = &self.room_state.room_name;
+        let stored = history.load(room, self.config.retention.max_messages);
+        let same = |a: &Log, b: &Log| a.ts == b.ts && a.from == b.from && a.msg == b.msg;
+        if save {
+            for log in chat_logs.iter() {
+                if log.ttl.is_none() && !stored.iter().any(|s| same(s, log)) {
+                    history.append(room, log);
+                }
+            }
+        }
+        let older: Vec<Log> = stored
+            .into_iter()
+            .filter(|s| !chat_logs.iter().any(|l| same(l, s)))
+            .collect();
+        chat_logs.extend(older);
+    }
+
+    /// Brings stored history into the logs already shown, for when the
+    /// store was unlocked after joining the room.
+    fn restore_history(&mut self) {
+        let mut logs: Vec<Log> = self.logs.iter().rev().cloned().collect();
+        self.merge_history(&mut logs, false);
+        logs.sort_by_key(|l| l.ts);
+        self.replace_logs(logs);
+    }
+
+    /// Saves a chat message to the history store. Messages we send come
+    /// back from the server like any other, so they're saved here too.
+    /// Ephemeral messages are never written to disk.
+    pub fn persist_log(&self, log: &Log) {
+        if let (Some(ref history), None) = (&self.history, log.ttl) {
+            history.append(&self.room_state.room_name, log);
+        }
     }
 
     pub fn replace_logs(&mut self, chat_logs: Vec<Log>) {
         self.logs = VecDeque::new();
+        self.scroll_offset = 0;
         for log in chat_logs {
-            self.push_log(log);
+            self.store_log(log);
+        }
+    }
+
+    /// Records the difference between the server's clock and ours, given a
+    /// server time received just now.
+    pub fn sync_server_time(&mut self, server_now: DateTime<Utc>) {
+        self.server_time_offset = Some(server_now - Utc::now());
+    }
+
+    /// Handles the reply to a time request, measuring the round trip and
+    /// allowing for the trip back when syncing the clock. Returns whether
+    /// the user asked for the time, background probes are kept quiet.
+    pub fn receive_server_time(&mut self, server_now: DateTime<Utc>) -> bool {
+        match self.latency.finish() {
+            Some((rtt, requested)) => {
+                let half = chrono::Duration::from_std(rtt / 2).unwrap_or(chrono::Duration::zero());
+                self.sync_server_time(server_now + half);
+                requested
+            }
+            None => {
+                self.sync_server_time(server_now);
+                true
+            }
+        }
+    }
+
+    /// Sends a time request every so often to keep the latency current.
+    pub fn probe_latency(&mut self) {
+        if self.connection == ConnectionState::Connected && self.latency.due() {
+            self.latency.start(false);
+            self.send_server_command(Command::GetServerTime);
         }
     }
 
+    pub fn server_time(&self) -> Option<DateTime<Utc>> {
+        self.server_time_offset.map(|offset| Utc::now() + offset)
+    }
+
     pub fn store_token(&mut self, token: String) {
         self.token = Some(token);
+        self.connection = ConnectionState::Connected;
+        self.session_started = Some(Utc::now());
+    }
+
+    /// Marks the connection as gone, nothing can be sent after this.
+    fn handle_reconnect(&mut self) {
+        if self.connection == ConnectionState::Connected {
+            return;
+        }
+        self.connection = ConnectionState::Connecting;
+        self.notify(Severity::Info, tr("sys.reconnecting"));
+        if let Some(ref chan) = self.command_sink {
+            _ = chan.try_send(Event::Reconnect);
+        }
+    }
+
+    pub fn disconnect(&mut self, state: ConnectionState) {
+        self.connection = state;
+        self.switch_mode(Mode::Disconnected);
+    }
+
+    /// Puts the buffer aside as the current room's draft and clears it.
+    fn stash_draft(&mut self) {
+        let room = self.room_state.room_name.clone();
+        match self.buffer.iter().any(|l| !l.is_empty()) {
+            true => {
+                let buffer = std::mem::replace(&mut self.buffer, vec!["".into()]);
+                self.drafts.insert(room, (buffer, self.caret_offset));
+            }
+            false => {
+                self.drafts.remove(&room);
+                self.buffer = vec!["".into()];
+            }
+        }
+        self.caret_offset = (1, 1);
+    }
+
+    /// Brings back the current room's draft, or an empty buffer.
+    fn restore_draft(&mut self) {
+        match self.drafts.remove(&self.room_state.room_name) {
+            Some((buffer, caret)) => {
+                self.buffer = buffer;
+                self.set_caret_2d(caret.0, caret.1);
+            }
+            None => {
+                self.buffer = vec!["".into()];
+                self.caret_offset = (1, 1);
+            }
+        }
+    }
+
+    /// Takes over autosaving to `store`, first bringing back whatever a
+    /// crash left in it.
+    pub fn restore_autosave(&mut self, store: DraftStore) {
+        self.draft_store = store;
+        let Some(saved) = self.draft_store.load() else {
+            return;
+        };
+        // come back to as each room is joined
+        for (room, draft) in saved.drafts {
+            self.drafts.entry(room).or_insert(draft);
+        }
+        if let Some((cmd, buffer)) = saved.staged {
+            self.stage_command(cmd);
+            self.switch_mode(Mode::InsertCommand);
+            self.load_buffer(&buffer.join("\n"));
+        }
+        self.notify(Severity::Info, tr("sys.draft_restored"));
+    }
+
+    /// Writes what's being typed out every so often.
+    pub fn autosave_if_due(&mut self) {
+        if self.draft_store.due() {
+            self.autosave();
+        }
+    }
+
+    /// Writes the drafts, and the command being given its parameter, out.
+    /// An edit of a sent message isn't a draft and is left out.
+    pub fn autosave(&mut self) {
+        let mut draft = Autosave {
+            drafts: self.drafts.clone(),
+            staged: None,
+        };
+        if self.buffer.iter().any(|l| !l.is_empty()) && self.editing.is_none() {
+            match (self.mode.holds_draft(), &self.staged_command) {
+                (true, _) => {
+                    let room = self.room_state.room_name.clone();
+                    draft
+                        .drafts
+                        .insert(room, (self.buffer.clone(), self.caret_offset));
+                }
+                (false, Some(cmd)) if self.mode == Mode::InsertCommand => {
+                    draft.staged = Some((cmd.clone(), self.buffer.clone()));
+                }
+                _ => {}
+            }
+        }
+        self.draft_store.save(draft);
+    }
+
+    /// Drops the autosave on a clean exit, the quit prompt has already
+    /// warned about unsent drafts.
+    pub fn discard_autosave(&mut self) {
+        self.draft_store.save(Autosave::default());
+    }
+
+    /// Whether there's unsent text for `room`, in the buffer or put aside.
+    pub fn has_draft_in(&self, room: &str) -> bool {
+        self.drafts.contains_key(room) || (room == self.room_state.room_name && self.has_draft())
+    }
+
+    /// Whether the buffer holds text that hasn't been sent yet.
+    pub fn has_draft(&self) -> bool {
+        self.mode != Mode::InsertCommand
+            && self.mode != Mode::Search
+            && self.buffer.iter().any(|l| !l.is_empty())
     }
 
     fn handle_capture(&mut self, c: char) {
-        let (row, col) = self.get_caret_2d();
-        let mut buf_line = self.buffer[row.checked_sub(1).unwrap_or(0)].clone();
-        if col < buf_line.len() {
-            let post = buf_line.split_off(col);
-            buf_line.push(c);
-            buf_line = buf_line + &post;
-        } else {
-            buf_line.push(c);
+        if let Some(dead) = self.composing.take() {
+            let (row, col) = self.get_caret_2d();
+            let (buf_line, new_col) = graphemes::delete_before(&self.buffer[row - 1], col - 1);
+            self.buffer[row - 1] = buf_line;
+            self.set_caret_2d(row, new_col + 1);
+            ime::compose(dead, c)
+                .chars()
+                .for_each(|c| self.insert_char(c));
+            return;
         }
-        self.buffer[row.checked_sub(1).unwrap_or(0)] = buf_line;
-        self.caret_offset = (self.caret_offset.0, self.caret_offset.1 + 1);
+        if self.config.input_method.dead_keys && ime::is_dead_key(c) {
+            self.composing = Some(c);
+        }
+        self.insert_char(c);
+    }
+
+    fn insert_char(&mut self, c: char) {
+        let (row, col) = self.get_caret_2d();
+        let idx = row.checked_sub(1).unwrap_or(0);
+        let (buf_line, new_col) = graphemes::insert(&self.buffer[idx], col - 1, c);
+        self.buffer[idx] = buf_line;
+        self.caret_offset = (self.caret_offset.0, new_col + 1);
     }
 }
 
@@ -488,6 +4262,10 @@ type KeyCheck = dyn Fn(KeyCode) -> Option<Command>;
 #[allow(dead_code)]
 pub enum KeyBinds {
     Explicit(KeyCode, Command),
+    /// A key pressed together with Ctrl, Alt or Shift.
+    Chord(KeyCode, KeyModifiers, Command),
+    /// Two keys pressed one after the other, like vim's `gg`.
+    Sequence(KeyCode, KeyCode, Command),
     Logical(Box<KeyCheck>),
     NoMap,
 }
@@ -496,6 +4274,8 @@ impl std::fmt::Debug for KeyBinds {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Explicit(k, c) => write!(f, "KeyBinds::Explicit({k:?}, {c:?})"),
+            Self::Chord(k, m, c) => write!(f, "KeyBinds::Chord({k:?}, {m:?}, {c:?})"),
+            Self::Sequence(a, b, c) => write!(f, "KeyBinds::Sequence({a:?}, {b:?}, {c:?})"),
             Self::Logical(_) => write!(f, "KeyBinds::Logical(fn)"),
             Self::NoMap => write!(f, "KeyBinds::NoMap"),
         }
@@ -506,18 +4286,28 @@ impl Display for KeyBinds {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Explicit(KeyCode::Char(c), cmd) => write!(f, "{c}\t -> {cmd}"),
-            Self::Explicit(KeyCode::Enter, cmd) => write!(f, "󰌑\t -> {cmd}"),
+            Self::Explicit(KeyCode::Enter, cmd) => {
+                write!(f, "{}\t -> {cmd}", capabilities::symbol("󰌑", "Enter"))
+            }
             Self::Explicit(KeyCode::Esc, cmd) => write!(f, "Esc\t -> {cmd}"),
             Self::Explicit(KeyCode::Delete, cmd) => write!(f, "Del\t -> {cmd}"),
-            Self::Explicit(KeyCode::Left, cmd) => write!(f, "←/→\t -> {cmd}"),
+            Self::Explicit(KeyCode::Left, cmd) => {
+                write!(f, "{}\t -> {cmd}", capabilities::symbol("←/→", "</>"))
+            }
+            Self::Chord(code, modifiers, cmd) => match chord_name(*code, *modifiers) {
+                Some(name) => write!(f, "{name}\t -> {cmd}"),
+                None => write!(f, ""),
+            },
+            Self::Sequence(KeyCode::Char(a), KeyCode::Char(b), cmd) => {
+                write!(f, "{a}{b}\t -> {cmd}")
+            }
             _ => write!(f, ""),
         }
     }
 }
 
 impl KeyBinds {
-    pub fn render(&self) -> Option<Line> {
-        let s = LogStyle::default();
+    pub fn render(&self, s: &LogStyle) -> Option<Line> {
         let styles = [s.time(), s.uname(), s.msg()];
         let formatted = format!("{}", self);
         if formatted.len() == 0 {
@@ -541,9 +4331,60 @@ impl KeyBinds {
         )
     }
 
-    pub fn check(&self, c: KeyCode) -> Option<Command> {
+    /// Binds a key, as a chord if any modifiers are given.
+    pub fn bind(code: KeyCode, modifiers: KeyModifiers, command: Command) -> Self {
+        match modifiers.is_empty() {
+            true => Self::Explicit(code, command),
+            false => Self::Chord(code, modifiers, command),
+        }
+    }
+
+    /// The key and modifiers of an explicit binding.
+    fn key(&self) -> Option<(KeyCode, KeyModifiers)> {
+        match self {
+            Self::Explicit(code, _) => Some((*code, KeyModifiers::NONE)),
+            Self::Chord(code, modifiers, _) => Some((*code, *modifiers)),
+            _ => None,
+        }
+    }
+
+    /// The key's name and its command, for the help overlay.
+    fn describe(&self) -> Option<(String, &Command)> {
+        match self {
+            Self::Explicit(code, command) => {
+                Some((chord_name(*code, KeyModifiers::NONE)?, command))
+            }
+            Self::Chord(code, modifiers, command) => {
+                Some((chord_name(*code, *modifiers)?, command))
+            }
+            Self::Sequence(a, b, command) => {
+                Some((key_name(*a)? + key_name(*b)?.as_str(), command))
+            }
+            _ => None,
+        }
+    }
+
+    fn command(&self) -> Option<&Command> {
+        match self {
+            Self::Explicit(_, command) | Self::Chord(_, _, command) => Some(command),
+            _ => None,
+        }
+    }
+
+    pub fn check(&self, c: KeyCode, modifiers: KeyModifiers) -> Option<Command> {
+        // shift is already part of the character for printable keys
+        let modifiers = match c {
+            KeyCode::Char(_) => modifiers - KeyModifiers::SHIFT,
+            _ => modifiers,
+        };
+        let held = modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT);
         match self {
-            Self::Explicit(code, ref command) if code.clone() == c => Some(command.clone()),
+            Self::Explicit(code, ref command) if code.clone() == c && !held => {
+                Some(command.clone())
+            }
+            Self::Chord(code, chord, ref command) if *code == c && *chord == modifiers => {
+                Some(command.clone())
+            }
             Self::Logical(check_fn) => check_fn(c),
             Self::NoMap => None,
             _ => None,
@@ -561,13 +4402,49 @@ impl KeyBinds {
 #[derive(Debug)]
 pub struct ModalKeyMaps {
     keymaps: HashMap<Mode, Vec<KeyBinds>>,
+    /// Going back to Navigate mode goes to Insert mode instead.
+    modeless: bool,
 }
 
 impl ModalKeyMaps {
-    fn get_cmd(&self, mode: &Mode, code: KeyCode) -> Option<Command> {
+    /// The built-in bindings of `preset`.
+    pub fn preset(preset: KeyPreset) -> Self {
+        Self {
+            keymaps: HashMap::from(default_keybinds::preset(preset)),
+            modeless: preset.modeless(),
+        }
+    }
+
+    /// Bindings for `--monitor`, for scrolling and changing what's shown.
+    pub fn monitor() -> Self {
+        Self {
+            keymaps: HashMap::from(default_keybinds::monitor()),
+            modeless: false,
+        }
+    }
+
+    /// Whether `code` is the first key of a sequence bound in `mode`.
+    fn starts_sequence(&self, mode: &Mode, code: KeyCode) -> bool {
+        self.keymaps.get(mode).is_some_and(|binds| {
+            binds
+                .iter()
+                .any(|b| matches!(b, KeyBinds::Sequence(first, ..) if *first == code))
+        })
+    }
+
+    fn get_sequence(&self, mode: &Mode, first: KeyCode, second: KeyCode) -> Option<Command> {
+        self.keymaps.get(mode)?.iter().find_map(|b| match b {
+            KeyBinds::Sequence(a, b, command) if *a == first && *b == second => {
+                Some(command.clone())
+            }
+            _ => None,
+        })
+    }
+
+    fn get_cmd(&self, mode: &Mode, code: KeyCode, modifiers: KeyModifiers) -> Option<Command> {
         if let Some(binds) = self.keymaps.get(&mode) {
             for binding in binds {
-                if let Some(cmd) = binding.check(code) {
+                if let Some(cmd) = binding.check(code, modifiers) {
                     return Some(cmd);
                 }
             }
@@ -576,11 +4453,121 @@ impl ModalKeyMaps {
         return None;
     }
 
-    fn render(&self, mode: &Mode) -> Text {
+    /// Collects the explicit bindings of every mode, logical bindings such as
+    /// text capture can't be represented in a file and are left out.
+    pub fn export(&self) -> KeymapFile {
+        self.keymaps
+            .iter()
+            .map(|(mode, binds)| {
+                let entries = binds
+                    .iter()
+                    .filter_map(|bind| {
+                        let (code, modifiers) = bind.key()?;
+                        Some(KeyBindEntry {
+                            key: chord_name(code, modifiers)?,
+                            command: bind.command()?.clone(),
+                        })
+                    })
+                    .collect();
+                (mode.clone(), entries)
+            })
+            .collect()
+    }
+
+    /// Replaces the explicit bindings of every mode present in `keymap`,
+    /// keeping the logical bindings. Returns a description of every
+    /// problem found: unknown keys, conflicting bindings and commands
+    /// that were bound before but no longer are.
+    pub fn import(&mut self, keymap: KeymapFile) -> Vec<String> {
+        let mut problems = vec![];
+        for (mode, entries) in keymap {
+            let mut seen: HashMap<(KeyCode, KeyModifiers), Command> = HashMap::new();
+            let mut explicit = vec![];
+            for KeyBindEntry { key, command } in entries {
+                let Some((code, modifiers)) = parse_chord(&key) else {
+                    problems.push(trf!("sys.keymap_unknown_key", mode, key));
+                    continue;
+                };
+                if let Some(existing) = seen.get(&(code, modifiers)) {
+                    problems.push(trf!("sys.keymap_conflict", mode, key, existing, command));
+                    continue;
+                }
+                seen.insert((code, modifiers), command.clone());
+                explicit.push(KeyBinds::bind(code, modifiers, command));
+            }
+
+            let previous = self.keymaps.remove(&mode).unwrap_or_default();
+            let bound: HashSet<&Command> = seen.values().collect();
+            let mut logical = vec![];
+            for bind in previous {
+                match bind.command() {
+                    Some(command) if !bound.contains(command) => {
+                        problems.push(trf!("sys.keymap_unbound", mode, command));
+                    }
+                    Some(_) => {}
+                    None => logical.push(bind),
+                }
+            }
+
+            explicit.extend(logical);
+            self.keymaps.insert(mode, explicit);
+        }
+
+        problems
+    }
+
+    /// Binds the keys in `keymap` on top of the current bindings, replacing
+    /// whatever was bound to the same key and leaving everything else as
+    /// it was. Returns the entries that couldn't be understood.
+    pub fn overlay(&mut self, keymap: &KeymapFile) -> Vec<String> {
+        let mut problems = vec![];
+        for (mode, entries) in keymap {
+            let binds = self.keymaps.entry(mode.clone()).or_default();
+            for KeyBindEntry { key, command } in entries.iter().rev() {
+                let Some((code, modifiers)) = parse_chord(key) else {
+                    problems.push(trf!("sys.keymap_unknown_key", mode, key));
+                    continue;
+                };
+                binds.retain(|b| b.key() != Some((code, modifiers)));
+                binds.insert(0, KeyBinds::bind(code, modifiers, command.clone()));
+            }
+        }
+
+        problems
+    }
+
+    /// Names the keys of every binding in `mode` that can be named, with
+    /// what they do, in the order they're matched.
+    fn describe(&self, mode: &Mode) -> Vec<(String, &Command)> {
+        self.keymaps
+            .get(mode)
+            .into_iter()
+            .flatten()
+            .filter_map(KeyBinds::describe)
+            .collect()
+    }
+
+    /// Every mode with bindings, in the help overlay's order, with what
+    /// each key does.
+    pub fn sheet(&self) -> Vec<(Mode, Vec<(String, String)>)> {
+        HELP_MODES
+            .into_iter()
+            .filter_map(|mode| {
+                let binds: Vec<_> = self
+                    .describe(&mode)
+                    .into_iter()
+                    .map(|(key, command)| (key, command.to_string()))
+                    .collect();
+                (!binds.is_empty()).then_some((mode, binds))
+            })
+            .collect()
+    }
+
+    fn render(&self, mode: &Mode, style: &LogStyle) -> Text {
         if let Some(binds) = self.keymaps.get(mode) {
             binds
                 .iter()
-                .filter_map(KeyBinds::render)
+                .filter_map(|b| b.render(style))
                 .collect::<Vec<Line>>()
                 .into()
         } else {
@@ -591,8 +4578,6 @@ impl ModalKeyMaps {
 
 impl Default for ModalKeyMaps {
     fn default() -> Self {
-        Self {
-            keymaps: HashMap::from(default_keybinds::keys()),
-        }
+        Self::preset(KeyPreset::default())
     }
 }
@@ -0,0 +1,147 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{app::Command, user_config::data_dir};
+
+/// How often what's being typed is written out, at most.
+const AUTOSAVE_EVERY: Duration = Duration::from_secs(2);
+
+/// Unsent text, as kept on disk in case the client doesn't get to exit.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Autosave {
+    /// Unsent text per room, with the caret in it.
+    pub drafts: HashMap<String, (Vec<String>, (usize, usize))>,
+    /// A command waiting for its parameter, with what was typed for it.
+    pub staged: Option<(Command, Vec<String>)>,
+}
+
+impl Autosave {
+    pub fn is_empty(&self) -> bool {
+        self.drafts.is_empty() && self.staged.is_none()
+    }
+}
+
+/// Where the draft is autosaved, `<data dir>/draft.json`. A clean exit
+/// removes it, so one found at startup was left by a crash.
+#[derive(Debug)]
+pub struct DraftStore {
+    /// None when nothing is kept between sessions.
+    path: Option<PathBuf>,
+    /// What's on disk, so an unchanged draft isn't written again.
+    saved: Autosave,
+    next_save: Instant,
+}
+
+impl Default for DraftStore {
+    fn default() -> Self {
+        Self {
+            path: None,
+            saved: Autosave::default(),
+            next_save: Instant::now(),
+        }
+    }
+}
+
+impl DraftStore {
+    pub fn open() -> Self {
+        Self::open_at(data_dir().join("draft.json"))
+    }
+
+    fn open_at(path: PathBuf) -> Self {
+        Self {
+            path: Some(path),
+            ..Self::default()
+        }
+    }
+
+    /// What the last session left unsent, if anything.
+    pub fn load(&mut self) -> Option<Autosave> {
+        let path = self.path.as_ref()?;
+        let saved: Autosave = fs::read_to_string(path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())?;
+        self.saved = saved.clone();
+        Some(saved).filter(|saved| !saved.is_empty())
+    }
+
+    /// Whether it's been long enough since the last save to save again.
+    pub fn due(&mut self) -> bool {
+        let now = Instant::now();
+        if self.path.is_none() || now < self.next_save {
+            return false;
+        }
+        self.next_save = now + AUTOSAVE_EVERY;
+        true
+    }
+
+    /// Writes `draft` out unless it's what was written last. Nothing left
+    /// unsent removes the file.
+    pub fn save(&mut self, draft: Autosave) {
+        let Some(ref path) = self.path else {
+            return;
+        };
+        if draft == self.saved {
+            return;
+        }
+        let result = match draft.is_empty() {
+            true => fs::remove_file(path).or_else(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => Ok(()),
+                _ => Err(e),
+            }),
+            false => serde_json::to_string(&draft)
+                .map_err(std::io::Error::from)
+                .and_then(|json| {
+                    if let Some(dir) = path.parent() {
+                        fs::create_dir_all(dir)?;
+                    }
+                    fs::write(path, json)
+                }),
+        };
+        match result {
+            Ok(()) => self.saved = draft,
+            Err(e) => tracing::error!("Could not autosave the draft: {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        app::{App, Mode},
+        user_config::UserConfig,
+    };
+
+    #[test]
+    fn a_draft_left_by_a_crash_comes_back() {
+        let path = std::env::temp_dir().join(format!("marain-draft-{}.json", std::process::id()));
+        let app = || App::for_test(UserConfig::default());
+        let mut crashed = app();
+        crashed.room_state.room_name = "rust".into();
+        crashed.switch_mode(Mode::Insert);
+        crashed.load_buffer("half a thought");
+        crashed.restore_autosave(DraftStore::open_at(path.clone()));
+        crashed.autosave();
+        assert!(path.exists());
+
+        let mut restarted = app();
+        restarted.restore_autosave(DraftStore::open_at(path.clone()));
+        assert_eq!(
+            restarted
+                .drafts
+                .get("rust")
+                .map(|(buffer, _)| buffer.clone()),
+            Some(vec!["half a thought".to_string()])
+        );
+        // sending it leaves nothing to restore
+        restarted.drafts.clear();
+        restarted.autosave();
+        assert!(!path.exists());
+    }
+}
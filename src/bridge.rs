@@ -0,0 +1,79 @@
+use color_eyre::{eyre::eyre, Result};
+use marain_api::prelude::ClientMsgBody;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::{error::ClientError, i18n::tr, oneshot::Sender, user_config::UserConfig};
+
+/// Speaks the protocol for tools that would rather not: every message
+/// from the server goes to stdout as a line of JSON, and each line of JSON
+/// on stdin is a `ClientMsgBody` to send, e.g.
+/// `{"SendToRoom":{"contents":"hi"}}`. Runs until either side closes.
+pub async fn run(config: UserConfig) -> Result<()> {
+    let mut sender = Sender::connect(&config, None).await?;
+    let (lines, mut incoming) = tokio::sync::mpsc::channel(1);
+    tokio::spawn(async move {
+        let mut stdin = BufReader::new(tokio::io::stdin()).lines();
+        while let Ok(Some(line)) = stdin.next_line().await {
+            if lines.send(line).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    loop {
+        tokio::select! {
+            line = incoming.recv() => match line {
+                Some(line) if line.trim().is_empty() => {}
+                Some(line) => match parse(&line) {
+                    Ok(body) => sender.deliver(body).await?,
+                    Err(e) => eprintln!("{e}"),
+                },
+                None => break,
+            },
+            msg = sender.client.next() => match msg {
+                Ok(Message::Binary(data)) => match read(&mut sender, data) {
+                    Ok(json) => println!("{json}"),
+                    Err(e) => tracing::error!("{e}"),
+                },
+                Ok(Message::Close(_)) | Err(_) => return Err(eyre!(tr("err.pipe_closed"))),
+                Ok(_) => {}
+            },
+        }
+    }
+
+    sender.close().await
+}
+
+/// A body to send from a line of JSON. Logging in again would leave the
+/// session behind, so that's refused.
+fn parse(line: &str) -> Result<ClientMsgBody, String> {
+    match serde_json::from_str(line) {
+        Ok(ClientMsgBody::Login(..)) => Err(tr("err.bridge_login")),
+        Ok(body) => Ok(body),
+        Err(e) => Err(format!("{line}: {e}")),
+    }
+}
+
+fn read(sender: &mut Sender, data: Vec<u8>) -> Result<String, ClientError> {
+    let msg = sender.session.decode(data)?;
+    serde_json::to_string(&msg).map_err(|e| ClientError::UnexpectedMessage(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bodies_come_from_json_but_not_logins() {
+        assert_eq!(
+            parse(r#"{"SendToRoom":{"contents":"hi"}}"#),
+            Ok(ClientMsgBody::SendToRoom {
+                contents: "hi".into()
+            })
+        );
+        assert_eq!(parse(r#""GetTime""#), Ok(ClientMsgBody::GetTime));
+        assert!(parse(r#"{"Login":["me",[0,0]]}"#).is_err());
+        assert!(parse("hi").is_err());
+    }
+}
@@ -0,0 +1,239 @@
+use std::{env, sync::OnceLock};
+
+use ratatui::{buffer::Buffer, style::Color};
+use serde::{Deserialize, Serialize};
+
+/// What the terminal was found, or configured, to be able to show.
+static DETECTED: OnceLock<Capabilities> = OnceLock::new();
+
+/// How many colors the terminal shows.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorDepth {
+    /// The 16 named ANSI colors.
+    Ansi16,
+    /// The xterm 256 color palette.
+    Ansi256,
+    /// Any RGB color.
+    TrueColor,
+}
+
+/// Which symbols are safe to draw.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Glyphs {
+    /// Plain ASCII only, for terminals without UTF-8.
+    Ascii,
+    /// Box drawing, arrows and the Nerd Font icons in the keybindings.
+    Nerd,
+}
+
+/// The `terminal` section of the user config, for when detection gets it
+/// wrong. Unset fields are worked out from the environment.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+#[serde(default)]
+pub struct TerminalConfig {
+    pub colors: Option<ColorDepth>,
+    pub glyphs: Option<Glyphs>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    pub colors: ColorDepth,
+    pub glyphs: Glyphs,
+}
+
+impl Capabilities {
+    /// Guesses from `TERM`, `COLORTERM` and the locale, read through `var`.
+    /// Fonts can't be asked about, so a UTF-8 terminal is taken to have
+    /// the Nerd Font glyphs.
+    fn detect(var: impl Fn(&str) -> Option<String>) -> Self {
+        let term = var("TERM").unwrap_or_default();
+        let colorterm = var("COLORTERM").unwrap_or_default();
+        let console =
+            term.is_empty() || term == "dumb" || term == "linux" || term.starts_with("vt");
+        let colors = if colorterm == "truecolor" || colorterm == "24bit" {
+            ColorDepth::TrueColor
+        } else if term.contains("256color") {
+            ColorDepth::Ansi256
+        } else {
+            ColorDepth::Ansi16
+        };
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .into_iter()
+            .find_map(|name| var(name).filter(|value| !value.is_empty()))
+            .unwrap_or_default()
+            .to_lowercase();
+        let utf8 = locale.contains("utf-8") || locale.contains("utf8");
+        let glyphs = match utf8 && !console {
+            true => Glyphs::Nerd,
+            false => Glyphs::Ascii,
+        };
+
+        Self { colors, glyphs }
+    }
+
+    fn with_overrides(self, conf: &TerminalConfig) -> Self {
+        Self {
+            colors: conf.colors.unwrap_or(self.colors),
+            glyphs: conf.glyphs.unwrap_or(self.glyphs),
+        }
+    }
+}
+
+/// Detects what the terminal can show, with the config's overrides on top.
+/// Called once at startup.
+pub fn init(conf: &TerminalConfig) {
+    _ = DETECTED.set(Capabilities::detect(|name| env::var(name).ok()).with_overrides(conf));
+}
+
+pub fn get() -> Capabilities {
+    *DETECTED.get_or_init(|| Capabilities::detect(|name| env::var(name).ok()))
+}
+
+/// `nerd` where the terminal can draw it, otherwise `ascii`.
+pub fn symbol(nerd: &'static str, ascii: &'static str) -> &'static str {
+    match get().glyphs {
+        Glyphs::Nerd => nerd,
+        Glyphs::Ascii => ascii,
+    }
+}
+
+/// The RGB value of one of the 16 named colors, as xterm shows them.
+const NAMED: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+/// Levels of each channel in the 6x6x6 cube of the 256 color palette.
+const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn rgb_of_index(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..=15 => NAMED[index as usize].1,
+        16..=231 => {
+            let i = index - 16;
+            (
+                CUBE[(i / 36) as usize],
+                CUBE[(i / 6 % 6) as usize],
+                CUBE[(i % 6) as usize],
+            )
+        }
+        _ => {
+            let level = 8 + (index - 232) * 10;
+            (level, level, level)
+        }
+    }
+}
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    [(r1, r2), (g1, g2), (b1, b2)]
+        .into_iter()
+        .map(|(a, b)| (a as i32 - b as i32).pow(2) as u32)
+        .sum()
+}
+
+fn nearest_named(rgb: (u8, u8, u8)) -> Color {
+    NAMED
+        .iter()
+        .min_by_key(|(_, named)| distance(rgb, *named))
+        .map(|(color, _)| *color)
+        .unwrap_or(Color::White)
+}
+
+fn nearest_index(rgb: (u8, u8, u8)) -> u8 {
+    let step = |c: u8| {
+        (0..CUBE.len())
+            .min_by_key(|&i| (CUBE[i] as i32 - c as i32).abs())
+            .unwrap_or(0) as u8
+    };
+    let cube = 16 + 36 * step(rgb.0) + 6 * step(rgb.1) + step(rgb.2);
+    let average = (rgb.0 as u32 + rgb.1 as u32 + rgb.2 as u32) / 3;
+    let gray = 232 + ((average.saturating_sub(8) / 10).min(23)) as u8;
+    match distance(rgb, rgb_of_index(gray)) < distance(rgb, rgb_of_index(cube)) {
+        true => gray,
+        false => cube,
+    }
+}
+
+/// The closest color to `color` the terminal can show.
+pub fn downsample(color: Color, depth: ColorDepth) -> Color {
+    match (color, depth) {
+        (_, ColorDepth::TrueColor) => color,
+        (Color::Rgb(r, g, b), ColorDepth::Ansi256) => Color::Indexed(nearest_index((r, g, b))),
+        (Color::Rgb(r, g, b), ColorDepth::Ansi16) => nearest_named((r, g, b)),
+        (Color::Indexed(i), ColorDepth::Ansi16) => nearest_named(rgb_of_index(i)),
+        _ => color,
+    }
+}
+
+/// Brings the colors of everything drawn down to what the terminal shows,
+/// so themes with hex colors still read on a 16 color terminal.
+pub fn fit_colors(buffer: &mut Buffer) {
+    let depth = get().colors;
+    if depth == ColorDepth::TrueColor {
+        return;
+    }
+    for cell in &mut buffer.content {
+        cell.fg = downsample(cell.fg, depth);
+        cell.bg = downsample(cell.bg, depth);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_from_the_environment() {
+        let env = |vars: &'static [(&str, &str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(n, _)| *n == name)
+                    .map(|(_, v)| v.to_string())
+            }
+        };
+        let kitty = Capabilities::detect(env(&[
+            ("TERM", "xterm-kitty"),
+            ("COLORTERM", "truecolor"),
+            ("LANG", "en_GB.UTF-8"),
+        ]));
+        assert_eq!(kitty.colors, ColorDepth::TrueColor);
+        assert_eq!(kitty.glyphs, Glyphs::Nerd);
+
+        let console = Capabilities::detect(env(&[("TERM", "linux"), ("LANG", "en_GB.UTF-8")]));
+        assert_eq!(console.colors, ColorDepth::Ansi16);
+        assert_eq!(console.glyphs, Glyphs::Ascii);
+
+        let xterm = Capabilities::detect(env(&[("TERM", "xterm-256color"), ("LC_ALL", "C")]));
+        assert_eq!(xterm.colors, ColorDepth::Ansi256);
+        assert_eq!(xterm.glyphs, Glyphs::Ascii);
+    }
+
+    #[test]
+    fn colors_come_down_to_the_nearest() {
+        let orange = Color::Rgb(255, 136, 0);
+        assert_eq!(downsample(orange, ColorDepth::TrueColor), orange);
+        assert_eq!(downsample(orange, ColorDepth::Ansi256), Color::Indexed(208));
+        assert_eq!(downsample(orange, ColorDepth::Ansi16), Color::Yellow);
+        assert_eq!(
+            downsample(Color::Indexed(196), ColorDepth::Ansi16),
+            Color::LightRed
+        );
+        assert_eq!(downsample(Color::Blue, ColorDepth::Ansi16), Color::Blue);
+    }
+}
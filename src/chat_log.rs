@@ -1,17 +1,113 @@
-use std::fmt::{Debug, Display};
+use std::{
+    cell::OnceCell,
+    collections::HashMap,
+    fmt::{Debug, Display},
+    path::PathBuf,
+};
 
-use chrono::{DateTime, Utc};
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, Duration, FixedOffset, Local, Utc,
+};
 use ratatui::{
-    style::{Color, Style, Stylize},
+    style::{Color, Modifier, Style, Stylize},
     text::{Line, Span},
 };
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    highlights::Highlights,
+    i18n::{tr, trf},
+    links, markdown,
+    signing::SignatureState,
+};
+
+/// Senders that are the client itself rather than people.
+const SYSTEM_SOURCES: &[&str] = &["SERVER", "CLIENT", "DEBUG", "MENTION", "PLUGIN"];
+
+/// Index of the first span of the message in a rendered log, the ones
+/// before it are the header.
+pub const MESSAGE_SPAN: usize = 6;
+
+/// Index of the timestamp's span in a rendered log.
+const TIME_SPAN: usize = 1;
+
+/// Timezone timestamps are shown in.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeZoneSetting {
+    #[default]
+    Local,
+    Utc,
+    /// A fixed offset from UTC in minutes, e.g. `{"offset": -300}`.
+    Offset(i32),
+}
+
+pub const DEFAULT_TIME_FORMAT: &str = "%H:%M:%S";
+
+/// Where a log came from, which decides how it's styled and whether it's
+/// shown.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum LogKind {
+    /// Said by someone in the room.
+    #[default]
+    Chat,
+    /// From the server about the room, such as someone joining or leaving.
+    Notification,
+    /// From the client itself.
+    System,
+    /// Raw protocol traffic, only shown in debug mode.
+    Debug,
+}
+
+impl LogKind {
+    /// The kind of a log by its sender, the client's own sources are
+    /// written in capitals.
+    pub fn of(sender: &str) -> Self {
+        match sender {
+            "SERVER" => Self::Notification,
+            "DEBUG" => Self::Debug,
+            s if SYSTEM_SOURCES.contains(&s) => Self::System,
+            _ => Self::Chat,
+        }
+    }
+}
+
+/// Styles that replace the usual ones for logs of one kind, each left as
+/// `None` keeps the usual one.
+#[derive(Debug, Clone, Default)]
+pub struct KindStyle {
+    pub time: Option<Style>,
+    pub uname: Option<Style>,
+    pub msg: Option<Style>,
+    pub delims: Option<Style>,
+    pub time_fmt: Option<String>,
+}
 
+#[derive(Debug, Clone)]
 pub struct LogStyle {
     time_style: Style,
     uname_style: Style,
     msg_style: Style,
     delim_style: Style,
+    highlight_style: Style,
+    dm_style: Style,
+    notification_style: Style,
+    system_style: Style,
+    debug_style: Style,
+    kinds: HashMap<LogKind, KindStyle>,
     time_fmt: String,
+    timezone: TimeZoneSetting,
+    /// Apply `*bold*`, `_italic_` and `` `code` `` in messages.
+    markdown: bool,
+    /// Show how long ago messages were sent rather than when.
+    relative: bool,
+    /// The user's own rules for picking out parts of messages.
+    highlights: Highlights,
+    /// Colors usernames are picked from, all names share `uname_style`
+    /// when empty.
+    palette: Vec<Color>,
 }
 
 impl Default for LogStyle {
@@ -21,18 +117,144 @@ impl Default for LogStyle {
             uname_style: Style::new().fg(Color::Yellow).bg(Color::Black).bold(),
             msg_style: Style::new().fg(Color::White).bg(Color::Black),
             delim_style: Style::new().fg(Color::Blue).bg(Color::Black),
-            time_fmt: "%H:%M:%S".to_string(),
+            highlight_style: Style::new().fg(Color::Black).bg(Color::Yellow),
+            dm_style: Style::new().fg(Color::Magenta).bg(Color::Black),
+            notification_style: Style::new().fg(Color::Blue).bg(Color::Black).italic(),
+            system_style: Style::new().fg(Color::Gray).bg(Color::Black).italic(),
+            debug_style: Style::new().fg(Color::Gray).bg(Color::Black),
+            kinds: HashMap::new(),
+            time_fmt: DEFAULT_TIME_FORMAT.to_string(),
+            timezone: TimeZoneSetting::default(),
+            markdown: true,
+            relative: false,
+            highlights: Highlights::default(),
+            palette: vec![
+                Color::LightRed,
+                Color::LightGreen,
+                Color::Yellow,
+                Color::LightBlue,
+                Color::LightMagenta,
+                Color::LightCyan,
+                Color::Green,
+                Color::Magenta,
+                Color::Cyan,
+            ],
         }
     }
 }
 
 impl LogStyle {
+    pub fn new(
+        time_style: Style,
+        uname_style: Style,
+        msg_style: Style,
+        delim_style: Style,
+        highlight_style: Style,
+    ) -> Self {
+        // the other kinds take after the header, so they stand apart from
+        // chat in any theme
+        Self {
+            notification_style: delim_style.italic(),
+            system_style: time_style.italic(),
+            debug_style: time_style.not_italic(),
+            time_style,
+            uname_style,
+            msg_style,
+            delim_style,
+            highlight_style,
+            ..Self::default()
+        }
+    }
+
     pub fn time(&self) -> Style {
         self.time_style.clone()
     }
 
-    pub fn time_fmt_str(&self) -> &str {
-        &self.time_fmt
+    /// Fluent setter for how timestamps are shown. A format chrono can't
+    /// parse is logged and the default used instead, as formatting with it
+    /// would panic.
+    pub fn with_time(mut self, time_fmt: &str, timezone: TimeZoneSetting) -> Self {
+        if valid_time_format(time_fmt) {
+            self.time_fmt = time_fmt.to_string();
+        }
+        self.timezone = timezone;
+
+        self
+    }
+
+    /// Fluent setter for the styles of logs of `kind`. An invalid time
+    /// format is dropped as in [`Self::with_time`].
+    pub fn with_kind(mut self, kind: LogKind, mut style: KindStyle) -> Self {
+        style.time_fmt = style.time_fmt.filter(|fmt| valid_time_format(fmt));
+        self.kinds.insert(kind, style);
+
+        self
+    }
+
+    /// Fluent setter for the username colors.
+    pub fn with_palette(mut self, palette: Vec<Color>) -> Self {
+        self.palette = palette;
+
+        self
+    }
+
+    pub fn palette(&self) -> &[Color] {
+        &self.palette
+    }
+
+    /// The style of a sender's name, the same color for a name every time.
+    /// The client's own sources keep the plain username style.
+    pub fn uname_for(&self, name: &str) -> Style {
+        if self.palette.is_empty() || SYSTEM_SOURCES.contains(&name) {
+            return self.uname_style;
+        }
+        // FNV-1a, std's hasher may change between releases
+        let hash = name.bytes().fold(0xcbf29ce484222325u64, |hash, b| {
+            (hash ^ b as u64).wrapping_mul(0x100000001b3)
+        });
+        self.uname_style
+            .fg(self.palette[(hash % self.palette.len() as u64) as usize])
+    }
+
+    /// Fluent setter for whether times are shown relative to now.
+    pub fn with_relative(mut self, relative: bool) -> Self {
+        self.relative = relative;
+
+        self
+    }
+
+    pub fn with_highlights(mut self, highlights: Highlights) -> Self {
+        self.highlights = highlights;
+
+        self
+    }
+
+    pub fn toggle_relative(&mut self) {
+        self.relative = !self.relative;
+    }
+
+    /// Fluent setter for whether inline formatting is applied.
+    pub fn with_markdown(mut self, markdown: bool) -> Self {
+        self.markdown = markdown;
+
+        self
+    }
+
+    pub fn format_time(&self, ts: DateTime<Utc>) -> String {
+        self.format_time_with(ts, &self.time_fmt)
+    }
+
+    /// Formats `ts` in the configured timezone with a format other than the
+    /// configured one.
+    pub fn format_time_with(&self, ts: DateTime<Utc>, fmt: &str) -> String {
+        match self.timezone {
+            TimeZoneSetting::Local => ts.with_timezone(&Local).format(fmt).to_string(),
+            TimeZoneSetting::Utc => ts.format(fmt).to_string(),
+            TimeZoneSetting::Offset(minutes) => match FixedOffset::east_opt(minutes * 60) {
+                Some(offset) => ts.with_timezone(&offset).format(fmt).to_string(),
+                None => ts.format(fmt).to_string(),
+            },
+        }
     }
 
     pub fn uname(&self) -> Style {
@@ -46,6 +268,111 @@ impl LogStyle {
     pub fn msg(&self) -> Style {
         self.msg_style.clone()
     }
+
+    pub fn highlight(&self) -> Style {
+        self.highlight_style
+    }
+
+    /// Direct messages.
+    pub fn dm(&self) -> Style {
+        self.dm_style
+    }
+
+    /// The message style of a log of `kind`.
+    pub fn msg_for(&self, kind: LogKind) -> Style {
+        let usual = match kind {
+            LogKind::Chat => self.msg_style,
+            LogKind::Notification => self.notification_style,
+            LogKind::System => self.system_style,
+            LogKind::Debug => self.debug_style,
+        };
+        self.kind(kind).and_then(|k| k.msg).unwrap_or(usual)
+    }
+
+    pub fn time_for(&self, kind: LogKind) -> Style {
+        self.kind(kind)
+            .and_then(|k| k.time)
+            .unwrap_or(self.time_style)
+    }
+
+    pub fn delims_for(&self, kind: LogKind) -> Style {
+        self.kind(kind)
+            .and_then(|k| k.delims)
+            .unwrap_or(self.delim_style)
+    }
+
+    /// The style of a sender's name in a log of `kind`, the kind's style
+    /// replaces the palette.
+    pub fn uname_for_kind(&self, name: &str, kind: LogKind) -> Style {
+        match self.kind(kind).and_then(|k| k.uname) {
+            Some(style) => style,
+            None => self.uname_for(name),
+        }
+    }
+
+    pub fn format_time_for(&self, ts: DateTime<Utc>, kind: LogKind) -> String {
+        match self.kind(kind).and_then(|k| k.time_fmt.as_deref()) {
+            Some(fmt) => self.format_time_with(ts, fmt),
+            None => self.format_time(ts),
+        }
+    }
+
+    fn kind(&self, kind: LogKind) -> Option<&KindStyle> {
+        self.kinds.get(&kind)
+    }
+}
+
+/// Whether chrono can format with `time_fmt`, formatting with a bad one
+/// panics. Bad ones are logged.
+fn valid_time_format(time_fmt: &str) -> bool {
+    let valid = !StrftimeItems::new(time_fmt).any(|item| item == Item::Error);
+    if !valid {
+        tracing::error!("Invalid time format {time_fmt:?}, using the default");
+    }
+    valid
+}
+
+/// How long before `now` `ts` was, in the largest whole unit.
+pub fn relative_time(ts: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let ago = now - ts;
+    match ago {
+        ago if ago < Duration::minutes(1) => tr("time.just_now"),
+        ago if ago < Duration::hours(1) => trf!("time.minutes", ago.num_minutes()),
+        ago if ago < Duration::days(1) => trf!("time.hours", ago.num_hours()),
+        ago => trf!("time.days", ago.num_days()),
+    }
+}
+
+/// How far one of our own messages has got.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Delivery {
+    /// Shown straight away, waiting for the server to echo it back.
+    Pending,
+    /// Held back while disconnected, goes out on reconnect.
+    Queued,
+    Sent,
+    Failed,
+}
+
+impl Delivery {
+    pub fn indicator(&self) -> String {
+        match self {
+            Delivery::Pending => tr("log.pending"),
+            Delivery::Queued => tr("log.queued"),
+            Delivery::Sent => tr("log.sent"),
+            Delivery::Failed => tr("log.failed"),
+        }
+    }
+
+    /// The state in words, for the message details.
+    pub fn name(&self) -> String {
+        match self {
+            Delivery::Pending => tr("delivery.pending"),
+            Delivery::Queued => tr("delivery.queued"),
+            Delivery::Sent => tr("delivery.sent"),
+            Delivery::Failed => tr("delivery.failed"),
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -53,16 +380,42 @@ pub struct Log {
     pub ts: DateTime<Utc>,
     pub from: String,
     pub msg: String,
-    pub debug: bool,
+    pub kind: LogKind,
+    pub signature: Option<SignatureState>,
+    pub highlight: bool,
+    /// Time to live of an ephemeral message.
+    pub ttl: Option<Duration>,
+    /// Set on our own messages until the server has them.
+    pub delivery: Option<Delivery>,
+    /// Recipient of a direct message.
+    pub to: Option<String>,
+    /// Image file the entry is about, shown under it.
+    pub image: Option<PathBuf>,
+    /// Changed by its sender after it was sent.
+    pub edited: bool,
+    /// Taken back by its sender, the text stays but is struck through.
+    pub deleted: bool,
+    /// The line as first rendered, the log style doesn't change while running.
+    line: OnceCell<Line<'static>>,
 }
 
 impl Log {
     pub fn new(uname: String, message: String) -> Self {
+        let kind = LogKind::of(&uname);
         Self {
             ts: Utc::now(),
             from: uname,
             msg: message,
-            debug: false,
+            kind,
+            signature: None,
+            highlight: false,
+            ttl: None,
+            delivery: None,
+            to: None,
+            image: None,
+            edited: false,
+            deleted: false,
+            line: OnceCell::new(),
         }
     }
 
@@ -71,17 +424,101 @@ impl Log {
     }
 
     pub fn as_debug(mut self) -> Self {
-        self.debug = true;
+        self.kind = LogKind::Debug;
 
         self
     }
 
+    pub fn of_kind(mut self, kind: LogKind) -> Self {
+        self.kind = kind;
+
+        self
+    }
+
+    pub fn signed(mut self, signature: Option<SignatureState>) -> Self {
+        self.signature = signature;
+
+        self
+    }
+
+    pub fn highlighted(mut self) -> Self {
+        self.highlight = true;
+
+        self
+    }
+
+    pub fn ephemeral(mut self, ttl: Option<Duration>) -> Self {
+        self.ttl = ttl;
+
+        self
+    }
+
+    pub fn direct(mut self, to: String) -> Self {
+        self.to = Some(to);
+
+        self
+    }
+
+    pub fn image(mut self, path: PathBuf) -> Self {
+        self.image = Some(path);
+
+        self
+    }
+
+    pub fn delivery(mut self, delivery: Delivery) -> Self {
+        self.delivery = Some(delivery);
+
+        self
+    }
+
+    /// Roughly what the entry takes up in memory, leaving out the cached
+    /// line.
+    pub fn approx_size(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.from.capacity()
+            + self.msg.capacity()
+            + self.to.as_ref().map_or(0, String::capacity)
+            + self.image.as_ref().map_or(0, |p| p.as_os_str().len())
+    }
+
+    /// When an ephemeral message is to be dropped, None for one that isn't
+    /// or whose time to live goes past what a date can hold.
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        self.ts.checked_add_signed(self.ttl?)
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at().is_some_and(|at| at <= now)
+    }
+
     pub fn at(mut self, dt: DateTime<Utc>) -> Self {
-        self.ts = dt;
+        self.retime(dt);
 
         self
     }
 
+    pub fn edit(&mut self, msg: String) {
+        self.msg = msg;
+        self.edited = true;
+        self.line.take();
+    }
+
+    pub fn delete(&mut self) {
+        self.deleted = true;
+        self.line.take();
+    }
+
+    /// Drops the rendered line, for when the style it was rendered in
+    /// has changed.
+    pub fn restyle(&mut self) {
+        self.line.take();
+    }
+
+    pub fn retime(&mut self, dt: DateTime<Utc>) {
+        self.ts = dt;
+        self.line.take();
+    }
+
     pub fn get_ts(&self) -> DateTime<Utc> {
         self.ts.clone()
     }
@@ -94,22 +531,80 @@ impl Log {
         self.from.clone()
     }
 
-    pub fn should_render(&self, show_debug: bool) -> bool {
-        (!self.debug) || show_debug
+    /// Whether the log is shown, given whether debug logs and the system
+    /// noise of notifications and the client's own messages are.
+    pub fn should_render(&self, show_debug: bool, show_system: bool) -> bool {
+        match self.kind {
+            LogKind::Chat => true,
+            LogKind::Notification | LogKind::System => show_system,
+            LogKind::Debug => show_debug,
+        }
     }
 
+    /// Renders the log, borrowing from the line cached on the first call so
+    /// redrawing allocates no strings.
     pub fn render(&self, styles: &LogStyle) -> Line {
-        Line::default().spans([
-            Span::styled("[ ", styles.delims()),
+        let cached = self.line.get_or_init(|| self.render_uncached(styles));
+        let mut spans: Vec<Span> = cached
+            .spans
+            .iter()
+            .map(|s| Span::styled(s.content.as_ref(), s.style))
+            .collect();
+        // relative times and the countdown change as time passes, so they're
+        // never cached
+        if styles.relative {
+            let style = spans[TIME_SPAN].style;
+            spans[TIME_SPAN] = Span::styled(relative_time(self.ts, Utc::now()), style);
+        }
+        if let Some(at) = self.expires_at() {
+            let left = (at - Utc::now()).num_seconds().max(0);
+            let at = MESSAGE_SPAN - 1;
+            spans.insert(at, Span::styled(format!(" ⏱{left}s"), styles.time()));
+        }
+
+        Line::from(spans)
+    }
+
+    fn render_uncached(&self, styles: &LogStyle) -> Line<'static> {
+        let marker = match self.signature {
+            Some(SignatureState::Verified) => Span::styled(" ✓", Style::new().fg(Color::Green)),
+            Some(SignatureState::Invalid) => Span::styled(" ✗", Style::new().fg(Color::Red)),
+            None => Span::raw(""),
+        };
+        let mut msg_style = match (self.highlight, &self.to) {
+            (true, _) => styles.highlight(),
+            (false, Some(_)) => styles.dm(),
+            (false, None) => styles.msg_for(self.kind),
+        };
+        if self.deleted {
+            msg_style = msg_style.add_modifier(Modifier::CROSSED_OUT);
+        }
+        let uname = match self.to {
+            Some(ref to) => format!("{} → {to}", self.from),
+            None => self.get_username(),
+        };
+        let delims = styles.delims_for(self.kind);
+        let mut spans = vec![
+            Span::styled("[ ", delims),
             Span::styled(
-                self.ts.format(styles.time_fmt_str()).to_string(),
-                styles.time(),
+                styles.format_time_for(self.ts, self.kind),
+                styles.time_for(self.kind),
             ),
-            Span::styled(" : ", styles.delims()),
-            Span::styled(self.get_username(), styles.uname()),
-            Span::styled(" ]: ", styles.delims()),
-            Span::styled(self.msg.clone(), styles.msg()),
-        ])
+            Span::styled(" : ", delims),
+            Span::styled(uname, styles.uname_for_kind(&self.from, self.kind)),
+            marker,
+            Span::styled(" ]: ", delims),
+        ];
+        let msg = match styles.markdown {
+            true => markdown::spans(&self.msg, msg_style),
+            false => vec![Span::styled(self.msg.clone(), msg_style)],
+        };
+        spans.extend(links::underline(styles.highlights.apply(msg)));
+        if self.edited {
+            spans.push(Span::styled(tr("log.edited"), styles.time()));
+        }
+
+        Line::from(spans)
     }
 }
 
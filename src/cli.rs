@@ -0,0 +1,177 @@
+use std::{path::PathBuf, sync::OnceLock};
+
+use clap::{Parser, Subcommand};
+
+use crate::default_keybinds::KeyPreset;
+
+/// A client for the marain chat server.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Args {
+    /// Server to connect to: a hostname e.g. 'localhost', an IP address, a
+    /// full url e.g. 'wss://chat.example.com:8443/ws', or a join link e.g.
+    /// 'marain://chat.example.com:1337/rust' to also move to its room.
+    /// Defaults to the server in the config
+    pub host: Option<String>,
+
+    /// Port of the server, unless the address has one [default: 1337]
+    pub port: Option<String>,
+
+    /// Servers to try in order when the main one can't be reached
+    #[arg(long, value_name = "ADDRESS")]
+    pub fallback: Vec<String>,
+
+    /// Connect over TLS (wss://)
+    #[arg(long)]
+    pub tls: bool,
+
+    /// Use the high contrast, reduced decoration theme
+    #[arg(long)]
+    pub accessible: bool,
+
+    /// Built-in key bindings to use instead of the configured ones
+    #[arg(long, value_name = "PRESET")]
+    pub keys: Option<KeyPreset>,
+
+    /// Profile from the config to log in as, instead of choosing one
+    #[arg(long, value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// Move to this room once logged in, instead of `default_room` or
+    /// where the server puts new sessions
+    #[arg(long, value_name = "ROOM")]
+    pub room: Option<String>,
+
+    /// Only watch this room: no input, and nothing is sent. For dashboards
+    /// and status screens
+    #[arg(long, value_name = "ROOM", conflicts_with_all = ["daemon", "headless"])]
+    pub monitor: Option<String>,
+
+    /// Print every message from the server as a line of JSON, and send
+    /// each JSON message body read from stdin, instead of starting the UI
+    #[arg(long, conflicts_with_all = ["daemon", "headless", "monitor"])]
+    pub json: bool,
+
+    /// Stay connected in the background for clients to attach to
+    #[arg(long, conflicts_with = "attach")]
+    pub daemon: bool,
+
+    /// Attach to a running session daemon instead of connecting to the server
+    #[arg(long)]
+    pub attach: bool,
+
+    /// Run without a terminal, driving the app from a script of key presses,
+    /// waits and expected log lines, and print the results
+    #[arg(long, value_name = "SCRIPT", conflicts_with = "daemon")]
+    pub headless: Option<PathBuf>,
+
+    /// Write every event the app handles, with its timing, to a JSON lines
+    /// file. Messages are written decrypted, so keep it private
+    #[arg(long, value_name = "EVENTS", conflicts_with = "daemon")]
+    pub record: Option<PathBuf>,
+
+    /// Play a recording made with --record back in the UI, without
+    /// connecting to a server
+    #[arg(long, value_name = "EVENTS", conflicts_with_all = ["daemon", "headless", "record", "attach"])]
+    pub replay: Option<PathBuf>,
+
+    /// Ask for the server's password before connecting, or read it from
+    /// MARAIN_PASSWORD
+    #[arg(long)]
+    pub password: bool,
+
+    /// Write the log to this file instead of the configured one
+    #[arg(long, value_name = "PATH")]
+    pub log_file: Option<String>,
+
+    /// Log level: off, error, warn, info, debug or trace, or filter
+    /// directives like info,marain_client::socket_client=trace
+    #[arg(long, value_name = "LEVEL")]
+    pub log_level: Option<String>,
+
+    /// Write every key binding, with the configured ones, to this file and
+    /// exit. Markdown if it ends in .md, plain text otherwise
+    #[arg(long, value_name = "PATH")]
+    pub dump_keys: Option<PathBuf>,
+
+    /// Don't write a log file
+    #[arg(long, conflicts_with_all = ["log_file", "log_level"])]
+    pub no_log: bool,
+
+    #[command(subcommand)]
+    pub action: Option<Action>,
+}
+
+/// Things to do instead of starting the UI.
+#[derive(Subcommand, Debug)]
+pub enum Action {
+    /// Log in, send one message and exit, for scripts and cron jobs
+    Send {
+        /// Room to send to, instead of the one the server puts you in
+        #[arg(long)]
+        room: Option<String>,
+        /// The message to send
+        message: String,
+    },
+    /// Send each line piped in as a chat message, until the input ends
+    Pipe {
+        /// Room to send to, instead of the one the server puts you in
+        #[arg(long)]
+        room: Option<String>,
+        /// Send lines that come within this many milliseconds of each
+        /// other as one message
+        #[arg(long, value_name = "MS", default_value_t = 0)]
+        batch: u64,
+        /// Messages per second at most, instead of the configured rate limit
+        #[arg(long, value_name = "N")]
+        rate: Option<f64>,
+    },
+}
+
+static ARGS: OnceLock<Args> = OnceLock::new();
+
+/// The parsed command line, exits with usage help if it is invalid.
+pub fn args() -> &'static Args {
+    ARGS.get_or_init(parse)
+}
+
+impl Args {
+    /// Splits a join link given as the host into the server and the room,
+    /// `--room` still taking precedence over the link's room.
+    fn with_invite(mut self) -> Self {
+        let invite = self.host.as_deref().and_then(crate::qr::parse_invite);
+        if let Some((address, room)) = invite {
+            self.host = Some(address);
+            self.room = self.room.or(room);
+        }
+
+        self
+    }
+}
+
+#[cfg(not(test))]
+fn parse() -> Args {
+    Args::parse().with_invite()
+}
+
+/// The test harness's own arguments aren't ours to parse.
+#[cfg(test)]
+fn parse() -> Args {
+    Args::parse_from(["marain-client", "localhost"])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_join_link_picks_the_server_and_room() {
+        let args = Args::parse_from(["marain-client", "marain://chat.example.com:1337/rust"]);
+        let args = args.with_invite();
+        assert_eq!(args.host.as_deref(), Some("chat.example.com:1337"));
+        assert_eq!(args.room.as_deref(), Some("rust"));
+
+        let args = Args::parse_from(["marain-client", "marain://localhost/rust", "--room", "go"]);
+        assert_eq!(args.with_invite().room.as_deref(), Some("go"));
+    }
+}
@@ -0,0 +1,87 @@
+use crate::slash_commands;
+
+/// Completions being cycled through with Tab.
+#[derive(Debug, Clone)]
+pub struct Completion {
+    pub candidates: Vec<String>,
+    pub selected: usize,
+    /// Candidates replace the word before the caret rather than the whole
+    /// buffer.
+    pub word: bool,
+}
+
+impl Completion {
+    /// Starts cycling from the first candidate, `None` if there are none.
+    pub fn start(candidates: Vec<String>, word: bool) -> Option<Self> {
+        match candidates.is_empty() {
+            true => None,
+            false => Some(Self {
+                candidates,
+                selected: 0,
+                word,
+            }),
+        }
+    }
+
+    pub fn next(&mut self) {
+        self.selected = (self.selected + 1) % self.candidates.len();
+    }
+
+    pub fn current(&self) -> &str {
+        &self.candidates[self.selected]
+    }
+}
+
+/// Slash commands starting with the typed name, once a space has been typed
+/// the name is done and there is nothing to complete.
+pub fn commands(input: &str) -> Vec<String> {
+    let Some(name) = input.strip_prefix('/') else {
+        return vec![];
+    };
+    if name.contains(' ') {
+        return vec![];
+    }
+    slash_commands::NAMES
+        .iter()
+        .filter(|n| n.starts_with(name))
+        .map(|n| format!("/{n} "))
+        .collect()
+}
+
+/// Occupants whose names start with `word`, ignoring case. A mention's `@`
+/// is kept on the completions, an empty word completes nothing.
+pub fn usernames<'a>(word: &str, occupants: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let (at, name) = match word.strip_prefix('@') {
+        Some(name) => ("@", name),
+        None => ("", word),
+    };
+    if name.is_empty() && at.is_empty() {
+        return vec![];
+    }
+    let name = name.to_lowercase();
+    let mut found: Vec<String> = occupants
+        .filter(|o| o.to_lowercase().starts_with(&name))
+        .map(|o| format!("{at}{o}"))
+        .collect();
+    found.sort_by_key(|o| o.to_lowercase());
+
+    found
+}
+
+/// Parameters given to a staged command before that start with `input`,
+/// newest first.
+pub fn params(input: &str, earlier: Vec<String>) -> Vec<String> {
+    earlier
+        .into_iter()
+        .filter(|p| p.starts_with(input))
+        .collect()
+}
+
+/// Known room names starting with `input`, ignoring case.
+pub fn rooms<'a>(input: &str, rooms: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let input = input.to_lowercase();
+    rooms
+        .filter(|r| r.to_lowercase().starts_with(&input))
+        .map(String::from)
+        .collect()
+}
@@ -0,0 +1,76 @@
+use std::io::{Read, Write};
+
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+
+/// Starts a compressed payload. A bincode message can't start with it, the
+/// first four bytes of one are a small enum tag.
+const MAGIC: &[u8] = b"MZD\x01";
+
+/// Payloads smaller than this aren't worth compressing.
+pub const MIN_SIZE: usize = 512;
+
+/// Largest payload inflated, well over any room's history. Anything bigger
+/// is refused rather than letting a few bytes of deflate fill the memory.
+const MAX_INFLATED: u64 = 16 * 1024 * 1024;
+
+pub fn is_compressed(payload: &[u8]) -> bool {
+    payload.starts_with(MAGIC)
+}
+
+/// Deflates `payload` behind the marker, or leaves it as it is if that
+/// wouldn't make it smaller.
+pub fn compress(payload: Vec<u8>) -> Vec<u8> {
+    if payload.len() < MIN_SIZE {
+        return payload;
+    }
+    let mut encoder = DeflateEncoder::new(MAGIC.to_vec(), Compression::default());
+    match encoder.write_all(&payload).and_then(|_| encoder.finish()) {
+        Ok(compressed) if compressed.len() < payload.len() => compressed,
+        _ => payload,
+    }
+}
+
+/// Inflates a compressed payload, anything else is passed through.
+pub fn decompress(payload: Vec<u8>) -> Result<Vec<u8>, String> {
+    if !is_compressed(&payload) {
+        return Ok(payload);
+    }
+    let mut inflated = vec![];
+    DeflateDecoder::new(&payload[MAGIC.len()..])
+        .take(MAX_INFLATED + 1)
+        .read_to_end(&mut inflated)
+        .map_err(|e| e.to_string())?;
+    if inflated.len() as u64 > MAX_INFLATED {
+        return Err(format!("inflates to over {MAX_INFLATED} bytes"));
+    }
+    Ok(inflated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_large_payloads_and_skips_small_ones() {
+        let large = "the same line over and over\n".repeat(100).into_bytes();
+        let compressed = compress(large.clone());
+        assert!(is_compressed(&compressed));
+        assert!(compressed.len() < large.len());
+        assert_eq!(decompress(compressed).unwrap(), large);
+
+        let small = b"hi".to_vec();
+        assert_eq!(compress(small.clone()), small);
+        assert_eq!(decompress(small.clone()).unwrap(), small);
+    }
+
+    #[test]
+    fn refuses_payloads_that_inflate_too_far() {
+        let mut encoder = DeflateEncoder::new(MAGIC.to_vec(), Compression::best());
+        encoder
+            .write_all(&vec![0; MAX_INFLATED as usize + 1])
+            .unwrap();
+        let bomb = encoder.finish().unwrap();
+        assert!(bomb.len() < 64 * 1024);
+        assert!(decompress(bomb).is_err());
+    }
+}
@@ -0,0 +1,64 @@
+use std::sync::Mutex;
+
+use arboard::Clipboard;
+
+/// Kept for the life of the process, on X11 the copied text is only
+/// available while the clipboard that set it is still around.
+static CLIPBOARD: Mutex<Option<Clipboard>> = Mutex::new(None);
+
+/// Selection of chat log lines in copy mode. Positions count rendered logs
+/// back from the newest, like the scroll offset.
+#[derive(Debug, Clone)]
+pub struct CopySelection {
+    pub cursor: usize,
+    /// Where the selection was started, nothing is selected until then.
+    pub anchor: Option<usize>,
+}
+
+impl CopySelection {
+    pub fn new(cursor: usize) -> Self {
+        Self {
+            cursor,
+            anchor: None,
+        }
+    }
+
+    /// Moves the cursor by `by` lines, positive is back in time, staying
+    /// within the `count` logs there are.
+    pub fn move_by(&mut self, by: isize, count: usize) {
+        let max = count.saturating_sub(1);
+        self.cursor = self.cursor.saturating_add_signed(by).min(max);
+    }
+
+    /// Starts the selection at the cursor, or drops it if already started.
+    pub fn toggle_anchor(&mut self) {
+        self.anchor = match self.anchor {
+            Some(_) => None,
+            None => Some(self.cursor),
+        };
+    }
+
+    /// Newest and oldest positions selected, just the cursor line if the
+    /// selection wasn't started.
+    pub fn range(&self) -> (usize, usize) {
+        let anchor = self.anchor.unwrap_or(self.cursor);
+        (anchor.min(self.cursor), anchor.max(self.cursor))
+    }
+
+    pub fn contains(&self, pos: usize) -> bool {
+        let (newest, oldest) = self.range();
+        (newest..=oldest).contains(&pos)
+    }
+}
+
+/// Puts `text` on the system clipboard.
+pub fn copy_to_clipboard(text: &str) -> Result<(), arboard::Error> {
+    let mut clipboard = CLIPBOARD.lock().unwrap_or_else(|e| e.into_inner());
+    if clipboard.is_none() {
+        *clipboard = Some(Clipboard::new()?);
+    }
+    match clipboard.as_mut() {
+        Some(clipboard) => clipboard.set_text(text),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,104 @@
+use std::{
+    fmt::Debug,
+    io::{self, stdout, Write},
+};
+
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    terminal,
+};
+
+use crate::{cli, i18n::tr, user_config::UserConfig};
+
+/// Read instead of asking when set, for scripted runs.
+const PASSWORD_VAR: &str = "MARAIN_PASSWORD";
+
+/// Password or pre-shared secret for servers that want one. Kept out of
+/// debug output so it never ends up in the log file.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Credential(String);
+
+impl Credential {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl From<&str> for Credential {
+    fn from(secret: &str) -> Self {
+        Self(secret.to_string())
+    }
+}
+
+impl Debug for Credential {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Credential(***)")
+    }
+}
+
+/// Whether the server wants a credential, per the config or command line.
+pub fn required(config: &UserConfig) -> bool {
+    config.ask_password || cli::args().password
+}
+
+/// Whether a refused credential can be asked for again.
+pub fn interactive() -> bool {
+    std::env::var_os(PASSWORD_VAR).is_none() && cli::args().headless.is_none()
+}
+
+/// The credential from the environment, or asked for on the terminal.
+pub fn obtain(prompt: &str) -> io::Result<Credential> {
+    match std::env::var(PASSWORD_VAR) {
+        Ok(secret) => Ok(Credential(secret)),
+        Err(_) => ask(prompt),
+    }
+}
+
+/// Asks on the plain terminal, before the UI takes it over. What is typed
+/// shows as `*`s.
+pub fn ask(prompt: &str) -> io::Result<Credential> {
+    let mut out = stdout();
+    write!(out, "{prompt}")?;
+    out.flush()?;
+    terminal::enable_raw_mode()?;
+    let secret = read_masked(&mut out);
+    terminal::disable_raw_mode()?;
+    writeln!(out)?;
+
+    secret.map(Credential)
+}
+
+fn cancelled() -> io::Error {
+    io::Error::new(io::ErrorKind::Interrupted, tr("err.password_cancelled"))
+}
+
+fn read_masked(out: &mut impl Write) -> io::Result<String> {
+    let mut secret = String::new();
+    loop {
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Enter => return Ok(secret),
+            KeyCode::Esc => return Err(cancelled()),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return Err(cancelled())
+            }
+            KeyCode::Backspace => {
+                if secret.pop().is_some() {
+                    write!(out, "\x08 \x08")?;
+                }
+            }
+            KeyCode::Char(c) => {
+                secret.push(c);
+                write!(out, "*")?;
+            }
+            _ => continue,
+        }
+        out.flush()?;
+    }
+}
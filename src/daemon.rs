@@ -0,0 +1,293 @@
+use std::{
+    collections::VecDeque,
+    fs::{self, DirBuilder},
+    os::unix::fs::{DirBuilderExt, PermissionsExt},
+    path::PathBuf,
+    time::Duration,
+};
+
+use color_eyre::{eyre::eyre, Result};
+use futures::{channel::mpsc, SinkExt, StreamExt};
+use marain_api::prelude::Key;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{
+        unix::{OwnedReadHalf, OwnedWriteHalf},
+        UnixListener, UnixStream,
+    },
+    sync::mpsc::{self as bounded, channel, error::TrySendError},
+    task::JoinHandle,
+};
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::{
+    credential,
+    i18n::tr,
+    shared_secret,
+    signing::KnownServers,
+    socket_client::{SocketClient, SocketConf, SOCKET_BUFFER},
+    user_config::{config_dir, UserConfig},
+};
+
+/// Number of server messages kept for replaying to a newly attached client.
+const HISTORY_LEN: usize = 1000;
+
+/// Largest frame read from the local socket. Anything that claims to be
+/// bigger isn't from a client of ours.
+const MAX_FRAME: u32 = 16 * 1024 * 1024;
+
+/// How long the attached client gets to take the last frames when the
+/// server closes the connection.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// What an attaching client needs to carry on the daemon's session, sent as
+/// the first frame after attaching.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Session {
+    pub username: String,
+    pub token: String,
+    pub shared_secret: Key,
+}
+
+/// The socket sits in a directory only the user can enter, so no one
+/// else can connect in the moment between binding it and locking it down.
+fn socket_dir() -> PathBuf {
+    config_dir().join("daemon")
+}
+
+pub fn socket_path() -> PathBuf {
+    socket_dir().join("marain.sock")
+}
+
+/// Frames on the local socket are a big endian u32 length followed by the
+/// payload. An empty frame from the daemon means the server closed the
+/// connection.
+async fn write_frame(stream: &mut OwnedWriteHalf, data: &[u8]) -> std::io::Result<()> {
+    stream.write_u32(data.len() as u32).await?;
+    stream.write_all(data).await
+}
+
+async fn read_frame(stream: &mut OwnedReadHalf) -> std::io::Result<Vec<u8>> {
+    let len = stream.read_u32().await?;
+    if len > MAX_FRAME {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("frame of {len} bytes"),
+        ));
+    }
+    let mut data = vec![0; len as usize];
+    stream.read_exact(&mut data).await?;
+    Ok(data)
+}
+
+/// The client attached to the daemon. Frames go through a writer task of
+/// its own, so a client that's slow to read doesn't hold up the server.
+struct Attached {
+    frames: bounded::Sender<Vec<u8>>,
+    writer: JoinHandle<()>,
+    /// Forwards what the client sends to the server.
+    reader: JoinHandle<()>,
+}
+
+impl Attached {
+    /// Starts writing to a client that just connected: the session first,
+    /// then the history so far, then whatever is relayed. `reader` is the
+    /// task forwarding what it sends, stopped along with the writer.
+    fn start(
+        mut stream: OwnedWriteHalf,
+        reader: JoinHandle<()>,
+        session: Vec<u8>,
+        history: Vec<Vec<u8>>,
+    ) -> Self {
+        let (frames, mut queued) = bounded::channel::<Vec<u8>>(SOCKET_BUFFER);
+        let writer = tokio::spawn(async move {
+            for data in std::iter::once(session).chain(history) {
+                if let Err(e) = write_frame(&mut stream, &data).await {
+                    tracing::info!("Client went away while attaching: {e}");
+                    return;
+                }
+            }
+            while let Some(data) = queued.recv().await {
+                if write_frame(&mut stream, &data).await.is_err() {
+                    return;
+                }
+            }
+        });
+        Self {
+            frames,
+            writer,
+            reader,
+        }
+    }
+
+    /// Queues a frame for the client, false once it's gone or has fallen
+    /// so far behind that it had better attach again.
+    fn relay(&self, data: Vec<u8>) -> bool {
+        match self.frames.try_send(data) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                tracing::warn!("Attached client is too slow, detaching it");
+                false
+            }
+            Err(TrySendError::Closed(_)) => false,
+        }
+    }
+
+    /// Stops talking to a client that's gone, given up on or taken over.
+    fn detach(self) {
+        self.writer.abort();
+        self.reader.abort();
+    }
+
+    /// Tells the client the server closed the connection.
+    async fn close(self) {
+        let Self {
+            frames,
+            writer,
+            reader,
+        } = self;
+        reader.abort();
+        let abort = writer.abort_handle();
+        let flushed = async move {
+            _ = frames.send(vec![]).await;
+            drop(frames);
+            _ = writer.await;
+        };
+        if tokio::time::timeout(CLOSE_TIMEOUT, flushed).await.is_err() {
+            abort.abort();
+        }
+    }
+}
+
+/// Logs in and keeps the session alive in the background, relaying server
+/// messages to whichever client is attached over the local socket.
+pub async fn serve(config: UserConfig) -> Result<()> {
+    let username = config.get_username();
+    let conf =
+        SocketConf::for_config(&config).with_credential(match credential::required(&config) {
+            true => Some(credential::obtain(&tr("prompt.password"))?),
+            false => None,
+        });
+    let (mut client, session, _) =
+        shared_secret::login(&conf, username.clone(), &KnownServers::load(&config_dir())).await?;
+    let session = serde_json::to_vec(&Session {
+        username,
+        shared_secret: session.shared_secret(),
+        token: session.token,
+    })?;
+
+    let dir = socket_dir();
+    DirBuilder::new().recursive(true).mode(0o700).create(&dir)?;
+    // in case it was made before it had to be private
+    fs::set_permissions(&dir, fs::Permissions::from_mode(0o700))?;
+    let path = socket_path();
+    // a socket left behind by a daemon that died would make bind fail
+    _ = fs::remove_file(&path);
+    let listener = UnixListener::bind(&path)?;
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+    tracing::info!("Session daemon listening on {}", path.display());
+
+    let mut history: VecDeque<Vec<u8>> = VecDeque::new();
+    let mut attached: Option<Attached> = None;
+    loop {
+        tokio::select! {
+            msg = client.next() => {
+                let data = match msg? {
+                    Message::Binary(data) => data,
+                    Message::Close(_) => {
+                        if let Some(client) = attached.take() {
+                            client.close().await;
+                        }
+                        break;
+                    }
+                    _ => continue,
+                };
+                if let Some(client) = attached.take_if(|client| !client.relay(data.clone())) {
+                    tracing::info!("Client detached");
+                    client.detach();
+                }
+                history.push_back(data);
+                if history.len() > HISTORY_LEN {
+                    history.pop_front();
+                }
+            }
+            conn = listener.accept() => {
+                // a client that fails to connect is no reason to stop
+                let stream = match conn {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        tracing::error!("Could not accept a client: {e}");
+                        continue;
+                    }
+                };
+                let (reader, writer) = stream.into_split();
+                let history = history.iter().cloned().collect();
+                tracing::info!("Client attached");
+
+                let mut out_sink = client.out_sink.clone();
+                let reader = tokio::spawn(async move {
+                    let mut reader = reader;
+                    while let Ok(data) = read_frame(&mut reader).await {
+                        if out_sink.send(Message::Binary(data)).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+                // only one client at a time, a new one takes over
+                let attaching = Attached::start(writer, reader, session.clone(), history);
+                if let Some(previous) = attached.replace(attaching) {
+                    tracing::info!("Previous client taken over");
+                    previous.detach();
+                }
+            }
+        }
+    }
+
+    _ = fs::remove_file(&path);
+    Ok(())
+}
+
+/// Attaches to a running daemon, returning a client that behaves like a
+/// websocket connection and the session to carry on.
+pub async fn attach() -> Result<(SocketClient, Session)> {
+    let path = socket_path();
+    let stream = UnixStream::connect(&path)
+        .await
+        .map_err(|e| eyre!("No session daemon at {}: {e}", path.display()))?;
+    let (mut reader, mut writer) = stream.into_split();
+    let session: Session = serde_json::from_slice(&read_frame(&mut reader).await?)?;
+
+    let (out_sink, mut out_source) = mpsc::channel::<Message>(SOCKET_BUFFER);
+    let (in_sink, in_source) = channel::<Message>(SOCKET_BUFFER);
+    tokio::spawn(async move {
+        while let Some(msg) = out_source.next().await {
+            if let Message::Binary(data) = msg {
+                if write_frame(&mut writer, &data).await.is_err() {
+                    break;
+                }
+            }
+        }
+    });
+    let task = tokio::spawn(async move {
+        loop {
+            let msg = match read_frame(&mut reader).await {
+                Ok(data) if data.is_empty() => Message::Close(None),
+                Ok(data) => Message::Binary(data),
+                Err(e) => {
+                    tracing::error!("Lost connection to the session daemon: {e}");
+                    Message::Close(None)
+                }
+            };
+            let closed = matches!(msg, Message::Close(_));
+            if in_sink.send(msg).await.is_err() || closed {
+                break;
+            }
+        }
+    });
+
+    Ok((
+        SocketClient::from_channels(task, out_sink, in_source),
+        session,
+    ))
+}
@@ -1,36 +1,339 @@
-use crossterm::event::KeyCode;
+use crossterm::event::{KeyCode, KeyModifiers};
+use serde::{Deserialize, Serialize};
 
 use crate::app::{CaretMotion, Command, KeyBinds, Mode};
 
-fn disocnnected() -> (Mode, Vec<KeyBinds>) {
-    (
-        Mode::Disconnected,
-        vec![KeyBinds::Explicit(KeyCode::Char('q'), Command::Quit)],
-    )
+/// The built-in binding sets, user bindings go on top of whichever is
+/// chosen.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyPreset {
+    /// Navigate and Insert modes with single letter commands.
+    #[default]
+    Vim,
+    /// Always writing, with Ctrl and Alt chords for everything else.
+    Emacs,
+    /// Always writing, arrows and common Ctrl shortcuts.
+    Plain,
 }
 
-fn navigate() -> (Mode, Vec<KeyBinds>) {
+impl KeyPreset {
+    /// Whether the app stays in Insert mode instead of going back to
+    /// Navigate.
+    pub fn modeless(self) -> bool {
+        self != Self::Vim
+    }
+}
+
+fn disocnnected() -> (Mode, Vec<KeyBinds>) {
     (
-        Mode::Navigate,
+        Mode::Disconnected,
         vec![
-            KeyBinds::Explicit(KeyCode::Char('i'), Command::Enter(Mode::Insert)),
             KeyBinds::Explicit(KeyCode::Char('q'), Command::Quit),
-            KeyBinds::Explicit(KeyCode::Char('r'), Command::Reset),
-            KeyBinds::Explicit(KeyCode::Char('t'), Command::GetServerTime),
-            KeyBinds::Explicit(KeyCode::Char('m'), Command::MoveRooms(None)),
-            KeyBinds::Explicit(KeyCode::Char('d'), Command::ToggleDebug),
+            KeyBinds::Explicit(KeyCode::Char('r'), Command::Reconnect),
+            // write messages to send once reconnected
+            KeyBinds::Explicit(KeyCode::Char('i'), Command::Enter(Mode::Insert)),
         ],
     )
 }
 
+/// Switching tabs works while writing too, so these go ahead of text
+/// capture.
+fn tabs() -> Vec<KeyBinds> {
+    let mut binds: Vec<KeyBinds> = ('1'..='9')
+        .enumerate()
+        .map(|(i, digit)| {
+            KeyBinds::Chord(
+                KeyCode::Char(digit),
+                KeyModifiers::CONTROL,
+                Command::SwitchTab(i),
+            )
+        })
+        .collect();
+    binds.extend([
+        KeyBinds::Chord(KeyCode::Tab, KeyModifiers::CONTROL, Command::CycleTab(1)),
+        KeyBinds::Chord(
+            KeyCode::BackTab,
+            KeyModifiers::CONTROL | KeyModifiers::SHIFT,
+            Command::CycleTab(-1),
+        ),
+    ]);
+
+    binds
+}
+
+fn navigate() -> (Mode, Vec<KeyBinds>) {
+    let mut binds = tabs();
+    binds.extend([
+        // for terminals that don't report Ctrl with Tab or digits
+        KeyBinds::Sequence(KeyCode::Char('g'), KeyCode::Char('t'), Command::CycleTab(1)),
+        KeyBinds::Sequence(KeyCode::Char('g'), KeyCode::Char('p'), Command::TogglePins),
+        KeyBinds::Sequence(
+            KeyCode::Char('g'),
+            KeyCode::Char('T'),
+            Command::CycleTab(-1),
+        ),
+        KeyBinds::Chord(KeyCode::Char('w'), KeyModifiers::CONTROL, Command::CloseTab),
+        KeyBinds::Explicit(KeyCode::Char('i'), Command::Enter(Mode::Insert)),
+        KeyBinds::Explicit(KeyCode::Char('q'), Command::Quit),
+        KeyBinds::Explicit(KeyCode::Char('r'), Command::Reset),
+        KeyBinds::Explicit(KeyCode::Char('t'), Command::GetServerTime),
+        KeyBinds::Explicit(KeyCode::Char('U'), Command::RefreshRoom),
+        KeyBinds::Explicit(KeyCode::Char('m'), Command::MoveRooms(None)),
+        KeyBinds::Explicit(KeyCode::Char('c'), Command::CreateRoom(None)),
+        KeyBinds::Explicit(KeyCode::Char('o'), Command::RoomDirectory),
+        KeyBinds::Explicit(KeyCode::Char('-'), Command::PreviousRoom),
+        KeyBinds::Explicit(KeyCode::Char('_'), Command::RecentRooms),
+        KeyBinds::Explicit(KeyCode::Char('D'), Command::ToggleDebug),
+        KeyBinds::Explicit(KeyCode::Char('W'), Command::ToggleTraffic),
+        KeyBinds::Explicit(KeyCode::Char('S'), Command::ToggleSystem),
+        KeyBinds::Explicit(KeyCode::Char('T'), Command::ToggleRelativeTime),
+        KeyBinds::Explicit(KeyCode::Char('z'), Command::ToggleDensity),
+        KeyBinds::Explicit(KeyCode::Char('O'), Command::SelectFailed),
+        KeyBinds::Explicit(KeyCode::Char('M'), Command::ToggleDms),
+        KeyBinds::Explicit(KeyCode::Char('u'), Command::OpenLink),
+        KeyBinds::Explicit(KeyCode::Char('?'), Command::ShowHelp),
+        KeyBinds::Explicit(KeyCode::Char('e'), Command::SelectOwnMessage),
+        KeyBinds::Explicit(KeyCode::Enter, Command::SelectLog),
+        KeyBinds::Explicit(KeyCode::Char('K'), Command::ExportKeymap(None)),
+        KeyBinds::Explicit(KeyCode::Char('L'), Command::ImportKeymap(None)),
+        KeyBinds::Explicit(KeyCode::Char('E'), Command::ExportLogs(None)),
+        KeyBinds::Explicit(KeyCode::Char('H'), Command::ExportHtml(None)),
+        KeyBinds::Explicit(KeyCode::Char('P'), Command::PinMessage),
+        KeyBinds::Explicit(KeyCode::Char('F'), Command::ShowServerKeys),
+        KeyBinds::Explicit(KeyCode::Char('I'), Command::ShowServerInfo),
+        KeyBinds::Explicit(KeyCode::Char('X'), Command::ClearHistory),
+        KeyBinds::Explicit(KeyCode::Char('x'), Command::CancelUpload),
+        KeyBinds::Explicit(KeyCode::Char('R'), Command::Enter(Mode::RoomList)),
+        // vim motions over the buffer
+        KeyBinds::Explicit(
+            KeyCode::Char('0'),
+            Command::MoveCaret(CaretMotion::LineBoundary, -1),
+        ),
+        KeyBinds::Explicit(
+            KeyCode::Char('$'),
+            Command::MoveCaret(CaretMotion::LineBoundary, 1),
+        ),
+        KeyBinds::Explicit(KeyCode::Char('w'), Command::MoveCaret(CaretMotion::Word, 1)),
+        KeyBinds::Explicit(
+            KeyCode::Char('b'),
+            Command::MoveCaret(CaretMotion::Word, -1),
+        ),
+        KeyBinds::Sequence(
+            KeyCode::Char('g'),
+            KeyCode::Char('g'),
+            Command::MoveCaret(CaretMotion::BufferBoundary, -1),
+        ),
+        KeyBinds::Explicit(
+            KeyCode::Char('G'),
+            Command::MoveCaret(CaretMotion::BufferBoundary, 1),
+        ),
+        KeyBinds::Sequence(KeyCode::Char('d'), KeyCode::Char('d'), Command::DeleteLine),
+        // copy from the chat log
+        KeyBinds::Explicit(KeyCode::Char('v'), Command::Enter(Mode::Copy)),
+        KeyBinds::Explicit(KeyCode::Char('p'), Command::PasteYanked),
+        // search
+        KeyBinds::Explicit(KeyCode::Char('/'), Command::Enter(Mode::Search)),
+        KeyBinds::Explicit(KeyCode::Char('n'), Command::SearchNext),
+        KeyBinds::Explicit(KeyCode::Char('N'), Command::SearchPrev),
+        KeyBinds::Explicit(KeyCode::Esc, Command::ClearSearch),
+        // scrollback
+        KeyBinds::Explicit(KeyCode::PageUp, Command::Scroll(10)),
+        KeyBinds::Explicit(KeyCode::PageDown, Command::Scroll(-10)),
+        KeyBinds::Explicit(KeyCode::Char('k'), Command::Scroll(1)),
+        KeyBinds::Explicit(KeyCode::Char('j'), Command::Scroll(-1)),
+        KeyBinds::Explicit(KeyCode::Home, Command::ScrollTop),
+        KeyBinds::Chord(
+            KeyCode::PageUp,
+            KeyModifiers::CONTROL,
+            Command::ScrollDebug(10),
+        ),
+        KeyBinds::Chord(
+            KeyCode::PageDown,
+            KeyModifiers::CONTROL,
+            Command::ScrollDebug(-10),
+        ),
+        KeyBinds::Explicit(KeyCode::End, Command::ScrollBottom),
+    ]);
+
+    (Mode::Navigate, binds)
+}
+
 fn insert() -> (Mode, Vec<KeyBinds>) {
+    let mut binds = tabs();
+    binds.extend([
+        // leave insert mode
+        KeyBinds::Explicit(KeyCode::Esc, Command::Enter(Mode::Navigate)),
+        // send message
+        KeyBinds::Explicit(KeyCode::Enter, Command::SendBuffer),
+        // Caret controls
+        KeyBinds::Explicit(
+            KeyCode::Left,
+            Command::MoveCaret(CaretMotion::Character, -1),
+        ),
+        KeyBinds::Explicit(
+            KeyCode::Right,
+            Command::MoveCaret(CaretMotion::Character, 1),
+        ),
+        KeyBinds::Explicit(KeyCode::Up, Command::MoveCaret(CaretMotion::Line, -1)),
+        KeyBinds::Explicit(KeyCode::Down, Command::MoveCaret(CaretMotion::Line, 1)),
+        KeyBinds::Chord(
+            KeyCode::Left,
+            KeyModifiers::CONTROL,
+            Command::MoveCaret(CaretMotion::Word, -1),
+        ),
+        KeyBinds::Chord(
+            KeyCode::Right,
+            KeyModifiers::CONTROL,
+            Command::MoveCaret(CaretMotion::Word, 1),
+        ),
+        KeyBinds::Chord(
+            KeyCode::Backspace,
+            KeyModifiers::CONTROL,
+            Command::DelWord(-1),
+        ),
+        KeyBinds::Chord(KeyCode::Delete, KeyModifiers::CONTROL, Command::DelWord(0)),
+        KeyBinds::Explicit(
+            KeyCode::Home,
+            Command::MoveCaret(CaretMotion::LineBoundary, -1),
+        ),
+        KeyBinds::Explicit(
+            KeyCode::End,
+            Command::MoveCaret(CaretMotion::LineBoundary, 1),
+        ),
+        KeyBinds::Chord(
+            KeyCode::Home,
+            KeyModifiers::CONTROL,
+            Command::MoveCaret(CaretMotion::BufferBoundary, -1),
+        ),
+        KeyBinds::Chord(
+            KeyCode::End,
+            KeyModifiers::CONTROL,
+            Command::MoveCaret(CaretMotion::BufferBoundary, 1),
+        ),
+        // history
+        KeyBinds::Chord(
+            KeyCode::Char('p'),
+            KeyModifiers::CONTROL,
+            Command::HistoryPrev,
+        ),
+        KeyBinds::Chord(
+            KeyCode::Char('n'),
+            KeyModifiers::CONTROL,
+            Command::HistoryNext,
+        ),
+        KeyBinds::Explicit(KeyCode::Tab, Command::Complete),
+        KeyBinds::Chord(
+            KeyCode::Char('$'),
+            KeyModifiers::ALT,
+            Command::SuggestSpelling,
+        ),
+        // readline editing
+        KeyBinds::Chord(
+            KeyCode::Char('a'),
+            KeyModifiers::CONTROL,
+            Command::MoveCaret(CaretMotion::LineBoundary, -1),
+        ),
+        KeyBinds::Chord(
+            KeyCode::Char('e'),
+            KeyModifiers::CONTROL,
+            Command::MoveCaret(CaretMotion::LineBoundary, 1),
+        ),
+        KeyBinds::Chord(
+            KeyCode::Char('u'),
+            KeyModifiers::CONTROL,
+            Command::Kill(CaretMotion::LineBoundary, -1),
+        ),
+        KeyBinds::Chord(
+            KeyCode::Char('k'),
+            KeyModifiers::CONTROL,
+            Command::Kill(CaretMotion::LineBoundary, 1),
+        ),
+        KeyBinds::Chord(
+            KeyCode::Char('w'),
+            KeyModifiers::CONTROL,
+            Command::Kill(CaretMotion::Word, -1),
+        ),
+        KeyBinds::Chord(KeyCode::Char('y'), KeyModifiers::CONTROL, Command::Yank),
+        KeyBinds::Chord(KeyCode::Char('z'), KeyModifiers::CONTROL, Command::Undo),
+        // Ctrl+^, which terminals send as Ctrl+6
+        KeyBinds::Chord(
+            KeyCode::Char('6'),
+            KeyModifiers::CONTROL,
+            Command::PreviousRoom,
+        ),
+        // text input
+        KeyBinds::capture(),
+        // deletion
+        KeyBinds::Explicit(KeyCode::Backspace, Command::Del(-1)),
+        KeyBinds::Explicit(KeyCode::Delete, Command::Del(0)),
+    ]);
+
+    (Mode::Insert, binds)
+}
+
+/// Insert mode for the `emacs` preset, which never leaves it. The chords
+/// go ahead of the vim ones so they win where both bind a key.
+fn emacs_insert() -> (Mode, Vec<KeyBinds>) {
+    let ctrl = |c, command| KeyBinds::Chord(KeyCode::Char(c), KeyModifiers::CONTROL, command);
+    let alt = |c, command| KeyBinds::Chord(KeyCode::Char(c), KeyModifiers::ALT, command);
+    let mut binds = vec![
+        ctrl('f', Command::MoveCaret(CaretMotion::Character, 1)),
+        ctrl('b', Command::MoveCaret(CaretMotion::Character, -1)),
+        alt('f', Command::MoveCaret(CaretMotion::Word, 1)),
+        alt('b', Command::MoveCaret(CaretMotion::Word, -1)),
+        ctrl('d', Command::Del(0)),
+        alt('d', Command::Kill(CaretMotion::Word, 1)),
+        KeyBinds::Chord(
+            KeyCode::Backspace,
+            KeyModifiers::ALT,
+            Command::Kill(CaretMotion::Word, -1),
+        ),
+        // scrolling the log
+        ctrl('v', Command::Scroll(-10)),
+        alt('v', Command::Scroll(10)),
+        alt('<', Command::ScrollTop),
+        alt('>', Command::ScrollBottom),
+        // everything Navigate mode would do
+        ctrl('g', Command::Enter(Mode::Navigate)),
+        ctrl('s', Command::Enter(Mode::Search)),
+        ctrl('q', Command::Quit),
+        alt('r', Command::Enter(Mode::RoomList)),
+        alt('m', Command::MoveRooms(None)),
+        alt('o', Command::RoomDirectory),
+        KeyBinds::Explicit(KeyCode::F(1), Command::ShowHelp),
+    ];
+    binds.extend(insert().1);
+
+    (Mode::Insert, binds)
+}
+
+/// Insert mode for the `plain` preset, which never leaves it.
+fn plain_insert() -> (Mode, Vec<KeyBinds>) {
+    let ctrl = |c, command| KeyBinds::Chord(KeyCode::Char(c), KeyModifiers::CONTROL, command);
+    let mut binds = vec![
+        KeyBinds::Explicit(KeyCode::PageUp, Command::Scroll(10)),
+        KeyBinds::Explicit(KeyCode::PageDown, Command::Scroll(-10)),
+        ctrl('q', Command::Quit),
+        ctrl('f', Command::Enter(Mode::Search)),
+        ctrl('r', Command::Enter(Mode::RoomList)),
+        ctrl('g', Command::MoveRooms(None)),
+        ctrl('o', Command::RoomDirectory),
+        KeyBinds::Explicit(KeyCode::F(1), Command::ShowHelp),
+    ];
+    binds.extend(insert().1);
+
+    (Mode::Insert, binds)
+}
+
+fn insert_cmd() -> (Mode, Vec<KeyBinds>) {
     (
-        Mode::Insert,
+        Mode::InsertCommand,
         vec![
             // leave insert mode
-            KeyBinds::Explicit(KeyCode::Esc, Command::Enter(Mode::Navigate)),
+            KeyBinds::Explicit(KeyCode::Esc, Command::AbortStagedCommand),
             // send message
-            KeyBinds::Explicit(KeyCode::Enter, Command::SendBuffer),
+            KeyBinds::Explicit(KeyCode::Enter, Command::SendStagedCommand),
             // Caret controls
             KeyBinds::Explicit(
                 KeyCode::Left,
@@ -42,6 +345,34 @@ fn insert() -> (Mode, Vec<KeyBinds>) {
             ),
             KeyBinds::Explicit(KeyCode::Up, Command::MoveCaret(CaretMotion::Line, -1)),
             KeyBinds::Explicit(KeyCode::Down, Command::MoveCaret(CaretMotion::Line, 1)),
+            KeyBinds::Chord(
+                KeyCode::Left,
+                KeyModifiers::CONTROL,
+                Command::MoveCaret(CaretMotion::Word, -1),
+            ),
+            KeyBinds::Chord(
+                KeyCode::Right,
+                KeyModifiers::CONTROL,
+                Command::MoveCaret(CaretMotion::Word, 1),
+            ),
+            KeyBinds::Chord(
+                KeyCode::Backspace,
+                KeyModifiers::CONTROL,
+                Command::DelWord(-1),
+            ),
+            KeyBinds::Chord(KeyCode::Delete, KeyModifiers::CONTROL, Command::DelWord(0)),
+            // history
+            KeyBinds::Chord(
+                KeyCode::Char('p'),
+                KeyModifiers::CONTROL,
+                Command::HistoryPrev,
+            ),
+            KeyBinds::Chord(
+                KeyCode::Char('n'),
+                KeyModifiers::CONTROL,
+                Command::HistoryNext,
+            ),
+            KeyBinds::Explicit(KeyCode::Tab, Command::Complete),
             // text input
             KeyBinds::capture(),
             // deletion
@@ -51,15 +382,26 @@ fn insert() -> (Mode, Vec<KeyBinds>) {
     )
 }
 
-fn insert_cmd() -> (Mode, Vec<KeyBinds>) {
+fn room_list() -> (Mode, Vec<KeyBinds>) {
     (
-        Mode::InsertCommand,
+        Mode::RoomList,
         vec![
-            // leave insert mode
-            KeyBinds::Explicit(KeyCode::Esc, Command::AbortStagedCommand),
-            // send message
-            KeyBinds::Explicit(KeyCode::Enter, Command::SendStagedCommand),
-            // Caret controls
+            KeyBinds::Explicit(KeyCode::Esc, Command::Enter(Mode::Navigate)),
+            KeyBinds::Explicit(KeyCode::Enter, Command::JoinSelectedRoom),
+            KeyBinds::Explicit(KeyCode::Up, Command::SelectRoom(-1)),
+            KeyBinds::Explicit(KeyCode::Down, Command::SelectRoom(1)),
+            KeyBinds::Explicit(KeyCode::Char('k'), Command::SelectRoom(-1)),
+            KeyBinds::Explicit(KeyCode::Char('j'), Command::SelectRoom(1)),
+        ],
+    )
+}
+
+fn search() -> (Mode, Vec<KeyBinds>) {
+    (
+        Mode::Search,
+        vec![
+            KeyBinds::Explicit(KeyCode::Esc, Command::ClearSearch),
+            KeyBinds::Explicit(KeyCode::Enter, Command::SubmitSearch),
             KeyBinds::Explicit(
                 KeyCode::Left,
                 Command::MoveCaret(CaretMotion::Character, -1),
@@ -68,8 +410,6 @@ fn insert_cmd() -> (Mode, Vec<KeyBinds>) {
                 KeyCode::Right,
                 Command::MoveCaret(CaretMotion::Character, 1),
             ),
-            KeyBinds::Explicit(KeyCode::Up, Command::MoveCaret(CaretMotion::Line, -1)),
-            KeyBinds::Explicit(KeyCode::Down, Command::MoveCaret(CaretMotion::Line, 1)),
             // text input
             KeyBinds::capture(),
             // deletion
@@ -79,6 +419,69 @@ fn insert_cmd() -> (Mode, Vec<KeyBinds>) {
     )
 }
 
-pub fn keys() -> [(Mode, Vec<KeyBinds>); 4] {
-    [disocnnected(), navigate(), insert(), insert_cmd()]
+fn copy() -> (Mode, Vec<KeyBinds>) {
+    (
+        Mode::Copy,
+        vec![
+            KeyBinds::Explicit(KeyCode::Esc, Command::Enter(Mode::Navigate)),
+            KeyBinds::Explicit(KeyCode::Up, Command::CopyMove(1)),
+            KeyBinds::Explicit(KeyCode::Down, Command::CopyMove(-1)),
+            KeyBinds::Explicit(KeyCode::Char('k'), Command::CopyMove(1)),
+            KeyBinds::Explicit(KeyCode::Char('j'), Command::CopyMove(-1)),
+            KeyBinds::Explicit(KeyCode::PageUp, Command::CopyMove(10)),
+            KeyBinds::Explicit(KeyCode::PageDown, Command::CopyMove(-10)),
+            KeyBinds::Explicit(KeyCode::Char(' '), Command::CopyMark),
+            KeyBinds::Explicit(KeyCode::Enter, Command::CopyYank),
+            KeyBinds::Explicit(KeyCode::Char('P'), Command::PinMessage),
+        ],
+    )
+}
+
+/// Every mode's bindings for `preset`. The modeless presets keep the
+/// Navigate map, it is only reached if a user binding enters it.
+/// Nothing that writes or sends, for watching a room with `--monitor`.
+pub fn monitor() -> [(Mode, Vec<KeyBinds>); 2] {
+    let view = || {
+        [
+            KeyBinds::Explicit(KeyCode::Char('D'), Command::ToggleDebug),
+            KeyBinds::Explicit(KeyCode::Char('W'), Command::ToggleTraffic),
+            KeyBinds::Explicit(KeyCode::Char('S'), Command::ToggleSystem),
+            KeyBinds::Explicit(KeyCode::Char('T'), Command::ToggleRelativeTime),
+            KeyBinds::Explicit(KeyCode::Char('z'), Command::ToggleDensity),
+            KeyBinds::Explicit(KeyCode::Char('?'), Command::ShowHelp),
+            KeyBinds::Explicit(KeyCode::PageUp, Command::Scroll(10)),
+            KeyBinds::Explicit(KeyCode::PageDown, Command::Scroll(-10)),
+            KeyBinds::Explicit(KeyCode::Char('k'), Command::Scroll(1)),
+            KeyBinds::Explicit(KeyCode::Char('j'), Command::Scroll(-1)),
+            KeyBinds::Explicit(KeyCode::Home, Command::ScrollTop),
+            KeyBinds::Explicit(KeyCode::End, Command::ScrollBottom),
+        ]
+    };
+    let mut navigate = vec![KeyBinds::Explicit(KeyCode::Char('q'), Command::Quit)];
+    navigate.extend(view());
+    let mut disconnected = vec![
+        KeyBinds::Explicit(KeyCode::Char('q'), Command::Quit),
+        KeyBinds::Explicit(KeyCode::Char('r'), Command::Reconnect),
+    ];
+    disconnected.extend(view());
+    [
+        (Mode::Navigate, navigate),
+        (Mode::Disconnected, disconnected),
+    ]
+}
+
+pub fn preset(preset: KeyPreset) -> [(Mode, Vec<KeyBinds>); 7] {
+    [
+        disocnnected(),
+        navigate(),
+        match preset {
+            KeyPreset::Vim => insert(),
+            KeyPreset::Emacs => emacs_insert(),
+            KeyPreset::Plain => plain_insert(),
+        },
+        insert_cmd(),
+        room_list(),
+        search(),
+        copy(),
+    ]
 }
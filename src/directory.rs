@@ -0,0 +1,62 @@
+/// A room shown in the directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectoryEntry {
+    pub name: String,
+    /// Unknown until the room has been visited this session.
+    pub occupants: Option<usize>,
+}
+
+/// The room directory popup, it has the keyboard while it's open.
+///
+/// The protocol has no way to ask for every room on the server, so the
+/// directory holds the rooms this client knows of: the ones visited this
+/// session and the ones with stored history. Room data from the server
+/// keeps the occupant counts current while it's open.
+#[derive(Debug, Clone, Default)]
+pub struct Directory {
+    pub entries: Vec<DirectoryEntry>,
+    pub selected: usize,
+}
+
+impl Directory {
+    /// Merges the rooms, sorted by name, keeping the first count seen for
+    /// each.
+    pub fn new(rooms: impl IntoIterator<Item = DirectoryEntry>) -> Self {
+        let mut entries: Vec<DirectoryEntry> = vec![];
+        for room in rooms {
+            match entries.iter_mut().find(|e| e.name == room.name) {
+                Some(entry) => entry.occupants = entry.occupants.or(room.occupants),
+                None => entries.push(room),
+            }
+        }
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Self {
+            entries,
+            selected: 0,
+        }
+    }
+
+    pub fn move_by(&mut self, by: isize) {
+        let max = self.entries.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(by).min(max);
+    }
+
+    pub fn selected_room(&self) -> Option<&str> {
+        self.entries.get(self.selected).map(|e| e.name.as_str())
+    }
+
+    /// Takes a fresh occupant count from the server.
+    pub fn update(&mut self, name: &str, occupants: usize) {
+        match self.entries.iter_mut().find(|e| e.name == name) {
+            Some(entry) => entry.occupants = Some(occupants),
+            None => {
+                self.entries.push(DirectoryEntry {
+                    name: name.to_string(),
+                    occupants: Some(occupants),
+                });
+                self.entries.sort_by(|a, b| a.name.cmp(&b.name));
+            }
+        }
+    }
+}
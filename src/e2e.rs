@@ -0,0 +1,244 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    ChaCha20Poly1305, Nonce,
+};
+use chrono::{DateTime, Duration, Utc};
+use marain_api::prelude::Key;
+use serde::{Deserialize, Serialize};
+
+use crate::i18n::tr;
+
+/// The server can't tell an encrypted message from any other, so they go
+/// to the room as chat messages starting with this, the rest base64.
+const MARKER: &str = "[e2e] ";
+
+/// Bytes of the nonce ahead of the ciphertext.
+const NONCE_LEN: usize = 12;
+
+/// How far the time a message was sealed can be from when the server says
+/// it was sent, so the server can't pass an old one off as new.
+const MAX_SKEW: Duration = Duration::minutes(10);
+
+/// Nonces of the live messages opened, to turn away a message sent again.
+/// Replays older than [`MAX_SKEW`] are turned away by their time instead.
+const SEEN_NONCES: usize = 4096;
+
+/// What's sealed: who sent the message and when, so the server can't
+/// pass it off as someone else's or as sent later.
+#[derive(Serialize, Deserialize)]
+struct Sealed {
+    sender: String,
+    sent: DateTime<Utc>,
+    message: String,
+}
+
+/// Keys for the rooms whose messages are encrypted before they reach the
+/// server, so only members who know the passphrase can read them. Keys
+/// are only held for the session, the passphrase is asked for again.
+/// Messages are sealed with ChaCha20-Poly1305, so one the server altered
+/// doesn't open at all.
+#[derive(Debug, Default)]
+pub struct RoomKeys {
+    keys: HashMap<String, Key>,
+    seen: HashSet<[u8; NONCE_LEN]>,
+    seen_order: VecDeque<[u8; NONCE_LEN]>,
+}
+
+impl RoomKeys {
+    /// Derives the key for `room` from `passphrase`. Everyone in the room
+    /// has to arrive at the same key, so the salt is the room's name.
+    pub fn set(&mut self, room: &str, passphrase: &str) -> Result<(), String> {
+        if passphrase.is_empty() {
+            return Err(tr("e2e.empty_passphrase"));
+        }
+        let salt = format!("marain e2e {room}");
+        let mut key = [0u8; 32];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt.as_bytes(), &mut key)
+            .map_err(|e| e.to_string())?;
+        self.keys.insert(room.to_string(), key);
+        Ok(())
+    }
+
+    /// Stops encrypting in `room`, returns false if it wasn't.
+    pub fn remove(&mut self, room: &str) -> bool {
+        self.keys.remove(room).is_some()
+    }
+
+    pub fn is_encrypted(&self, room: &str) -> bool {
+        self.keys.contains_key(room)
+    }
+
+    /// Encrypts what `sender` sends to `room` if it has a key. `None` if
+    /// that failed, the message mustn't go out in the clear instead.
+    pub fn seal(&self, room: &str, sender: &str, contents: String) -> Option<String> {
+        let Some(key) = self.keys.get(room) else {
+            return Some(contents);
+        };
+        let sealed = Sealed {
+            sender: sender.to_string(),
+            sent: Utc::now(),
+            message: contents,
+        };
+        let plain = serde_json::to_vec(&sealed).ok()?;
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        // the room goes in as associated data, so a message can't be moved
+        // to another room with the same passphrase
+        let payload = Payload {
+            msg: &plain,
+            aad: room.as_bytes(),
+        };
+        ChaCha20Poly1305::new(key.into())
+            .encrypt(&nonce, payload)
+            .map_err(|e| tracing::error!("Could not encrypt for the room: {e}"))
+            .ok()
+            .map(|sealed| {
+                format!(
+                    "{MARKER}{}",
+                    STANDARD.encode([&nonce[..], &sealed].concat())
+                )
+            })
+    }
+
+    /// Decrypts a message from `room` that the server says `sender` sent
+    /// at `sent`, as a notice if it can't be read or doesn't check out.
+    /// Unencrypted ones come back as they are. For messages from history,
+    /// which may have been opened before.
+    pub fn open(&self, room: &str, sender: &str, sent: DateTime<Utc>, contents: String) -> String {
+        match self.unseal(room, sender, sent, &contents) {
+            Ok((_, message)) => message,
+            Err(notice) => notice.unwrap_or(contents),
+        }
+    }
+
+    /// Like [`RoomKeys::open`], for a message that just arrived, which is
+    /// turned away if it's been seen before.
+    pub fn open_live(
+        &mut self,
+        room: &str,
+        sender: &str,
+        sent: DateTime<Utc>,
+        contents: String,
+    ) -> String {
+        let (nonce, message) = match self.unseal(room, sender, sent, &contents) {
+            Ok(opened) => opened,
+            Err(notice) => return notice.unwrap_or(contents),
+        };
+        if !self.seen.insert(nonce) {
+            return tr("e2e.forged");
+        }
+        self.seen_order.push_back(nonce);
+        if self.seen_order.len() > SEEN_NONCES {
+            if let Some(oldest) = self.seen_order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        message
+    }
+
+    /// The nonce and message of an encrypted message. `Err(None)` for one
+    /// that isn't encrypted, otherwise the notice to show instead.
+    fn unseal(
+        &self,
+        room: &str,
+        sender: &str,
+        sent: DateTime<Utc>,
+        contents: &str,
+    ) -> Result<([u8; NONCE_LEN], String), Option<String>> {
+        let Some(sealed) = contents.strip_prefix(MARKER) else {
+            return Err(None);
+        };
+        let Some(key) = self.keys.get(room) else {
+            return Err(Some(tr("e2e.unreadable")));
+        };
+        let forged = || Some(tr("e2e.forged"));
+        let data = STANDARD.decode(sealed.trim()).map_err(|_| forged())?;
+        if data.len() < NONCE_LEN {
+            return Err(forged());
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        let payload = Payload {
+            msg: ciphertext,
+            aad: room.as_bytes(),
+        };
+        let plain = ChaCha20Poly1305::new(key.into())
+            .decrypt(Nonce::from_slice(nonce), payload)
+            .map_err(|_| forged())?;
+        let opened: Sealed = serde_json::from_slice(&plain).map_err(|_| forged())?;
+        if opened.sender != sender || (opened.sent - sent).abs() > MAX_SKEW {
+            return Err(forged());
+        }
+        let nonce = nonce.try_into().map_err(|_| forged())?;
+        Ok((nonce, opened.message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_same_passphrase_opens_a_message() {
+        let now = Utc::now();
+        let mut ours = RoomKeys::default();
+        ours.set("rust", "hunter2").unwrap();
+        let sealed = ours.seal("rust", "me", "hello".into()).unwrap();
+        assert!(sealed.starts_with(MARKER) && !sealed.contains("hello"));
+
+        let mut theirs = RoomKeys::default();
+        theirs.set("rust", "hunter2").unwrap();
+        assert_eq!(theirs.open("rust", "me", now, sealed.clone()), "hello");
+
+        let mut wrong = RoomKeys::default();
+        wrong.set("rust", "hunter3").unwrap();
+        assert_eq!(
+            wrong.open("rust", "me", now, sealed.clone()),
+            tr("e2e.forged")
+        );
+        assert_eq!(
+            RoomKeys::default().open("rust", "me", now, sealed),
+            tr("e2e.unreadable")
+        );
+        // other rooms are left alone
+        assert_eq!(ours.seal("go", "me", "hi".into()).unwrap(), "hi");
+        assert_eq!(ours.open("rust", "me", now, "plain".into()), "plain");
+    }
+
+    #[test]
+    fn a_tampered_or_replayed_message_is_turned_away() {
+        let now = Utc::now();
+        let mut keys = RoomKeys::default();
+        keys.set("rust", "hunter2").unwrap();
+        let sealed = keys.seal("rust", "me", "pay alice 10".into()).unwrap();
+
+        let mut data = STANDARD.decode(&sealed[MARKER.len()..]).unwrap();
+        let last = data.len() - 1;
+        data[last] ^= 1;
+        let tampered = format!("{MARKER}{}", STANDARD.encode(data));
+        assert_eq!(keys.open("rust", "me", now, tampered), tr("e2e.forged"));
+
+        // the server can't say someone else sent it, or that it's new
+        assert_eq!(
+            keys.open("rust", "eve", now, sealed.clone()),
+            tr("e2e.forged")
+        );
+        let later = now + Duration::hours(1);
+        assert_eq!(
+            keys.open("rust", "me", later, sealed.clone()),
+            tr("e2e.forged")
+        );
+        // nor move it to another room with the same passphrase
+        keys.set("go", "hunter2").unwrap();
+        assert_eq!(keys.open("go", "me", now, sealed.clone()), tr("e2e.forged"));
+
+        assert_eq!(
+            keys.open_live("rust", "me", now, sealed.clone()),
+            "pay alice 10"
+        );
+        assert_eq!(keys.open_live("rust", "me", now, sealed), tr("e2e.forged"));
+    }
+}
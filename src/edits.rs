@@ -0,0 +1,45 @@
+use chrono::{DateTime, Utc};
+
+/// The server can't change messages it has sent out, so edits and deletions
+/// are chat messages that name the original by its sender and timestamp.
+/// Other clients show them as they are.
+const EDIT_PREFIX: &str = "[edit:";
+const DELETE_PREFIX: &str = "[delete:";
+
+/// A change to an earlier message from the same sender.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Edit { ts: i64, text: String },
+    Delete { ts: i64 },
+}
+
+impl Change {
+    /// Timestamp of the message changed, in milliseconds.
+    pub fn ts(&self) -> i64 {
+        match self {
+            Change::Edit { ts, .. } | Change::Delete { ts } => *ts,
+        }
+    }
+}
+
+pub fn edit(original: DateTime<Utc>, text: &str) -> String {
+    format!("{EDIT_PREFIX}{}] {text}", original.timestamp_millis())
+}
+
+pub fn delete(original: DateTime<Utc>) -> String {
+    format!("{DELETE_PREFIX}{}]", original.timestamp_millis())
+}
+
+pub fn parse(content: &str) -> Option<Change> {
+    if let Some(rest) = content.strip_prefix(EDIT_PREFIX) {
+        let (ts, text) = rest.split_once("] ")?;
+        return Some(Change::Edit {
+            ts: ts.parse().ok()?,
+            text: text.to_string(),
+        });
+    }
+    let ts = content.strip_prefix(DELETE_PREFIX)?.strip_suffix(']')?;
+    Some(Change::Delete {
+        ts: ts.parse().ok()?,
+    })
+}
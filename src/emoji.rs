@@ -0,0 +1,112 @@
+//! `:shortcode:` expansion for outgoing messages.
+
+/// Shortcodes and the emoji they stand for, sorted by shortcode.
+const SHORTCODES: &[(&str, &str)] = &[
+    ("+1", "👍"),
+    ("-1", "👎"),
+    ("100", "💯"),
+    ("angry", "😠"),
+    ("beer", "🍺"),
+    ("blush", "😊"),
+    ("broken_heart", "💔"),
+    ("bug", "🐛"),
+    ("cake", "🍰"),
+    ("check", "✅"),
+    ("clap", "👏"),
+    ("coffee", "☕"),
+    ("confused", "😕"),
+    ("cool", "😎"),
+    ("crab", "🦀"),
+    ("cry", "😢"),
+    ("eyes", "👀"),
+    ("facepalm", "🤦"),
+    ("fire", "🔥"),
+    ("grin", "😁"),
+    ("heart", "❤️"),
+    ("hourglass", "⌛"),
+    ("hug", "🤗"),
+    ("joy", "😂"),
+    ("kiss", "😘"),
+    ("laughing", "😆"),
+    ("lock", "🔒"),
+    ("muscle", "💪"),
+    ("neutral_face", "😐"),
+    ("ok_hand", "👌"),
+    ("party", "🥳"),
+    ("pizza", "🍕"),
+    ("pray", "🙏"),
+    ("question", "❓"),
+    ("rocket", "🚀"),
+    ("rofl", "🤣"),
+    ("sad", "🙁"),
+    ("scream", "😱"),
+    ("shrug", "🤷"),
+    ("skull", "💀"),
+    ("sleeping", "😴"),
+    ("smile", "😄"),
+    ("smirk", "😏"),
+    ("sparkles", "✨"),
+    ("star", "⭐"),
+    ("sunglasses", "😎"),
+    ("sweat_smile", "😅"),
+    ("tada", "🎉"),
+    ("thinking", "🤔"),
+    ("thumbsdown", "👎"),
+    ("thumbsup", "👍"),
+    ("tongue", "😛"),
+    ("warning", "⚠️"),
+    ("wave", "👋"),
+    ("wink", "😉"),
+    ("x", "❌"),
+    ("zap", "⚡"),
+];
+
+/// The emoji for `code`, given without the surrounding colons.
+pub fn lookup(code: &str) -> Option<&'static str> {
+    SHORTCODES
+        .binary_search_by_key(&code, |(c, _)| c)
+        .ok()
+        .map(|i| SHORTCODES[i].1)
+}
+
+/// Replaces every known `:shortcode:` in `text`, leaving unknown ones and
+/// stray colons alone.
+pub fn expand(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(':') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let emoji = after
+            .find(':')
+            .and_then(|end| lookup(&after[..end]).map(|e| (e, end)));
+        match emoji {
+            Some((emoji, end)) => {
+                out.push_str(emoji);
+                rest = &after[end + 1..];
+            }
+            None => {
+                out.push(':');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// `:shortcode:`s completing a partly typed one, e.g. `:th` gives
+/// `:thinking:`, `:thumbsdown:` and `:thumbsup:`.
+pub fn completions(word: &str) -> Vec<String> {
+    let Some(partial) = word.strip_prefix(':') else {
+        return vec![];
+    };
+    if partial.is_empty() || partial.contains(':') {
+        return vec![];
+    }
+    SHORTCODES
+        .iter()
+        .filter(|(code, _)| code.starts_with(partial))
+        .map(|(code, _)| format!(":{code}:"))
+        .collect()
+}
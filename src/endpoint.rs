@@ -0,0 +1,89 @@
+use std::{fmt::Display, net::Ipv6Addr};
+
+use url::Url;
+
+use crate::error::ClientError;
+
+/// A websocket address to connect to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Endpoint {
+    url: Url,
+}
+
+impl Endpoint {
+    /// Parses a full `ws://` or `wss://` url, or a host with an optional
+    /// port. Hosts can be names, IPv4 or IPv6 literals, the latter in
+    /// brackets if a port follows, e.g. `[::1]:1337`. The scheme and port
+    /// default to the ones given.
+    pub fn parse(spec: &str, default_port: &str, secure: bool) -> Result<Self, ClientError> {
+        let invalid = |e: &dyn Display| ClientError::Connect(format!("{spec}: {e}"));
+        let url = match spec.contains("://") {
+            true => Url::parse(spec).map_err(|e| invalid(&e))?,
+            false => {
+                let scheme = match secure {
+                    true => "wss",
+                    false => "ws",
+                };
+                let authority = match spec.parse::<Ipv6Addr>() {
+                    Ok(ip) => format!("[{ip}]:{default_port}"),
+                    Err(_) if has_port(spec) => spec.to_string(),
+                    Err(_) => format!("{spec}:{default_port}"),
+                };
+                Url::parse(&format!("{scheme}://{authority}")).map_err(|e| invalid(&e))?
+            }
+        };
+        if !matches!(url.scheme(), "ws" | "wss") {
+            return Err(invalid(&"only ws:// and wss:// urls are supported"));
+        }
+        if url.host_str().is_none() {
+            return Err(invalid(&"no host given"));
+        }
+
+        Ok(Self { url })
+    }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    pub fn is_secure(&self) -> bool {
+        self.url.scheme() == "wss"
+    }
+
+    /// Host without the brackets around IPv6 literals, for opening sockets.
+    pub fn host(&self) -> &str {
+        self.url
+            .host_str()
+            .unwrap_or_default()
+            .trim_matches(['[', ']'])
+    }
+
+    pub fn port(&self) -> u16 {
+        self.url.port_or_known_default().unwrap_or_default()
+    }
+
+    /// `host:port`, with IPv6 hosts in brackets.
+    pub fn address(&self) -> String {
+        format!(
+            "{}:{}",
+            self.url.host_str().unwrap_or_default(),
+            self.port()
+        )
+    }
+}
+
+impl Display for Endpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.url)
+    }
+}
+
+/// Whether a host spec ends in a port, `[::1]:80` or `host:80`.
+fn has_port(spec: &str) -> bool {
+    match spec.rsplit_once(':') {
+        Some((host, port)) => {
+            port.parse::<u16>().is_ok() && (host.ends_with(']') || !host.contains(':'))
+        }
+        None => false,
+    }
+}
@@ -0,0 +1,38 @@
+use chrono::Duration;
+
+/// The server can't delete messages, so ephemeral messages carry their time
+/// to live in a prefix, e.g. `[shh:60] see you at 5`, and clients that
+/// understand it remove them from the log once it has passed.
+const PREFIX: &str = "[shh:";
+
+/// Longest time to live honoured, a week. Anyone in the room picks the
+/// prefix, so anything longer is taken as an ordinary message.
+const MAX_TTL_SECS: u32 = 7 * 24 * 60 * 60;
+
+pub fn mark(ttl_secs: u64, contents: &str) -> String {
+    let ttl_secs = ttl_secs.min(MAX_TTL_SECS.into());
+    format!("{PREFIX}{ttl_secs}] {contents}")
+}
+
+/// Splits an ephemeral message into its time to live and contents.
+pub fn parse(content: &str) -> Option<(Duration, &str)> {
+    let (ttl, rest) = content.strip_prefix(PREFIX)?.split_once("] ")?;
+    let ttl = ttl.parse::<u32>().ok().filter(|ttl| *ttl <= MAX_TTL_SECS)?;
+    Some((Duration::try_seconds(ttl.into())?, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_a_sensible_ttl_makes_a_message_ephemeral() {
+        assert_eq!(parse("[shh:60] hi"), Some((Duration::seconds(60), "hi")));
+        assert_eq!(
+            parse(&mark(u64::MAX, "hi")).map(|(_, rest)| rest),
+            Some("hi")
+        );
+        assert_eq!(parse("[shh:99999999999999] x"), None);
+        assert_eq!(parse("[shh:-5] x"), None);
+    }
+}
@@ -0,0 +1,81 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    i18n::{tr, trf},
+    protocol,
+};
+
+/// Things that can go wrong talking to the server, reported to the app as
+/// `Event::Error` instead of taking the terminal down with a panic.
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ClientError {
+    /// Reading from the terminal failed.
+    Input(String),
+    /// Couldn't reach the server at all.
+    Connect(String),
+    /// The socket worker or the channel to it went away.
+    Socket(String),
+    /// A websocket message the client has no use for.
+    UnexpectedMessage(String),
+    /// The server didn't accept the login.
+    Login(String),
+    /// The server refused the password, with its reason.
+    Auth(String),
+    Encrypt(String),
+    Decrypt(String),
+    /// A compressed message that wouldn't inflate.
+    Decompress(String),
+    /// A message that opened fine but isn't one this protocol knows.
+    Deserialize(String),
+    /// There is no shared secret yet to encrypt or decrypt with.
+    NoSharedSecret,
+    /// Too many messages were waiting on the rate limit to take another.
+    RateLimited,
+    /// The server speaks another version of the protocol, this one.
+    ProtocolMismatch(u32),
+}
+
+impl ClientError {
+    /// Errors after which the connection can't be used any more.
+    pub fn is_fatal(&self) -> bool {
+        matches!(
+            self,
+            ClientError::Connect(_)
+                | ClientError::Socket(_)
+                | ClientError::Login(_)
+                | ClientError::Auth(_)
+                | ClientError::NoSharedSecret
+                | ClientError::ProtocolMismatch(_)
+        )
+    }
+}
+
+impl Display for ClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let msg = match self {
+            ClientError::Input(e) => trf!("err.input", e),
+            ClientError::Connect(e) => trf!("err.connect", e),
+            ClientError::Socket(e) => trf!("err.socket", e),
+            ClientError::UnexpectedMessage(msg) => trf!("err.unexpected_message", msg),
+            ClientError::Login(e) => trf!("err.login", e),
+            ClientError::Auth(e) => trf!("err.auth", e),
+            ClientError::Encrypt(e) => trf!("err.encrypt", e),
+            ClientError::Decrypt(e) => trf!("err.decrypt", e),
+            ClientError::Decompress(e) => trf!("err.decompress", e),
+            ClientError::Deserialize(e) => trf!("err.deserialize", protocol::VERSION, e),
+            ClientError::NoSharedSecret => tr("err.no_shared_secret"),
+            ClientError::RateLimited => tr("err.rate_limited"),
+            ClientError::ProtocolMismatch(server) if *server < protocol::VERSION => {
+                trf!("err.server_older", server, protocol::VERSION)
+            }
+            ClientError::ProtocolMismatch(server) => {
+                trf!("err.client_older", protocol::VERSION, server)
+            }
+        };
+        write!(f, "{msg}")
+    }
+}
+
+impl std::error::Error for ClientError {}
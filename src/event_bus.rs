@@ -1,49 +1,77 @@
+use std::time::Instant;
+
 use color_eyre::Result;
-use marain_api::prelude::{ClientMsg, ClientMsgBody, Timestamp};
 
 use crate::{
+    alerts::{notify_desktop, raise},
     app::App,
+    error::ClientError,
     tui_framework::{Event, Tui},
-    update::update,
+    update::{reduce, Effect},
 };
 
+/// Runs an event through the reducer and carries out the effects it asks for.
 pub fn dispatch(app: &mut App, tui: &mut Tui, event: Event) -> Result<()> {
-    match event {
-        Event::Render => {
-            tui.draw(app)?;
+    let _span = tracing::debug_span!("update", event = event.name()).entered();
+    let event = match event {
+        Event::Error(e @ (ClientError::Decrypt(_) | ClientError::Decompress(_))) => {
+            tui.traffic.last_decrypt_error = Some(e.to_string());
+            Event::Error(e)
         }
-        Event::Send {
-            token,
-            timestamp,
-            ref contents,
-            ..
-        } => {
-            let msg = ClientMsg {
-                token: Some(token),
-                body: ClientMsgBody::SendToRoom {
-                    contents: contents.clone(),
-                },
-                timestamp: Timestamp::from(timestamp),
-            };
-            tui.push_binary_msg_to_server(msg);
+        Event::Tick => {
+            if let Err(e) = tui.flush_throttled() {
+                dispatch(app, tui, Event::Error(e))?;
+            }
+            app.throttled = tui.throttled();
+            if app.show_traffic {
+                app.traffic = tui.traffic();
+                app.traffic.dropped_debug_logs = app.dropped_debug_logs;
+            }
+            Event::Tick
         }
-        Event::ServerCommand {
-            token,
-            timestamp,
-            message_body,
-            ..
-        } => {
-            let server_msg = ClientMsg {
-                token: Some(token),
-                timestamp: Timestamp::from(timestamp),
-                body: message_body,
-            };
-            tui.push_binary_msg_to_server(server_msg);
+        event => event,
+    };
+    if let Some(ref mut recorder) = tui.recorder {
+        recorder.record(&event);
+    }
+    for effect in reduce(app, event) {
+        execute(app, tui, effect)?;
+    }
+    Ok(())
+}
+
+fn execute(app: &mut App, tui: &mut Tui, effect: Effect) -> Result<()> {
+    match effect {
+        Effect::Draw => {
+            let started = Instant::now();
+            tui.draw(app)?;
+            app.perf.rendered(started.elapsed());
+            app.drawn();
         }
-        e => {
-            log::info!("No handling for {e:?}");
-            update(app, tui, e);
+        Effect::Send { msg, chat } => {
+            if !tui.is_connected() {
+                tui.queue_msg(msg);
+                if chat {
+                    app.mark_queued();
+                }
+            } else if let Err(e) = tui.push_binary_msg_to_server(msg) {
+                match e {
+                    // only the message just sent was dropped
+                    ClientError::RateLimited if chat => app.fail_newest_pending(),
+                    _ if chat => app.fail_pending(),
+                    _ => {}
+                }
+                return dispatch(app, tui, Event::Error(e));
+            }
+            app.throttled = tui.throttled();
         }
+        Effect::Alert(alert) => raise(&app.config.notifications, alert),
+        Effect::Bell => tui.bell()?,
+        Effect::Notify {
+            room,
+            sender,
+            message,
+        } => notify_desktop(&app.config.notifications.desktop, &room, &sender, &message),
     }
     Ok(())
 }
@@ -0,0 +1,73 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::chat_log::{Log, LogKind};
+
+/// Bumped whenever a field is removed or changes meaning.
+const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    /// A message from a user in the room.
+    Chat,
+    /// A message from the client or the server.
+    System,
+    /// Joined a room.
+    Room,
+}
+
+/// One line of the event log.
+#[derive(Serialize, Debug)]
+pub struct EventRecord<'a> {
+    pub version: u32,
+    pub ts: DateTime<Utc>,
+    pub kind: EventKind,
+    pub room: &'a str,
+    pub sender: &'a str,
+    pub message: &'a str,
+}
+
+/// Mirrors client activity to a JSON-lines file for external tools.
+#[derive(Debug)]
+pub struct EventLog {
+    file: File,
+}
+
+impl EventLog {
+    pub fn open(path: &str) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { file })
+    }
+
+    pub fn write(&mut self, kind: EventKind, room: &str, log: &Log) {
+        let record = EventRecord {
+            version: SCHEMA_VERSION,
+            ts: log.ts,
+            kind,
+            room,
+            sender: &log.from,
+            message: &log.msg,
+        };
+        let result = serde_json::to_string(&record)
+            .map_err(io::Error::from)
+            .and_then(|line| writeln!(self.file, "{line}"));
+        if let Err(e) = result {
+            tracing::error!("Could not write to the event log: {e}");
+        }
+    }
+}
+
+/// Chat logs come from users, anything from the client or server is a
+/// system event.
+pub fn kind_of(log: &Log) -> EventKind {
+    match log.kind {
+        LogKind::Chat => EventKind::Chat,
+        _ => EventKind::System,
+    }
+}
@@ -0,0 +1,284 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::Utc;
+use color_eyre::Result;
+
+/// Raw bytes per chunk, before base64 encoding.
+const CHUNK_SIZE: usize = 4096;
+
+/// Largest file accepted from others, in chunks (64 MiB).
+const MAX_DOWNLOAD_CHUNKS: usize = 16384;
+
+/// Files arriving from one sender at once, and from everyone.
+const MAX_DOWNLOADS_PER_SENDER: usize = 2;
+const MAX_DOWNLOADS: usize = 8;
+
+/// Finished files waiting to be saved or discarded, more are dropped.
+pub const MAX_FINISHED: usize = 4;
+
+/// How long a download waits for its next chunk before it's given up on.
+const CHUNK_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// A file being sent to the current room, one chunk at a time. The protocol
+/// has no attachment mechanism, so chunks are sent as base64 chat messages.
+#[derive(Debug)]
+pub struct Upload {
+    pub name: String,
+    /// Where the file was read from, none for clipboard images.
+    pub path: Option<PathBuf>,
+    data: Vec<u8>,
+    sent_chunks: usize,
+}
+
+impl Upload {
+    pub fn from_path(path: &str) -> Result<Self> {
+        let data = std::fs::read(path)?;
+        let name = Path::new(path)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.to_string());
+
+        Ok(Self {
+            name,
+            path: Some(PathBuf::from(path)),
+            data,
+            sent_chunks: 0,
+        })
+    }
+
+    /// Takes the image on the clipboard, encoded as a PNG.
+    pub fn from_clipboard_image() -> Result<Self> {
+        let image = arboard::Clipboard::new()?.get_image()?;
+        let mut data = vec![];
+        let mut encoder = png::Encoder::new(&mut data, image.width as u32, image.height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&image.bytes)?;
+
+        Ok(Self {
+            name: format!("clipboard-{}.png", Utc::now().format("%Y%m%d-%H%M%S")),
+            path: None,
+            data,
+            sent_chunks: 0,
+        })
+    }
+
+    pub fn total_chunks(&self) -> usize {
+        self.data.len().div_ceil(CHUNK_SIZE).max(1)
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.sent_chunks >= self.total_chunks()
+    }
+
+    pub fn progress_percent(&self) -> usize {
+        self.sent_chunks * 100 / self.total_chunks()
+    }
+
+    /// Encodes the next chunk as a chat message, e.g.
+    /// `[file:notes.txt 1/3] aGVsbG8=`.
+    pub fn next_chunk(&mut self) -> Option<String> {
+        if self.is_done() {
+            return None;
+        }
+        let start = self.sent_chunks * CHUNK_SIZE;
+        let end = (start + CHUNK_SIZE).min(self.data.len());
+        self.sent_chunks += 1;
+
+        Some(format!(
+            "[file:{} {}/{}] {}",
+            self.name,
+            self.sent_chunks,
+            self.total_chunks(),
+            STANDARD.encode(&self.data[start..end])
+        ))
+    }
+}
+
+/// One chunk of a file sent by `Upload::next_chunk`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+    pub name: String,
+    /// Counted from 1.
+    pub index: usize,
+    pub total: usize,
+    pub data: Vec<u8>,
+}
+
+impl Chunk {
+    /// Parses a chat message sent as a file chunk, `None` for anything else.
+    pub fn parse(content: &str) -> Option<Self> {
+        let rest = content.strip_prefix("[file:")?;
+        let (header, data) = rest.split_once("] ")?;
+        let (name, count) = header.rsplit_once(' ')?;
+        let (index, total) = count.split_once('/')?;
+        let (index, total) = (index.parse().ok()?, total.parse().ok()?);
+        if index == 0 || index > total {
+            return None;
+        }
+
+        Some(Self {
+            name: name.to_string(),
+            index,
+            total,
+            data: STANDARD.decode(data).ok()?,
+        })
+    }
+}
+
+/// A file arriving from someone in the room.
+#[derive(Debug)]
+pub struct Download {
+    pub from: String,
+    pub name: String,
+    chunks: Vec<Option<Vec<u8>>>,
+    received: usize,
+    last_chunk: Instant,
+}
+
+impl Download {
+    /// Starts a download from its first chunk to arrive, `None` if the file
+    /// is too large to accept.
+    pub fn new(from: String, chunk: &Chunk) -> Option<Self> {
+        if chunk.total > MAX_DOWNLOAD_CHUNKS {
+            return None;
+        }
+
+        Some(Self {
+            from,
+            name: chunk.name.clone(),
+            chunks: vec![None; chunk.total],
+            received: 0,
+            last_chunk: Instant::now(),
+        })
+    }
+
+    /// Whether `chunk` from `from` belongs to this download.
+    pub fn matches(&self, from: &str, chunk: &Chunk) -> bool {
+        self.from == from && self.name == chunk.name && self.chunks.len() == chunk.total
+    }
+
+    pub fn add(&mut self, chunk: Chunk) {
+        let slot = &mut self.chunks[chunk.index - 1];
+        if slot.is_none() {
+            self.received += 1;
+        }
+        *slot = Some(chunk.data);
+        self.last_chunk = Instant::now();
+    }
+
+    /// Whether the sender seems to have stopped sending.
+    pub fn is_stale(&self) -> bool {
+        self.last_chunk.elapsed() >= CHUNK_TIMEOUT
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.received == self.chunks.len()
+    }
+
+    pub fn progress_percent(&self) -> usize {
+        self.received * 100 / self.chunks.len()
+    }
+
+    pub fn size(&self) -> usize {
+        self.chunks.iter().flatten().map(Vec::len).sum()
+    }
+
+    /// Writes the file into `dir`, numbering the name if it's taken. Only
+    /// the file name the sender gave is used, never a path.
+    pub fn save(&self, dir: &Path) -> Result<PathBuf> {
+        std::fs::create_dir_all(dir)?;
+        let name = Path::new(&self.name)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .filter(|n| !n.starts_with('.'))
+            .unwrap_or_else(|| "download".into());
+        let mut path = dir.join(&name);
+        for n in 1.. {
+            if !path.exists() {
+                break;
+            }
+            path = dir.join(format!("{n}-{name}"));
+        }
+        std::fs::write(
+            &path,
+            self.chunks
+                .iter()
+                .flatten()
+                .flatten()
+                .copied()
+                .collect::<Vec<u8>>(),
+        )?;
+
+        Ok(path)
+    }
+}
+
+/// Gives up on the oldest downloads, from `from` and then from anyone,
+/// until there's room for another from `from`.
+pub fn make_room(downloads: &mut Vec<Download>, from: &str) {
+    downloads.retain(|download| !download.is_stale());
+    while downloads.iter().filter(|d| d.from == from).count() >= MAX_DOWNLOADS_PER_SENDER {
+        drop_oldest(downloads, Some(from));
+    }
+    while downloads.len() >= MAX_DOWNLOADS {
+        drop_oldest(downloads, None);
+    }
+}
+
+fn drop_oldest(downloads: &mut Vec<Download>, from: Option<&str>) {
+    let oldest = downloads
+        .iter()
+        .enumerate()
+        .filter(|(_, download)| from.is_none_or(|from| download.from == from))
+        .min_by_key(|(_, download)| download.last_chunk)
+        .map(|(i, _)| i);
+    if let Some(i) = oldest {
+        let download = downloads.remove(i);
+        tracing::warn!(
+            "Too many files arriving, dropping {} from {}",
+            download.name,
+            download.from
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(name: &str) -> Chunk {
+        Chunk {
+            name: name.into(),
+            index: 1,
+            total: 2,
+            data: vec![0],
+        }
+    }
+
+    #[test]
+    fn downloads_in_flight_are_capped() {
+        let mut downloads = vec![];
+        for n in 0..MAX_DOWNLOADS * 2 {
+            let from = format!("user{}", n % 2);
+            make_room(&mut downloads, &from);
+            downloads.extend(Download::new(from, &chunk(&n.to_string())));
+        }
+        assert_eq!(downloads.len(), 2 * MAX_DOWNLOADS_PER_SENDER);
+
+        for n in 0..MAX_DOWNLOADS * 2 {
+            let from = format!("user{n}");
+            make_room(&mut downloads, &from);
+            downloads.extend(Download::new(from, &chunk("file")));
+        }
+        assert_eq!(downloads.len(), MAX_DOWNLOADS);
+        // the newest are kept
+        assert!(downloads
+            .iter()
+            .any(|d| d.from == format!("user{}", MAX_DOWNLOADS * 2 - 1)));
+    }
+}
@@ -0,0 +1,188 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+
+/// Starts the header of every part.
+const PREFIX: &str = "[part:";
+
+/// Most parts a message is split into, longer ones aren't sent.
+pub const MAX_PARTS: usize = 16;
+
+/// Most messages held waiting for their other parts, from any one sender
+/// and from the whole room. Past these the oldest is given up on, so no
+/// one can make the client hold on to parts without end.
+const MAX_PARTIAL_PER_SENDER: usize = 4;
+const MAX_PARTIAL: usize = 64;
+
+/// The longest header a part can have, for working out how much of the
+/// message fits in one.
+pub const HEADER_MAX: &str = "[part:ffffffffffffffff 16/16] ";
+
+/// How long to wait for the rest of a message before giving up on it.
+const PART_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Splits `contents` into parts of at most `size` bytes each, headed like
+/// `[part:18c3f0a2 1/3] `. Splits fall on character boundaries.
+pub fn split(contents: &str, size: usize) -> Vec<String> {
+    let size = size.max(4);
+    let mut pieces = vec![];
+    let mut rest = contents;
+    while !rest.is_empty() {
+        let mut end = size.min(rest.len());
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        pieces.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+    let id = format!("{:x}", Utc::now().timestamp_micros());
+    let total = pieces.len();
+    pieces
+        .iter()
+        .enumerate()
+        .map(|(i, piece)| format!("{PREFIX}{id} {}/{total}] {piece}", i + 1))
+        .collect()
+}
+
+/// Whether `contents` would be taken for a part at the other end. Such a
+/// message is sent as the one part of itself, which comes out as it was.
+pub fn looks_like_part(contents: &str) -> bool {
+    contents.starts_with(PREFIX)
+}
+
+fn parse(content: &str) -> Option<(&str, usize, usize, &str)> {
+    let rest = content.strip_prefix(PREFIX)?;
+    let (header, piece) = rest.split_once("] ")?;
+    let (id, count) = header.split_once(' ')?;
+    let (index, total) = count.split_once('/')?;
+    let (index, total): (usize, usize) = (index.parse().ok()?, total.parse().ok()?);
+    if index == 0 || index > total || total > MAX_PARTS {
+        return None;
+    }
+
+    Some((id, index, total, piece))
+}
+
+#[derive(Debug)]
+struct Partial {
+    pieces: Vec<Option<String>>,
+    started: Instant,
+}
+
+/// Parts of messages still waiting for the rest, by sender and message.
+#[derive(Debug, Default)]
+pub struct Reassembly {
+    partial: HashMap<(String, String), Partial>,
+}
+
+impl Reassembly {
+    /// Passes whole messages through. A part is held on to, and the whole
+    /// message comes out once its last part is in.
+    pub fn take(&mut self, sender: &str, content: String) -> Option<String> {
+        let Some((id, index, total, piece)) = parse(&content) else {
+            return Some(content);
+        };
+        self.partial
+            .retain(|_, partial| partial.started.elapsed() < PART_TIMEOUT);
+        let key = (sender.to_string(), id.to_string());
+        if !self.partial.contains_key(&key) {
+            self.make_room(sender);
+        }
+        let partial = self.partial.entry(key.clone()).or_insert_with(|| Partial {
+            pieces: vec![None; total],
+            started: Instant::now(),
+        });
+        if partial.pieces.len() != total {
+            tracing::error!("Parts of one message from {sender} disagree on their number");
+            self.partial.remove(&key);
+            return None;
+        }
+        partial.pieces[index - 1] = Some(piece.to_string());
+        if partial.pieces.iter().any(Option::is_none) {
+            return None;
+        }
+
+        let partial = self.partial.remove(&key)?;
+        Some(partial.pieces.into_iter().flatten().collect())
+    }
+
+    /// Gives up on the oldest messages waiting for parts, from `sender`
+    /// and then from anyone, until there's room for another from `sender`.
+    fn make_room(&mut self, sender: &str) {
+        while self
+            .partial
+            .keys()
+            .filter(|(from, _)| from == sender)
+            .count()
+            >= MAX_PARTIAL_PER_SENDER
+        {
+            self.drop_oldest(Some(sender));
+        }
+        while self.partial.len() >= MAX_PARTIAL {
+            self.drop_oldest(None);
+        }
+    }
+
+    fn drop_oldest(&mut self, sender: Option<&str>) {
+        let oldest = self
+            .partial
+            .iter()
+            .filter(|((from, _), _)| sender.is_none_or(|sender| from == sender))
+            .min_by_key(|(_, partial)| partial.started)
+            .map(|(key, _)| key.clone());
+        if let Some(key) = oldest {
+            tracing::warn!(
+                "Too many messages waiting for parts, dropping one from {}",
+                key.0
+            );
+            self.partial.remove(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parts_in_any_order_make_the_message() {
+        let message = "ünïcödé ".repeat(20);
+        let mut parts = split(&message, 50);
+        assert!(parts.len() > 1);
+        assert!(parts.iter().all(|p| p.len() <= 50 + HEADER_MAX.len()));
+        parts.reverse();
+        let mut reassembly = Reassembly::default();
+        let last = parts.pop().unwrap();
+        for part in parts {
+            assert_eq!(reassembly.take("them", part), None);
+        }
+        assert_eq!(reassembly.take("them", last), Some(message));
+        assert_eq!(reassembly.take("them", "hi".into()), Some("hi".into()));
+
+        // a message that only looks like a part comes through as it was
+        let literal = "[part:1 1/2] is how parts start";
+        assert!(looks_like_part(literal));
+        let escaped = split(literal, 500);
+        assert_eq!(escaped.len(), 1);
+        assert_eq!(
+            reassembly.take("them", escaped[0].clone()),
+            Some(literal.into())
+        );
+    }
+
+    #[test]
+    fn holds_on_to_a_bounded_number_of_messages() {
+        let mut reassembly = Reassembly::default();
+        for id in 0..100 {
+            reassembly.take("spammer", format!("[part:{id} 1/2] x"));
+        }
+        assert_eq!(reassembly.partial.len(), MAX_PARTIAL_PER_SENDER);
+        for sender in 0..100 {
+            reassembly.take(&sender.to_string(), "[part:1 1/2] x".into());
+        }
+        assert_eq!(reassembly.partial.len(), MAX_PARTIAL);
+    }
+}
@@ -0,0 +1,234 @@
+//! Caret math for the edit buffer. Caret columns count grapheme clusters,
+//! so emoji, accented and combined characters move and delete as one.
+//! Where they land on screen is measured in cells, as CJK characters and
+//! most emoji take two.
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Number of graphemes in `line`.
+pub fn len(line: &str) -> usize {
+    line.graphemes(true).count()
+}
+
+/// Cells `text` takes up on screen.
+pub fn width(text: &str) -> usize {
+    text.width()
+}
+
+/// Cells taken by the graphemes of `line` before index `idx`.
+pub fn column(line: &str, idx: usize) -> usize {
+    width(split_at(line, idx).0)
+}
+
+/// Index of the grapheme `column` cells into `line`, or the one covering
+/// that cell if it's wide. The end of the line if it's shorter.
+pub fn index_at_column(line: &str, column: usize) -> usize {
+    let mut used = 0;
+    for (idx, grapheme) in line.graphemes(true).enumerate() {
+        used += width(grapheme);
+        if used > column {
+            return idx;
+        }
+    }
+    len(line)
+}
+
+/// Byte offset of the grapheme at index `idx`, or the end of the line.
+pub fn byte_index(line: &str, idx: usize) -> usize {
+    line.grapheme_indices(true)
+        .nth(idx)
+        .map(|(i, _)| i)
+        .unwrap_or(line.len())
+}
+
+/// Splits `line` before the grapheme at index `idx`.
+pub fn split_at(line: &str, idx: usize) -> (&str, &str) {
+    line.split_at(byte_index(line, idx))
+}
+
+/// Inserts `c` before the grapheme at index `idx`, returning the new line
+/// and the index just after the inserted character. A combining character
+/// joins the grapheme before it, so the index doesn't always move on.
+pub fn insert(line: &str, idx: usize, c: char) -> (String, usize) {
+    let (pre, post) = split_at(line, idx);
+    let mut pre = pre.to_string();
+    pre.push(c);
+    let new_idx = len(&pre);
+
+    (pre + post, new_idx)
+}
+
+/// Removes the grapheme before index `idx`, returning the new line and
+/// the new index.
+pub fn delete_before(line: &str, idx: usize) -> (String, usize) {
+    if idx == 0 {
+        return (line.to_string(), 0);
+    }
+    let (pre, post) = split_at(line, idx);
+    let (kept, _) = split_at(pre, idx - 1);
+
+    (kept.to_string() + post, idx - 1)
+}
+
+/// Removes the grapheme at index `idx`.
+pub fn delete_at(line: &str, idx: usize) -> String {
+    let (pre, post) = split_at(line, idx);
+    let (_, kept) = split_at(post, 1);
+
+    pre.to_string() + kept
+}
+
+/// Grapheme index of the byte offset `byte`.
+fn grapheme_index(line: &str, byte: usize) -> usize {
+    len(&line[..byte])
+}
+
+/// Word segments of `line` as byte ranges, skipping whitespace and
+/// punctuation between words.
+fn words(line: &str) -> impl Iterator<Item = (usize, usize)> + '_ {
+    line.split_word_bound_indices()
+        .filter(|(_, w)| w.chars().any(char::is_alphanumeric))
+        .map(|(i, w)| (i, i + w.len()))
+}
+
+/// Index of the start of the word before index `idx`.
+pub fn word_left(line: &str, idx: usize) -> usize {
+    let byte = byte_index(line, idx);
+    words(line)
+        .filter(|(start, _)| *start < byte)
+        .last()
+        .map(|(start, _)| grapheme_index(line, start))
+        .unwrap_or(0)
+}
+
+/// Index of the end of the word after index `idx`.
+pub fn word_right(line: &str, idx: usize) -> usize {
+    let byte = byte_index(line, idx);
+    words(line)
+        .find(|(_, end)| *end > byte)
+        .map(|(_, end)| grapheme_index(line, end))
+        .unwrap_or(len(line))
+}
+
+/// Removes the graphemes from index `from` up to, not including, `to`.
+pub fn delete_range(line: &str, from: usize, to: usize) -> String {
+    let (pre, _) = split_at(line, from);
+    let (_, post) = split_at(line, to);
+
+    pre.to_string() + post
+}
+
+/// Indices of the graphemes each row starts at when `line` is wrapped at
+/// `cells` columns. A wide grapheme that doesn't fit at the end of a row
+/// moves to the next. A line that fills its last row gets an empty row
+/// after it, for the caret at its end.
+pub fn wrap_starts(line: &str, cells: usize) -> Vec<usize> {
+    let cells = cells.max(1);
+    let mut starts = vec![0];
+    let mut used = 0;
+    for (idx, grapheme) in line.graphemes(true).enumerate() {
+        let w = width(grapheme);
+        if used > 0 && used + w > cells {
+            starts.push(idx);
+            used = 0;
+        }
+        used += w;
+    }
+    if used >= cells {
+        starts.push(len(line));
+    }
+
+    starts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_graphemes_not_bytes() {
+        assert_eq!(len("héllo"), 5);
+        assert_eq!(len("👍🏽ok"), 3);
+        assert_eq!(len("e\u{301}"), 1);
+        assert_eq!(len(""), 0);
+    }
+
+    #[test]
+    fn splits_on_grapheme_boundaries() {
+        assert_eq!(split_at("a😀b", 1), ("a", "😀b"));
+        assert_eq!(split_at("a😀b", 2), ("a😀", "b"));
+        assert_eq!(split_at("a😀b", 10), ("a😀b", ""));
+    }
+
+    #[test]
+    fn inserts_after_multibyte_input() {
+        assert_eq!(insert("ñ", 1, 'x'), ("ñx".to_string(), 2));
+        assert_eq!(insert("ab", 1, '🦀'), ("a🦀b".to_string(), 2));
+        assert_eq!(insert("", 0, 'é'), ("é".to_string(), 1));
+    }
+
+    #[test]
+    fn combining_character_joins_previous_grapheme() {
+        let (line, idx) = insert("e", 1, '\u{301}');
+        assert_eq!(line, "e\u{301}");
+        assert_eq!(idx, 1);
+    }
+
+    #[test]
+    fn backspace_removes_whole_grapheme() {
+        assert_eq!(delete_before("a👍🏽", 2), ("a".to_string(), 1));
+        assert_eq!(delete_before("e\u{301}x", 1), ("x".to_string(), 0));
+        assert_eq!(delete_before("abc", 0), ("abc".to_string(), 0));
+    }
+
+    #[test]
+    fn word_motions_skip_spaces_and_punctuation() {
+        let line = "héllo, wörld  again";
+        assert_eq!(word_right(line, 0), 5);
+        assert_eq!(word_right(line, 5), 12);
+        assert_eq!(word_right(line, 19), 19);
+        assert_eq!(word_left(line, 19), 14);
+        assert_eq!(word_left(line, 14), 7);
+        assert_eq!(word_left(line, 3), 0);
+    }
+
+    #[test]
+    fn deletes_word_ranges() {
+        let line = "héllo wörld";
+        assert_eq!(delete_range(line, word_left(line, 11), 11), "héllo ");
+        assert_eq!(delete_range(line, 0, word_right(line, 0)), " wörld");
+    }
+
+    #[test]
+    fn wraps_at_width_in_cells() {
+        assert_eq!(wrap_starts("", 4), vec![0]);
+        assert_eq!(wrap_starts("héllo", 4), vec![0, 4]);
+        assert_eq!(wrap_starts("abcd", 4), vec![0, 4]);
+        assert_eq!(wrap_starts("a👍🏽b", 2), vec![0, 1, 2]);
+        // a wide character that would straddle the edge moves down whole
+        assert_eq!(wrap_starts("ab日本語", 5), vec![0, 3]);
+        assert_eq!(wrap_starts("日本", 4), vec![0, 2]);
+    }
+
+    #[test]
+    fn columns_count_cells_for_mixed_widths() {
+        let line = "a日本b";
+        assert_eq!(width(line), 6);
+        assert_eq!(column(line, 1), 1);
+        assert_eq!(column(line, 3), 5);
+        assert_eq!(index_at_column(line, 0), 0);
+        assert_eq!(index_at_column(line, 1), 1);
+        assert_eq!(index_at_column(line, 2), 1);
+        assert_eq!(index_at_column(line, 3), 2);
+        assert_eq!(index_at_column(line, 5), 3);
+        assert_eq!(index_at_column(line, 9), 4);
+    }
+
+    #[test]
+    fn delete_removes_grapheme_at_caret() {
+        assert_eq!(delete_at("a🇬🇧b", 1), "ab");
+        assert_eq!(delete_at("日本語", 0), "本語");
+        assert_eq!(delete_at("abc", 3), "abc");
+    }
+}
@@ -0,0 +1,256 @@
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ratatui::prelude::Rect;
+use serde::{Deserialize, Serialize};
+
+/// Rows a thumbnail takes up in the log pane, under its placeholder.
+pub const THUMBNAIL_ROWS: u16 = 6;
+/// Columns a thumbnail takes up at most.
+pub const THUMBNAIL_COLS: u16 = 24;
+
+/// Larger images are shown as a placeholder only.
+const MAX_IMAGE_BYTES: u64 = 8 * 1024 * 1024;
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+/// Base64 bytes per kitty escape, the most the protocol allows.
+const KITTY_CHUNK: usize = 4096;
+/// Assumed cell size in pixels when the terminal doesn't report one.
+const DEFAULT_CELL: (u32, u32) = (8, 16);
+
+/// Terminal graphics protocols thumbnails can be drawn with.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    Kitty,
+    /// Also spoken by WezTerm.
+    Iterm,
+    Sixel,
+}
+
+impl Protocol {
+    /// Guesses what the terminal supports from its environment, there is no
+    /// reliable query that works before the UI starts.
+    pub fn detect() -> Option<Self> {
+        let var = |name| std::env::var(name).unwrap_or_default();
+        let (term, program) = (var("TERM"), var("TERM_PROGRAM"));
+        if std::env::var_os("KITTY_WINDOW_ID").is_some()
+            || term.contains("kitty")
+            || term.contains("ghostty")
+        {
+            Some(Self::Kitty)
+        } else if matches!(program.as_str(), "iTerm.app" | "WezTerm") {
+            Some(Self::Iterm)
+        } else if term.contains("sixel") || matches!(term.as_str(), "foot" | "mlterm") {
+            Some(Self::Sixel)
+        } else {
+            None
+        }
+    }
+}
+
+/// Whether `path` names a PNG, the only format thumbnails are drawn for.
+pub fn is_image(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("png"))
+}
+
+/// Image files read for thumbnails, by path. Files that can't be shown are
+/// remembered as `None` so they aren't read again.
+#[derive(Debug, Default)]
+pub struct Thumbnails {
+    images: HashMap<PathBuf, Option<Vec<u8>>>,
+}
+
+impl Thumbnails {
+    pub fn load(&mut self, path: &Path) {
+        if self.images.contains_key(path) {
+            return;
+        }
+        let data = std::fs::metadata(path)
+            .ok()
+            .filter(|meta| meta.len() <= MAX_IMAGE_BYTES)
+            .and_then(|_| std::fs::read(path).ok())
+            .filter(|data| data.starts_with(PNG_SIGNATURE));
+        self.images.insert(path.to_path_buf(), data);
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&[u8]> {
+        self.images.get(path)?.as_deref()
+    }
+}
+
+/// Draws the thumbnails over the blank rows left for them. Kitty keeps its
+/// images apart from the text, so the old ones are taken down first; the
+/// other protocols paint into the cells and are cleared with them.
+pub fn draw(
+    protocol: Protocol,
+    placements: &[(Rect, PathBuf)],
+    thumbnails: &Thumbnails,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    // save the cursor, the input caret has to stay where it is
+    write!(out, "\x1b7")?;
+    if protocol == Protocol::Kitty {
+        write!(out, "\x1b_Ga=d,q=2\x1b\\")?;
+    }
+    for (area, path) in placements {
+        let Some(png) = thumbnails.get(path) else {
+            continue;
+        };
+        write!(out, "\x1b[{};{}H", area.y + 1, area.x + 1)?;
+        match protocol {
+            Protocol::Kitty => kitty(png, area, out)?,
+            Protocol::Iterm => iterm(png, area, out)?,
+            Protocol::Sixel => match sixel(png, area) {
+                Some(image) => out.write_all(image.as_bytes())?,
+                None => continue,
+            },
+        }
+    }
+    write!(out, "\x1b8")?;
+    out.flush()
+}
+
+fn kitty(png: &[u8], area: &Rect, out: &mut impl Write) -> io::Result<()> {
+    let encoded = STANDARD.encode(png);
+    let chunks: Vec<&[u8]> = encoded.as_bytes().chunks(KITTY_CHUNK).collect();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = (i + 1 < chunks.len()) as u8;
+        match i {
+            // below the text, so popups drawn over it stay readable
+            0 => write!(
+                out,
+                "\x1b_Ga=T,f=100,q=2,C=1,z=-1,c={},r={},m={more};",
+                area.width, area.height
+            )?,
+            _ => write!(out, "\x1b_Gm={more};")?,
+        }
+        out.write_all(chunk)?;
+        write!(out, "\x1b\\")?;
+    }
+    Ok(())
+}
+
+fn iterm(png: &[u8], area: &Rect, out: &mut impl Write) -> io::Result<()> {
+    write!(
+        out,
+        "\x1b]1337;File=inline=1;size={};width={};height={};preserveAspectRatio=1:{}\x07",
+        png.len(),
+        area.width,
+        area.height,
+        STANDARD.encode(png)
+    )
+}
+
+/// Decodes to 8 bit RGBA.
+fn decode(png: &[u8]) -> Option<(u32, u32, Vec<[u8; 4]>)> {
+    let mut decoder = png::Decoder::new(png);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().ok()?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).ok()?;
+    let bytes = &buf[..info.buffer_size()];
+    let pixels = match info.color_type {
+        png::ColorType::Rgba => bytes.chunks(4).map(|p| [p[0], p[1], p[2], p[3]]).collect(),
+        png::ColorType::Rgb => bytes.chunks(3).map(|p| [p[0], p[1], p[2], 255]).collect(),
+        png::ColorType::GrayscaleAlpha => {
+            bytes.chunks(2).map(|p| [p[0], p[0], p[0], p[1]]).collect()
+        }
+        png::ColorType::Grayscale => bytes.iter().map(|&g| [g, g, g, 255]).collect(),
+        png::ColorType::Indexed => return None,
+    };
+
+    Some((info.width, info.height, pixels))
+}
+
+/// Pixel size of a cell, from the terminal if it says.
+fn cell_size() -> (u32, u32) {
+    match crossterm::terminal::window_size() {
+        Ok(size) if size.width > 0 && size.columns > 0 && size.rows > 0 => (
+            (size.width / size.columns) as u32,
+            (size.height / size.rows) as u32,
+        ),
+        _ => DEFAULT_CELL,
+    }
+}
+
+/// Encodes the image scaled down to fit `area`, in a 6x6x6 colour cube
+/// palette. Transparent pixels are left out.
+fn sixel(png: &[u8], area: &Rect) -> Option<String> {
+    let (width, height, pixels) = decode(png)?;
+    let (cell_w, cell_h) = cell_size();
+    let (max_w, max_h) = (area.width as u32 * cell_w, area.height as u32 * cell_h);
+    let scale = f64::min(max_w as f64 / width as f64, max_h as f64 / height as f64).min(1.0);
+    let w = ((width as f64 * scale) as u32).max(1);
+    let h = ((height as f64 * scale) as u32).max(1);
+    // nearest neighbour, good enough for a thumbnail
+    let color = |x: u32, y: u32| -> Option<usize> {
+        let [r, g, b, a] = pixels[((y * height / h) * width + x * width / w) as usize];
+        let level = |c: u8| (c as usize * 5 + 127) / 255;
+        (a >= 128).then(|| level(r) * 36 + level(g) * 6 + level(b))
+    };
+
+    let mut out = format!("\x1bPq\"1;1;{w};{h}");
+    for i in 0..216 {
+        let percent = |level: usize| level * 100 / 5;
+        out += &format!(
+            "#{i};2;{};{};{}",
+            percent(i / 36),
+            percent(i / 6 % 6),
+            percent(i % 6)
+        );
+    }
+    for band in (0..h).step_by(6) {
+        let rows = band..(band + 6).min(h);
+        let mut used = vec![false; 216];
+        for y in rows.clone() {
+            for x in 0..w {
+                if let Some(c) = color(x, y) {
+                    used[c] = true;
+                }
+            }
+        }
+        for c in (0..216).filter(|&c| used[c]) {
+            out += &format!("#{c}");
+            let sixels = (0..w).map(|x| {
+                let bits = rows
+                    .clone()
+                    .filter(|&y| color(x, y) == Some(c))
+                    .fold(0u8, |bits, y| bits | 1 << (y - band));
+                (63 + bits) as char
+            });
+            push_run_length(&mut out, sixels);
+            out.push('$');
+        }
+        out.push('-');
+    }
+    out += "\x1b\\";
+
+    Some(out)
+}
+
+/// Appends sixels, repeats as `!<count><sixel>`.
+fn push_run_length(out: &mut String, sixels: impl Iterator<Item = char>) {
+    let mut run: Option<(char, usize)> = None;
+    let flush = |out: &mut String, (c, n): (char, usize)| match n {
+        1..=3 => out.extend(std::iter::repeat_n(c, n)),
+        _ => *out += &format!("!{n}{c}"),
+    };
+    for c in sixels {
+        run = match run {
+            Some((last, n)) if last == c => Some((c, n + 1)),
+            Some(prev) => {
+                flush(out, prev);
+                Some((c, 1))
+            }
+            None => Some((c, 1)),
+        };
+    }
+    if let Some(run) = run {
+        flush(out, run);
+    }
+}
@@ -0,0 +1,206 @@
+use chrono::{DateTime, Utc};
+use marain_api::prelude::{ChatMsg, ServerMsg, ServerMsgBody};
+
+use crate::{
+    alerts::{Alert, AlertEvent, Attention},
+    app::App,
+    chat_log::Log,
+    edits,
+    file_transfer::Chunk,
+    i18n::{tr, trf},
+    update::{raise_alert, ring, Effect},
+};
+
+use super::{incoming_log, translate_ts, Handler};
+
+/// Chat messages, and the client to client messages that ride on them:
+/// presence, edits, file chunks and direct messages.
+pub struct ChatHandler;
+
+impl Handler for ChatHandler {
+    fn handle(&self, app: &mut App, msg: ServerMsg, effects: &mut Vec<Effect>) {
+        let dt = translate_ts(msg.timestamp);
+        let ServerMsgBody::ChatRecv {
+            direct,
+            chat_msg: ChatMsg {
+                sender, content, ..
+            },
+        } = msg.body
+        else {
+            return;
+        };
+        // the parts of a long message are nothing until the last one is in
+        let Some(content) = app.fragments.take(&sender, content) else {
+            return;
+        };
+        let content = app
+            .room_keys
+            .open_live(&app.room_state.room_name, &sender, dt, content);
+        if app.take_presence(&sender, &content) {
+            return;
+        }
+        if let Some(change) = edits::parse(&content) {
+            app.apply_change(&sender, change);
+            return;
+        }
+        if let Some(chunk) = Chunk::parse(&content) {
+            if sender != app.username {
+                app.receive_chunk(sender, chunk);
+            }
+            return;
+        }
+        if direct {
+            if !app.is_ignored(&sender) {
+                handle_direct(app, sender, content, dt, effects);
+            }
+            return;
+        }
+        if sender != app.username && !app.plugins.message_received(&sender, &content) {
+            app.run_plugin_actions();
+            return;
+        }
+        let mut log = incoming_log(app, sender, content).at(dt);
+        // kept, so unignoring brings them back, but they never alert
+        if app.is_ignored(&log.from) {
+            app.persist_log(&log);
+            app.push_log(log);
+            return;
+        }
+        let attention = Attention::of(
+            &app.config.notifications.rules,
+            &app.room_state.room_name,
+            &log.from,
+            &log.msg,
+            app.focused,
+        );
+        let mut mention = None;
+        if app.is_mention(&log.from, &log.msg) || app.matches_room_keyword(&log.from, &log.msg) {
+            log = log.highlighted();
+            if attention.bell {
+                let alert = Alert::new(AlertEvent::Mention, &log.from, &log.msg);
+                raise_alert(app, alert, effects);
+            }
+            if !app.focused {
+                app.unread_mentions += 1;
+            }
+            mention = Some(Log::new(
+                "MENTION".into(),
+                trf!("sys.mentioned_by", log.from, app.room_state.room_name),
+            ));
+        }
+        if attention.bell && mention.is_none() && log.from != app.username {
+            ring(app, AlertEvent::Message, effects);
+        }
+        if attention.bell && app.matches_keyword(&log.from, &log.msg) {
+            let alert = Alert::new(AlertEvent::Keyword, &log.from, &log.msg);
+            raise_alert(app, alert, effects);
+        }
+        if attention.desktop {
+            effects.push(Effect::Notify {
+                room: app.room_state.room_name.clone(),
+                sender: log.from.clone(),
+                message: log.msg.clone(),
+            });
+        }
+        app.persist_log(&log);
+        if log.from == app.username && app.confirm_delivery(&log) {
+            return;
+        }
+        match attention.badge {
+            true => app.push_incoming_log(log),
+            false => app.push_log(log),
+        }
+        // a separate entry so mentions stand out even when scrolled past
+        if let Some(mention) = mention {
+            app.push_log(mention);
+        }
+        app.run_plugin_actions();
+    }
+}
+
+fn handle_direct(
+    app: &mut App,
+    sender: String,
+    text: String,
+    dt: DateTime<Utc>,
+    effects: &mut Vec<Effect>,
+) {
+    let to = app.username.clone();
+    let log = incoming_log(app, sender, text).at(dt).direct(to);
+    let alert = Alert::new(AlertEvent::DirectMessage, &log.from, &log.msg);
+    raise_alert(app, alert, effects);
+    if !app.focused {
+        effects.push(Effect::Notify {
+            room: tr("pane.dms_room"),
+            sender: log.from.clone(),
+            message: log.msg.clone(),
+        });
+    }
+    app.push_dm(log);
+}
+
+#[cfg(test)]
+mod tests {
+    use marain_api::prelude::Timestamp;
+
+    use super::super::{test_app, test_msg};
+    use super::*;
+    use crate::alerts::{NotificationRule, RuleAction};
+
+    fn chat(sender: &str, content: &str) -> ServerMsg {
+        message(sender, content, false)
+    }
+
+    fn message(sender: &str, content: &str, direct: bool) -> ServerMsg {
+        test_msg(ServerMsgBody::ChatRecv {
+            direct,
+            chat_msg: ChatMsg {
+                sender: sender.into(),
+                timestamp: Timestamp::from(Utc::now()),
+                content: content.into(),
+            },
+        })
+    }
+
+    #[test]
+    fn direct_messages_go_to_their_own_pane() {
+        let mut app = test_app();
+        let logged = app.logs.len();
+        ChatHandler.handle(&mut app, message("alice", "psst", true), &mut vec![]);
+        assert_eq!(app.logs.len(), logged);
+        assert_eq!(app.dm_logs.front().map(|l| l.msg.as_str()), Some("psst"));
+    }
+
+    #[test]
+    fn ignored_users_never_alert() {
+        let mut app = test_app();
+        app.config.ignored = vec!["alice".into()];
+        let mut effects = vec![];
+        ChatHandler.handle(&mut app, chat("alice", "hey @me"), &mut effects);
+        assert!(effects.is_empty());
+        assert_eq!(app.logs.front().map(|l| l.msg.as_str()), Some("hey @me"));
+    }
+
+    #[test]
+    fn the_first_matching_rule_decides() {
+        let mut app = test_app();
+        app.set_focus(false);
+        app.config.notifications.rules = vec![
+            NotificationRule {
+                sender: Some("ci-bot".into()),
+                actions: vec![RuleAction::Silent],
+                ..Default::default()
+            },
+            NotificationRule {
+                actions: vec![RuleAction::Desktop],
+                ..Default::default()
+            },
+        ];
+        let mut effects = vec![];
+        ChatHandler.handle(&mut app, chat("ci-bot", "build passed"), &mut effects);
+        assert!(effects.is_empty());
+        assert_eq!(app.unread_count, 0);
+        ChatHandler.handle(&mut app, chat("alice", "hi"), &mut effects);
+        assert!(matches!(&effects[..], [Effect::Notify { .. }]));
+    }
+}
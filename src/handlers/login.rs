@@ -0,0 +1,14 @@
+use marain_api::prelude::ServerMsg;
+
+use crate::{app::App, update::Effect};
+
+use super::Handler;
+
+/// Logins are handled before the app runs, a second one is ignored.
+pub struct LoginHandler;
+
+impl Handler for LoginHandler {
+    fn handle(&self, _: &mut App, _: ServerMsg, _: &mut Vec<Effect>) {
+        tracing::error!("Received a second LoginSuccess message from the server.")
+    }
+}
@@ -0,0 +1,148 @@
+use std::{collections::HashMap, sync::OnceLock};
+
+use chrono::{DateTime, Utc};
+use marain_api::prelude::{ServerMsg, ServerMsgBody, Timestamp};
+
+use crate::{app::App, chat_log::Log, update::Effect};
+
+mod chat;
+mod login;
+mod notification;
+mod room_data;
+mod server_time;
+
+/// Kinds of message the server sends, what handlers are registered by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    LoginSuccess,
+    ChatRecv,
+    RoomData,
+    Notification,
+    /// The answer to a time request.
+    Empty,
+}
+
+impl Kind {
+    pub fn of(body: &ServerMsgBody) -> Self {
+        match body {
+            ServerMsgBody::LoginSuccess { .. } => Self::LoginSuccess,
+            ServerMsgBody::ChatRecv { .. } => Self::ChatRecv,
+            ServerMsgBody::RoomData { .. } => Self::RoomData,
+            ServerMsgBody::Notification { .. } => Self::Notification,
+            ServerMsgBody::Empty => Self::Empty,
+        }
+    }
+}
+
+/// Applies one kind of successful server message to the app. The message
+/// it is given is always of the kind it was registered for.
+pub trait Handler: Send + Sync {
+    fn handle(&self, app: &mut App, msg: ServerMsg, effects: &mut Vec<Effect>);
+}
+
+/// Handlers by the kind of message they take. A new kind of message gets a
+/// module with its handler, registered in [`Registry::default`].
+pub struct Registry {
+    handlers: HashMap<Kind, Box<dyn Handler>>,
+}
+
+impl Registry {
+    pub fn empty() -> Self {
+        Self {
+            handlers: HashMap::new(),
+        }
+    }
+
+    /// Fluent setter for the handler of `kind`, replacing any before it.
+    pub fn with(mut self, kind: Kind, handler: impl Handler + 'static) -> Self {
+        self.handlers.insert(kind, Box::new(handler));
+
+        self
+    }
+
+    pub fn dispatch(&self, app: &mut App, msg: ServerMsg, effects: &mut Vec<Effect>) {
+        let kind = Kind::of(&msg.body);
+        match self.handlers.get(&kind) {
+            Some(handler) => handler.handle(app, msg, effects),
+            None => tracing::error!("No handler for {kind:?} messages"),
+        }
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self::empty()
+            .with(Kind::LoginSuccess, login::LoginHandler)
+            .with(Kind::ChatRecv, chat::ChatHandler)
+            .with(Kind::RoomData, room_data::RoomDataHandler)
+            .with(Kind::Notification, notification::NotificationHandler)
+            .with(Kind::Empty, server_time::ServerTimeHandler)
+    }
+}
+
+/// The built-in handlers, shared by every call to `reduce`.
+pub fn registry() -> &'static Registry {
+    static REGISTRY: OnceLock<Registry> = OnceLock::new();
+    REGISTRY.get_or_init(Registry::default)
+}
+
+pub fn translate_ts(ts: Timestamp) -> DateTime<Utc> {
+    Into::<Option<DateTime<Utc>>>::into(ts).unwrap_or(Utc::now())
+}
+
+/// Builds the log for an incoming chat message. This is the hook point where
+/// the registered transforms get to rewrite the content, after the signature
+/// of the original content has been checked.
+fn incoming_log(app: &mut App, sender: String, content: String) -> Log {
+    let mut log = app.chat_log(sender, content);
+    log.msg = app.transforms.apply(&log.from, log.msg);
+    log
+}
+
+#[cfg(test)]
+fn test_app() -> App {
+    App::for_test(crate::user_config::UserConfig::default())
+}
+
+#[cfg(test)]
+fn test_msg(body: ServerMsgBody) -> ServerMsg {
+    ServerMsg {
+        status: marain_api::prelude::Status::Yes,
+        timestamp: Timestamp::from(Utc::now()),
+        body,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    struct Counting(AtomicUsize);
+
+    impl Handler for &'static Counting {
+        fn handle(&self, _: &mut App, _: ServerMsg, _: &mut Vec<Effect>) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn dispatches_by_kind() {
+        static COUNT: Counting = Counting(AtomicUsize::new(0));
+        let registry = Registry::default().with(Kind::Notification, &COUNT);
+        let mut app = test_app();
+        let body = ServerMsgBody::Notification { body: "hi".into() };
+        registry.dispatch(&mut app, test_msg(body), &mut vec![]);
+        registry.dispatch(&mut app, test_msg(ServerMsgBody::Empty), &mut vec![]);
+        assert_eq!(COUNT.0.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn unregistered_kinds_are_skipped() {
+        let mut app = test_app();
+        let body = ServerMsgBody::Notification { body: "hi".into() };
+        Registry::empty().dispatch(&mut app, test_msg(body), &mut vec![]);
+        assert!(app.logs.iter().all(|l| l.msg != "hi"));
+    }
+}
@@ -0,0 +1,38 @@
+use marain_api::prelude::{ServerMsg, ServerMsgBody};
+
+use crate::{app::App, chat_log::Log, update::Effect};
+
+use super::{translate_ts, Handler};
+
+/// Announcements from the server itself.
+pub struct NotificationHandler;
+
+impl Handler for NotificationHandler {
+    fn handle(&self, app: &mut App, msg: ServerMsg, _: &mut Vec<Effect>) {
+        let dt = translate_ts(msg.timestamp);
+        if let ServerMsgBody::Notification { body } = msg.body {
+            if let Some(welcome) = app.welcome.as_mut().filter(|w| w.open()) {
+                welcome.add_motd(body.clone());
+            }
+            app.push_incoming_log(Log::new("SERVER".to_owned(), body).at(dt))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{test_app, test_msg};
+    use super::*;
+
+    #[test]
+    fn notifications_are_logged_from_the_server() {
+        let mut app = test_app();
+        let body = ServerMsgBody::Notification {
+            body: "restarting soon".into(),
+        };
+        NotificationHandler.handle(&mut app, test_msg(body), &mut vec![]);
+        let newest = app.logs.front().expect("no logs");
+        assert_eq!(newest.from, "SERVER");
+        assert_eq!(newest.msg, "restarting soon");
+    }
+}
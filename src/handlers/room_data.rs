@@ -0,0 +1,108 @@
+use marain_api::prelude::{ServerMsg, ServerMsgBody};
+
+use crate::{
+    app::{App, Presence},
+    chat_log::{Log, LogKind},
+    edits,
+    file_transfer::Chunk,
+    update::Effect,
+};
+
+use super::{incoming_log, translate_ts, Handler};
+
+/// The state of the room just joined: its history, occupants and
+/// notifications.
+pub struct RoomDataHandler;
+
+impl Handler for RoomDataHandler {
+    fn handle(&self, app: &mut App, msg: ServerMsg, _: &mut Vec<Effect>) {
+        let dt = translate_ts(msg.timestamp);
+        let ServerMsgBody::RoomData {
+            logs,
+            notifications,
+            occupants,
+            room_name,
+            ..
+        } = msg.body
+        else {
+            return;
+        };
+        let logs: Vec<_> = logs
+            .into_iter()
+            .map(|mut cm| {
+                let sent = translate_ts(cm.timestamp.clone());
+                cm.content = app.room_keys.open(&room_name, &cm.sender, sent, cm.content);
+                cm
+            })
+            .collect();
+        let chat_logs: Vec<Log> = logs
+            .iter()
+            // old presence updates are of no interest
+            .filter(|cm| !Presence::is_update(&cm.content))
+            .filter(|cm| Chunk::parse(&cm.content).is_none())
+            .filter(|cm| edits::parse(&cm.content).is_none())
+            .map(|cm| {
+                incoming_log(app, cm.sender.clone(), cm.content.clone())
+                    .at(translate_ts(cm.timestamp.clone()))
+            })
+            .collect();
+        let notifications: Vec<Log> = notifications
+            .iter()
+            .map(|n| {
+                Log::new(n.sender.clone(), n.content.clone())
+                    .of_kind(LogKind::Notification)
+                    .at(translate_ts(n.timestamp.clone()))
+            })
+            .collect();
+        app.update_room(chat_logs, notifications, occupants, dt, room_name);
+        app.check_welcome(true);
+        // edits in the history apply to the messages before them
+        for cm in logs.iter() {
+            if let Some(change) = edits::parse(&cm.content) {
+                app.apply_change(&cm.sender, change);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+    use marain_api::prelude::{ChatMsg, Timestamp};
+
+    use super::super::{test_app, test_msg};
+    use super::*;
+
+    fn room_data(logs: &[(&str, &str)]) -> ServerMsg {
+        // history keeps its times however often it's sent
+        let now = Timestamp::from(DateTime::from_timestamp(1_700_000_000, 0).unwrap());
+        test_msg(ServerMsgBody::RoomData {
+            logs: logs
+                .iter()
+                .map(|(sender, content)| ChatMsg {
+                    sender: sender.to_string(),
+                    timestamp: now.clone(),
+                    content: content.to_string(),
+                })
+                .collect(),
+            notifications: vec![],
+            occupants: vec!["me".into()],
+            room_name: "lobby".into(),
+            timestamp: now,
+        })
+    }
+
+    #[test]
+    fn room_data_after_a_reconnect_keeps_what_only_we_had() {
+        let mut app = test_app();
+        RoomDataHandler.handle(&mut app, room_data(&[("alice", "hi")]), &mut vec![]);
+        app.reconnected(0);
+        let data = room_data(&[("alice", "hi"), ("bob", "missed this")]);
+        RoomDataHandler.handle(&mut app, data, &mut vec![]);
+        let msgs: Vec<&str> = app.logs.iter().map(|l| l.msg.as_str()).collect();
+        assert_eq!(msgs.iter().filter(|m| **m == "hi").count(), 1);
+        assert!(msgs.contains(&"missed this"));
+        assert!(msgs.iter().any(|m| m.starts_with("Reconnected")));
+        assert!(!app.resyncing);
+    }
+}
@@ -0,0 +1,23 @@
+use marain_api::prelude::ServerMsg;
+
+use crate::{app::App, chat_log::Log, i18n::trf, update::Effect};
+
+use super::{translate_ts, Handler};
+
+/// The answer to a time request, only the timestamp matters.
+pub struct ServerTimeHandler;
+
+impl Handler for ServerTimeHandler {
+    fn handle(&self, app: &mut App, msg: ServerMsg, _: &mut Vec<Effect>) {
+        let dt = translate_ts(msg.timestamp);
+        if app.receive_server_time(dt) {
+            app.push_log(Log::new(
+                "SERVER".into(),
+                trf!(
+                    "sys.server_time",
+                    app.theme.log.format_time_with(dt, "%Y-%m-%d %H:%M:%S")
+                ),
+            ))
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use std::{
+    fs::read_to_string,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use color_eyre::{eyre::eyre, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::{
+    app::App,
+    event_bus::dispatch,
+    keymap_file::parse_chord,
+    tui_framework::{Event, Tui},
+};
+
+/// How long an `expect` waits for its line before failing.
+const EXPECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// One line of a headless script.
+///
+/// ```text
+/// # comments and blank lines are skipped
+/// key i
+/// type hello there
+/// key Enter
+/// wait 200
+/// expect hello there
+/// ```
+#[derive(Debug, Clone)]
+pub enum Step {
+    /// `key <chord>`, named like in keymap files, e.g. `Ctrl+s`.
+    Key(KeyCode, KeyModifiers),
+    /// `type <text>`, each character as a key press.
+    Type(String),
+    /// `wait <millis>` while events keep being handled.
+    Wait(Duration),
+    /// `expect <text>`, some log line has to contain the text.
+    Expect(String),
+}
+
+/// Parses a script, failing on the first line that isn't a step.
+pub fn parse_script(script: &str) -> Result<Vec<(usize, Step)>> {
+    let mut steps = vec![];
+    for (n, line) in script.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (cmd, arg) = line.split_once(' ').unwrap_or((line, ""));
+        let step = match cmd {
+            "key" => parse_chord(arg).map(|(code, modifiers)| Step::Key(code, modifiers)),
+            "type" => Some(Step::Type(arg.to_string())),
+            "wait" => arg
+                .parse()
+                .ok()
+                .map(|ms| Step::Wait(Duration::from_millis(ms))),
+            "expect" if !arg.is_empty() => Some(Step::Expect(arg.to_string())),
+            _ => None,
+        };
+        match step {
+            Some(step) => steps.push((n + 1, step)),
+            None => return Err(eyre!("Invalid step on line {}: {line}", n + 1)),
+        }
+    }
+
+    Ok(steps)
+}
+
+pub fn read_script(path: &Path) -> Result<Vec<(usize, Step)>> {
+    parse_script(&read_to_string(path)?)
+}
+
+/// Runs the steps against a connected app, printing the outcome of each
+/// expectation. Returns how many of them failed.
+pub async fn run(app: &mut App, tui: &mut Tui, steps: &[(usize, Step)]) -> Result<usize> {
+    let mut failed = 0;
+    for (line, step) in steps {
+        match step {
+            Step::Key(code, modifiers) => {
+                dispatch(app, tui, Event::Key(KeyEvent::new(*code, *modifiers)))?
+            }
+            Step::Type(text) => {
+                for c in text.chars() {
+                    dispatch(app, tui, c.into())?;
+                }
+            }
+            Step::Wait(time) => _ = pump(app, tui, *time, |_| false).await?,
+            Step::Expect(text) => {
+                let found = pump(app, tui, EXPECT_TIMEOUT, |app| has_line(app, text)).await?;
+                match found {
+                    true => println!("ok   {line}: {text}"),
+                    false => {
+                        failed += 1;
+                        println!("FAIL {line}: {text}");
+                    }
+                }
+            }
+        }
+        if app.should_quit {
+            break;
+        }
+    }
+    println!("{} passed, {failed} failed", expectations(steps) - failed);
+
+    Ok(failed)
+}
+
+/// Handles events for up to `time`, stopping early once `done` holds.
+async fn pump(
+    app: &mut App,
+    tui: &mut Tui,
+    time: Duration,
+    done: impl Fn(&App) -> bool,
+) -> Result<bool> {
+    let deadline = Instant::now() + time;
+    while !done(app) {
+        let left = deadline.saturating_duration_since(Instant::now());
+        match tokio::time::timeout(left, tui.next()).await {
+            Ok(event) => match event? {
+                Event::Reconnect => crate::reconnect(app, tui).await?,
+                event => dispatch(app, tui, event)?,
+            },
+            Err(_) => return Ok(false),
+        }
+    }
+
+    Ok(true)
+}
+
+fn has_line(app: &App, text: &str) -> bool {
+    app.logs.iter().any(|log| log.to_string().contains(text))
+}
+
+fn expectations(steps: &[(usize, Step)]) -> usize {
+    steps
+        .iter()
+        .filter(|(_, step)| matches!(step, Step::Expect(_)))
+        .count()
+}
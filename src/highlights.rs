@@ -0,0 +1,104 @@
+use ratatui::{style::Style, text::Span};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{i18n::trf, theme::StyleSpec};
+
+/// One entry of `highlights` in the user config, e.g.
+/// `{"pattern": "deploy|prod", "style": {"fg": "red", "bold": true}}`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HighlightRule {
+    pub pattern: String,
+    #[serde(default)]
+    pub style: StyleSpec,
+}
+
+/// Styles picked out parts of messages with, on top of the message style.
+#[derive(Debug, Clone, Default)]
+pub struct Highlights {
+    rules: Vec<(Regex, Style)>,
+}
+
+impl Highlights {
+    /// Compiles the configured rules, the ones that aren't valid patterns are
+    /// left out and described.
+    pub fn from_config(rules: &[HighlightRule]) -> (Self, Vec<String>) {
+        let mut highlights = Self::default();
+        let mut problems = vec![];
+        for rule in rules {
+            match Regex::new(&rule.pattern) {
+                Ok(regex) => highlights
+                    .rules
+                    .push((regex, rule.style.apply(Style::new()))),
+                Err(e) => problems.push(trf!("sys.highlight_invalid", rule.pattern, e)),
+            }
+        }
+
+        (highlights, problems)
+    }
+
+    /// Splits already styled spans around the matches of each rule in turn,
+    /// patching the matches with the rule's style.
+    pub fn apply(&self, mut spans: Vec<Span<'static>>) -> Vec<Span<'static>> {
+        for (regex, style) in &self.rules {
+            let mut out = vec![];
+            for span in spans {
+                let mut plain = 0;
+                for found in regex.find_iter(&span.content) {
+                    if found.is_empty() {
+                        continue;
+                    }
+                    if plain < found.start() {
+                        out.push(Span::styled(
+                            span.content[plain..found.start()].to_string(),
+                            span.style,
+                        ));
+                    }
+                    out.push(Span::styled(
+                        found.as_str().to_string(),
+                        span.style.patch(*style),
+                    ));
+                    plain = found.end();
+                }
+                match plain {
+                    0 => out.push(span),
+                    _ if plain < span.content.len() => {
+                        out.push(Span::styled(span.content[plain..].to_string(), span.style))
+                    }
+                    _ => {}
+                }
+            }
+            spans = out;
+        }
+
+        spans
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::style::Color;
+
+    use super::*;
+
+    #[test]
+    fn styles_only_the_matches() {
+        let rule = HighlightRule {
+            pattern: "deploy|prod".into(),
+            style: StyleSpec {
+                fg: Some(Color::Red),
+                ..Default::default()
+            },
+        };
+        let (highlights, problems) = Highlights::from_config(&[rule]);
+        assert!(problems.is_empty());
+        let spans = highlights.apply(vec![Span::raw("deploy to prod now")]);
+        let red: Vec<&str> = spans
+            .iter()
+            .filter(|s| s.style.fg == Some(Color::Red))
+            .map(|s| s.content.as_ref())
+            .collect();
+        assert_eq!(red, ["deploy", "prod"]);
+        assert_eq!(spans.last().unwrap().content, " now");
+    }
+}
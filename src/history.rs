@@ -0,0 +1,409 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    ChaCha20Poly1305, Nonce,
+};
+use chrono::{DateTime, Utc};
+use marain_api::prelude::Key;
+use rand_core::RngCore;
+use serde::{Deserialize, Serialize};
+use sphinx::prelude::cbc_decode;
+
+use crate::{chat_log::Log, user_config::data_dir};
+
+/// Encrypted into `<history dir>/check` to tell a wrong passphrase apart
+/// from the right one before any history is read.
+const KEY_CHECK: &[u8] = b"marain history";
+
+/// Bytes of the nonce ahead of each encrypted line.
+const NONCE_LEN: usize = 12;
+
+/// How the store was kept before it was unlocked, told by its check file.
+/// The check file is only rewritten once what was there has been moved
+/// over, so everything else is taken in at most once.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Found {
+    /// Encrypted the current way, lines that aren't are dropped.
+    Current,
+    /// Never encrypted, the lines in the clear are taken in.
+    Plain,
+    /// Encrypted by an older version without authentication, its lines
+    /// and ones in the clear are taken in.
+    Legacy,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct StoredLog {
+    ts: DateTime<Utc>,
+    sender: String,
+    message: String,
+}
+
+/// Chat messages kept on disk so scrollback survives restarts, one JSON
+/// lines file per room under `<data dir>/history`. With a key each line
+/// is sealed with ChaCha20-Poly1305 and base64 encoded, and only lines
+/// that check out are read.
+#[derive(Debug)]
+pub struct HistoryStore {
+    dir: PathBuf,
+    key: Option<Key>,
+}
+
+impl HistoryStore {
+    pub fn open() -> io::Result<Self> {
+        Self::open_at(data_dir().join("history"))
+    }
+
+    fn open_at(dir: PathBuf) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir, key: None })
+    }
+
+    /// Opens the store encrypted with a key derived from `passphrase`. The
+    /// first passphrase used is the only one accepted from then on.
+    pub fn open_encrypted(passphrase: &str) -> io::Result<Self> {
+        Self::open_encrypted_at(data_dir().join("history"), passphrase)
+    }
+
+    /// Unlocks the store. The first time, what's stored in the clear is
+    /// encrypted and lines encrypted by older versions are moved over to
+    /// the current encryption, which unlike theirs can tell when a line was
+    /// changed. After that lines that don't check out are dropped.
+    fn open_encrypted_at(dir: PathBuf, passphrase: &str) -> io::Result<Self> {
+        let mut store = Self::open_at(dir)?;
+        let salt = store.salt()?;
+        let mut key = [0u8; 32];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        store.key = Some(key);
+
+        let check = store.dir.join("check");
+        let found = match fs::read_to_string(&check) {
+            Ok(line) if store.decrypt(&line, "check").as_deref() == Some(KEY_CHECK) => {
+                Found::Current
+            }
+            Ok(line) if legacy_decrypt(&key, &line).as_deref() == Some(KEY_CHECK) => Found::Legacy,
+            Ok(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    "wrong passphrase",
+                ))
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Found::Plain,
+            Err(e) => return Err(e),
+        };
+        store.migrate(found)?;
+        if found != Found::Current {
+            let line = store
+                .encrypt(KEY_CHECK.to_vec(), "check")
+                .ok_or(io::ErrorKind::Other)?;
+            fs::write(&check, line)?;
+        }
+        Ok(store)
+    }
+
+    /// Rewrites every file with a line that isn't encrypted the current
+    /// way. Lines in the clear and ones encrypted the older way are taken
+    /// in if the store was `found` to be from before, anything else is
+    /// dropped, it can't be trusted.
+    fn migrate(&self, found: Found) -> io::Result<()> {
+        let Some(key) = self.key else {
+            return Ok(());
+        };
+        let files = fs::read_dir(&self.dir)?
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| {
+                p.extension()
+                    .is_some_and(|ext| ext == "jsonl" || ext == "pins")
+            });
+        for path in files {
+            let contents = fs::read_to_string(&path)?;
+            let name = file_name(&path);
+            if contents
+                .lines()
+                .all(|line| self.decrypt(line, &name).is_some())
+            {
+                continue;
+            }
+            let mut migrated = String::new();
+            for line in contents.lines() {
+                let plain = match self.decrypt(line, &name) {
+                    Some(plain) => plain,
+                    None if found != Found::Current
+                        && serde_json::from_str::<StoredLog>(line).is_ok() =>
+                    {
+                        line.as_bytes().to_vec()
+                    }
+                    None => match (found == Found::Legacy)
+                        .then(|| legacy_decrypt(&key, line))
+                        .flatten()
+                    {
+                        Some(plain) if serde_json::from_slice::<StoredLog>(&plain).is_ok() => plain,
+                        _ => {
+                            tracing::warn!(
+                                "Dropping a line of {} that doesn't check out",
+                                path.display()
+                            );
+                            continue;
+                        }
+                    },
+                };
+                migrated += &self.encrypt(plain, &name).ok_or(io::ErrorKind::Other)?;
+                migrated.push('\n');
+            }
+            let tmp = path.with_extension("tmp");
+            fs::write(&tmp, migrated)?;
+            fs::rename(&tmp, &path)?;
+            tracing::info!("Encrypted {}", path.display());
+        }
+        Ok(())
+    }
+
+    /// Salt for the key derivation, made the first time it's needed.
+    fn salt(&self) -> io::Result<Vec<u8>> {
+        let path = self.dir.join("salt");
+        match fs::read(&path) {
+            Ok(salt) => Ok(salt),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let mut salt = vec![0u8; 16];
+                OsRng.fill_bytes(&mut salt);
+                fs::write(&path, &salt)?;
+                Ok(salt)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Seals `data` for the file called `name`, which goes in as associated
+    /// data so a line can't be moved to another file.
+    fn encrypt(&self, data: Vec<u8>, name: &str) -> Option<String> {
+        let key = self.key?;
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let payload = Payload {
+            msg: &data,
+            aad: name.as_bytes(),
+        };
+        ChaCha20Poly1305::new(&key.into())
+            .encrypt(&nonce, payload)
+            .map_err(|e| tracing::error!("Could not encrypt history: {e}"))
+            .ok()
+            .map(|sealed| STANDARD.encode([&nonce[..], &sealed].concat()))
+    }
+
+    fn decrypt(&self, line: &str, name: &str) -> Option<Vec<u8>> {
+        let key = self.key?;
+        let data = STANDARD.decode(line.trim()).ok()?;
+        if data.len() < NONCE_LEN {
+            return None;
+        }
+        let (nonce, sealed) = data.split_at(NONCE_LEN);
+        let payload = Payload {
+            msg: sealed,
+            aad: name.as_bytes(),
+        };
+        ChaCha20Poly1305::new(&key.into())
+            .decrypt(Nonce::from_slice(nonce), payload)
+            .ok()
+    }
+
+    /// Parses a stored line of the file called `name`. With a key only
+    /// lines that decrypt and check out are read.
+    fn parse(&self, line: &str, name: &str) -> Option<StoredLog> {
+        match self.key {
+            Some(_) => serde_json::from_slice(&self.decrypt(line, name)?).ok(),
+            None => serde_json::from_str(line).ok(),
+        }
+    }
+
+    fn path(&self, room: &str) -> PathBuf {
+        let name: String = room
+            .chars()
+            .map(|c| match c.is_alphanumeric() || c == '-' {
+                true => c,
+                false => '_',
+            })
+            .collect();
+        self.dir.join(format!("{name}.jsonl"))
+    }
+
+    /// Rooms there is history for. Names are as stored, so characters that
+    /// aren't allowed in file names come back as `_`.
+    pub fn rooms(&self) -> Vec<String> {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return vec![];
+        };
+        entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|ext| ext == "jsonl"))
+            .filter_map(|p| Some(p.file_stem()?.to_string_lossy().into_owned()))
+            .collect()
+    }
+
+    /// The line a log is stored as in `path`, encrypted if the store has
+    /// a key.
+    fn line(&self, log: &Log, path: &Path) -> io::Result<String> {
+        let record = StoredLog {
+            ts: log.ts,
+            sender: log.from.clone(),
+            message: log.msg.clone(),
+        };
+        let line = serde_json::to_string(&record)?;
+        match self.key {
+            Some(_) => Ok(self
+                .encrypt(line.into_bytes(), &file_name(path))
+                .ok_or(io::ErrorKind::Other)?),
+            None => Ok(line),
+        }
+    }
+
+    pub fn append(&self, room: &str, log: &Log) {
+        let path = self.path(room);
+        let result = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| writeln!(file, "{}", self.line(log, &path)?));
+        if let Err(e) = result {
+            tracing::error!("Could not write history for {room}: {e}");
+        }
+    }
+
+    /// Deletes what's stored for `room`, its pins stay.
+    pub fn clear(&self, room: &str) -> io::Result<()> {
+        match fs::remove_file(self.path(room)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Replaces the messages pinned in `room`, kept next to its history.
+    pub fn save_pins(&self, room: &str, pins: &[Log]) {
+        let path = self.path(room).with_extension("pins");
+        let result = pins
+            .iter()
+            .map(|log| Ok(self.line(log, &path)? + "\n"))
+            .collect::<io::Result<String>>()
+            .and_then(|lines| fs::write(&path, lines));
+        if let Err(e) = result {
+            tracing::error!("Could not write pins for {room}: {e}");
+        }
+    }
+
+    pub fn load_pins(&self, room: &str) -> Vec<Log> {
+        let path = self.path(room).with_extension("pins");
+        match fs::read_to_string(&path) {
+            Ok(contents) => contents
+                .lines()
+                .filter_map(|line| self.parse(line, &file_name(&path)))
+                .map(|s| Log::new(s.sender, s.message).at(s.ts))
+                .collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => vec![],
+            Err(e) => {
+                tracing::error!("Could not read pins for {room}: {e}");
+                vec![]
+            }
+        }
+    }
+
+    /// The newest `limit` messages stored for `room`, oldest first. Lines
+    /// that don't parse are skipped rather than losing the whole room.
+    pub fn load(&self, room: &str, limit: usize) -> Vec<Log> {
+        let path = self.path(room);
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return vec![],
+            Err(e) => {
+                tracing::error!("Could not read history for {room}: {e}");
+                return vec![];
+            }
+        };
+        let mut logs: Vec<Log> = contents
+            .lines()
+            .filter_map(|line| self.parse(line, &file_name(&path)))
+            .map(|s| Log::new(s.sender, s.message).at(s.ts))
+            .collect();
+        logs.sort_by_key(|l| l.ts);
+        let skip = logs.len().saturating_sub(limit);
+        logs.split_off(skip)
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Decrypts a line the way versions before lines were authenticated
+/// encrypted them, only to move them over.
+fn legacy_decrypt(key: &Key, line: &str) -> Option<Vec<u8>> {
+    cbc_decode(key.to_vec(), STANDARD.decode(line.trim()).ok()?).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unlocking_encrypts_what_was_plain_and_turns_away_changed_lines() {
+        let dir = std::env::temp_dir().join(format!("marain-history-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let plain = HistoryStore::open_at(dir.clone()).unwrap();
+        plain.append(
+            "rust",
+            &Log::new("alice".into(), "said in the clear".into()),
+        );
+
+        let store = HistoryStore::open_encrypted_at(dir.clone(), "hunter2").unwrap();
+        let stored = fs::read_to_string(dir.join("rust.jsonl")).unwrap();
+        assert!(!stored.contains("said in the clear"));
+        assert_eq!(store.load("rust", 10)[0].msg, "said in the clear");
+
+        store.append("rust", &Log::new("bob".into(), "sealed".into()));
+        let mut lines: Vec<String> = fs::read_to_string(dir.join("rust.jsonl"))
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect();
+        let mut tampered = STANDARD.decode(&lines[1]).unwrap();
+        *tampered.last_mut().unwrap() ^= 1;
+        lines[1] = STANDARD.encode(tampered);
+        lines.push(
+            serde_json::to_string(&StoredLog {
+                ts: Utc::now(),
+                sender: "mallory".into(),
+                message: "slipped in".into(),
+            })
+            .unwrap(),
+        );
+        fs::write(dir.join("rust.jsonl"), lines.join("\n")).unwrap();
+        // moved to another room's file it doesn't open either
+        fs::write(dir.join("go.jsonl"), &lines[0]).unwrap();
+
+        let logs = store.load("rust", 10);
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].msg, "said in the clear");
+        assert!(store.load("go", 10).is_empty());
+
+        // a line slipped in after the first unlock isn't taken in by the next
+        let store = HistoryStore::open_encrypted_at(dir.clone(), "hunter2").unwrap();
+        let logs = store.load("rust", 10);
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].msg, "said in the clear");
+        let stored = fs::read_to_string(dir.join("rust.jsonl")).unwrap();
+        assert!(!stored.contains("slipped in"));
+        assert_eq!(stored.lines().count(), 1);
+        assert!(HistoryStore::open_encrypted_at(dir.clone(), "hunter3").is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,175 @@
+use ratatui::style::{Color, Modifier, Style};
+
+use crate::{chat_log::Log, links, theme::Theme};
+
+/// Messages from the same sender closer together than this share a
+/// heading.
+const GROUP_GAP_SECS: i64 = 5 * 60;
+
+/// A standalone page of `logs`, oldest first, in the theme's colors.
+/// Consecutive messages from one sender go under a single heading, each
+/// with its time.
+pub fn render(room: &str, logs: &[&Log], theme: &Theme) -> String {
+    let style = &theme.log;
+    let mut page = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{room}</title>\n\
+         <style>\n\
+         body {{ font-family: monospace; margin: 2em; {body} }}\n\
+         h1 {{ font-size: 1.2em; {title} }}\n\
+         .group {{ margin-top: 0.8em; }}\n\
+         .msg {{ display: flex; gap: 1em; white-space: pre-wrap; }}\n\
+         .time {{ flex: none; }}\n\
+         a {{ color: inherit; }}\n\
+         </style>\n</head>\n<body>\n<h1>{room}</h1>\n",
+        room = escape(room),
+        body = css(theme.input),
+        title = css(theme.title),
+    );
+    let mut previous: Option<&Log> = None;
+    for log in logs {
+        let grouped = previous.is_some_and(|p| {
+            p.from == log.from
+                && p.kind == log.kind
+                && (log.ts - p.ts).num_seconds() < GROUP_GAP_SECS
+        });
+        if !grouped {
+            if previous.is_some() {
+                page += "</div>\n";
+            }
+            page += &format!(
+                "<div class=\"group\">\n<div style=\"{}\">{}</div>\n",
+                css(style.uname_for_kind(&log.from, log.kind)),
+                escape(&log.from),
+            );
+        }
+        page += &format!(
+            "<div class=\"msg\"><span class=\"time\" style=\"{}\" title=\"{}\">{}</span>\
+             <span style=\"{}\">{}</span></div>\n",
+            css(style.time_for(log.kind)),
+            log.ts.to_rfc3339(),
+            escape(&style.format_time_for(log.ts, log.kind)),
+            css(style.msg_for(log.kind)),
+            linked(&log.msg),
+        );
+        previous = Some(log);
+    }
+    if previous.is_some() {
+        page += "</div>\n";
+    }
+    page += "</body>\n</html>\n";
+
+    page
+}
+
+/// The message escaped, with its links made into anchors.
+fn linked(text: &str) -> String {
+    let mut out = String::new();
+    let mut plain = 0;
+    for link in links::find(text) {
+        out += &escape(&text[plain..link.start]);
+        let link_text = escape(&text[link.clone()]);
+        out += &format!(
+            "<a href=\"{}\">{link_text}</a>",
+            escape(&links::url(&text[link.clone()]))
+        );
+        plain = link.end;
+    }
+    out += &escape(&text[plain..]);
+
+    out
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// The declarations for a style, colors the terminal picks are left to the
+/// page.
+fn css(style: Style) -> String {
+    let mut out = String::new();
+    if let Some(fg) = style.fg.and_then(hex) {
+        out += &format!("color: {fg}; ");
+    }
+    if let Some(bg) = style.bg.and_then(hex) {
+        out += &format!("background-color: {bg}; ");
+    }
+    let modifiers = style.add_modifier - style.sub_modifier;
+    if modifiers.contains(Modifier::BOLD) {
+        out += "font-weight: bold; ";
+    }
+    if modifiers.contains(Modifier::ITALIC) {
+        out += "font-style: italic; ";
+    }
+    if modifiers.contains(Modifier::UNDERLINED) {
+        out += "text-decoration: underline; ";
+    }
+    if modifiers.contains(Modifier::CROSSED_OUT) {
+        out += "text-decoration: line-through; ";
+    }
+
+    out.trim_end().to_string()
+}
+
+/// The usual xterm values of the named and indexed colors.
+fn hex(color: Color) -> Option<String> {
+    const ANSI: [(u8, u8, u8); 16] = [
+        (0, 0, 0),
+        (128, 0, 0),
+        (0, 128, 0),
+        (128, 128, 0),
+        (0, 0, 128),
+        (128, 0, 128),
+        (0, 128, 128),
+        (192, 192, 192),
+        (128, 128, 128),
+        (255, 0, 0),
+        (0, 255, 0),
+        (255, 255, 0),
+        (0, 0, 255),
+        (255, 0, 255),
+        (0, 255, 255),
+        (255, 255, 255),
+    ];
+    let index = match color {
+        Color::Reset => return None,
+        Color::Rgb(r, g, b) => return Some(format!("#{r:02x}{g:02x}{b:02x}")),
+        Color::Indexed(i) => i,
+        Color::Black => 0,
+        Color::Red => 1,
+        Color::Green => 2,
+        Color::Yellow => 3,
+        Color::Blue => 4,
+        Color::Magenta => 5,
+        Color::Cyan => 6,
+        Color::Gray => 7,
+        Color::DarkGray => 8,
+        Color::LightRed => 9,
+        Color::LightGreen => 10,
+        Color::LightYellow => 11,
+        Color::LightBlue => 12,
+        Color::LightMagenta => 13,
+        Color::LightCyan => 14,
+        Color::White => 15,
+    };
+    let (r, g, b) = match index {
+        0..=15 => ANSI[index as usize],
+        16..=231 => {
+            let level = |n: u8| match n {
+                0 => 0,
+                n => 55 + n * 40,
+            };
+            let i = index - 16;
+            (level(i / 36), level(i / 6 % 6), level(i % 6))
+        }
+        _ => {
+            let gray = 8 + (index - 232) * 10;
+            (gray, gray, gray)
+        }
+    };
+
+    Some(format!("#{r:02x}{g:02x}{b:02x}"))
+}
@@ -0,0 +1,476 @@
+use std::{collections::HashMap, fmt::Display, fs, sync::OnceLock};
+
+use crate::user_config::config_dir;
+
+/// Translations loaded from `<config dir>/locales/<locale>.json`, a flat
+/// object mapping the keys below to translated text.
+static CATALOG: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+/// The built-in English text for every translatable key. This doubles as
+/// the reference list of keys for translators.
+fn english(key: &str) -> &'static str {
+    match key {
+        // keymap descriptions
+        "cmd.reset" => "Reset",
+        "cmd.quit" => "Quit",
+        "cmd.move_caret" => "Move Cursor",
+        "cmd.delete" => "Delete",
+        "cmd.enter_navigate" => "Enter Navigation Mode",
+        "cmd.enter_insert" => "Enter Insert Mode",
+        "cmd.enter_insert_command" => "Enter command params mode",
+        "cmd.enter_disconnected" => "Disconnect from server",
+        "cmd.enter_room_list" => "Pick a room",
+        "cmd.send_buffer" => "Send Message",
+        "cmd.get_server_time" => "Get Server Time",
+        "cmd.refresh_room" => "Refresh room",
+        "cmd.move_rooms" => "Move rooms",
+        "cmd.create_room" => "Create room",
+        "cmd.send_staged" => "Send Staged Command",
+        "cmd.abort_staged" => "Abort Command Staging",
+        "cmd.toggle_debug" => "Toggle debug output",
+        "cmd.send_direct" => "Send direct message",
+        "cmd.toggle_dms" => "Toggle direct messages",
+        "cmd.open_link" => "Open link",
+        "cmd.select_own" => "Edit or delete my messages",
+        "cmd.pin_message" => "Pin or unpin message",
+        "cmd.toggle_pins" => "Toggle pins",
+        "cmd.toggle_traffic" => "Toggle traffic overlay",
+        "cmd.export_keymap" => "Export keymap",
+        "cmd.import_keymap" => "Import keymap",
+        "cmd.export_keys" => "Export key cheat sheet",
+        "cmd.export_logs" => "Export chat log",
+        "cmd.export_html" => "Export chat log as HTML",
+        "cmd.upload" => "Upload file",
+        "cmd.cancel_upload" => "Cancel upload",
+        "cmd.save_download" => "Save download",
+        "cmd.discard_download" => "Discard download",
+        "cmd.run" => "Run shell command",
+        "cmd.bio" => "Set status message",
+        "cmd.whois" => "Show user profile",
+        "cmd.ignore" => "Ignore user",
+        "cmd.unignore" => "Stop ignoring user",
+        "cmd.qr" => "Show QR code",
+        "cmd.copy_join_link" => "Copy link to room",
+        "cmd.suggest_spelling" => "Suggest spelling",
+        "cmd.paste_image" => "Upload clipboard image",
+        "cmd.send_ephemeral" => "Send ephemeral message",
+        "cmd.stats" => "Show statistics",
+        "cmd.help" => "Help",
+        "cmd.reload" => "Reload config",
+        "cmd.room_directory" => "Room directory",
+        "cmd.scroll_up" => "Scroll back",
+        "cmd.scroll_down" => "Scroll forward",
+        "cmd.scroll_top" => "Scroll to oldest",
+        "cmd.scroll_bottom" => "Scroll to newest",
+        "cmd.history_prev" => "Previous in history",
+        "cmd.history_next" => "Next in history",
+        "cmd.delete_word" => "Delete word",
+        "cmd.select_room" => "Select room",
+        "cmd.join_room" => "Join selected room",
+        "cmd.enter_search" => "Search",
+        "cmd.submit_search" => "Find",
+        "cmd.trust_server_key" => "Trust new server key",
+        "cmd.server_keys" => "Show server keys",
+        "cmd.server_info" => "Show server info",
+        "cmd.set_room_key" => "Encrypt this room",
+        "cmd.forget_room_key" => "Stop encrypting this room",
+        "cmd.clear_history" => "Clear room history",
+        "cmd.clear_history_now" => "Clear room history without asking",
+        "cmd.quit_now" => "Quit without asking",
+        "cmd.complete" => "Complete",
+        "cmd.clear_search" => "Clear search",
+        "cmd.search_next" => "Older match",
+        "cmd.search_prev" => "Newer match",
+        "cmd.reconnect" => "Reconnect",
+        "cmd.delete_line" => "Delete line",
+        "cmd.enter_copy" => "Copy from log",
+        "cmd.copy_up" => "Cursor up",
+        "cmd.copy_down" => "Cursor down",
+        "cmd.copy_mark" => "Start/drop selection",
+        "cmd.copy_yank" => "Copy selection",
+        "cmd.paste_yanked" => "Paste copied text",
+        "cmd.kill" => "Cut",
+        "cmd.yank" => "Paste cut text",
+        "cmd.scroll_debug_up" => "Scroll debug up",
+        "cmd.scroll_debug_down" => "Scroll debug down",
+        "cmd.switch_tab" => "Go to tab",
+        "cmd.next_tab" => "Next tab",
+        "cmd.prev_tab" => "Previous tab",
+        "cmd.close_tab" => "Close tab",
+        "cmd.undo" => "Undo the last paste or cut",
+        "cmd.previous_room" => "Back to the previous room",
+        "cmd.recent_rooms" => "Recent rooms",
+        "cmd.toggle_system" => "Show/hide system messages",
+        "cmd.toggle_relative_time" => "Relative/absolute times",
+        "cmd.toggle_density" => "Compact/comfortable log",
+        "cmd.select_failed" => "Retry/discard failed",
+        "cmd.select_log" => "Message details",
+
+        // pane titles and status
+        "pane.info" => "INFO: {}",
+        "pane.room" => "ROOM: {}",
+        "pane.logs" => "LOGS",
+        "pane.debug" => "DEBUG ({})",
+        "pane.new_below" => " {} new ↓ ",
+        "pane.dms" => "DIRECT ({})",
+        "pane.pins" => "PINS ({})",
+        "pane.dms_room" => "a direct message",
+        "pane.logs_unread" => "LOGS ({} unread)",
+        "title.app" => "marain",
+        "title.room" => "{}{} - marain",
+        "title.unread" => "({}) ",
+        "title.unread_mentions" => "({}, {} @) ",
+        "pane.logs_scrolled" => " [{} newer below]",
+        "pane.logs_search" => " [search: {}]",
+        "pane.logs_search_none" => " [search: {} - no matches]",
+        "pane.search" => "SEARCH",
+        "pane.rooms" => "ROOMS",
+        "pane.msg" => "MSG",
+        "pane.cmd" => "CMD: {}",
+        "pane.upload" => "Uploading {}",
+        "pane.download" => "Downloading {} from {}",
+        "popup.download" => "Receive file?",
+        "popup.links" => "Open link",
+        "popup.links_footer" => "1-9 open, any other key closes",
+        "popup.recent_rooms" => "Recent rooms",
+        "popup.recent_footer" => "1-9 move there, any other key closes",
+        "popup.download_body" => "{} sent {} ({} bytes).\n\nSave it in {}?",
+        "pane.server_clock" => "SERVER {}",
+        "pane.traffic" => "TRAFFIC",
+        "perf.summary" => " render {} (max {}) | {} skipped | jitter {} | {} logs ({}) ",
+        "traffic.sent" => "sent {}/s, {} msgs, {}",
+        "traffic.received" => "recv {}/s, {} msgs, {}",
+        "traffic.reconnects" => "reconnects {}",
+        "traffic.queues" => "queued: {} events, {} offline, {} throttled",
+        "traffic.dropped" => "coalesced {} ticks, {} sends waited, dropped {} debug logs",
+        "traffic.decrypt_error" => "last decrypt error: {}",
+        "traffic.none" => "none",
+        "ui.too_small" => "Terminal too small ({}x{}), need at least {}x{}",
+        "log.unread_divider" => "── {} unread ──",
+        "log.ignored" => "── {} messages hidden ──",
+        "log.catch_up" => "── {} new messages since {} ──",
+        "log.date_separator" => "— {} —",
+        "log.pending" => " …",
+        "log.queued" => " ⧗ queued",
+        "log.sent" => " ✓",
+        "log.failed" => " ✗ not delivered",
+        "delivery.pending" => "waiting for the server",
+        "delivery.queued" => "queued until reconnected",
+        "delivery.sent" => "delivered",
+        "delivery.failed" => "not delivered",
+        "detail.from" => "From:      {}",
+        "detail.to" => "To:        {}",
+        "detail.utc" => "UTC:       {}",
+        "detail.local" => "Local:     {}",
+        "detail.delivery" => "Delivery:  {}",
+        "detail.length" => "Length:    {} characters, {} bytes",
+        "detail.edited" => "Edited by its sender",
+        "detail.deleted" => "Deleted by its sender",
+        "popup.fingerprint" => "IDENTITY FINGERPRINT",
+        "popup.invite" => "INVITE: {}",
+        "popup.log_details" => "MESSAGE DETAILS",
+        "popup.dismiss" => "press any key to close",
+        "popup.help" => "Help",
+        "popup.help_footer" => "j/k scroll, g/G top/bottom, q close",
+        "help.slash_commands" => "Slash commands",
+        "help.usage.bio" => "/bio <status>",
+        "help.usage.clear" => "/clear",
+        "help.usage.e2e" => "/e2e [off]",
+        "help.usage.keys" => "/keys export <path>",
+        "help.usage.link" => "/link",
+        "help.usage.msg" => "/msg <username> <message>",
+        "help.usage.paste-image" => "/paste-image",
+        "help.usage.qr" => "/qr [invite]",
+        "help.usage.reload" => "/reload",
+        "help.usage.rooms" => "/rooms",
+        "help.usage.run" => "/run <command>",
+        "help.usage.send" => "/send <path>",
+        "help.usage.server" => "/server",
+        "help.usage.shh" => "/shh <message>",
+        "help.usage.stats" => "/stats",
+        "help.usage.statusmsg" => "/statusmsg <status>",
+        "help.usage.upload" => "/upload <path>",
+        "help.usage.whois" => "/whois <username>",
+        "help.usage.ignore" => "/ignore <username>",
+        "help.usage.unignore" => "/unignore <username>",
+        "help.slash.bio" => "Set your status message",
+        "help.slash.clear" => "Forget this room's messages, after asking",
+        "help.slash.e2e" => "Encrypt this room's messages with a passphrase the server never sees, or stop",
+        "help.slash.keys" => "Write the key bindings to a file, Markdown if it ends in .md",
+        "help.slash.link" => "Copy a link to this room, for others to start the client with",
+        "help.slash.msg" => "Send a direct message",
+        "keys.title" => "marain key bindings",
+        "keys.key" => "Key",
+        "keys.command" => "Command",
+        "help.slash.paste-image" => "Send the image on the clipboard",
+        "help.slash.qr" => "Show your fingerprint, or an invite to this room, as a QR code",
+        "help.slash.reload" => "Re-read the config file",
+        "help.slash.rooms" => "Open the room directory",
+        "help.slash.run" => "Send the output of a shell command",
+        "help.slash.send" => "Send a file",
+        "help.slash.server" => "Show the server, its key and how the connection is doing",
+        "help.slash.shh" => "Send a message that disappears after a while",
+        "help.slash.stats" => "Show statistics for this room",
+        "help.slash.statusmsg" => "Same as /bio",
+        "help.slash.upload" => "Same as /send",
+        "help.slash.whois" => "Show what is known about a user",
+        "help.slash.ignore" => "Hide a user's messages",
+        "help.slash.unignore" => "Show a user's messages again",
+        "popup.confirm" => "y/n, ←/→ and Enter",
+        "modal.yes" => "Yes",
+        "modal.no" => "No",
+        "modal.cancel" => "Cancel",
+        "popup.quit" => "QUIT?",
+        "popup.quit_drafts" => "{} unsent draft(s) will be lost.",
+        "popup.quit_queued" => "{} message(s) are still waiting for the connection.",
+        "popup.quit_body" => "Quit anyway?",
+        "popup.clear_history" => "CLEAR HISTORY?",
+        "popup.clear_history_body" => "Forget every message in {}, here and on disk?",
+        "sys.history_cleared" => "Cleared the history of {}",
+        "sys.clear_history_failed" => "Could not clear the history of {}: {}",
+        "popup.directory" => "Enter to join, Esc to close",
+        "popup.directory_title" => "ROOMS",
+        "popup.directory_empty" => "No rooms known yet",
+        "popup.prompt" => "Enter to submit, Esc to skip",
+        "prompt.history_title" => "Unlock history",
+        "prompt.history_body" => "Enter the passphrase for your chat history.\nThe first one used sets it.",
+        "prompt.history_error" => "Could not unlock: {}",
+        "prompt.e2e_title" => "Encrypt room",
+        "prompt.e2e_body" => "Enter the passphrase shared by the members of {}.\nOnly those who enter the same one can read what's sent.",
+        "prompt.password" => "Server password: ",
+        "prompt.password_retry" => "Try again: ",
+        "popup.server_keys" => "SERVER KEYS",
+        "popup.server_key_changed" => "SERVER KEY CHANGED",
+        "popup.server_key_changed_body" => "The key for {} has changed!\n\nPinned:    {}\nPresented: {}\n\nSomeone may be intercepting the connection.\nTrust the new key? Answering no quits.",
+        "popup.stats" => "STATS",
+        "popup.server_info" => "SERVER",
+        "toast.info" => "Note",
+        "toast.success" => "Done",
+        "toast.error" => "Error",
+        "info.host" => "Host: {}",
+        "info.protocol" => "Protocol: marain-protocol {}",
+        "info.server_key" => "Server key: {}",
+        "info.session_started" => "Logged in: {}",
+        "info.latency" => "Latency: {}",
+        "info.connection" => "Connection: {}",
+        "info.unknown" => "unknown",
+        "info.not_measured" => "not measured yet",
+        "stats.users" => "Messages per user",
+        "stats.hours" => "Busiest hours (UTC)",
+        "stats.rooms" => "Messages per room this session",
+        "presence.online" => "online",
+        "presence.away" => "away: {}",
+        "room.presence" => " [{}]",
+        "room.since" => " since {}",
+        "room.none" => "None",
+        "room.favorite" => "[{}] {}",
+        "welcome.title" => "Welcome to {}",
+        "welcome.room" => "{}: {} here",
+        "room.unread_badge" => " [{}]",
+        "room.encrypted" => " {}",
+        "room.draft_badge" => " ✎",
+        "conn.connecting" => "connecting",
+        "conn.connected" => "connected",
+        "conn.closed" => "closed by server",
+        "conn.lost" => "connection lost",
+        "status.caret" => "Ln {}, Col {}",
+        "status.bell" => " ● ",
+        "status.draft" => "draft",
+        "status.throttled" => "{} held back",
+        "status.failed" => "{} failed",
+        "pane.counter" => "{} chars, {} bytes (max {})",
+        "sys.message_too_long" => "Not sent, the message is {} bytes and the limit is {}",
+        "status.rtt" => "RTT {}ms",
+
+        // errors
+        "err.input" => "Could not read terminal input: {}",
+        "err.connect" => "Could not connect to the server: {}",
+        "err.no_daemon" => "The session daemon needs unix sockets, which this platform doesn't have",
+        "err.socket" => "Lost the connection to the socket worker: {}",
+        "err.unexpected_message" => "Unexpected message from the server: {}",
+        "err.login" => "Login failed: {}",
+        "err.auth" => "The server refused the password: {}",
+        "err.password_cancelled" => "No password given",
+        "err.password_needs_tls" => "The password is only sent over TLS, connect with --tls",
+        "err.no_auth_reply" => "The server didn't answer the password",
+        "err.password_key_changed" => "The server's key is not the one pinned for it, the password was not sent. Remove it from known_servers.json if the key changed on purpose",
+        "err.encrypt" => "Could not encrypt message: {}",
+        "err.decrypt" => "Could not decrypt message: {}",
+        "err.decompress" => "Could not decompress message: {}",
+        "err.deserialize" => "Could not deserialize inbound message, the server may speak a protocol other than {}: {}",
+        "err.no_shared_secret" => "Not logged in, there is no key to encrypt with",
+        "err.rate_limited" => "Sending too fast, the message was dropped",
+        "err.server_older" => "The server speaks marain protocol {}, older than this client's {}. The server needs upgrading, or use an older client.",
+        "err.client_older" => "This client speaks marain protocol {}, older than the server's {}. Update the client to connect.",
+        "err.fatal_title" => "CANNOT CONTINUE",
+        "err.fatal_footer" => "Press any key to quit",
+        "err.no_server" => "No server to connect to, give one on the command line or in the config",
+        "err.bridge_login" => "Already logged in, a Login can't be sent",
+        "err.pipe_terminal" => "pipe sends what is piped into it, e.g. `tail -f build.log | marain-client pipe`",
+        "err.pipe_closed" => "The server closed the connection",
+        "err.unknown_profile" => "No profile named {} in the config",
+        "err.wizard_cancelled" => "Setup cancelled, using the defaults",
+        "profile.heading" => "Log in as:",
+        "profile.prompt" => "Profile, by number or name",
+        "profile.unknown" => "No such profile",
+        "wizard.welcome" => "Welcome to marain! A few questions to set up {}, empty answers keep the default in brackets.",
+        "wizard.username" => "Username (empty picks one at random)",
+        "wizard.host" => "Server",
+        "wizard.port" => "Port",
+        "wizard.theme" => "Theme: dark, light or high_contrast",
+        "wizard.unknown_theme" => "Unknown theme, pick dark, light or high_contrast",
+
+        // system log messages
+        "sys.connection_closed" => "Connection closed by server",
+        "sys.connection_lost" => "Connection lost, the server stopped responding",
+        "sys.reconnecting" => "Reconnecting...",
+        "sys.reconnected" => "Reconnected, sent {} queued messages",
+        "sys.resynced" => "Back in sync, {} messages arrived while disconnected",
+        "sys.login_failed" => "Failed to login",
+        "sys.server_time" => "The time is: {}",
+        "sys.presence_changed" => "is now {}",
+        "sys.mentioned_by" => "{} mentioned you in {}",
+        "sys.bad_search" => "Invalid search pattern: {}",
+        "sys.server_key_trusted" => "Pinned the new key for {}",
+        "sys.e2e_on" => "Messages in {} are now encrypted end to end",
+        "sys.e2e_off" => "Messages in {} are no longer encrypted",
+        "sys.e2e_not_on" => "{} isn't encrypted",
+        "e2e.empty_passphrase" => "The passphrase can't be empty",
+        "e2e.unreadable" => "[encrypted message, set the room's passphrase with /e2e to read it]",
+        "e2e.forged" => "[encrypted message that doesn't check out: the wrong passphrase, or changed or sent again on the way]",
+        "sys.upload_in_progress" => "Already uploading {}, cancel it first",
+        "sys.read_failed" => "Failed to read {}: {}",
+        "sys.upload_cancelled" => "Cancelled upload of {}",
+        "sys.upload_done" => "Uploaded {}",
+        "sys.download_saved" => "Saved {}",
+        "sys.no_links" => "No links in this room yet",
+        "sys.no_recent_rooms" => "No other rooms visited yet",
+        "sys.no_own_messages" => "You haven't sent anything here yet",
+        "sys.no_failed" => "No failed messages",
+        "log.edited" => " (edited)",
+        "time.just_now" => "just now",
+        "time.minutes" => "{}m",
+        "time.hours" => "{}h",
+        "time.days" => "{}d",
+        "pane.edit" => "EDIT",
+        "sys.link_opened" => "Opened {}",
+        "sys.link_failed" => "Could not open {}: {}",
+        "log.image" => "  [image: {}]",
+        "sys.download_failed" => "Could not save {}: {}",
+        "sys.download_discarded" => "Discarded {}",
+        "sys.download_too_large" => "Refused {}, it is too large",
+        "sys.download_dropped" => "Dropped {} from {}, too many files are waiting to be saved",
+        "sys.pasting" => "Uploading code block...",
+        "sys.run_disabled" => "/run is disabled, set allow_run in the config to enable it",
+        "sys.keymap_exported" => "Exported keymap to {}",
+        "sys.keymap_export_failed" => "Failed to export keymap to {}: {}",
+        "sys.keymap_imported" => "Imported keymap from {}",
+        "sys.keys_exported" => "Wrote the key bindings to {}",
+        "sys.keys_export_failed" => "Failed to write the key bindings to {}: {}",
+        "sys.usage_keys" => "Usage: /keys export <path>",
+        "sys.logs_exported" => "Exported chat log to {}",
+        "sys.copied" => "Copied {} lines to the clipboard",
+        "sys.pinned" => "Pinned, show or hide the pins with gp",
+        "sys.unpinned" => "Unpinned",
+        "sys.copied_to_buffer" => "Copied {} lines, paste them with p or Ctrl+Y",
+        "sys.link_copied" => "Copied {} to the clipboard",
+        "sys.spelling_off" => "Spell checking is off, set spelling.language in the config",
+        "sys.no_suggestions" => "No suggestions",
+        "sys.draft_restored" => "Restored an unsent draft",
+        "sys.link_not_copied" => "Could not reach the clipboard, the link is {}",
+        "sys.history_locked" => "History stays locked, nothing will be saved this session",
+        "sys.logs_export_failed" => "Failed to export chat log to {}: {}",
+        "sys.keymap_import_failed" => "Failed to import keymap from {}: {}",
+        "sys.keymap_unknown_key" => "{}: unknown key '{}'",
+        "sys.keymap_conflict" => "{}: '{}' is bound to both {} and {}, keeping the first",
+        "sys.keymap_unbound" => "{}: {} is no longer bound",
+        "sys.highlight_invalid" => "Highlight pattern '{}' is not valid: {}",
+        "sys.usage_upload" => "Usage: /send <path>",
+        "sys.room_name_empty" => "A room needs a name",
+        "sys.room_exists" => "There already is a room called {}",
+        "sys.creating_room" => "Creating room {}",
+        "sys.room_created" => "Created room {}",
+        "sys.usage_run" => "Usage: /run <command>",
+        "sys.usage_whois" => "Usage: /whois <username>",
+        "sys.usage_ignore" => "Usage: /ignore <username>",
+        "sys.usage_e2e" => "Usage: /e2e to encrypt this room, /e2e off to stop",
+        "sys.usage_unignore" => "Usage: /unignore <username>",
+        "sys.ignored" => "Ignoring {}",
+        "sys.unignored" => "No longer ignoring {}",
+        "sys.already_ignored" => "Already ignoring {}",
+        "sys.not_ignored" => "{} is not being ignored",
+        "sys.ignore_self" => "You can't ignore yourself",
+        "sys.usage_msg" => "Usage: /msg <username> <message>",
+        "sys.no_direct_send" => "Not sent to {}, the server has no way to send a direct message, only the whole room would see it",
+        "sys.whois" => "{}: {}",
+        "sys.whois_unknown" => "{} has not set a status message",
+        "sys.usage_qr" => "Usage: /qr [invite]",
+        "sys.config_reloaded" => "Config reloaded",
+        "sys.save_config_failed" => "Could not save the config: {}",
+        "sys.reload_failed" => "Could not reload config, {}",
+        "sys.plugin_error" => "Plugin {}: {}",
+        "sys.no_identity" => "No identity to show, set signing in the config to create one",
+        "sys.qr_failed" => "Could not make a QR code: {}",
+        "sys.clipboard_image_failed" => "No image on the clipboard: {}",
+        "sys.usage_shh" => "Usage: /shh <message>",
+        "sys.unknown_command" => "Unknown command: /{}",
+        _ => "",
+    }
+}
+
+/// Loads the catalog for `locale`, English is used for anything missing.
+pub fn init(locale: Option<&str>) {
+    let catalog = match locale {
+        Some(locale) => {
+            let path = config_dir().join("locales").join(format!("{locale}.json"));
+            match fs::read_to_string(&path).map(|s| serde_json::from_str(&s)) {
+                Ok(Ok(catalog)) => catalog,
+                Ok(Err(e)) => {
+                    tracing::error!("Invalid locale file {}: {e}", path.display());
+                    HashMap::new()
+                }
+                Err(e) => {
+                    tracing::error!("Could not read locale file {}: {e}", path.display());
+                    HashMap::new()
+                }
+            }
+        }
+        None => HashMap::new(),
+    };
+    _ = CATALOG.set(catalog);
+}
+
+/// Looks up the text for `key` in the active locale.
+pub fn tr(key: &str) -> String {
+    match CATALOG.get().and_then(|c| c.get(key)) {
+        Some(text) => text.clone(),
+        None => english(key).to_string(),
+    }
+}
+
+/// Like [`tr`], substituting each `{}` in the text with the next of `args`.
+/// Usually called through [`trf!`].
+pub fn format_tr(key: &str, args: &[&dyn Display]) -> String {
+    let text = tr(key);
+    let mut out = String::with_capacity(text.len());
+    let mut args = args.iter();
+    let mut chunks = text.split("{}").peekable();
+    while let Some(chunk) = chunks.next() {
+        out.push_str(chunk);
+        if chunks.peek().is_some() {
+            if let Some(arg) = args.next() {
+                out.push_str(&arg.to_string());
+            }
+        }
+    }
+
+    out
+}
+
+/// Translates a key with format arguments, e.g. `trf!("sys.upload_done", name)`.
+macro_rules! trf {
+    ($key:expr $(, $arg:expr)* $(,)?) => {
+        $crate::i18n::format_tr($key, &[$(&$arg as &dyn std::fmt::Display),*])
+    };
+}
+pub(crate) use trf;
@@ -0,0 +1,71 @@
+/// Spacing accents that terminals without a compose step pass on as they
+/// are when a dead key is pressed, with the combining mark each stands for.
+const DEAD_KEYS: &[(char, char)] = &[
+    ('´', '\u{301}'),
+    ('`', '\u{300}'),
+    ('^', '\u{302}'),
+    ('¨', '\u{308}'),
+    ('~', '\u{303}'),
+    ('¸', '\u{327}'),
+    ('ˇ', '\u{30c}'),
+    ('˚', '\u{30a}'),
+];
+
+/// Letters that have a character of their own with each mark, the rest
+/// are sent as the letter followed by the combining mark.
+const PRECOMPOSED: &[(char, &str, &str)] = &[
+    ('\u{301}', "aeiouyAEIOUY", "áéíóúýÁÉÍÓÚÝ"),
+    ('\u{300}', "aeiouAEIOU", "àèìòùÀÈÌÒÙ"),
+    ('\u{302}', "aeiouAEIOU", "âêîôûÂÊÎÔÛ"),
+    ('\u{308}', "aeiouyAEIOU", "äëïöüÿÄËÏÖÜ"),
+    ('\u{303}', "anoANO", "ãñõÃÑÕ"),
+    ('\u{327}', "cC", "çÇ"),
+    ('\u{30c}', "cszCSZ", "čšžČŠŽ"),
+    ('\u{30a}', "auAU", "åůÅŮ"),
+];
+
+/// Whether `c` starts a composition rather than being typed as it is.
+pub fn is_dead_key(c: char) -> bool {
+    DEAD_KEYS.iter().any(|(dead, _)| *dead == c)
+}
+
+/// What a dead key followed by `next` types: the letter with the accent,
+/// the accent alone after a space, or both as they were if they don't go
+/// together.
+pub fn compose(dead: char, next: char) -> String {
+    let Some(&(_, mark)) = DEAD_KEYS.iter().find(|(d, _)| *d == dead) else {
+        return format!("{dead}{next}");
+    };
+    if next == ' ' {
+        return dead.to_string();
+    }
+    if !next.is_alphabetic() {
+        return format!("{dead}{next}");
+    }
+    let precomposed = PRECOMPOSED
+        .iter()
+        .find(|(m, _, _)| *m == mark)
+        .and_then(|(_, from, to)| {
+            let at = from.chars().position(|c| c == next)?;
+            to.chars().nth(at)
+        });
+    match precomposed {
+        Some(c) => c.to_string(),
+        None => format!("{next}{mark}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dead_keys_put_accents_on_what_follows() {
+        assert!(is_dead_key('´') && !is_dead_key('e'));
+        assert_eq!(compose('´', 'e'), "é");
+        assert_eq!(compose('^', 'O'), "Ô");
+        assert_eq!(compose('´', 'w'), "w\u{301}");
+        assert_eq!(compose('~', ' '), "~");
+        assert_eq!(compose('`', '1'), "`1");
+    }
+}
@@ -0,0 +1,71 @@
+use std::collections::VecDeque;
+
+/// Entries kept per history.
+const HISTORY_LEN: usize = 100;
+
+/// Previously sent input, newest first, browsed like a shell history.
+#[derive(Debug, Default)]
+pub struct InputHistory {
+    entries: VecDeque<String>,
+    /// Index of the entry currently recalled into the buffer.
+    pos: Option<usize>,
+    /// What was in the buffer before browsing started.
+    draft: String,
+}
+
+impl InputHistory {
+    /// A history of `entries`, newest first, as kept from before.
+    pub fn with_entries(entries: Vec<String>) -> Self {
+        Self {
+            entries: entries.into_iter().take(HISTORY_LEN).collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Entries newest first.
+    pub fn entries(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(String::as_str)
+    }
+
+    pub fn push(&mut self, entry: String) {
+        self.pos = None;
+        if entry.is_empty() || self.entries.front() == Some(&entry) {
+            return;
+        }
+        self.entries.push_front(entry);
+        self.entries.truncate(HISTORY_LEN);
+    }
+
+    /// The entry last recalled, if still browsing.
+    pub fn current(&self) -> Option<&str> {
+        self.entries.get(self.pos?).map(String::as_str)
+    }
+
+    /// Steps back to an older entry, `current` is kept to come back to.
+    pub fn older(&mut self, current: &str) -> Option<String> {
+        let next = match self.pos {
+            None => 0,
+            Some(i) => i + 1,
+        };
+        let entry = self.entries.get(next)?.clone();
+        if self.pos.is_none() {
+            self.draft = current.to_string();
+        }
+        self.pos = Some(next);
+        Some(entry)
+    }
+
+    /// Steps forward to a newer entry, ending with the original draft.
+    pub fn newer(&mut self) -> Option<String> {
+        match self.pos? {
+            0 => {
+                self.pos = None;
+                Some(std::mem::take(&mut self.draft))
+            }
+            i => {
+                self.pos = Some(i - 1);
+                self.entries.get(i - 1).cloned()
+            }
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use color_eyre::Result;
+use crossterm::event::{KeyCode, KeyModifiers};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app::{Command, Mode},
+    i18n::tr,
+};
+
+/// A single key binding as it appears in a keymap file.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct KeyBindEntry {
+    pub key: String,
+    pub command: Command,
+}
+
+/// The on-disk keymap format: explicit bindings per mode.
+pub type KeymapFile = HashMap<Mode, Vec<KeyBindEntry>>;
+
+pub fn key_name(code: KeyCode) -> Option<String> {
+    let name = match code {
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::F(n) => format!("F{n}"),
+        KeyCode::Enter => "Enter".to_string(),
+        KeyCode::Esc => "Esc".to_string(),
+        KeyCode::Backspace => "Backspace".to_string(),
+        KeyCode::Delete => "Delete".to_string(),
+        KeyCode::Tab => "Tab".to_string(),
+        KeyCode::Left => "Left".to_string(),
+        KeyCode::Right => "Right".to_string(),
+        KeyCode::Up => "Up".to_string(),
+        KeyCode::Down => "Down".to_string(),
+        KeyCode::Home => "Home".to_string(),
+        KeyCode::End => "End".to_string(),
+        KeyCode::PageUp => "PageUp".to_string(),
+        KeyCode::PageDown => "PageDown".to_string(),
+        _ => return None,
+    };
+
+    Some(name)
+}
+
+pub fn parse_key(name: &str) -> Option<KeyCode> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(KeyCode::Char(c));
+    }
+
+    let code = match name {
+        "Space" => KeyCode::Char(' '),
+        "Enter" => KeyCode::Enter,
+        "Esc" => KeyCode::Esc,
+        "Backspace" => KeyCode::Backspace,
+        "Delete" => KeyCode::Delete,
+        "Tab" => KeyCode::Tab,
+        "Left" => KeyCode::Left,
+        "Right" => KeyCode::Right,
+        "Up" => KeyCode::Up,
+        "Down" => KeyCode::Down,
+        "Home" => KeyCode::Home,
+        "End" => KeyCode::End,
+        "PageUp" => KeyCode::PageUp,
+        "PageDown" => KeyCode::PageDown,
+        f if f.starts_with('F') => KeyCode::F(f[1..].parse().ok()?),
+        _ => return None,
+    };
+
+    Some(code)
+}
+
+/// Names a key with its modifiers, e.g. `Ctrl+a` or `Alt+Left`.
+pub fn chord_name(code: KeyCode, modifiers: KeyModifiers) -> Option<String> {
+    let mut name = String::new();
+    if modifiers.contains(KeyModifiers::CONTROL) {
+        name += "Ctrl+";
+    }
+    if modifiers.contains(KeyModifiers::ALT) {
+        name += "Alt+";
+    }
+    if modifiers.contains(KeyModifiers::SHIFT) {
+        name += "Shift+";
+    }
+
+    Some(name + key_name(code)?.as_str())
+}
+
+/// Parses a key name with optional `Ctrl+`, `Alt+` and `Shift+` prefixes.
+pub fn parse_chord(name: &str) -> Option<(KeyCode, KeyModifiers)> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = name;
+    while let Some((modifier, key)) = rest.split_once('+').filter(|(_, key)| !key.is_empty()) {
+        modifiers |= match modifier {
+            "Ctrl" => KeyModifiers::CONTROL,
+            "Alt" => KeyModifiers::ALT,
+            "Shift" => KeyModifiers::SHIFT,
+            _ => return None,
+        };
+        rest = key;
+    }
+
+    Some((parse_key(rest)?, modifiers))
+}
+
+pub fn write_keymap(path: &Path, keymap: &KeymapFile) -> Result<()> {
+    fs::write(path, serde_json::to_string_pretty(keymap)?)?;
+    Ok(())
+}
+
+pub fn read_keymap(path: &Path) -> Result<KeymapFile> {
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+/// Each mode with its key names and what they do, as a printable sheet.
+/// Markdown gets a table per mode, plain text a padded column of keys.
+pub fn cheat_sheet(modes: &[(Mode, Vec<(String, String)>)], markdown: bool) -> String {
+    let key_width = modes
+        .iter()
+        .flat_map(|(_, binds)| binds.iter().map(|(key, _)| key.chars().count()))
+        .max()
+        .unwrap_or(0);
+    let mut sheet = match markdown {
+        true => format!("# {}\n", tr("keys.title")),
+        false => format!("{}\n", tr("keys.title")),
+    };
+    for (mode, binds) in modes {
+        match markdown {
+            true => {
+                sheet += &format!("\n## {mode}\n\n");
+                sheet += &format!(
+                    "| {} | {} |\n|---|---|\n",
+                    tr("keys.key"),
+                    tr("keys.command")
+                );
+                for (key, command) in binds {
+                    sheet += &format!("| `{}` | {command} |\n", key.replace('|', "\\|"));
+                }
+            }
+            false => {
+                sheet += &format!("\n{mode}\n");
+                for (key, command) in binds {
+                    sheet += &format!("  {key:<key_width$}  {command}\n");
+                }
+            }
+        }
+    }
+
+    sheet
+}
+
+/// Writes the cheat sheet, as Markdown if the path ends in `.md`.
+pub fn write_cheat_sheet(path: &Path, modes: &[(Mode, Vec<(String, String)>)]) -> Result<()> {
+    let markdown = path.extension().is_some_and(|ext| ext == "md");
+    fs::write(path, cheat_sheet(modes, markdown))?;
+    Ok(())
+}
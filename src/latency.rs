@@ -0,0 +1,62 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Round trips averaged over.
+const SAMPLES: usize = 10;
+
+/// How often the link is probed in the background.
+pub const PROBE_EVERY: Duration = Duration::from_secs(30);
+
+/// Round trip times measured from `GetTime` requests to the server's reply.
+#[derive(Debug)]
+pub struct Latency {
+    samples: VecDeque<Duration>,
+    /// When the request in flight was sent, and whether the user asked
+    /// for it and so wants to see the reply.
+    pending: Option<(Instant, bool)>,
+    last_probe: Instant,
+}
+
+impl Default for Latency {
+    fn default() -> Self {
+        Self {
+            samples: VecDeque::new(),
+            pending: None,
+            last_probe: Instant::now(),
+        }
+    }
+}
+
+impl Latency {
+    pub fn start(&mut self, requested: bool) {
+        let now = Instant::now();
+        self.pending = Some((now, requested));
+        self.last_probe = now;
+    }
+
+    /// Whether it's time for a background probe.
+    pub fn due(&self) -> bool {
+        self.pending.is_none() && self.last_probe.elapsed() >= PROBE_EVERY
+    }
+
+    /// Records the round trip for the reply that just arrived, returning
+    /// it and whether the request came from the user.
+    pub fn finish(&mut self) -> Option<(Duration, bool)> {
+        let (sent, requested) = self.pending.take()?;
+        let rtt = sent.elapsed();
+        self.samples.push_back(rtt);
+        if self.samples.len() > SAMPLES {
+            self.samples.pop_front();
+        }
+        Some((rtt, requested))
+    }
+
+    pub fn average(&self) -> Option<Duration> {
+        match self.samples.len() {
+            0 => None,
+            n => Some(self.samples.iter().sum::<Duration>() / n as u32),
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use std::{
+    io::Write,
+    ops::Range,
+    process::{Command, Stdio},
+};
+
+use crossterm::{
+    cursor::{MoveTo, RestorePosition, SavePosition},
+    queue,
+    style::{Attribute, Print, ResetColor, SetAttribute, SetBackgroundColor, SetForegroundColor},
+};
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Modifier},
+    text::Span,
+};
+
+/// Most links offered by the picker, one per digit.
+pub const MAX_PICKER_LINKS: usize = 9;
+
+const SCHEMES: &[&str] = &["https://", "http://", "www."];
+/// Characters a url can't hold without escaping them, and that shells and
+/// openers give a meaning of their own.
+const UNSAFE: &[char] = &['"', '<', '>', '\\', '^', '`', '{', '|', '}'];
+/// Left off the end of a link, they're far more often punctuation.
+const TRAILING: &[char] = &['.', ',', ';', ':', '!', '?', ')', ']', '\'', '"', '>'];
+
+/// Byte ranges of the links in `text`.
+pub fn find(text: &str) -> Vec<Range<usize>> {
+    let mut links = vec![];
+    let mut from = 0;
+    while let Some((start, scheme)) = SCHEMES
+        .iter()
+        .filter_map(|scheme| text[from..].find(scheme).map(|i| (from + i, scheme)))
+        .min()
+    {
+        let len = text[start..]
+            .find(char::is_whitespace)
+            .unwrap_or(text.len() - start);
+        let link = text[start..start + len].trim_end_matches(TRAILING);
+        let at_word_start = !text[..start]
+            .chars()
+            .next_back()
+            .is_some_and(char::is_alphanumeric);
+        if at_word_start && link.len() > scheme.len() {
+            links.push(start..start + link.len());
+        }
+        from = start + len.max(1);
+    }
+
+    links
+}
+
+/// The link as a browser wants it, `www.` ones without a scheme get one.
+pub fn url(link: &str) -> String {
+    match link.starts_with("www.") {
+        true => format!("https://{link}"),
+        false => link.to_string(),
+    }
+}
+
+/// Underlines the links in already styled spans.
+pub fn underline(spans: Vec<Span<'static>>) -> Vec<Span<'static>> {
+    let mut out = vec![];
+    for span in spans {
+        let links = find(&span.content);
+        if links.is_empty() {
+            out.push(span);
+            continue;
+        }
+        let mut plain = 0;
+        for link in links {
+            if plain < link.start {
+                out.push(Span::styled(
+                    span.content[plain..link.start].to_string(),
+                    span.style,
+                ));
+            }
+            out.push(Span::styled(
+                span.content[link.clone()].to_string(),
+                span.style.add_modifier(Modifier::UNDERLINED),
+            ));
+            plain = link.end;
+        }
+        if plain < span.content.len() {
+            out.push(Span::styled(span.content[plain..].to_string(), span.style));
+        }
+    }
+
+    out
+}
+
+/// Whether the terminal is known to understand OSC 8 hyperlinks.
+pub fn detect_hyperlinks() -> bool {
+    let var = |name| std::env::var(name).unwrap_or_default();
+    let (term, program) = (var("TERM"), var("TERM_PROGRAM"));
+    ["VTE_VERSION", "KITTY_WINDOW_ID", "WT_SESSION"]
+        .iter()
+        .any(|name| std::env::var_os(name).is_some())
+        || matches!(program.as_str(), "iTerm.app" | "WezTerm" | "vscode")
+        || ["kitty", "foot", "ghostty"]
+            .iter()
+            .any(|t| term.contains(t))
+}
+
+/// A link on screen, to be written over again as an OSC 8 hyperlink.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hyperlink {
+    pub x: u16,
+    pub y: u16,
+    pub text: String,
+    pub url: String,
+    pub fg: Color,
+    pub bg: Color,
+}
+
+/// Finds the links rendered in `area`. Links wrapped onto the next row are
+/// linked as far as each row goes.
+pub fn find_hyperlinks(buf: &Buffer, area: Rect) -> Vec<Hyperlink> {
+    let mut found = vec![];
+    for y in area.top()..area.bottom() {
+        let row: String = (area.left()..area.right())
+            .map(|x| buf.get(x, y).symbol())
+            .collect();
+        // only ASCII rows line up byte for cell, links are ASCII anyway
+        if row.len() != area.width as usize {
+            continue;
+        }
+        for link in find(&row) {
+            let x = area.x + link.start as u16;
+            let cell = buf.get(x, y);
+            found.push(Hyperlink {
+                x,
+                y,
+                url: url(&row[link.clone()]),
+                text: row[link].to_string(),
+                fg: cell.fg,
+                bg: cell.bg,
+            });
+        }
+    }
+
+    found
+}
+
+/// Writes the links over themselves wrapped in OSC 8 escapes. This happens
+/// after ratatui has drawn, as it would take the escapes for visible text.
+pub fn write_hyperlinks(links: &[Hyperlink], out: &mut impl Write) -> std::io::Result<()> {
+    if links.is_empty() {
+        return Ok(());
+    }
+    queue!(out, SavePosition)?;
+    for link in links {
+        queue!(
+            out,
+            MoveTo(link.x, link.y),
+            SetForegroundColor(link.fg.into()),
+            SetBackgroundColor(link.bg.into()),
+            SetAttribute(Attribute::Underlined),
+            Print(format!(
+                "\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\",
+                link.url, link.text
+            )),
+            SetAttribute(Attribute::Reset),
+            ResetColor,
+        )?;
+    }
+    queue!(out, RestorePosition)?;
+    out.flush()
+}
+
+/// The url to open for `link`, refused if it holds anything a url can't.
+/// Links come from other users' messages.
+fn checked(link: &str) -> std::io::Result<String> {
+    let url = url(link);
+    if url.contains(UNSAFE) || url.contains(char::is_control) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "not a valid url",
+        ));
+    }
+    Ok(url)
+}
+
+/// Opens the link in the system browser, without waiting for it. None of
+/// the openers go through a shell, on Windows it's `ShellExecute`.
+pub fn open(link: &str) -> std::io::Result<()> {
+    let url = checked(link)?;
+    let mut command = match std::env::consts::OS {
+        "macos" => Command::new("open"),
+        "windows" => {
+            let mut command = Command::new("rundll32");
+            command.arg("url.dll,FileProtocolHandler");
+            command
+        }
+        _ => Command::new("xdg-open"),
+    };
+    let mut child = command
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    // reaped off the UI thread, the opener can take a while to hand over
+    std::thread::spawn(move || child.wait());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links_that_could_run_commands_are_refused() {
+        assert!(checked("https://example.com/a|calc").is_err());
+        assert!(checked("https://example.com/\"^&calc").is_err());
+        assert!(checked("www.example.com/<x>").is_err());
+        assert_eq!(
+            checked("www.example.com/?a=1&b=%20").unwrap(),
+            "https://www.example.com/?a=1&b=%20"
+        );
+    }
+}
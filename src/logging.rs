@@ -0,0 +1,146 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+use crate::{cli, user_config::LoggingConfig};
+
+/// Filter directives that take precedence over the configured level, like
+/// `marain_client=debug,tungstenite=trace`.
+const FILTER_VAR: &str = "MARAIN_LOG";
+
+/// The log file, moved aside to `<path>.1` once it reaches `max_size`,
+/// with older ones shuffled along to `.2` and so on up to `keep`.
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    written: u64,
+    max_size: u64,
+    keep: usize,
+}
+
+impl RotatingFile {
+    fn open(path: PathBuf, max_size: u64, keep: usize) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let written = file.metadata()?.len();
+        Ok(Self {
+            path,
+            file,
+            written,
+            max_size,
+            keep,
+        })
+    }
+
+    fn rotated(path: &Path, n: usize) -> PathBuf {
+        let mut name = path.as_os_str().to_owned();
+        name.push(format!(".{n}"));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.keep == 0 {
+            self.file.set_len(0)?;
+        } else {
+            for n in (1..self.keep).rev() {
+                let from = Self::rotated(&self.path, n);
+                if from.exists() {
+                    fs::rename(from, Self::rotated(&self.path, n + 1))?;
+                }
+            }
+            fs::rename(&self.path, Self::rotated(&self.path, 1))?;
+            self.file = File::create(&self.path)?;
+        }
+        self.written = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.written > 0 && self.written + buf.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Starts file logging as configured, the command line taking precedence.
+/// Lines carry the spans they were written in, so what the socket worker
+/// saw can be lined up with what the update loop did about it.
+pub fn start(conf: &LoggingConfig) {
+    let args = cli::args();
+    if args.no_log || !conf.enabled {
+        return;
+    }
+    let path = args
+        .log_file
+        .as_ref()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| conf.path());
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        _ = fs::create_dir_all(dir);
+    }
+    let file = match RotatingFile::open(path, conf.max_size, conf.keep) {
+        Ok(file) => Mutex::new(file),
+        Err(e) => {
+            eprintln!("Could not open the log file: {e}");
+            return;
+        }
+    };
+    let level = args.log_level.as_deref().unwrap_or(&conf.level);
+    let filter = EnvFilter::try_from_env(FILTER_VAR).unwrap_or_else(|_| EnvFilter::new(level));
+    let (text, json) = match conf.json {
+        true => (
+            None,
+            Some(
+                fmt::layer()
+                    .json()
+                    .with_current_span(true)
+                    .with_span_list(true)
+                    .with_writer(file),
+            ),
+        ),
+        false => (Some(fmt::layer().with_ansi(false).with_writer(file)), None),
+    };
+    let started = tracing_subscriber::registry()
+        .with(filter)
+        .with(text)
+        .with(json)
+        .try_init();
+    if let Err(e) = started {
+        eprintln!("Could not start logging: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotates_once_full_keeping_the_newest() {
+        let dir = std::env::temp_dir().join(format!("marain-logs-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("marain.log");
+        let mut file = RotatingFile::open(path.clone(), 10, 2).unwrap();
+        for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+            file.write_all(line.as_bytes()).unwrap();
+        }
+        let read = |path| fs::read_to_string(path).unwrap();
+        assert_eq!(read(path.clone()), "fourth\n");
+        assert_eq!(read(RotatingFile::rotated(&path, 1)), "third\n");
+        assert_eq!(read(RotatingFile::rotated(&path, 2)), "second\n");
+        assert!(!RotatingFile::rotated(&path, 3).exists());
+        _ = fs::remove_dir_all(dir);
+    }
+}
@@ -1,13 +1,80 @@
+mod alerts;
 mod app;
+mod autosave;
+mod bridge;
+mod capabilities;
 mod chat_log;
+mod cli;
+mod completion;
+mod compression;
+mod copy_mode;
+mod credential;
+// the session daemon listens on a unix socket
+#[cfg(unix)]
+mod daemon;
 mod default_keybinds;
+mod directory;
+mod e2e;
+mod edits;
+mod emoji;
+mod endpoint;
+mod ephemeral;
+mod error;
 mod event_bus;
+mod event_log;
+mod file_transfer;
+mod fragments;
+mod graphemes;
+mod graphics;
+mod handlers;
+mod headless;
+mod highlights;
+mod history;
+mod html_export;
+mod i18n;
+mod ime;
+mod input_history;
+mod keymap_file;
+mod latency;
+mod links;
+mod logging;
+mod markdown;
+#[cfg(test)]
+mod mock_transport;
+mod modal;
+mod oneshot;
+mod param_history;
+mod paste;
+mod perf;
+mod plugins;
+mod profiles;
+mod protocol;
+mod proxy;
+mod qr;
+mod rate_limit;
+mod recording;
+mod search;
+mod secrets;
+mod session;
 mod shared_secret;
+mod shell;
+mod signing;
+mod slash_commands;
 mod socket_client;
+mod spelling;
+mod stats;
+mod theme;
+mod toasts;
+mod traffic;
+mod transcripts;
+mod transforms;
+mod transport;
 mod tui_framework;
 mod ui;
 mod update;
 mod user_config;
+mod welcome;
+mod wizard;
 
 use color_eyre::Result;
 use crossterm::{
@@ -15,47 +82,247 @@ use crossterm::{
     ExecutableCommand,
 };
 use ratatui::prelude::{CrosstermBackend, Terminal};
-use std::io::stdout;
+use std::{io::stdout, path::Path};
 
-use crate::app::App;
+use crate::app::{App, Command};
+use crate::autosave::DraftStore;
+use crate::cli::Action;
+use crate::error::ClientError;
 use crate::event_bus::dispatch;
-use crate::user_config::load_config;
+use crate::i18n::{tr, trf};
+use crate::recording::Recorder;
+use crate::session::Session;
+use crate::socket_client::{SocketClient, SocketConf};
+use crate::user_config::{load_config, UserConfig};
+use crate::welcome::Welcome;
 use tui_framework::*;
 
-async fn setup() -> Result<(App, Tui)> {
-    let terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
-    let mut tui = Tui::from_conf(terminal, TuiConf::default()).default_client();
+/// Times the password is asked for before giving up.
+const MAX_PASSWORD_ATTEMPTS: usize = 3;
 
-    let mut app = App::new(load_config().await);
-    let client = shared_secret::handle_login_success(&mut tui, &mut app).await;
+async fn setup(config: UserConfig) -> Result<(App, Tui)> {
+    i18n::init(config.locale.as_deref());
+    capabilities::init(&config.terminal);
+    // asked before the UI takes the terminal over
+    let credential = match credential::required(&config) && !cli::args().attach {
+        true => Some(credential::obtain(&tr("prompt.password"))?),
+        false => None,
+    };
+    let terminal = match cli::args().headless {
+        Some(_) => None,
+        None => Some(Terminal::new(CrosstermBackend::new(stdout()))?),
+    };
+    let recorder = match cli::args().record {
+        Some(ref path) => Some(Recorder::create(path)?),
+        None => None,
+    };
+    let mut tui = Tui::from_conf(terminal, TuiConf::default())
+        .configure_client(SocketConf::for_config(&config).with_credential(credential))
+        .with_rate_limit(&config.rate_limit)
+        .with_recorder(recorder);
 
-    stdout().execute(EnterAlternateScreen)?;
-    enable_raw_mode()?;
+    let mut app = App::new(config);
+    app.server = tui.socket_conf.address();
+    let (client, last_room) = match cli::args().attach {
+        #[cfg(not(unix))]
+        true => return Err(color_eyre::eyre::eyre!(tr("err.no_daemon"))),
+        #[cfg(unix)]
+        true => {
+            let (client, session) = daemon::attach().await?;
+            tui.set_session(Session::new(session.token.clone(), session.shared_secret));
+            app.username = session.username;
+            app.store_token(session.token);
+            (client, None)
+        }
+        false => login(&mut tui, &mut app).await?,
+    };
 
-    tui.enter(client).await?;
+    match tui.terminal {
+        Some(_) => {
+            stdout().execute(EnterAlternateScreen)?;
+            enable_raw_mode()?;
+            tui.enter(client).await?;
+        }
+        None => tui.start(client).await,
+    }
     app.set_send_chan(tui.get_sender());
+    let fresh = !cli::args().attach && cli::args().headless.is_none();
+    if fresh && app.config.welcome_screen && !app.monitor {
+        app.welcome = Some(Welcome::start());
+    }
+    // sync the server clock up front for the clock widget
+    app.handle(Command::GetServerTime);
+    let room = [
+        &cli::args().monitor,
+        &cli::args().room,
+        &last_room,
+        &app.config.default_room,
+    ]
+    .into_iter()
+    .find_map(Clone::clone);
+    if let Some(room) = room {
+        app.handle(Command::MoveRooms(Some(room)));
+    }
+    // let the room know our status message from the config
+    if let Some(bio) = app.config.bio.clone() {
+        app.handle(Command::SetBio(bio));
+    }
+    if app.config.persist_history && !app.monitor && cli::args().headless.is_none() {
+        app.restore_autosave(DraftStore::open());
+    }
 
     Ok((app, tui))
 }
 
-async fn run() -> Result<()> {
-    let (mut app, mut tui) = setup().await?;
+/// Logs in, asking for the password again while the server refuses it.
+async fn login(tui: &mut Tui, app: &mut App) -> Result<(SocketClient, Option<String>)> {
+    let mut attempts = 1;
+    loop {
+        match shared_secret::resume_or_login(tui, app).await {
+            Err(ClientError::Auth(reason))
+                if tui.socket_conf.credential().is_some()
+                    && credential::interactive()
+                    && attempts < MAX_PASSWORD_ATTEMPTS =>
+            {
+                eprintln!("{}", trf!("err.auth", reason));
+                let credential = credential::ask(&tr("prompt.password_retry"))?;
+                tui.socket_conf.set_credential(credential);
+                attempts += 1;
+            }
+            result => return Ok(result?),
+        }
+    }
+}
+
+async fn run(config: UserConfig) -> Result<()> {
+    let (mut app, mut tui) = setup(config).await?;
+
+    if let Some(script) = &cli::args().headless {
+        let steps = headless::read_script(script)?;
+        let failed = headless::run(&mut app, &mut tui, &steps).await?;
+        tui.exit().await?;
+        if failed > 0 {
+            return Err(color_eyre::eyre::eyre!("{failed} expectation(s) failed"));
+        }
+        return Ok(());
+    }
 
     while !app.should_quit {
-        let event = tui.next().await?;
-        dispatch(&mut app, &mut tui, event)?;
+        match tui.next().await? {
+            Event::Reconnect => reconnect(&mut app, &mut tui).await?,
+            event => dispatch(&mut app, &mut tui, event)?,
+        }
+    }
+
+    tui.exit().await?;
+    app.discard_autosave();
+    save_session(&app, &tui);
+
+    Ok(())
+}
+
+/// Plays a `--record`ed session back in the UI without connecting, then
+/// waits for a key so the end can be looked at.
+async fn replay(config: UserConfig, path: &Path) -> Result<()> {
+    i18n::init(config.locale.as_deref());
+    capabilities::init(&config.terminal);
+    let events = recording::read(path)?;
+    let mut tui = Tui::new(Some(Terminal::new(CrosstermBackend::new(stdout()))?));
+    let mut app = App::new(config);
+    app.set_send_chan(tui.get_sender());
+    stdout().execute(EnterAlternateScreen)?;
+    enable_raw_mode()?;
+    let played = recording::replay(&mut app, &mut tui, events).await;
+    if played.is_ok() && !app.should_quit {
+        tokio::task::spawn_blocking(crossterm::event::read).await??;
     }
+    tui.exit().await?;
 
-    tui.exit()?;
+    played
+}
+
+/// Logs in again on a fresh connection, back into the room the app was in.
+async fn reconnect(app: &mut App, tui: &mut Tui) -> Result<()> {
+    let room = app
+        .rooms
+        .iter()
+        .find(|r| r.name == app.room_state.room_name)
+        .map(|r| r.name.clone());
+    match tui.reconnect(app, room).await {
+        Ok(flushed) => {
+            app.reconnected(flushed);
+            app.handle(Command::GetServerTime);
+        }
+        Err(e) => dispatch(app, tui, Event::Error(e))?,
+    }
+    Ok(())
+}
+
+/// Saves the session on a clean exit so the next start can resume it.
+fn save_session(app: &App, tui: &Tui) {
+    if !app.config.resume_session || cli::args().attach {
+        return;
+    }
+    let Some(session) = tui.session() else {
+        return;
+    };
+    let session = shared_secret::SavedSession {
+        username: app.username.clone(),
+        token: session.token.clone(),
+        shared_secret: session.shared_secret(),
+        // only a room the server actually put us in
+        room: app
+            .rooms
+            .iter()
+            .find(|r| r.name == app.room_state.room_name)
+            .map(|r| r.name.clone()),
+    };
+    let server = tui.socket_conf.address();
+    if let Err(e) = shared_secret::save_session(app.config.secret_storage, &server, &session) {
+        tracing::error!("Could not save the session: {e}");
+    }
+}
+
+/// Writes the cheat sheet for `--dump-keys` without starting the UI.
+fn dump_keys(config: &UserConfig, path: &Path) -> Result<()> {
+    i18n::init(config.locale.as_deref());
+    let (keymaps, problems) = app::keymaps_for(config);
+    for problem in problems {
+        eprintln!("{problem}");
+    }
+    keymap_file::write_cheat_sheet(path, &keymaps.sheet())?;
+    println!("{}", trf!("sys.keys_exported", path.display()));
 
     Ok(())
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    _ = log2::open("log.txt").module(true).start();
+    let config = load_config().await;
+    if let Some(path) = &cli::args().dump_keys {
+        return dump_keys(&config.with_host_overrides(), path);
+    }
+    let config = profiles::choose(config)?.with_host_overrides();
+    logging::start(&config.logging);
+    if let Some(path) = &cli::args().replay {
+        return replay(config, path).await;
+    }
 
-    let result = run().await;
+    let result = match (&cli::args().action, cli::args().daemon) {
+        (Some(Action::Send { room, message }), _) => {
+            oneshot::send(config, room.clone(), message.clone()).await
+        }
+        (Some(Action::Pipe { room, batch, rate }), _) => {
+            let batch = std::time::Duration::from_millis(*batch);
+            oneshot::pipe(config, room.clone(), batch, *rate).await
+        }
+        (None, _) if cli::args().json => bridge::run(config).await,
+        #[cfg(unix)]
+        (None, true) => daemon::serve(config).await,
+        #[cfg(not(unix))]
+        (None, true) => Err(color_eyre::eyre::eyre!(tr("err.no_daemon"))),
+        (None, false) => run(config).await,
+    };
 
     result?;
 
@@ -0,0 +1,109 @@
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::Span,
+};
+
+/// Splits a message into spans with its inline formatting applied:
+/// `*bold*`, `_italic_` and `` `code` ``. Markers only count at word
+/// boundaries, so `snake_case` and `2*3*4` are left alone. Nothing inside
+/// code is formatted.
+pub fn spans(text: &str, style: Style) -> Vec<Span<'static>> {
+    let mut spans = vec![];
+    let mut plain = 0;
+    let mut i = 0;
+    while i < text.len() {
+        let Some(marker) = text[i..].chars().next() else {
+            break;
+        };
+        let found = match marker {
+            '*' | '_' | '`' if opens(text, i) => closing(text, i, marker),
+            _ => None,
+        };
+        let Some(end) = found else {
+            i += marker.len_utf8();
+            continue;
+        };
+        if plain < i {
+            spans.push(Span::styled(text[plain..i].to_string(), style));
+        }
+        let inner = &text[i + 1..end];
+        match marker {
+            '*' => spans.extend(self::spans(inner, style.add_modifier(Modifier::BOLD))),
+            '_' => spans.extend(self::spans(inner, style.add_modifier(Modifier::ITALIC))),
+            _ => spans.push(Span::styled(inner.to_string(), style.fg(Color::Cyan))),
+        }
+        i = end + 1;
+        plain = i;
+    }
+    if plain < text.len() || spans.is_empty() {
+        spans.push(Span::styled(text[plain..].to_string(), style));
+    }
+
+    spans
+}
+
+/// A marker opens at the start of a word, with something other than
+/// whitespace straight after it.
+fn opens(text: &str, at: usize) -> bool {
+    let before = text[..at].chars().next_back();
+    let after = text[at + 1..].chars().next();
+    !before.is_some_and(char::is_alphanumeric) && after.is_some_and(|c| !c.is_whitespace())
+}
+
+/// Where the marker opened at `at` closes, at the end of a word.
+fn closing(text: &str, at: usize, marker: char) -> Option<usize> {
+    let start = at + 1;
+    text[start..]
+        .match_indices(marker)
+        .map(|(i, _)| start + i)
+        .find(|&end| {
+            let before = text[..end].chars().next_back();
+            let after = text[end + 1..].chars().next();
+            end > start
+                && !before.is_some_and(char::is_whitespace)
+                && !after.is_some_and(char::is_alphanumeric)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rendered(text: &str) -> Vec<(String, Style)> {
+        spans(text, Style::new())
+            .into_iter()
+            .map(|s| (s.content.to_string(), s.style))
+            .collect()
+    }
+
+    #[test]
+    fn formats_marked_words() {
+        let bold = Style::new().add_modifier(Modifier::BOLD);
+        let code = Style::new().fg(Color::Cyan);
+        assert_eq!(
+            rendered("a *big* `x_y`"),
+            vec![
+                ("a ".into(), Style::new()),
+                ("big".into(), bold),
+                (" ".into(), Style::new()),
+                ("x_y".into(), code),
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_markers_inside_words() {
+        assert_eq!(rendered("snake_case_name").len(), 1);
+        assert_eq!(rendered("2*3*4").len(), 1);
+        assert_eq!(rendered("a * b * c").len(), 1);
+        assert_eq!(rendered("*unclosed").len(), 1);
+    }
+
+    #[test]
+    fn nests_bold_and_italic() {
+        let both = Style::new()
+            .add_modifier(Modifier::BOLD)
+            .add_modifier(Modifier::ITALIC);
+        assert_eq!(rendered("*_both_*"), vec![("both".into(), both)]);
+    }
+}
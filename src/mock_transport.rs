@@ -0,0 +1,260 @@
+use std::collections::VecDeque;
+
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::{error::ClientError, transport::Transport};
+
+/// A transport for tests with a scripted server behind it: `script` is
+/// given each message sent and answers with whatever should come back.
+pub struct MockTransport {
+    script: Box<dyn FnMut(Message) -> Vec<Message> + Send>,
+    replies: VecDeque<Message>,
+    /// Everything sent, in order.
+    pub sent: Vec<Message>,
+    /// Pretend to be a TLS connection.
+    pub secure: bool,
+}
+
+impl MockTransport {
+    pub fn new(script: impl FnMut(Message) -> Vec<Message> + Send + 'static) -> Self {
+        Self {
+            script: Box::new(script),
+            replies: VecDeque::new(),
+            sent: vec![],
+            secure: false,
+        }
+    }
+}
+
+impl Transport for MockTransport {
+    async fn send(&mut self, msg: Message) -> Result<(), ClientError> {
+        self.sent.push(msg.clone());
+        self.replies.extend((self.script)(msg));
+        Ok(())
+    }
+
+    /// Fails rather than waiting when the script has nothing more to say,
+    /// a test would otherwise hang.
+    async fn recv(&mut self) -> Result<Message, ClientError> {
+        self.replies
+            .pop_front()
+            .ok_or_else(|| ClientError::Socket("the script has nothing more to send".into()))
+    }
+
+    fn is_secure(&self) -> bool {
+        self.secure
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use crossterm::event::KeyCode;
+    use marain_api::prelude::{
+        ChatMsg, ClientMsg, ClientMsgBody, Key, ServerMsg, ServerMsgBody, Status, Timestamp,
+    };
+    use tokio::sync::mpsc::{channel, Receiver};
+    use x25519_dalek::PublicKey;
+
+    use super::*;
+    use crate::{
+        app::{App, Command},
+        credential::Credential,
+        session::Session,
+        shared_secret::{self, create_key_pair},
+        tui_framework::Event,
+        update::{reduce, Effect},
+        user_config::UserConfig,
+    };
+
+    /// Just enough of a marain server for one user: logs in, moves rooms
+    /// and echoes chat back.
+    fn fake_server() -> impl FnMut(Message) -> Vec<Message> + Send {
+        let mut session: Option<Session> = None;
+        let reply = |body| ServerMsg {
+            status: Status::Yes,
+            timestamp: Timestamp::from(Utc::now()),
+            body,
+        };
+        move |msg| {
+            let Message::Binary(data) = msg else {
+                return vec![];
+            };
+            let Some(ref mut session) = session else {
+                let Ok(ClientMsg {
+                    body: ClientMsgBody::Login(_, client_key),
+                    ..
+                }) = bincode::deserialize(&data)
+                else {
+                    return vec![];
+                };
+                let (secret, public) = create_key_pair();
+                let shared = secret.diffie_hellman(&PublicKey::from(client_key));
+                let token = String::from("token");
+                session = Some(Session::new(token.clone(), *shared.as_bytes()));
+                let login = reply(ServerMsgBody::LoginSuccess {
+                    token,
+                    public_key: *public.as_bytes(),
+                });
+                return vec![Message::Binary(bincode::serialize(&login).unwrap())];
+            };
+            let opened = session.open(data).unwrap();
+            let Ok(msg) = bincode::deserialize::<ClientMsg>(&opened) else {
+                // the only other thing sent is the password
+                let status = match opened.windows(7).any(|w| w == b"hunter2") {
+                    true => Status::Yes,
+                    false => Status::No("wrong password".into()),
+                };
+                let answer = ServerMsg {
+                    status,
+                    ..reply(ServerMsgBody::Empty)
+                };
+                let sealed = session.seal(bincode::serialize(&answer).unwrap());
+                return vec![Message::Binary(sealed.unwrap())];
+            };
+            let body = match msg.body {
+                ClientMsgBody::Move { target } => ServerMsgBody::RoomData {
+                    logs: vec![],
+                    notifications: vec![],
+                    occupants: vec!["me".into()],
+                    room_name: target,
+                    timestamp: Timestamp::from(Utc::now()),
+                },
+                ClientMsgBody::SendToRoom { contents } => ServerMsgBody::ChatRecv {
+                    direct: false,
+                    chat_msg: ChatMsg {
+                        sender: "me".into(),
+                        timestamp: Timestamp::from(Utc::now()),
+                        content: contents,
+                    },
+                },
+                _ => ServerMsgBody::Empty,
+            };
+            let sealed = session.seal(bincode::serialize(&reply(body)).unwrap());
+            vec![Message::Binary(sealed.unwrap())]
+        }
+    }
+
+    async fn logged_in() -> (MockTransport, Session, Key) {
+        let mut transport = MockTransport::new(fake_server());
+        let (session, server_key) =
+            shared_secret::login_over(&mut transport, "me".into(), None, None, None)
+                .await
+                .expect("login failed");
+        (transport, session, server_key)
+    }
+
+    fn app(session: &Session) -> (App, Receiver<Event>) {
+        let mut app = App::for_test(UserConfig::default());
+        let (sender, receiver) = channel(16);
+        app.set_send_chan(sender);
+        app.store_token(session.token.clone());
+        (app, receiver)
+    }
+
+    /// Sends whatever the app asked to and feeds the server's answers back
+    /// in, as the event loop would.
+    async fn round_trip(
+        app: &mut App,
+        asked: &mut Receiver<Event>,
+        transport: &mut MockTransport,
+        session: &Session,
+    ) {
+        while let Ok(event) = asked.try_recv() {
+            for effect in reduce(app, event) {
+                if let Effect::Send { msg, .. } = effect {
+                    let sealed = session.encode(&msg).unwrap();
+                    transport.send(Message::Binary(sealed)).await.unwrap();
+                }
+            }
+        }
+        while let Ok(Message::Binary(data)) = transport.recv().await {
+            reduce(app, Event::ServerMsg(session.decode(data).unwrap()));
+        }
+    }
+
+    #[tokio::test]
+    async fn logs_in_and_agrees_on_a_secret() {
+        let (mut transport, session, _) = logged_in().await;
+        assert_eq!(session.token, "token");
+        assert_eq!(transport.sent.len(), 1);
+        // the server can only answer if both ends worked out the same key
+        let get_time = session.encode(&session.message(ClientMsgBody::GetTime));
+        transport
+            .send(Message::Binary(get_time.unwrap()))
+            .await
+            .unwrap();
+        let Ok(Message::Binary(reply)) = transport.recv().await else {
+            panic!("no reply");
+        };
+        let reply = session.decode(reply).unwrap();
+        assert_eq!(reply.status, Status::Yes);
+    }
+
+    #[tokio::test]
+    async fn a_refused_login_is_an_error() {
+        let mut transport = MockTransport::new(|_| {
+            let no = ServerMsg {
+                status: Status::No("marain-protocol 7".into()),
+                timestamp: Timestamp::from(Utc::now()),
+                body: ServerMsgBody::Empty,
+            };
+            vec![Message::Binary(bincode::serialize(&no).unwrap())]
+        });
+        let login = shared_secret::login_over(&mut transport, "me".into(), None, None, None);
+        assert!(matches!(login.await, Err(ClientError::ProtocolMismatch(7))));
+    }
+
+    #[tokio::test]
+    async fn the_password_only_goes_out_sealed_and_over_tls() {
+        let (right, wrong) = (Credential::from("hunter2"), Credential::from("hunter3"));
+        let mut plain = MockTransport::new(fake_server());
+        let login = shared_secret::login_over(&mut plain, "me".into(), Some(&right), None, None);
+        assert!(matches!(login.await, Err(ClientError::Login(_))));
+        assert!(plain.sent.is_empty());
+
+        let mut tls = MockTransport::new(fake_server());
+        tls.secure = true;
+        let login = shared_secret::login_over(&mut tls, "me".into(), Some(&right), None, None);
+        assert!(login.await.is_ok());
+        assert_eq!(tls.sent.len(), 2);
+        let sent: Vec<Vec<u8>> = tls.sent.iter().cloned().map(Message::into_data).collect();
+        assert!(!sent
+            .iter()
+            .any(|data| data.windows(7).any(|w| w == b"hunter2")));
+        let login: ClientMsg = bincode::deserialize(&sent[0]).unwrap();
+        assert!(matches!(login.body, ClientMsgBody::Login(ref name, _) if name == "me"));
+
+        let mut refused = MockTransport::new(fake_server());
+        refused.secure = true;
+        let login = shared_secret::login_over(&mut refused, "me".into(), Some(&wrong), None, None);
+        assert!(matches!(login.await, Err(ClientError::Auth(_))));
+
+        // a server with another key than the one pinned gets nothing
+        let mut impostor = MockTransport::new(fake_server());
+        impostor.secure = true;
+        let pinned = Some("not the server's key");
+        let login =
+            shared_secret::login_over(&mut impostor, "me".into(), Some(&right), None, pinned);
+        assert!(matches!(login.await, Err(ClientError::Login(_))));
+        assert_eq!(impostor.sent.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn moves_rooms_and_gets_its_messages_back() {
+        let (mut transport, session, _) = logged_in().await;
+        let (mut app, mut asked) = app(&session);
+
+        app.handle(Command::MoveRooms(Some("rust".into())));
+        round_trip(&mut app, &mut asked, &mut transport, &session).await;
+        assert_eq!(app.room_state.room_name, "rust");
+
+        app.handle(Command::Enter(crate::app::Mode::Insert));
+        for c in "hello".chars() {
+            app.handle(Command::Capture(c));
+        }
+        reduce(&mut app, KeyCode::Enter.into());
+        round_trip(&mut app, &mut asked, &mut transport, &session).await;
+        assert!(app.logs.iter().any(|l| l.from == "me" && l.msg == "hello"));
+    }
+}
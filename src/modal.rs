@@ -0,0 +1,129 @@
+use crossterm::event::KeyCode;
+
+use crate::app::Command;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Choice {
+    Yes,
+    No,
+    /// Close the dialog and carry on as if it was never asked.
+    Cancel,
+}
+
+impl Choice {
+    pub fn label(self) -> &'static str {
+        match self {
+            Choice::Yes => "modal.yes",
+            Choice::No => "modal.no",
+            Choice::Cancel => "modal.cancel",
+        }
+    }
+}
+
+/// A question shown over the rest of the UI that takes every key until
+/// it's answered, for anything that can't be taken back.
+#[derive(Debug, Clone)]
+pub struct Modal {
+    pub title: String,
+    pub body: String,
+    on_yes: Command,
+    /// What No does, if more than closing the dialog.
+    on_no: Option<Command>,
+    cancellable: bool,
+    /// The button Enter presses.
+    pub focus: Choice,
+}
+
+impl Modal {
+    /// A Yes/No question, with No focused so a stray Enter does no harm.
+    pub fn new(title: String, body: String, on_yes: Command) -> Self {
+        Self {
+            title,
+            body,
+            on_yes,
+            on_no: None,
+            cancellable: false,
+            focus: Choice::No,
+        }
+    }
+
+    pub fn with_no(mut self, on_no: Command) -> Self {
+        self.on_no = Some(on_no);
+        self
+    }
+
+    /// Adds a Cancel button, focused, for when No does something too.
+    pub fn with_cancel(mut self) -> Self {
+        self.cancellable = true;
+        self.focus = Choice::Cancel;
+        self
+    }
+
+    pub fn with_focus(mut self, focus: Choice) -> Self {
+        self.focus = focus;
+        self
+    }
+
+    /// The buttons, left to right.
+    pub fn choices(&self) -> &'static [Choice] {
+        match self.cancellable {
+            true => &[Choice::Yes, Choice::No, Choice::Cancel],
+            false => &[Choice::Yes, Choice::No],
+        }
+    }
+
+    /// Moves the focus for arrow keys and Tab, returning the choice once a
+    /// key makes one. y and n answer straight away, Esc backs out.
+    pub fn key(&mut self, key: KeyCode) -> Option<Choice> {
+        let choices = self.choices();
+        let at = choices.iter().position(|&c| c == self.focus).unwrap_or(0);
+        let step = |by: usize| choices[(at + by) % choices.len()];
+        match key {
+            KeyCode::Char('y') | KeyCode::Char('Y') => Some(Choice::Yes),
+            KeyCode::Char('n') | KeyCode::Char('N') => Some(Choice::No),
+            KeyCode::Char('c') | KeyCode::Char('C') if self.cancellable => Some(Choice::Cancel),
+            KeyCode::Esc if self.cancellable => Some(Choice::Cancel),
+            KeyCode::Esc => Some(Choice::No),
+            KeyCode::Enter => Some(self.focus),
+            KeyCode::Right | KeyCode::Tab | KeyCode::Char('l') => {
+                self.focus = step(1);
+                None
+            }
+            KeyCode::Left | KeyCode::BackTab | KeyCode::Char('h') => {
+                self.focus = step(choices.len() - 1);
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// What to run for a choice, if anything.
+    pub fn command(&self, choice: Choice) -> Option<Command> {
+        match choice {
+            Choice::Yes => Some(self.on_yes.clone()),
+            Choice::No => self.on_no.clone(),
+            Choice::Cancel => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enter_presses_the_focused_button() {
+        let mut modal = Modal::new("t".into(), "b".into(), Command::QuitNow)
+            .with_no(Command::Reset)
+            .with_cancel();
+        assert_eq!(modal.key(KeyCode::Enter), Some(Choice::Cancel));
+        assert_eq!(modal.key(KeyCode::Right), None);
+        assert_eq!(modal.focus, Choice::Yes);
+        assert_eq!(modal.key(KeyCode::Left), None);
+        assert_eq!(modal.key(KeyCode::Left), None);
+        assert_eq!(modal.key(KeyCode::Enter), Some(Choice::No));
+        assert_eq!(modal.command(Choice::No), Some(Command::Reset));
+        assert_eq!(modal.key(KeyCode::Char('x')), None);
+        assert_eq!(modal.key(KeyCode::Char('y')), Some(Choice::Yes));
+    }
+}
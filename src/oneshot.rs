@@ -0,0 +1,206 @@
+use std::{
+    io::{stdin, IsTerminal},
+    time::Duration,
+};
+
+use color_eyre::{eyre::eyre, Result};
+use futures::SinkExt;
+use marain_api::prelude::ClientMsgBody;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, BufReader, Lines};
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::{
+    credential,
+    i18n::tr,
+    rate_limit::{RateLimitConfig, TokenBucket},
+    session::Session,
+    shared_secret,
+    signing::{Identity, KnownServers},
+    socket_client::{SocketClient, SocketConf},
+    user_config::{config_dir, UserConfig},
+};
+
+/// How long to wait for the server to close the socket after the message.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often to check whether the rate limit lets the next message go.
+const RATE_POLL: Duration = Duration::from_millis(50);
+
+/// A logged in connection for sending without the UI, in `room` if given.
+pub struct Sender {
+    pub client: SocketClient,
+    pub session: Session,
+    identity: Option<Identity>,
+}
+
+impl Sender {
+    pub async fn connect(config: &UserConfig, room: Option<String>) -> Result<Self> {
+        let conf =
+            SocketConf::for_config(config).with_credential(match credential::required(config) {
+                true => Some(credential::obtain(&tr("prompt.password"))?),
+                false => None,
+            });
+        let (client, session, _) = shared_secret::login(
+            &conf,
+            config.get_username(),
+            &KnownServers::load(&config_dir()),
+        )
+        .await?;
+        let identity = match config.signing {
+            true => Some(Identity::load_or_create(&config.identity_path())?),
+            false => None,
+        };
+        let mut sender = Self {
+            client,
+            session,
+            identity,
+        };
+        if let Some(target) = room {
+            sender.deliver(ClientMsgBody::Move { target }).await?;
+        }
+
+        Ok(sender)
+    }
+
+    pub async fn deliver(&mut self, body: ClientMsgBody) -> Result<()> {
+        let encoded = self.session.encode(&self.session.message(body))?;
+        self.client.out_sink.send(Message::Binary(encoded)).await?;
+        Ok(())
+    }
+
+    async fn send_chat(&mut self, message: String) -> Result<()> {
+        let contents = match self.identity {
+            Some(ref identity) => identity.sign(&message),
+            None => message,
+        };
+        self.deliver(ClientMsgBody::SendToRoom { contents }).await
+    }
+
+    /// Closes the socket, waiting a little for the server to close its end.
+    /// That it does means it has had everything sent before.
+    pub async fn close(mut self) -> Result<()> {
+        self.client.out_sink.send(Message::Close(None)).await?;
+        let drain = async { while self.client.next().await.is_ok() {} };
+        if tokio::time::timeout(CLOSE_TIMEOUT, drain).await.is_err() {
+            tracing::error!("The server did not close the socket in time");
+        }
+        Ok(())
+    }
+}
+
+/// Logs in, sends `message` to `room`, or the room the server puts new
+/// users in, and logs out again, all without the UI.
+pub async fn send(config: UserConfig, room: Option<String>, message: String) -> Result<()> {
+    let mut sender = Sender::connect(&config, room).await?;
+    sender.send_chat(message).await?;
+    sender.close().await
+}
+
+/// Sends each line read from stdin as a chat message until it ends. Lines
+/// coming within `batch` of the first go together in one message, and
+/// `per_sec` overrides the configured rate limit.
+pub async fn pipe(
+    config: UserConfig,
+    room: Option<String>,
+    batch: Duration,
+    per_sec: Option<f64>,
+) -> Result<()> {
+    if stdin().is_terminal() {
+        return Err(eyre!(tr("err.pipe_terminal")));
+    }
+    let bucket = TokenBucket::new(&RateLimitConfig {
+        per_sec: per_sec.unwrap_or(config.rate_limit.per_sec),
+        ..config.rate_limit.clone()
+    });
+    let mut sender = Sender::connect(&config, room).await?;
+    let (batches, mut ready) = tokio::sync::mpsc::channel(1);
+    tokio::spawn(read_batches(batch, bucket, batches));
+    loop {
+        tokio::select! {
+            message = ready.recv() => match message {
+                Some(message) => sender.send_chat(message).await?,
+                None => break,
+            },
+            // nothing here reads what the server sends, but it mustn't back up
+            msg = sender.client.next() => {
+                if let Ok(Message::Close(_)) | Err(_) = msg {
+                    return Err(eyre!(tr("err.pipe_closed")));
+                }
+            }
+        }
+    }
+
+    sender.close().await
+}
+
+/// Reads stdin into messages and hands them over as the rate limit lets
+/// them go, until stdin ends.
+async fn read_batches(
+    batch: Duration,
+    mut bucket: TokenBucket,
+    batches: tokio::sync::mpsc::Sender<String>,
+) {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    loop {
+        let message = match next_batch(&mut lines, batch).await {
+            Ok(Some(message)) => message,
+            Ok(None) => break,
+            Err(e) => {
+                tracing::error!("Could not read stdin: {e}");
+                break;
+            }
+        };
+        while !bucket.try_take() {
+            tokio::time::sleep(RATE_POLL).await;
+        }
+        if batches.send(message).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// The next non-empty line, with any that follow within `batch`. `None`
+/// once stdin has ended.
+async fn next_batch(
+    lines: &mut Lines<impl AsyncBufRead + Unpin>,
+    batch: Duration,
+) -> std::io::Result<Option<String>> {
+    let first = loop {
+        match lines.next_line().await? {
+            Some(line) if line.trim().is_empty() => continue,
+            Some(line) => break line,
+            None => return Ok(None),
+        }
+    };
+    let mut message = first;
+    if batch.is_zero() {
+        return Ok(Some(message));
+    }
+    let deadline = tokio::time::Instant::now() + batch;
+    while let Ok(Ok(Some(line))) = tokio::time::timeout_at(deadline, lines.next_line()).await {
+        message.push('\n');
+        message.push_str(&line);
+    }
+
+    Ok(Some(message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn batches_skip_blank_lines_and_stop_at_the_end() {
+        let mut lines = BufReader::new(&b"one\n\n  \ntwo\nthree\n"[..]).lines();
+        let batch = Duration::from_millis(50);
+        assert_eq!(
+            next_batch(&mut lines, Duration::ZERO).await.unwrap(),
+            Some("one".into())
+        );
+        assert_eq!(
+            next_batch(&mut lines, batch).await.unwrap(),
+            Some("two\nthree".into())
+        );
+        assert_eq!(next_batch(&mut lines, batch).await.unwrap(), None);
+    }
+}
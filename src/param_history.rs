@@ -0,0 +1,96 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use crate::{app::Command, input_history::InputHistory, user_config::data_dir};
+
+/// Parameters typed for staged commands, with a history per command so
+/// moving rooms offers rooms and exporting offers paths.
+#[derive(Debug, Default)]
+pub struct ParamHistory {
+    histories: HashMap<String, InputHistory>,
+    /// Where they're kept between sessions, if they are.
+    path: Option<PathBuf>,
+}
+
+/// Staged commands are told apart by their variant, whatever they hold.
+fn key(cmd: &Command) -> String {
+    let debug = format!("{cmd:?}");
+    debug.split('(').next().unwrap_or(&debug).to_string()
+}
+
+impl ParamHistory {
+    /// What earlier sessions typed, from `<data dir>/params.json`.
+    pub fn load() -> Self {
+        Self::load_from(data_dir().join("params.json"))
+    }
+
+    fn load_from(path: PathBuf) -> Self {
+        let saved: HashMap<String, Vec<String>> = fs::read_to_string(&path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        Self {
+            histories: saved
+                .into_iter()
+                .map(|(cmd, entries)| (cmd, InputHistory::with_entries(entries)))
+                .collect(),
+            path: Some(path),
+        }
+    }
+
+    pub fn for_command(&mut self, cmd: &Command) -> &mut InputHistory {
+        self.histories.entry(key(cmd)).or_default()
+    }
+
+    /// Parameters given to `cmd` before, newest first.
+    pub fn entries(&self, cmd: &Command) -> Vec<String> {
+        self.histories
+            .get(&key(cmd))
+            .map(|history| history.entries().map(String::from).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn push(&mut self, cmd: &Command, params: String) {
+        self.for_command(cmd).push(params);
+        if let Err(e) = self.save() {
+            tracing::error!("Could not save command parameters: {e}");
+        }
+    }
+
+    fn save(&self) -> std::io::Result<()> {
+        let Some(ref path) = self.path else {
+            return Ok(());
+        };
+        let saved: HashMap<&String, Vec<&str>> = self
+            .histories
+            .iter()
+            .map(|(cmd, history)| (cmd, history.entries().collect()))
+            .collect();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(&saved)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_command_keeps_its_own_parameters_between_sessions() {
+        let path = std::env::temp_dir().join(format!("marain-params-{}.json", std::process::id()));
+        let mut params = ParamHistory::load_from(path.clone());
+        params.push(&Command::MoveRooms(None), "lobby".into());
+        params.push(&Command::MoveRooms(None), "rust".into());
+        params.push(&Command::ExportLogs(None), "log.md".into());
+
+        let loaded = ParamHistory::load_from(path.clone());
+        _ = fs::remove_file(path);
+        assert_eq!(
+            loaded.entries(&Command::MoveRooms(Some("x".into()))),
+            vec!["rust", "lobby"]
+        );
+        assert_eq!(loaded.entries(&Command::ExportLogs(None)), vec!["log.md"]);
+        assert!(loaded.entries(&Command::Upload(None)).is_empty());
+    }
+}
@@ -0,0 +1,36 @@
+use tokio::sync::mpsc::Sender;
+
+use crate::{tui_framework::Event, user_config::PasteConfig};
+
+/// Large code blocks get pasted instead of sent inline.
+pub fn should_paste(conf: &PasteConfig, contents: &str) -> bool {
+    conf.url.is_some() && contents.contains("```") && contents.len() > conf.threshold
+}
+
+/// Uploads `contents` to the configured paste service in the background.
+/// The service is expected to accept the raw text as the POST body and
+/// respond with the URL of the paste, like paste.rs does.
+pub fn spawn_paste(conf: &PasteConfig, contents: String, sink: Sender<Event>) {
+    let Some(url) = conf.url.clone() else {
+        return;
+    };
+
+    tokio::spawn(async move {
+        let result = match reqwest::Client::new()
+            .post(&url)
+            .body(contents)
+            .send()
+            .await
+        {
+            Ok(resp) if resp.status().is_success() => match resp.text().await {
+                Ok(link) => Ok(link.trim().to_string()),
+                Err(e) => Err(format!("Could not read paste service response: {e}")),
+            },
+            Ok(resp) => Err(format!("Paste service returned {}", resp.status())),
+            Err(e) => Err(format!("Could not reach paste service: {e}")),
+        };
+        if let Err(e) = sink.send(Event::PasteResult(result)).await {
+            tracing::error!("Failed to report paste result: {e}");
+        }
+    });
+}
@@ -0,0 +1,84 @@
+use std::time::{Duration, Instant};
+
+use crate::{i18n::trf, traffic};
+
+/// Weight of the newest sample in the running averages.
+const SMOOTHING: f64 = 1.0 / 16.0;
+
+/// How the event loop and the drawing are keeping up, for the performance
+/// line under the debug pane.
+#[derive(Debug, Default)]
+pub struct Perf {
+    /// Running average of how long a draw takes.
+    render: Duration,
+    render_max: Duration,
+    /// Render ticks that drew nothing because nothing had changed.
+    pub skipped: u64,
+    last_tick: Option<Instant>,
+    /// Running average of the time between ticks.
+    interval: Duration,
+    /// Running average of how far a tick lands from the average interval.
+    jitter: Duration,
+}
+
+impl Perf {
+    pub fn rendered(&mut self, took: Duration) {
+        self.render = smooth(self.render, took);
+        self.render_max = self.render_max.max(took);
+    }
+
+    pub fn tick(&mut self) {
+        self.tick_at(Instant::now());
+    }
+
+    fn tick_at(&mut self, now: Instant) {
+        if let Some(last) = self.last_tick.replace(now) {
+            let interval = now.duration_since(last);
+            if self.interval.is_zero() {
+                self.interval = interval;
+            }
+            let off = match interval > self.interval {
+                true => interval - self.interval,
+                false => self.interval - interval,
+            };
+            self.jitter = smooth(self.jitter, off);
+            self.interval = smooth(self.interval, interval);
+        }
+    }
+
+    /// The HUD line, with the number of logs held and roughly how much
+    /// memory they take.
+    pub fn summary(&self, logs: usize, bytes: usize) -> String {
+        let ms = |d: Duration| format!("{:.2}ms", d.as_secs_f64() * 1000.0);
+        trf!(
+            "perf.summary",
+            ms(self.render),
+            ms(self.render_max),
+            self.skipped,
+            ms(self.jitter),
+            logs,
+            traffic::size(bytes as u64)
+        )
+    }
+}
+
+fn smooth(average: Duration, sample: Duration) -> Duration {
+    average.mul_f64(1.0 - SMOOTHING) + sample.mul_f64(SMOOTHING)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steady_ticks_have_no_jitter() {
+        let mut perf = Perf::default();
+        let start = Instant::now();
+        for n in 0..10 {
+            perf.tick_at(start + Duration::from_millis(16 * n));
+        }
+        assert!(perf.jitter.is_zero());
+        perf.tick_at(start + Duration::from_millis(16 * 9 + 100));
+        assert!(perf.jitter > Duration::ZERO);
+    }
+}
@@ -0,0 +1,176 @@
+use std::{
+    fmt::Debug,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use rhai::{CallFnOptions, Dynamic, Engine, FuncArgs, Scope, AST};
+
+use crate::i18n::trf;
+
+/// Script operations per hook call before it is stopped, so a runaway
+/// script can't hang the UI.
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// Something a script asked the client to do, carried out once the hook
+/// has returned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Send a message to the current room.
+    Send(String),
+    /// Show a line in the log, to this user only.
+    Echo(String),
+}
+
+struct Script {
+    name: String,
+    ast: AST,
+    scope: Scope<'static>,
+}
+
+/// Rhai scripts loaded from the `plugins` directory next to the config
+/// file. A script can define any of these hooks:
+///
+/// - `on_message_received(sender, text)`, return `false` to hide the message
+/// - `on_before_send(text)`, return a string to send instead or `false` to
+///   send nothing
+/// - `on_command(name, args)`, return `true` if it handled `/name args`
+///
+/// and call `send(text)` to post to the room or `echo(text)` to show a line
+/// only to this user. Variables set at the top level of a script keep their
+/// values between hook calls.
+pub struct Plugins {
+    engine: Engine,
+    scripts: Vec<Script>,
+    actions: Arc<Mutex<Vec<Action>>>,
+}
+
+impl Debug for Plugins {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names: Vec<&str> = self.scripts.iter().map(|s| s.name.as_str()).collect();
+        f.debug_struct("Plugins").field("scripts", &names).finish()
+    }
+}
+
+impl Plugins {
+    /// Loads every `.rhai` file in `dir` in name order, running each one's
+    /// top level once. Scripts that don't compile or run are left out and
+    /// reported.
+    pub fn load(dir: &Path) -> (Self, Vec<String>) {
+        let actions = Arc::new(Mutex::new(vec![]));
+        let mut plugins = Self {
+            engine: engine(&actions),
+            scripts: vec![],
+            actions,
+        };
+        let mut problems = vec![];
+        let mut paths: Vec<_> = match std::fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|e| e.ok().map(|e| e.path()))
+                .filter(|p| p.extension().is_some_and(|ext| ext == "rhai"))
+                .collect(),
+            Err(_) => return (plugins, problems),
+        };
+        paths.sort();
+        for path in paths {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let mut scope = Scope::new();
+            let loaded = plugins.engine.compile_file(path.clone()).and_then(|ast| {
+                plugins.engine.run_ast_with_scope(&mut scope, &ast)?;
+                Ok(ast)
+            });
+            match loaded {
+                Ok(ast) => plugins.scripts.push(Script {
+                    name: name.to_string(),
+                    ast,
+                    scope,
+                }),
+                Err(e) => problems.push(trf!("sys.plugin_error", name, e)),
+            }
+        }
+
+        (plugins, problems)
+    }
+
+    /// Whether an incoming message should be shown. Every script sees it,
+    /// even once one has hidden it.
+    pub fn message_received(&mut self, sender: &str, text: &str) -> bool {
+        let args = (sender.to_string(), text.to_string());
+        let mut shown = true;
+        for i in 0..self.scripts.len() {
+            if let Some(result) = self.call(i, "on_message_received", args.clone()) {
+                shown &= result.as_bool() != Ok(false);
+            }
+        }
+
+        shown
+    }
+
+    /// The message to send in place of `text`, after every script has had
+    /// its turn, or `None` if one of them cancelled it.
+    pub fn before_send(&mut self, text: String) -> Option<String> {
+        let mut text = text;
+        for i in 0..self.scripts.len() {
+            match self.call(i, "on_before_send", (text.clone(),)) {
+                Some(result) if result.as_bool() == Ok(false) => return None,
+                Some(result) if result.is_string() => text = result.cast(),
+                _ => {}
+            }
+        }
+
+        Some(text)
+    }
+
+    /// Offers a slash command to the scripts, returns true once one has
+    /// handled it.
+    pub fn command(&mut self, name: &str, args: &str) -> bool {
+        let args = (name.to_string(), args.to_string());
+        (0..self.scripts.len()).any(|i| {
+            self.call(i, "on_command", args.clone())
+                .is_some_and(|result| result.as_bool() == Ok(true))
+        })
+    }
+
+    pub fn take_actions(&mut self) -> Vec<Action> {
+        std::mem::take(&mut *self.actions.lock().unwrap())
+    }
+
+    /// Calls the hook if the script defines it. Errors are echoed, the
+    /// script stays loaded for the next call.
+    fn call(&mut self, i: usize, hook: &str, args: impl FuncArgs) -> Option<Dynamic> {
+        let script = &mut self.scripts[i];
+        script.ast.iter_functions().find(|f| f.name == hook)?;
+        let options = CallFnOptions::new().eval_ast(false);
+        match self
+            .engine
+            .call_fn_with_options(options, &mut script.scope, &script.ast, hook, args)
+        {
+            Ok(result) => Some(result),
+            Err(e) => {
+                let problem = trf!("sys.plugin_error", script.name, e);
+                self.actions.lock().unwrap().push(Action::Echo(problem));
+                None
+            }
+        }
+    }
+}
+
+/// An engine with the functions scripts use to act on the client. `print`
+/// would write over the UI, so it echoes into the log instead.
+fn engine(actions: &Arc<Mutex<Vec<Action>>>) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    let queue = actions.clone();
+    engine.register_fn("send", move |text: &str| {
+        queue.lock().unwrap().push(Action::Send(text.to_string()))
+    });
+    let queue = actions.clone();
+    engine.register_fn("echo", move |text: &str| {
+        queue.lock().unwrap().push(Action::Echo(text.to_string()))
+    });
+    let queue = actions.clone();
+    engine.on_print(move |text| queue.lock().unwrap().push(Action::Echo(text.to_string())));
+    engine.on_debug(|text, _, _| tracing::debug!("plugin: {text}"));
+
+    engine
+}
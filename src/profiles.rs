@@ -0,0 +1,95 @@
+use std::io::{self, stdout, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cli,
+    i18n::{tr, trf},
+    user_config::{ServerConfig, UserConfig},
+    wizard,
+};
+
+/// One of several identities to log in as, each with its own name, server
+/// and signing key.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Profile {
+    pub name: String,
+    pub username: Option<String>,
+    pub server: ServerConfig,
+    /// File of the signing key, created if it isn't there.
+    pub identity_key: Option<String>,
+}
+
+/// The config with the chosen profile in place of the top level username,
+/// server and key. `--profile` picks one by name, otherwise with more than
+/// one there is a list to choose from on the plain terminal.
+pub fn choose(config: UserConfig) -> io::Result<UserConfig> {
+    let profile = match (&cli::args().profile, config.profiles.as_slice()) {
+        (Some(name), profiles) => profiles
+            .iter()
+            .find(|p| &p.name == name)
+            .cloned()
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, trf!("err.unknown_profile", name))
+            })?,
+        (None, []) => return Ok(config),
+        (None, [only]) => only.clone(),
+        (None, profiles) if cli::args().attach || !wizard::interactive() => profiles[0].clone(),
+        (None, profiles) => pick(profiles)?,
+    };
+
+    Ok(config.with_profile(profile))
+}
+
+fn pick(profiles: &[Profile]) -> io::Result<Profile> {
+    let mut out = stdout();
+    writeln!(out, "{}", tr("profile.heading"))?;
+    for (n, profile) in profiles.iter().enumerate() {
+        let username = profile.username.as_deref().unwrap_or("?");
+        let host = profile.server.host.as_deref().unwrap_or("localhost");
+        writeln!(out, "  {}) {}  {username}@{host}", n + 1, profile.name)?;
+    }
+    loop {
+        let answer = wizard::ask(&tr("profile.prompt"), "1")?;
+        let chosen = match answer.parse::<usize>() {
+            Ok(n) => n.checked_sub(1).and_then(|i| profiles.get(i)),
+            Err(_) => profiles.iter().find(|p| p.name == answer),
+        };
+        match chosen {
+            Some(profile) => return Ok(profile.clone()),
+            None => writeln!(out, "{}", tr("profile.unknown"))?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_profile_goes_in_place_of_the_identity() {
+        let config = UserConfig::default()
+            .with_username(Some("me".into()))
+            .with_server(ServerConfig {
+                host: Some("home.example.com".into()),
+                port: None,
+            });
+        let work = config.clone().with_profile(Profile {
+            name: "work".into(),
+            username: Some("boss".into()),
+            server: ServerConfig {
+                host: Some("work.example.com".into()),
+                port: Some("8443".into()),
+            },
+            identity_key: Some("/keys/work.key".into()),
+        });
+        assert_eq!(work.get_username(), "boss");
+        assert_eq!(work.server.host.as_deref(), Some("work.example.com"));
+        assert_eq!(work.identity_path().to_str(), Some("/keys/work.key"));
+
+        let nameless = config.with_profile(Profile::default());
+        assert_eq!(nameless.get_username(), "me");
+        assert_eq!(nameless.server.host.as_deref(), Some("home.example.com"));
+    }
+}
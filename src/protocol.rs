@@ -0,0 +1,54 @@
+/// Version of the `marain_api` messages this client speaks. Goes up with
+/// every change to them that an older peer couldn't read.
+pub const VERSION: u32 = 1;
+
+/// What the version line of the login, and a server's refusal over a
+/// version mismatch, start with.
+const TAG: &str = "marain-protocol ";
+
+/// Optional features the client can take part in, offered after the
+/// version to servers that are known to read it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Capabilities {
+    pub deflate: bool,
+}
+
+/// The login name with the version and capabilities on a line of their
+/// own at the end, if they're to be announced at all.
+pub fn login_name(name: String, capabilities: Option<Capabilities>) -> String {
+    let Some(capabilities) = capabilities else {
+        return name;
+    };
+    let mut line = format!("{TAG}{VERSION}");
+    if capabilities.deflate {
+        line += " deflate";
+    }
+    format!("{name}\n{line}")
+}
+
+/// The server's version, if `reason` is it refusing a login over the
+/// versions not matching. Such servers answer `marain-protocol <version>`.
+pub fn mismatch(reason: &str) -> Option<u32> {
+    reason.trim().strip_prefix(TAG)?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn announces_only_when_asked_to() {
+        assert_eq!(login_name("me".into(), None), "me");
+        assert_eq!(
+            login_name("me".into(), Some(Capabilities { deflate: true })),
+            "me\nmarain-protocol 1 deflate"
+        );
+    }
+
+    #[test]
+    fn reads_the_version_from_a_refusal() {
+        assert_eq!(mismatch("marain-protocol 3"), Some(3));
+        assert_eq!(mismatch("wrong password"), None);
+        assert_eq!(mismatch("marain-protocol soon"), None);
+    }
+}
@@ -0,0 +1,169 @@
+use std::net::IpAddr;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+use url::Url;
+
+use crate::{error::ClientError, user_config::ProxyConfig};
+
+/// Longest HTTP proxy response header accepted.
+const MAX_HTTP_HEADER: usize = 8192;
+
+fn proxy_err(e: impl std::fmt::Display) -> ClientError {
+    ClientError::Connect(format!("proxy: {e}"))
+}
+
+/// Opens a TCP stream to `host:port` tunnelled through the configured
+/// `socks5://` or `http://` proxy, ready for the websocket handshake.
+pub async fn connect(conf: &ProxyConfig, host: &str, port: u16) -> Result<TcpStream, ClientError> {
+    let url = conf.url.as_deref().ok_or_else(|| proxy_err("no url set"))?;
+    let url = Url::parse(url).map_err(proxy_err)?;
+    let proxy_host = url.host_str().ok_or_else(|| proxy_err("no host in url"))?;
+    let proxy_port = url
+        .port_or_known_default()
+        .or(match url.scheme() {
+            "socks5" | "socks5h" => Some(1080),
+            _ => None,
+        })
+        .ok_or_else(|| proxy_err("no port in url"))?;
+    let credentials = credentials(conf, &url);
+
+    let mut stream = TcpStream::connect((proxy_host.trim_matches(['[', ']']), proxy_port))
+        .await
+        .map_err(proxy_err)?;
+    match url.scheme() {
+        "socks5" | "socks5h" => socks5(&mut stream, host, port, credentials).await?,
+        "http" => http_connect(&mut stream, host, port, credentials).await?,
+        scheme => return Err(proxy_err(format!("unsupported scheme {scheme}"))),
+    }
+
+    Ok(stream)
+}
+
+/// Credentials from the config, or failing that from the url.
+fn credentials(conf: &ProxyConfig, url: &Url) -> Option<(String, String)> {
+    match (&conf.username, &conf.password) {
+        (Some(user), pass) => Some((user.clone(), pass.clone().unwrap_or_default())),
+        (None, _) if !url.username().is_empty() => Some((
+            url.username().to_string(),
+            url.password().unwrap_or_default().to_string(),
+        )),
+        _ => None,
+    }
+}
+
+/// SOCKS5 handshake as in RFC 1928, with username/password auth from RFC 1929.
+async fn socks5(
+    stream: &mut TcpStream,
+    host: &str,
+    port: u16,
+    credentials: Option<(String, String)>,
+) -> Result<(), ClientError> {
+    let methods: &[u8] = match credentials {
+        Some(_) => &[5, 2, 0x00, 0x02],
+        None => &[5, 1, 0x00],
+    };
+    stream.write_all(methods).await.map_err(proxy_err)?;
+    let mut reply = [0u8; 2];
+    stream.read_exact(&mut reply).await.map_err(proxy_err)?;
+    match (reply[1], credentials) {
+        (0x00, _) => {}
+        (0x02, Some((user, pass))) => {
+            let (user, pass) = (user.as_bytes(), pass.as_bytes());
+            if user.len() > 255 || pass.len() > 255 {
+                return Err(proxy_err("credentials too long"));
+            }
+            let mut auth = vec![1, user.len() as u8];
+            auth.extend_from_slice(user);
+            auth.push(pass.len() as u8);
+            auth.extend_from_slice(pass);
+            stream.write_all(&auth).await.map_err(proxy_err)?;
+            stream.read_exact(&mut reply).await.map_err(proxy_err)?;
+            if reply[1] != 0 {
+                return Err(proxy_err("authentication failed"));
+            }
+        }
+        _ => return Err(proxy_err("no acceptable authentication method")),
+    }
+
+    let mut request = vec![5, 1, 0];
+    match host.trim_matches(['[', ']']).parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            request.push(1);
+            request.extend_from_slice(&ip.octets());
+        }
+        Ok(IpAddr::V6(ip)) => {
+            request.push(4);
+            request.extend_from_slice(&ip.octets());
+        }
+        // left to the proxy to resolve
+        Err(_) => {
+            let name = host.as_bytes();
+            if name.len() > 255 {
+                return Err(proxy_err("host name too long"));
+            }
+            request.extend_from_slice(&[3, name.len() as u8]);
+            request.extend_from_slice(name);
+        }
+    }
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request).await.map_err(proxy_err)?;
+
+    let mut head = [0u8; 4];
+    stream.read_exact(&mut head).await.map_err(proxy_err)?;
+    if head[1] != 0 {
+        return Err(proxy_err(format!("connect refused with code {}", head[1])));
+    }
+    // skip the bound address and port, the stream is ready after them
+    let addr_len = match head[3] {
+        1 => 4,
+        4 => 16,
+        3 => stream.read_u8().await.map_err(proxy_err)? as usize,
+        atyp => return Err(proxy_err(format!("unknown address type {atyp}"))),
+    };
+    let mut bound = vec![0u8; addr_len + 2];
+    stream.read_exact(&mut bound).await.map_err(proxy_err)?;
+
+    Ok(())
+}
+
+/// Opens a tunnel with an HTTP `CONNECT` request.
+async fn http_connect(
+    stream: &mut TcpStream,
+    host: &str,
+    port: u16,
+    credentials: Option<(String, String)>,
+) -> Result<(), ClientError> {
+    let target = match host.parse::<IpAddr>() {
+        Ok(IpAddr::V6(ip)) => format!("[{ip}]:{port}"),
+        _ => format!("{host}:{port}"),
+    };
+    let mut request = format!("CONNECT {target} HTTP/1.1\r\nHost: {target}\r\n");
+    if let Some((user, pass)) = credentials {
+        let token = STANDARD.encode(format!("{user}:{pass}"));
+        request += &format!("Proxy-Authorization: Basic {token}\r\n");
+    }
+    request += "\r\n";
+    stream
+        .write_all(request.as_bytes())
+        .await
+        .map_err(proxy_err)?;
+
+    // read a byte at a time so nothing past the header is consumed
+    let mut response = vec![];
+    while !response.ends_with(b"\r\n\r\n") {
+        if response.len() >= MAX_HTTP_HEADER {
+            return Err(proxy_err("response header too long"));
+        }
+        response.push(stream.read_u8().await.map_err(proxy_err)?);
+    }
+    let response = String::from_utf8_lossy(&response);
+    let status = response.lines().next().unwrap_or_default();
+    match status.split_whitespace().nth(1) {
+        Some(code) if code.starts_with('2') => Ok(()),
+        _ => Err(proxy_err(status.to_string())),
+    }
+}
@@ -0,0 +1,53 @@
+use qrcode::{render::unicode::Dense1x2, QrCode};
+
+/// Renders `data` as a QR code made of unicode half blocks, two modules per
+/// character cell so it stays roughly square in a terminal.
+pub fn render(data: &str) -> Result<String, String> {
+    let code = QrCode::new(data.as_bytes()).map_err(|e| e.to_string())?;
+    Ok(code
+        .render::<Dense1x2>()
+        .dark_color(Dense1x2::Light)
+        .light_color(Dense1x2::Dark)
+        .quiet_zone(true)
+        .build())
+}
+
+/// Link for joining a room on the server at `address`.
+pub fn invite_url(address: &str, room: &str) -> String {
+    format!("marain://{address}/{room}")
+}
+
+/// The `host:port` and room of a link made by [`invite_url`], none if
+/// `url` isn't one. Links to just the server have no room.
+pub fn parse_invite(url: &str) -> Option<(String, Option<String>)> {
+    let rest = url.strip_prefix("marain://")?;
+    let (address, room) = match rest.split_once('/') {
+        Some((address, room)) => (address, room.trim_end_matches('/')),
+        None => (rest, ""),
+    };
+    if address.is_empty() {
+        return None;
+    }
+    let room = Some(room.to_string()).filter(|room| !room.is_empty());
+    Some((address.to_string(), room))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_back_the_invites_it_makes() {
+        let url = invite_url("chat.example.com:1337", "rust");
+        assert_eq!(
+            parse_invite(&url),
+            Some(("chat.example.com:1337".into(), Some("rust".into())))
+        );
+        assert_eq!(
+            parse_invite("marain://[::1]:1337/"),
+            Some(("[::1]:1337".into(), None))
+        );
+        assert_eq!(parse_invite("marain:///rust"), None);
+        assert_eq!(parse_invite("wss://chat.example.com/rust"), None);
+    }
+}
@@ -0,0 +1,94 @@
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+/// Which message goes when the queue of held back messages is full.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Overflow {
+    /// The one being sent, it shows as failed.
+    #[default]
+    DropNewest,
+    /// The one that has waited longest.
+    DropOldest,
+}
+
+/// The `rate_limit` section of the user config.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    /// Messages per second sent in the long run, 0 turns the limit off.
+    pub per_sec: f64,
+    /// Messages that can go at once after a quiet spell.
+    pub burst: u32,
+    /// Messages held back at most before dropping one.
+    pub max_queued: usize,
+    pub overflow: Overflow,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            per_sec: 2.0,
+            burst: 5,
+            max_queued: 20,
+            overflow: Overflow::default(),
+        }
+    }
+}
+
+/// Holds up to `burst` tokens, refilled at `per_sec`. Each message sent
+/// takes one.
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    per_sec: f64,
+    capacity: f64,
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        let capacity = config.burst.max(1) as f64;
+        Self {
+            per_sec: config.per_sec,
+            capacity,
+            tokens: capacity,
+            refilled: Instant::now(),
+        }
+    }
+
+    /// Takes a token if there is one, returns false if the message has to
+    /// wait.
+    pub fn try_take(&mut self) -> bool {
+        if self.per_sec <= 0.0 {
+            return true;
+        }
+        let now = Instant::now();
+        let refill = now.duration_since(self.refilled).as_secs_f64() * self.per_sec;
+        self.tokens = (self.tokens + refill).min(self.capacity);
+        self.refilled = now;
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lets_a_burst_through_then_holds_back() {
+        let config = RateLimitConfig {
+            per_sec: 0.001,
+            burst: 3,
+            ..Default::default()
+        };
+        let mut bucket = TokenBucket::new(&config);
+        let taken: Vec<bool> = (0..4).map(|_| bucket.try_take()).collect();
+        assert_eq!(taken, vec![true, true, true, false]);
+    }
+}
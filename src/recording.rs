@@ -0,0 +1,127 @@
+use std::{
+    fs::{read_to_string, File},
+    io::{self, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use color_eyre::{eyre::eyre, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app::App,
+    tui_framework::{Event, Tui},
+    update::{reduce, Effect},
+};
+
+/// One line of a recording.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Recorded {
+    /// Milliseconds since the recording started.
+    pub at: u64,
+    pub event: Event,
+}
+
+/// Writes every event the app handles to a JSON lines file, for `--record`.
+/// Messages from the server are kept decrypted, as the app saw them.
+#[derive(Debug)]
+pub struct Recorder {
+    file: File,
+    started: Instant,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Ok(Self {
+            file: File::create(path)?,
+            started: Instant::now(),
+        })
+    }
+
+    pub fn record(&mut self, event: &Event) {
+        let line = Recorded {
+            at: self.started.elapsed().as_millis() as u64,
+            event: event.clone(),
+        };
+        let result = serde_json::to_string(&line)
+            .map_err(io::Error::from)
+            .and_then(|line| writeln!(self.file, "{line}"));
+        if let Err(e) = result {
+            tracing::error!("Could not write to the recording: {e}");
+        }
+    }
+}
+
+/// Reads a recording, failing on the first line that doesn't parse.
+pub fn read(path: &Path) -> Result<Vec<Recorded>> {
+    read_to_string(path)?
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(n, line)| {
+            serde_json::from_str(line)
+                .map_err(|e| eyre!("Invalid event on line {} of the recording: {e}", n + 1))
+        })
+        .collect()
+}
+
+/// Feeds one recorded event through the update loop. Only drawing is
+/// carried out, nothing goes to the server, and what the app asks to send
+/// itself is dropped since the recording already has it.
+fn step(app: &mut App, tui: &mut Tui, event: Event) -> Result<()> {
+    for effect in reduce(app, event) {
+        if effect == Effect::Draw {
+            tui.draw(app)?;
+            app.drawn();
+        }
+    }
+    while tui.receiver.try_recv().is_ok() {}
+    Ok(())
+}
+
+/// Plays a recording back with the timing it was made with, until it ends
+/// or the app quits.
+pub async fn replay(app: &mut App, tui: &mut Tui, recording: Vec<Recorded>) -> Result<()> {
+    let started = Instant::now();
+    for Recorded { at, event } in recording {
+        let due = Duration::from_millis(at).saturating_sub(started.elapsed());
+        tokio::time::sleep(due).await;
+        step(app, tui, event)?;
+        if app.should_quit {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user_config::UserConfig;
+
+    #[test]
+    fn replays_what_was_recorded() {
+        let path = std::env::temp_dir().join(format!("marain-events-{}.jsonl", std::process::id()));
+        let mut recorder = Recorder::create(&path).unwrap();
+        for event in [
+            Event::from('i'),
+            Event::from('h'),
+            Event::from('i'),
+            Event::Tick,
+        ] {
+            recorder.record(&event);
+        }
+        let recording = read(&path).unwrap();
+        _ = std::fs::remove_file(path);
+        assert_eq!(recording.len(), 4);
+        assert_eq!(recording[1].event, Event::from('h'));
+
+        let mut app = App::for_test(UserConfig::default());
+        let mut tui = Tui::new(None);
+        app.set_send_chan(tui.get_sender());
+        for Recorded { event, .. } in recording {
+            step(&mut app, &mut tui, event).unwrap();
+        }
+        assert_eq!(app.buffer, vec!["hi"]);
+    }
+}
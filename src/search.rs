@@ -0,0 +1,48 @@
+use ratatui::{style::Style, text::Span};
+use regex::{Regex, RegexBuilder};
+
+/// A search through the chat log. Queries are plain case-insensitive text,
+/// unless wrapped in slashes like `/v\d+/` which makes them a regex.
+#[derive(Debug, Clone)]
+pub struct Search {
+    pub query: String,
+    pattern: Regex,
+    /// Position among the rendered logs of the match being shown, counted
+    /// from the newest like `App::scroll_offset`.
+    pub current: Option<usize>,
+}
+
+impl Search {
+    pub fn new(query: &str) -> Result<Self, regex::Error> {
+        let pattern = match query.strip_prefix('/').and_then(|q| q.strip_suffix('/')) {
+            Some(re) if !re.is_empty() => re.to_string(),
+            _ => regex::escape(query),
+        };
+        Ok(Self {
+            query: query.to_string(),
+            pattern: RegexBuilder::new(&pattern).case_insensitive(true).build()?,
+            current: None,
+        })
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.pattern.is_match(text)
+    }
+
+    /// Splits `text` into spans with the matches styled with `found`.
+    pub fn highlight<'a>(&self, text: &'a str, base: Style, found: Style) -> Vec<Span<'a>> {
+        let mut spans = vec![];
+        let mut end = 0;
+        for m in self.pattern.find_iter(text) {
+            if m.start() > end {
+                spans.push(Span::styled(&text[end..m.start()], base));
+            }
+            spans.push(Span::styled(m.as_str(), found));
+            end = m.end();
+        }
+        if end < text.len() {
+            spans.push(Span::styled(&text[end..], base));
+        }
+        spans
+    }
+}
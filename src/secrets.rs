@@ -0,0 +1,78 @@
+#[cfg(unix)]
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+};
+
+use keyring::Entry;
+use serde::{Deserialize, Serialize};
+
+use crate::user_config::config_dir;
+
+/// Service the keychain entries are filed under.
+const SERVICE: &str = "marain";
+
+/// Where session tokens, keys and passwords are kept.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretStorage {
+    /// The platform keychain: Secret Service or the kernel keyring on
+    /// Linux, Keychain on macOS, Credential Manager on Windows.
+    #[default]
+    Keyring,
+    /// Files only the user can read, under the config directory. For
+    /// headless systems with no keychain.
+    File,
+}
+
+fn io_err(e: keyring::Error) -> io::Error {
+    io::Error::other(e)
+}
+
+/// Stores `secret` as `name`, replacing what was there.
+pub fn store(storage: SecretStorage, name: &str, secret: &str) -> io::Result<()> {
+    match storage {
+        SecretStorage::Keyring => Entry::new(SERVICE, name)
+            .and_then(|entry| entry.set_password(secret))
+            .map_err(io_err),
+        SecretStorage::File => {
+            // private before anything is written, a file left by an older
+            // version may not have been
+            let mut options = OpenOptions::new();
+            options.write(true).create(true).truncate(true);
+            #[cfg(unix)]
+            options.mode(0o600);
+            let mut file = options.open(config_dir().join(name))?;
+            #[cfg(unix)]
+            file.set_permissions(fs::Permissions::from_mode(0o600))?;
+            file.write_all(secret.as_bytes())
+        }
+    }
+}
+
+/// The secret stored as `name`, if there is one.
+pub fn load(storage: SecretStorage, name: &str) -> Option<String> {
+    match storage {
+        SecretStorage::Keyring => match Entry::new(SERVICE, name).and_then(|e| e.get_password()) {
+            Ok(secret) => Some(secret),
+            Err(keyring::Error::NoEntry) => None,
+            Err(e) => {
+                tracing::error!("Could not read {name} from the keyring: {e}");
+                None
+            }
+        },
+        SecretStorage::File => fs::read_to_string(config_dir().join(name)).ok(),
+    }
+}
+
+pub fn remove(storage: SecretStorage, name: &str) {
+    match storage {
+        SecretStorage::Keyring => {
+            _ = Entry::new(SERVICE, name).and_then(|entry| entry.delete_password());
+        }
+        SecretStorage::File => {
+            _ = fs::remove_file(config_dir().join(name));
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use chrono::Utc;
+use marain_api::prelude::{ClientMsg, ClientMsgBody, Key, ServerMsg, Timestamp};
+use sphinx::prelude::{cbc_decode, cbc_encode, get_rng};
+
+use crate::{compression, error::ClientError};
+
+/// A logged in session: the token and shared secret agreed at login, and
+/// what the server turned out to understand. Whatever goes over the socket
+/// is sealed and opened here, in the UI and in one-shot commands alike.
+/// Clones share what they learn about the server, so the copy the socket
+/// worker opens messages with tells the one sealing them.
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub token: String,
+    shared_secret: Key,
+    /// The server has sent compressed messages, so it can read them too.
+    peer_deflates: Arc<AtomicBool>,
+}
+
+impl Session {
+    pub fn new(token: String, shared_secret: Key) -> Self {
+        Self {
+            token,
+            shared_secret,
+            peer_deflates: Arc::default(),
+        }
+    }
+
+    pub fn shared_secret(&self) -> Key {
+        self.shared_secret
+    }
+
+    /// A message carrying the session's token, timestamped now.
+    pub fn message(&self, body: ClientMsgBody) -> ClientMsg {
+        ClientMsg {
+            token: Some(self.token.clone()),
+            body,
+            timestamp: Timestamp::from(Utc::now()),
+        }
+    }
+
+    /// Compresses a serialized message if the server can take that, and
+    /// encrypts it.
+    pub fn seal(&self, serialized: Vec<u8>) -> Result<Vec<u8>, ClientError> {
+        let serialized = match self.peer_deflates.load(Ordering::Relaxed) {
+            true => compression::compress(serialized),
+            false => serialized,
+        };
+        cbc_encode(self.shared_secret.to_vec(), serialized, get_rng())
+            .map_err(|e| ClientError::Encrypt(e.to_string()))
+    }
+
+    /// Serializes and seals a message.
+    pub fn encode(&self, msg: &ClientMsg) -> Result<Vec<u8>, ClientError> {
+        let serialized =
+            bincode::serialize(msg).map_err(|e| ClientError::Encrypt(e.to_string()))?;
+        self.seal(serialized)
+    }
+
+    /// Decrypts a message from the server, inflating it if it came
+    /// compressed.
+    pub fn open(&self, enc: Vec<u8>) -> Result<Vec<u8>, ClientError> {
+        let decrypted = cbc_decode(self.shared_secret.to_vec(), enc)
+            .map_err(|e| ClientError::Decrypt(e.to_string()))?;
+        if compression::is_compressed(&decrypted) {
+            self.peer_deflates.store(true, Ordering::Relaxed);
+        }
+        compression::decompress(decrypted).map_err(ClientError::Decompress)
+    }
+
+    /// Opens and deserializes a message from the server.
+    pub fn decode(&self, enc: Vec<u8>) -> Result<ServerMsg, ClientError> {
+        bincode::deserialize(&self.open(enc)?).map_err(|e| ClientError::Deserialize(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compresses_once_the_server_has() {
+        let large = "the same line over and over\n".repeat(100).into_bytes();
+        let ours = Session::new("token".into(), [7; 32]);
+        let theirs = Session::new("token".into(), [7; 32]);
+        // opened on the socket worker, sealed on the UI side
+        let worker = ours.clone();
+
+        let plain = ours.seal(large.clone()).unwrap();
+        assert_eq!(theirs.open(plain.clone()).unwrap(), large);
+        let compressed = compression::compress(large.clone());
+        assert_eq!(
+            worker.open(theirs.seal(compressed).unwrap()).unwrap(),
+            large
+        );
+        assert!(ours.seal(large).unwrap().len() < plain.len());
+    }
+}
@@ -1,9 +1,105 @@
+use std::time::Duration;
+
 use chrono::Utc;
-use marain_api::prelude::{ClientMsg, ClientMsgBody, Timestamp};
+use marain_api::prelude::{
+    ClientMsg, ClientMsgBody, Key, ServerMsg, ServerMsgBody, Status, Timestamp,
+};
 use rand_core::OsRng;
+use serde::{Deserialize, Serialize};
+use tokio_tungstenite::tungstenite::Message;
 use x25519_dalek::{EphemeralSecret, PublicKey};
 
-use crate::{app::App, socket_client::SocketClient, Tui};
+use crate::{
+    app::App,
+    credential::Credential,
+    error::ClientError,
+    i18n::tr,
+    protocol,
+    secrets::{self, SecretStorage},
+    session::Session,
+    signing::KnownServers,
+    socket_client::{SocketClient, SocketConf},
+    transport::Transport,
+    Tui,
+};
+
+/// How long to wait for the server to accept a resumed session, or the
+/// password.
+const RESUME_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Starts the sealed frame that carries the password, which tells it
+/// apart from a `ClientMsg`. `ClientMsgBody::Login` has no field for a
+/// password, so this is an extension servers that want one implement:
+///
+/// 1. The client sends `Login` as usual and the server answers with
+///    `LoginSuccess`, which agrees the shared secret.
+/// 2. The client sends one binary frame sealed like any other message
+///    with the shared secret. Opened, it is `MZP\x01` followed by the
+///    bincode encoding of [`CredentialMsg`].
+/// 3. The server answers with a sealed `ServerMsg`, `Status::Yes` if it
+///    takes the password, and ignores everything else from the client
+///    until it has.
+///
+/// A server without the extension never answers, and logging in gives up
+/// after [`RESUME_TIMEOUT`].
+const CREDENTIAL_MAGIC: &[u8] = b"MZP\x01";
+
+/// The password, for the token `LoginSuccess` gave.
+#[derive(Serialize)]
+struct CredentialMsg<'a> {
+    token: &'a str,
+    credential: &'a str,
+}
+
+/// What's needed to pick a session back up after a restart, saved on a
+/// clean exit when `resume_session` is set in the config.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SavedSession {
+    pub username: String,
+    pub token: String,
+    pub shared_secret: Key,
+    pub room: Option<String>,
+}
+
+/// Name of the session saved for `server` among the secrets. The shared
+/// secret decrypts everything sent in the session, so it's kept like a
+/// password, and per server so it's never offered to another one.
+fn session_secret(server: &str) -> String {
+    let server: String = server
+        .chars()
+        .map(|c| match c.is_alphanumeric() || c == '.' || c == '-' {
+            true => c,
+            false => '_',
+        })
+        .collect();
+    format!("session-{server}.json")
+}
+
+/// Saves the session with `server`, the `host:port` it was made with.
+pub fn save_session(
+    storage: SecretStorage,
+    server: &str,
+    session: &SavedSession,
+) -> std::io::Result<()> {
+    secrets::store(
+        storage,
+        &session_secret(server),
+        &serde_json::to_string(session)?,
+    )
+}
+
+/// Takes the session saved for `server`, if any. It's removed so a
+/// session that fails to resume isn't tried again.
+pub fn take_session(storage: SecretStorage, server: &str) -> Option<SavedSession> {
+    // one saved before sessions were kept per server could be for any
+    secrets::remove(storage, "session.json");
+    let name = session_secret(server);
+    let contents = secrets::load(storage, &name)?;
+    secrets::remove(storage, &name);
+    serde_json::from_str(&contents)
+        .map_err(|e| tracing::error!("Invalid saved session: {e}"))
+        .ok()
+}
 
 pub fn create_key_pair() -> (EphemeralSecret, PublicKey) {
     let client_secret = EphemeralSecret::random_from_rng(OsRng);
@@ -12,24 +108,227 @@ pub fn create_key_pair() -> (EphemeralSecret, PublicKey) {
     (client_secret, client_public)
 }
 
-fn login_msg(app: &App, client_public: PublicKey) -> ClientMsg {
+fn login_msg(
+    username: String,
+    client_public: PublicKey,
+    capabilities: Option<protocol::Capabilities>,
+) -> ClientMsg {
+    // Login has no field for the protocol version, that goes after the
+    // name for servers that want it
+    let name = protocol::login_name(username, capabilities);
     ClientMsg {
         token: None,
-        body: ClientMsgBody::Login(app.username.clone(), *client_public.as_bytes()),
+        body: ClientMsgBody::Login(name, *client_public.as_bytes()),
         timestamp: Timestamp::from(Utc::now()),
     }
 }
 
-pub async fn handle_login_success(tui: &mut Tui, app: &mut App) -> SocketClient {
+/// Connects to the server, logs in and agrees on the shared secret,
+/// returning the client, the session and the server's public key. The key
+/// is checked against the one pinned in `known` before a password goes out.
+pub async fn login(
+    conf: &SocketConf,
+    username: String,
+    known: &KnownServers,
+) -> Result<(SocketClient, Session, Key), ClientError> {
+    let mut client = conf.spawn_client().await?;
+    // a fallback may have answered instead of the main server
+    let server = client.address.clone().unwrap_or_else(|| conf.address());
+    let (session, server_key) = login_over(
+        &mut client,
+        username,
+        conf.credential(),
+        conf.capabilities(),
+        known.pinned(&server),
+    )
+    .await?;
+
+    Ok((client, session, server_key))
+}
+
+/// Logs in over a transport that's already connected, returning the
+/// session and the server's public key. The password only goes to a server
+/// whose key is `pinned`, if one is.
+#[tracing::instrument(name = "handshake", skip_all, fields(username = %username))]
+pub async fn login_over(
+    transport: &mut impl Transport,
+    username: String,
+    credential: Option<&Credential>,
+    capabilities: Option<protocol::Capabilities>,
+    pinned: Option<&str>,
+) -> Result<(Session, Key), ClientError> {
+    // the login itself goes out in the clear, the password mustn't
+    if credential.is_some() && !transport.is_secure() {
+        return Err(ClientError::Login(tr("err.password_needs_tls")));
+    }
     let (client_secret, client_public) = create_key_pair();
-    let (client, token, server_public_key) = match tui.connect(login_msg(app, client_public)).await
-    {
-        Some(x) => x,
-        None => panic!("Could not retrieve token from server"),
+    let login = login_msg(username, client_public, capabilities);
+    let login = bincode::serialize(&login).map_err(|e| ClientError::Login(e.to_string()))?;
+    transport.send(Message::Binary(login)).await?;
+
+    let msg = transport.recv().await?;
+    let (token, server_public_key) = match msg {
+        Message::Binary(ref data) => match bincode::deserialize::<ServerMsg>(&data[..]) {
+            Ok(ServerMsg {
+                status: Status::Yes,
+                body: ServerMsgBody::LoginSuccess { token, public_key },
+                ..
+            }) => (token, PublicKey::from(public_key)),
+            Ok(ServerMsg {
+                status: Status::No(reason),
+                ..
+            }) => {
+                return Err(match protocol::mismatch(&reason) {
+                    Some(server) => ClientError::ProtocolMismatch(server),
+                    None => ClientError::Auth(reason),
+                })
+            }
+            Ok(ServerMsg {
+                status: Status::JustNo,
+                ..
+            }) => return Err(ClientError::Auth(tr("sys.login_failed"))),
+            _ => {
+                tracing::error!("Login failed, could not deserialize server message: {msg:?}");
+                return Err(ClientError::Login(format!("{msg:?}")));
+            }
+        },
+        _ => {
+            tracing::error!("Unexpected message format from server {msg:?}");
+            return Err(ClientError::UnexpectedMessage(format!("{msg:?}")));
+        }
     };
     let shared_secret = client_secret.diffie_hellman(&server_public_key);
-    tui.set_shared_secret(*shared_secret.as_bytes());
-    app.store_token(token);
+    let session = Session::new(token, *shared_secret.as_bytes());
+    if let Some(credential) = credential {
+        // without a password the app asks whether to trust a changed key
+        let fingerprint = KnownServers::fingerprint(server_public_key.as_bytes());
+        if pinned.is_some_and(|pinned| pinned != fingerprint) {
+            return Err(ClientError::Login(tr("err.password_key_changed")));
+        }
+        authenticate(transport, &session, credential).await?;
+    }
+    tracing::info!("Logged in");
+
+    Ok((session, *server_public_key.as_bytes()))
+}
+
+/// Gives the server the password, sealed with the secret just agreed, and
+/// waits for it to be accepted.
+async fn authenticate(
+    transport: &mut impl Transport,
+    session: &Session,
+    credential: &Credential,
+) -> Result<(), ClientError> {
+    let msg = bincode::serialize(&CredentialMsg {
+        token: &session.token,
+        credential: credential.expose(),
+    })
+    .map_err(|e| ClientError::Login(e.to_string()))?;
+    let sealed = session.seal([CREDENTIAL_MAGIC, &msg].concat())?;
+    transport.send(Message::Binary(sealed)).await?;
+
+    let reply = tokio::time::timeout(RESUME_TIMEOUT, transport.recv())
+        .await
+        .map_err(|_| ClientError::Login(tr("err.no_auth_reply")))??;
+    let Message::Binary(data) = reply else {
+        return Err(ClientError::UnexpectedMessage(format!("{reply:?}")));
+    };
+    match session.decode(data)?.status {
+        Status::Yes => Ok(()),
+        Status::No(reason) => Err(ClientError::Auth(reason)),
+        Status::JustNo => Err(ClientError::Auth(tr("sys.login_failed"))),
+    }
+}
+
+/// Reconnects with a saved token and shared secret, checking the server
+/// still accepts them with a time request. There is no resume message in
+/// the protocol, so this relies on the server keeping the session around.
+pub async fn resume(
+    conf: &SocketConf,
+    saved: &SavedSession,
+) -> Result<(SocketClient, Session), ClientError> {
+    let mut client = conf.spawn_client().await?;
+    let session = resume_over(&mut client, saved).await?;
+
+    Ok((client, session))
+}
+
+/// Checks a saved session still works over a connected transport.
+#[tracing::instrument(name = "resume", skip_all, fields(username = %saved.username))]
+pub async fn resume_over(
+    transport: &mut impl Transport,
+    saved: &SavedSession,
+) -> Result<Session, ClientError> {
+    let session = Session::new(saved.token.clone(), saved.shared_secret);
+    let encrypted = session.encode(&session.message(ClientMsgBody::GetTime))?;
+    transport.send(Message::Binary(encrypted)).await?;
+
+    let reply = tokio::time::timeout(RESUME_TIMEOUT, transport.recv())
+        .await
+        .map_err(|_| ClientError::Login("no reply to resumed session".into()))??;
+    let Message::Binary(data) = reply else {
+        return Err(ClientError::UnexpectedMessage(format!("{reply:?}")));
+    };
+    // a server that forgot the session can't answer with our key
+    let decrypted = session.open(data)?;
+    match bincode::deserialize::<ServerMsg>(&decrypted) {
+        Ok(ServerMsg {
+            status: Status::Yes,
+            ..
+        }) => Ok(session),
+        Ok(ServerMsg {
+            status: Status::No(reason),
+            ..
+        }) => Err(match protocol::mismatch(&reason) {
+            Some(server) => ClientError::ProtocolMismatch(server),
+            None => ClientError::Login(format!("{:?}", Status::No(reason))),
+        }),
+        Ok(msg) => Err(ClientError::Login(format!("{:?}", msg.status))),
+        Err(e) => Err(ClientError::Login(e.to_string())),
+    }
+}
+
+/// Resumes the saved session if there is one and resuming is enabled,
+/// otherwise logs in afresh. Returns the room to go back to.
+pub async fn resume_or_login(
+    tui: &mut Tui,
+    app: &mut App,
+) -> Result<(SocketClient, Option<String>), ClientError> {
+    let saved = match app.config.resume_session {
+        true => take_session(app.config.secret_storage, &tui.socket_conf.address()),
+        false => None,
+    };
+    let Some(saved) = saved else {
+        return Ok((handle_login_success(tui, app).await?, None));
+    };
+    match resume(&tui.socket_conf, &saved).await {
+        Ok((client, session)) => {
+            tracing::info!("Resumed session for {}", saved.username);
+            tui.set_session(session);
+            app.username = saved.username;
+            app.store_token(saved.token);
+            Ok((client, saved.room))
+        }
+        Err(e) => {
+            tracing::error!("Could not resume session, logging in again: {e}");
+            Ok((handle_login_success(tui, app).await?, saved.room))
+        }
+    }
+}
+
+pub async fn handle_login_success(
+    tui: &mut Tui,
+    app: &mut App,
+) -> Result<SocketClient, ClientError> {
+    let (client, session, server_key) =
+        login(&tui.socket_conf, app.username.clone(), &app.known_servers).await?;
+    app.store_token(session.token.clone());
+    tui.set_session(session);
+    // a fallback may have answered instead of the main server
+    if let Some(ref address) = client.address {
+        app.server = address.clone();
+    }
+    app.verify_server_key(&server_key);
 
-    client
+    Ok(client)
 }
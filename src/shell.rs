@@ -0,0 +1,22 @@
+use tokio::{process::Command, sync::mpsc::Sender};
+
+use crate::tui_framework::Event;
+
+/// Runs `cmd` with `sh -c` in the background and reports its stdout back
+/// as an [`Event::RunResult`].
+pub fn spawn_run(cmd: String, sink: Sender<Event>) {
+    tokio::spawn(async move {
+        let result = match Command::new("sh").arg("-c").arg(&cmd).output().await {
+            Ok(out) if out.status.success() => Ok(String::from_utf8_lossy(&out.stdout).into()),
+            Ok(out) => Err(format!(
+                "`{cmd}` exited with {}: {}",
+                out.status,
+                String::from_utf8_lossy(&out.stderr).trim()
+            )),
+            Err(e) => Err(format!("Failed to run `{cmd}`: {e}")),
+        };
+        if let Err(e) = sink.send(Event::RunResult(result)).await {
+            tracing::error!("Failed to report /run result: {e}");
+        }
+    });
+}
@@ -0,0 +1,211 @@
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::{
+    collections::HashMap,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use color_eyre::Result;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use rand_core::OsRng;
+
+use crate::user_config::config_dir;
+
+/// Separates the message from its signature trailer, which has the form
+/// `<message> ~sig:<base64 public key>:<base64 signature>`.
+const SIG_MARKER: &str = " ~sig:";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureState {
+    /// Signed with the key we have on record for the sender.
+    Verified,
+    /// Bad signature, or signed with a different key than the one on record.
+    Invalid,
+}
+
+/// The persistent key used to sign outgoing messages.
+#[derive(Debug)]
+pub struct Identity {
+    key: SigningKey,
+}
+
+impl Identity {
+    /// `<config dir>/identity.key` unless the config names another file.
+    pub fn default_path() -> PathBuf {
+        config_dir().join("identity.key")
+    }
+
+    pub fn load_or_create(path: &Path) -> Result<Self> {
+        if let Ok(bytes) = fs::read(path) {
+            let seed: [u8; 32] = bytes
+                .try_into()
+                .map_err(|_| color_eyre::eyre::eyre!("{} is corrupt", path.display()))?;
+            return Ok(Self {
+                key: SigningKey::from_bytes(&seed),
+            });
+        }
+
+        // only the user may read the private key, from the moment it exists
+        let key = SigningKey::generate(&mut OsRng);
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        options.open(path)?.write_all(&key.to_bytes())?;
+        Ok(Self { key })
+    }
+
+    /// The public key as it appears in signature trailers, which is what
+    /// other users pin.
+    pub fn fingerprint(&self) -> String {
+        STANDARD.encode(self.key.verifying_key().as_bytes())
+    }
+
+    pub fn sign(&self, msg: &str) -> String {
+        let sig = self.key.sign(msg.as_bytes());
+        format!(
+            "{msg}{SIG_MARKER}{}:{}",
+            self.fingerprint(),
+            STANDARD.encode(sig.to_bytes())
+        )
+    }
+}
+
+/// How a server's key compares to the one pinned for its address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerKeyCheck {
+    /// First connection, the key is now pinned.
+    New,
+    Match,
+    /// The key differs from the pinned one, which is given.
+    Changed(String),
+}
+
+/// Public keys of the servers connected to, pinned on first use per
+/// `host:port`.
+#[derive(Debug)]
+pub struct KnownServers {
+    keys: HashMap<String, String>,
+    path: PathBuf,
+}
+
+impl KnownServers {
+    /// Reads the keys kept in `dir`, where new ones are saved too.
+    pub fn load(dir: &Path) -> Self {
+        let path = dir.join("known_servers.json");
+        let keys = fs::read_to_string(&path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        Self { keys, path }
+    }
+
+    fn save(&self) -> Result<()> {
+        fs::write(&self.path, serde_json::to_string_pretty(&self.keys)?)?;
+        Ok(())
+    }
+
+    pub fn fingerprint(key: &[u8; 32]) -> String {
+        STANDARD.encode(key)
+    }
+
+    /// Checks `fingerprint` against the key pinned for `server`, pinning it
+    /// if the server hasn't been seen before.
+    pub fn check(&mut self, server: &str, fingerprint: &str) -> ServerKeyCheck {
+        match self.keys.get(server) {
+            Some(known) if known == fingerprint => ServerKeyCheck::Match,
+            Some(known) => ServerKeyCheck::Changed(known.clone()),
+            None => {
+                self.pin(server, fingerprint);
+                ServerKeyCheck::New
+            }
+        }
+    }
+
+    /// The fingerprint pinned for `server`, if it's been seen before.
+    pub fn pinned(&self, server: &str) -> Option<&str> {
+        self.keys.get(server).map(String::as_str)
+    }
+
+    /// Trusts `fingerprint` for `server` from now on.
+    pub fn pin(&mut self, server: &str, fingerprint: &str) {
+        self.keys
+            .insert(server.to_string(), fingerprint.to_string());
+        if let Err(e) = self.save() {
+            tracing::error!("Could not save known servers: {e}");
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.keys.iter()
+    }
+}
+
+/// Public keys of other users, pinned the first time a signed message from
+/// them is seen.
+#[derive(Debug)]
+pub struct KnownSigners {
+    keys: HashMap<String, String>,
+    path: PathBuf,
+}
+
+impl KnownSigners {
+    /// Reads the keys kept in `dir`, where new ones are saved too.
+    pub fn load(dir: &Path) -> Self {
+        let path = dir.join("known_signers.json");
+        let keys = fs::read_to_string(&path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        Self { keys, path }
+    }
+
+    fn save(&self) -> Result<()> {
+        fs::write(&self.path, serde_json::to_string_pretty(&self.keys)?)?;
+        Ok(())
+    }
+
+    /// Strips the signature trailer off `content` if there is one and checks
+    /// it against the sender's key on record.
+    pub fn verify(&mut self, sender: &str, content: &str) -> (String, Option<SignatureState>) {
+        let Some((msg, trailer)) = content.rsplit_once(SIG_MARKER) else {
+            return (content.to_string(), None);
+        };
+        let Some((key_b64, sig_b64)) = trailer.split_once(':') else {
+            return (content.to_string(), None);
+        };
+
+        let valid = Self::check(msg, key_b64, sig_b64);
+        let state = match self.keys.get(sender) {
+            Some(known) if valid && known == key_b64 => SignatureState::Verified,
+            None if valid => {
+                self.keys.insert(sender.to_string(), key_b64.to_string());
+                if let Err(e) = self.save() {
+                    tracing::error!("Could not save known signers: {e}");
+                }
+                SignatureState::Verified
+            }
+            _ => SignatureState::Invalid,
+        };
+
+        (msg.to_string(), Some(state))
+    }
+
+    fn check(msg: &str, key_b64: &str, sig_b64: &str) -> bool {
+        let (Ok(key), Ok(sig)) = (STANDARD.decode(key_b64), STANDARD.decode(sig_b64)) else {
+            return false;
+        };
+        let (Ok(key), Ok(sig)) = (<[u8; 32]>::try_from(key), <[u8; 64]>::try_from(sig)) else {
+            return false;
+        };
+        match VerifyingKey::from_bytes(&key) {
+            Ok(key) => key
+                .verify(msg.as_bytes(), &Signature::from_bytes(&sig))
+                .is_ok(),
+            Err(_) => false,
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use crate::{
+    app::{Command, QrKind},
+    i18n::{tr, trf},
+};
+
+/// Every command name, for completion.
+pub const NAMES: &[&str] = &[
+    "bio",
+    "clear",
+    "e2e",
+    "ignore",
+    "keys",
+    "link",
+    "msg",
+    "paste-image",
+    "qr",
+    "reload",
+    "rooms",
+    "run",
+    "send",
+    "server",
+    "shh",
+    "stats",
+    "statusmsg",
+    "unignore",
+    "upload",
+    "whois",
+];
+
+/// How to call each command and what it does, for the help overlay.
+pub fn help() -> Vec<(String, String)> {
+    NAMES
+        .iter()
+        .map(|name| {
+            (
+                tr(&format!("help.usage.{name}")),
+                tr(&format!("help.slash.{name}")),
+            )
+        })
+        .collect()
+}
+
+/// Parses a composer line starting with `/` into a command. Returns `None`
+/// for ordinary chat messages and `Some(Err(..))` for malformed commands.
+pub fn parse(input: &str) -> Option<Result<Command, String>> {
+    let input = input.strip_prefix('/')?;
+    let (name, args) = match input.split_once(' ') {
+        Some((name, args)) => (name, args.trim()),
+        None => (input, ""),
+    };
+
+    let cmd = match name {
+        "upload" | "send" if args.is_empty() => Err(tr("sys.usage_upload")),
+        "upload" | "send" => Ok(Command::Upload(Some(args.to_string()))),
+        "run" if args.is_empty() => Err(tr("sys.usage_run")),
+        "run" => Ok(Command::Run(args.to_string())),
+        "bio" | "statusmsg" => Ok(Command::SetBio(args.to_string())),
+        "ignore" if args.is_empty() => Err(tr("sys.usage_ignore")),
+        "ignore" => Ok(Command::Ignore(args.to_string())),
+        "unignore" if args.is_empty() => Err(tr("sys.usage_unignore")),
+        "unignore" => Ok(Command::Unignore(args.to_string())),
+        "whois" if args.is_empty() => Err(tr("sys.usage_whois")),
+        "whois" => Ok(Command::Whois(args.to_string())),
+        "shh" if args.is_empty() => Err(tr("sys.usage_shh")),
+        "shh" => Ok(Command::SendEphemeral(args.to_string())),
+        "stats" => Ok(Command::ShowStats),
+        "server" => Ok(Command::ShowServerInfo),
+        "clear" => Ok(Command::ClearHistory),
+        "e2e" if args == "off" => Ok(Command::ForgetRoomKey),
+        "e2e" if args.is_empty() => Ok(Command::SetRoomKey),
+        "e2e" => Err(tr("sys.usage_e2e")),
+        "keys" => match args.split_once(' ') {
+            Some(("export", path)) if !path.trim().is_empty() => {
+                Ok(Command::ExportKeys(path.trim().to_string()))
+            }
+            _ => Err(tr("sys.usage_keys")),
+        },
+        "msg" => match args.split_once(' ') {
+            Some((to, text)) if !text.trim().is_empty() => {
+                Ok(Command::SendDirect(to.to_string(), text.trim().to_string()))
+            }
+            _ => Err(tr("sys.usage_msg")),
+        },
+        "paste-image" => Ok(Command::PasteImage),
+        "rooms" => Ok(Command::RoomDirectory),
+        "link" => Ok(Command::CopyJoinLink),
+        "reload" => Ok(Command::ReloadConfig),
+        "qr" if args.is_empty() => Ok(Command::ShowQr(QrKind::Fingerprint)),
+        "qr" if args == "invite" => Ok(Command::ShowQr(QrKind::Invite)),
+        "qr" => Err(tr("sys.usage_qr")),
+        _ => Err(trf!("sys.unknown_command", name)),
+    };
+
+    Some(cmd)
+}
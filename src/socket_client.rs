@@ -1,118 +1,375 @@
+use std::{
+    fs::File,
+    io::BufReader,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
 use color_eyre::Result;
-use futures::channel::mpsc::unbounded;
+use futures::channel::mpsc;
 use futures_util::{
     future, pin_mut,
     stream::{SplitSink, SplitStream},
-    StreamExt,
+    SinkExt, StreamExt,
 };
-use log2 as log;
+use rustls::{ClientConfig, RootCertStore};
 use tokio::{
     net::TcpStream,
-    sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+    sync::mpsc::{channel, Receiver, Sender},
     task::JoinHandle,
 };
 use tokio_tungstenite::{
-    connect_async,
+    client_async_tls_with_config, connect_async_tls_with_config,
     tungstenite::{handshake::client::Response, Message},
-    MaybeTlsStream, WebSocketStream,
+    Connector, MaybeTlsStream, WebSocketStream,
+};
+use tracing::Instrument;
+
+use crate::{
+    credential::Credential,
+    endpoint::Endpoint,
+    error::ClientError,
+    i18n::tr,
+    protocol::Capabilities,
+    proxy,
+    transport::Transport,
+    user_config::{
+        KeepaliveConfig, ProxyConfig, ServerConfig, TlsConfig, UserConfig, DEFAULT_PORT,
+    },
 };
-use url::Url;
 
 #[derive(Clone, Debug)]
 pub struct SocketConf {
-    host: String,
+    /// The server followed by the fallbacks to try in order, as given on
+    /// the command line.
+    servers: Vec<String>,
+    /// Port for servers given without one.
     port: String,
+    secure: bool,
+    tls: TlsConfig,
+    keepalive: KeepaliveConfig,
+    proxy: ProxyConfig,
+    credential: Option<Credential>,
+    capabilities: Option<Capabilities>,
 }
 
 impl SocketConf {
-    pub fn url(&self) -> Url {
-        if self.host.contains("/") {
-            panic!("Just supply the hostname e.g. 'localhost'");
+    /// Everything the user config says about connecting: the server, TLS,
+    /// keepalive, proxy and what to offer at login.
+    pub fn for_config(config: &UserConfig) -> Self {
+        Self::default()
+            .with_server(&config.server)
+            .with_tls(&config.tls)
+            .with_keepalive(&config.keepalive)
+            .with_proxy(&config.proxy)
+            .with_capabilities(config.announce_protocol.then_some(Capabilities {
+                deflate: config.compression,
+            }))
+    }
+
+    /// Fluent setter for the server from the user config, for whatever the
+    /// command line leaves out.
+    pub fn with_server(mut self, server: &ServerConfig) -> Self {
+        let args = crate::cli::args();
+        if let (None, Some(host)) = (&args.host, &server.host) {
+            self.servers.insert(0, host.clone());
+        }
+        if let (None, Some(port)) = (&args.port, &server.port) {
+            self.port = port.clone();
+        }
+
+        self
+    }
+
+    /// Fluent setter for the TLS options from the user config.
+    pub fn with_tls(mut self, tls: &TlsConfig) -> Self {
+        self.secure |= tls.enabled;
+        self.tls = tls.clone();
+
+        self
+    }
+
+    /// Fluent setter for the ping interval and dead connection timeout.
+    pub fn with_keepalive(mut self, keepalive: &KeepaliveConfig) -> Self {
+        self.keepalive = keepalive.clone();
+
+        self
+    }
+
+    /// Fluent setter for the proxy to connect through.
+    pub fn with_proxy(mut self, proxy: &ProxyConfig) -> Self {
+        self.proxy = proxy.clone();
+
+        self
+    }
+
+    /// Fluent setter for the password the server wants, if any.
+    pub fn with_credential(mut self, credential: Option<Credential>) -> Self {
+        self.credential = credential;
+
+        self
+    }
+
+    /// Fluent setter for the optional features offered at login, None to
+    /// log in with the bare username.
+    pub fn with_capabilities(mut self, capabilities: Option<Capabilities>) -> Self {
+        self.capabilities = capabilities;
+
+        self
+    }
+
+    pub fn capabilities(&self) -> Option<Capabilities> {
+        self.capabilities
+    }
+
+    /// Replaces a password the server refused.
+    pub fn set_credential(&mut self, credential: Credential) {
+        self.credential = Some(credential);
+    }
+
+    pub fn credential(&self) -> Option<&Credential> {
+        self.credential.as_ref()
+    }
+
+    /// `host:port` of the main server.
+    pub fn address(&self) -> String {
+        match self.endpoints() {
+            Ok(endpoints) => endpoints[0].address(),
+            Err(_) => self.servers.first().cloned().unwrap_or_default(),
         }
-        let url = Url::parse(&format!("ws://{}:{}", self.host, self.port))
-            .expect("Failed to parse the socket url");
-        log::info!("Parsed socket url: {}", url);
+    }
 
-        url
+    /// The servers to try in order, failing if any of them is invalid.
+    pub fn endpoints(&self) -> Result<Vec<Endpoint>, ClientError> {
+        if self.servers.is_empty() {
+            return Err(ClientError::Connect(tr("err.no_server")));
+        }
+        self.servers
+            .iter()
+            .map(|spec| Endpoint::parse(spec, &self.port, self.secure))
+            .collect()
     }
 
-    pub async fn spawn_client(&self) -> SocketClient {
+    /// Builds the rustls connector from the trusted roots in the config.
+    fn tls_connector(&self) -> Result<Connector> {
+        let mut roots = RootCertStore::empty();
+        if self.tls.system_roots {
+            for cert in rustls_native_certs::load_native_certs()? {
+                roots.add(cert)?;
+            }
+        }
+        if let Some(ref path) = self.tls.pinned_cert {
+            let mut reader = BufReader::new(File::open(path)?);
+            for cert in rustls_pemfile::certs(&mut reader) {
+                roots.add(cert?)?;
+            }
+        }
+        let config = ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+
+        Ok(Connector::Rustls(Arc::new(config)))
+    }
+
+    pub async fn spawn_client(&self) -> Result<SocketClient, ClientError> {
         SocketClient::init(self.clone()).await
     }
 }
 
 impl Default for SocketConf {
     fn default() -> Self {
+        let args = crate::cli::args();
         Self {
-            host: std::env::args()
-                .nth(1)
-                .expect("Provide a host as the first position arg"),
-            port: std::env::args().nth(2).unwrap_or("1337".into()),
+            servers: args.host.iter().chain(&args.fallback).cloned().collect(),
+            port: args.port.clone().unwrap_or(DEFAULT_PORT.into()),
+            secure: args.tls,
+            tls: TlsConfig::default(),
+            keepalive: KeepaliveConfig::default(),
+            proxy: ProxyConfig::default(),
+            credential: None,
+            capabilities: None,
         }
     }
 }
 
+/// Messages each way that can wait between the socket and the event loop.
+/// A full inbound buffer stops reading the socket until there's room, a
+/// full outbound one makes the sender wait.
+pub const SOCKET_BUFFER: usize = 256;
+
 pub struct SocketClient {
     _task: JoinHandle<()>,
-    pub out_sink: futures::channel::mpsc::UnboundedSender<Message>,
-    pub in_source: UnboundedReceiver<Message>,
+    pub out_sink: mpsc::Sender<Message>,
+    pub in_source: Receiver<Message>,
+    /// `host:port` of the server connected to, none if the messages don't
+    /// come from a websocket.
+    pub address: Option<String>,
+    /// Connected over TLS.
+    pub secure: bool,
 }
 
 impl SocketClient {
     /// This is the async process that handles forwarding of inbound and outbound messages to/from
-    /// the socket stream.
+    /// the socket stream. It also pings the server to keep the link alive, and gives up on it when
+    /// nothing has been heard for the keepalive timeout. Either way the inbound channel is closed
+    /// when this returns.
     async fn work(
-        outbound_source: futures::channel::mpsc::UnboundedReceiver<Message>,
-        inbound_sink: UnboundedSender<Message>,
+        outbound_source: mpsc::Receiver<Message>,
+        inbound_sink: Sender<Message>,
         ws_sink: SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>,
         ws_source: SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>,
+        mut ping_sink: mpsc::Sender<Message>,
+        keepalive: KeepaliveConfig,
     ) {
+        // milliseconds since `start` that the server was last heard from
+        let start = Instant::now();
+        let last_seen = AtomicU64::new(0);
         let ws_to_inbound = ws_source.for_each(|message| async {
+            if message.is_ok() {
+                last_seen.store(start.elapsed().as_millis() as u64, Ordering::Relaxed);
+            }
             match message {
                 Ok(msg) => match msg {
                     Message::Text(_) => {
-                        log::error!("Incorrect protocol detected");
+                        tracing::error!("Incorrect protocol detected");
                     }
                     Message::Binary(_) | Message::Close(_) => {
-                        inbound_sink
-                            .send(msg)
-                            .expect("Could not forward inbound message from SocketClient");
+                        tracing::trace!(bytes = msg.len(), "from the server");
+                        if let Err(e) = inbound_sink.send(msg).await {
+                            tracing::error!(
+                                "Could not forward inbound message from SocketClient: {e}"
+                            );
+                        }
                     }
-                    _ => {
-                        panic!("UNEXPECTED {msg:?}");
+                    // pings are answered by tungstenite, both only count as traffic
+                    Message::Ping(_) | Message::Pong(_) => {}
+                    Message::Frame(_) => {
+                        tracing::error!("Unexpected raw frame from the server");
                     }
                 },
                 Err(e) => {
-                    log::error!("SocketClient got error trying to read msg: {e}");
+                    tracing::error!("SocketClient got error trying to read msg: {e}");
                 }
             };
         });
         let outbound_to_ws = outbound_source.map(|s| Ok(s)).forward(ws_sink);
+        let watchdog = async {
+            let timeout = Duration::from_secs(keepalive.timeout_secs);
+            let mut pings = tokio::time::interval(Duration::from_secs(keepalive.ping_secs.max(1)));
+            loop {
+                pings.tick().await;
+                let silent_for =
+                    start.elapsed() - Duration::from_millis(last_seen.load(Ordering::Relaxed));
+                if silent_for > timeout {
+                    tracing::error!("No traffic from the server for {silent_for:?}, giving up");
+                    return;
+                }
+                // a full buffer means messages are going out anyway
+                if let Err(e) = ping_sink.try_send(Message::Ping(vec![])) {
+                    if e.is_disconnected() {
+                        return;
+                    }
+                }
+            }
+        };
 
-        pin_mut!(ws_to_inbound, outbound_to_ws);
-        future::select(ws_to_inbound, outbound_to_ws).await;
+        pin_mut!(ws_to_inbound, outbound_to_ws, watchdog);
+        future::select(future::select(ws_to_inbound, outbound_to_ws), watchdog).await;
     }
 
-    pub async fn init(conf: SocketConf) -> Self {
-        let (out_sink, out_source) = unbounded::<Message>();
-        let (in_sink, in_source) = unbounded_channel::<Message>();
-        let url = conf.url();
+    /// Wraps channels fed by something other than a websocket, e.g. a
+    /// session daemon, so the rest of the client can't tell the difference.
+    pub fn from_channels(
+        task: JoinHandle<()>,
+        out_sink: mpsc::Sender<Message>,
+        in_source: Receiver<Message>,
+    ) -> Self {
+        Self {
+            _task: task,
+            out_sink,
+            in_source,
+            address: None,
+            secure: false,
+        }
+    }
+
+    /// Opens the websocket to one endpoint. Every address the host resolves
+    /// to is tried before giving up on it.
+    async fn connect(
+        conf: &SocketConf,
+        endpoint: &Endpoint,
+    ) -> Result<WebSocketStream<MaybeTlsStream<TcpStream>>, ClientError> {
+        let url = endpoint.url().clone();
+        let connector = match endpoint.is_secure() {
+            true => Some(
+                conf.tls_connector()
+                    .map_err(|e| ClientError::Connect(format!("Invalid TLS configuration: {e}")))?,
+            ),
+            false => None,
+        };
+        let handshake = match conf.proxy.url {
+            Some(_) => {
+                let stream = proxy::connect(&conf.proxy, endpoint.host(), endpoint.port()).await?;
+                client_async_tls_with_config(url.clone(), stream, None, connector).await
+            }
+            None => connect_async_tls_with_config(url.clone(), None, false, connector).await,
+        };
         let (ws_stream, _smth): (WebSocketStream<MaybeTlsStream<TcpStream>>, Response) =
-            connect_async(url.clone())
-                .await
-                .expect(&format!("Failed to connect to {}", url));
+            handshake.map_err(|e| ClientError::Connect(format!("{url}: {e}")))?;
+
+        Ok(ws_stream)
+    }
+
+    /// Connects to the first of the configured endpoints that answers.
+    pub async fn init(conf: SocketConf) -> Result<Self, ClientError> {
+        let (out_sink, out_source) = mpsc::channel::<Message>(SOCKET_BUFFER);
+        let (in_sink, in_source) = channel::<Message>(SOCKET_BUFFER);
+        let mut failures = vec![];
+        let mut connected = None;
+        for endpoint in conf.endpoints()? {
+            tracing::info!("Connecting to {endpoint}");
+            match Self::connect(&conf, &endpoint).await {
+                Ok(ws_stream) => {
+                    connected = Some((ws_stream, endpoint));
+                    break;
+                }
+                Err(e) => {
+                    tracing::error!("Could not connect to {endpoint}: {e}");
+                    failures.push(e.to_string());
+                }
+            }
+        }
+        let Some((ws_stream, endpoint)) = connected else {
+            return Err(ClientError::Connect(failures.join("; ")));
+        };
 
         let (ws_sink, ws_source): (
             SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>,
             SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>,
         ) = ws_stream.split();
 
-        let _task = tokio::spawn(Self::work(out_source, in_sink, ws_sink, ws_source));
-        Self {
+        let span = tracing::info_span!("socket", server = %endpoint.address());
+        let _task = tokio::spawn(
+            Self::work(
+                out_source,
+                in_sink,
+                ws_sink,
+                ws_source,
+                out_sink.clone(),
+                conf.keepalive.clone(),
+            )
+            .instrument(span),
+        );
+        Ok(Self {
             _task,
             out_sink,
             in_source,
-        }
+            address: Some(endpoint.address()),
+            secure: endpoint.is_secure(),
+        })
     }
 
     pub async fn next(&mut self) -> Result<Message> {
@@ -122,3 +379,22 @@ impl SocketClient {
             .ok_or(color_eyre::eyre::eyre!("Could not get socket message"))
     }
 }
+
+impl Transport for SocketClient {
+    async fn send(&mut self, msg: Message) -> Result<(), ClientError> {
+        self.out_sink
+            .send(msg)
+            .await
+            .map_err(|e| ClientError::Socket(e.to_string()))
+    }
+
+    async fn recv(&mut self) -> Result<Message, ClientError> {
+        self.next()
+            .await
+            .map_err(|e| ClientError::Socket(e.to_string()))
+    }
+
+    fn is_secure(&self) -> bool {
+        self.secure
+    }
+}
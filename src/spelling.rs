@@ -0,0 +1,214 @@
+use std::{collections::HashSet, fs, ops::Range, path::PathBuf};
+
+use ratatui::style::{Color, Modifier, Style};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::user_config::SpellingConfig;
+
+/// Most suggestions offered for one word.
+const MAX_SUGGESTIONS: usize = 8;
+
+/// Endings tried off words that aren't in the dictionary as they are.
+/// Hunspell dictionaries list stems and leave inflections to affix rules,
+/// which this doesn't read, so the common English ones are undone here.
+const SUFFIXES: &[(&str, &str)] = &[
+    ("'s", ""),
+    ("s", ""),
+    ("es", ""),
+    ("ies", "y"),
+    ("ed", ""),
+    ("ed", "e"),
+    ("ied", "y"),
+    ("ier", "y"),
+    ("iest", "y"),
+    ("ing", ""),
+    ("ing", "e"),
+    ("ly", ""),
+    ("er", ""),
+    ("est", ""),
+];
+
+/// How misspelled words are underlined in the input area.
+pub fn style() -> Style {
+    Style::new()
+        .add_modifier(Modifier::UNDERLINED)
+        .underline_color(Color::Red)
+}
+
+/// Words of one language, from a hunspell `.dic` file or a plain word list.
+#[derive(Debug, Default)]
+pub struct Dictionary {
+    words: HashSet<String>,
+}
+
+impl Dictionary {
+    /// The dictionary for the configured language, looked for in each of
+    /// the configured directories in turn. None if spell checking is off.
+    pub fn load(conf: &SpellingConfig) -> Option<Self> {
+        let language = conf.language.as_ref()?;
+        let file = format!("{language}.dic");
+        let found = conf
+            .dictionaries
+            .iter()
+            .map(|dir| PathBuf::from(dir).join(&file))
+            .find_map(|path| fs::read_to_string(path).ok());
+        if found.is_none() {
+            tracing::warn!("No {file} in any of {:?}", conf.dictionaries);
+        }
+        found.map(|text| Self::parse(&text))
+    }
+
+    /// Reads one word per line, without the affix flags after a `/`. The
+    /// word count hunspell puts on the first line is skipped.
+    pub fn parse(text: &str) -> Self {
+        let words = text
+            .lines()
+            .filter_map(|line| line.split('/').next())
+            .map(str::trim)
+            .filter(|word| !word.is_empty() && !word.chars().all(|c| c.is_ascii_digit()))
+            .map(str::to_lowercase)
+            .collect();
+        Self { words }
+    }
+
+    fn knows(&self, word: &str) -> bool {
+        self.words.contains(word)
+    }
+
+    /// Whether `word` is spelled right. Anything that isn't all letters,
+    /// like numbers, @names and urls, is left alone, as are single letters.
+    pub fn check(&self, word: &str) -> bool {
+        let word = word.to_lowercase();
+        if word.chars().count() < 2 || !word.chars().all(|c| c.is_alphabetic() || c == '\'') {
+            return true;
+        }
+        self.knows(&word)
+            || SUFFIXES.iter().any(|(suffix, stem)| {
+                word.strip_suffix(suffix)
+                    .filter(|rest| rest.len() > 1)
+                    .is_some_and(|rest| self.knows(&format!("{rest}{stem}")))
+            })
+    }
+
+    /// Grapheme ranges of the misspelled words in `line`.
+    pub fn misspelled(&self, line: &str) -> Vec<Range<usize>> {
+        words(line)
+            .filter(|(_, word)| !self.check(word))
+            .map(|(range, _)| range)
+            .collect()
+    }
+
+    /// Words one edit away from `word` that are in the dictionary, keeping
+    /// a leading capital.
+    pub fn suggest(&self, word: &str) -> Vec<String> {
+        let lower = word.to_lowercase();
+        let capital = word.chars().next().is_some_and(char::is_uppercase);
+        let mut suggestions: Vec<String> = vec![];
+        for candidate in edits(&lower) {
+            if suggestions.len() == MAX_SUGGESTIONS {
+                break;
+            }
+            if self.knows(&candidate) && !suggestions.contains(&candidate) {
+                suggestions.push(candidate);
+            }
+        }
+        if capital {
+            for suggestion in &mut suggestions {
+                let mut chars = suggestion.chars();
+                if let Some(first) = chars.next() {
+                    *suggestion = first.to_uppercase().chain(chars).collect();
+                }
+            }
+        }
+        suggestions
+    }
+}
+
+/// Punctuation around words rather than part of them, markdown included.
+const AROUND: &[&str] = &[
+    "\"", "'", ".", ",", ";", ":", "!", "?", "(", ")", "[", "]", "*", "_", "~", "`",
+];
+
+/// The words in `line`, split at whitespace and without the punctuation
+/// around them, with their grapheme ranges.
+fn words(line: &str) -> impl Iterator<Item = (Range<usize>, &str)> + '_ {
+    let graphemes: Vec<(usize, &str)> = line.grapheme_indices(true).collect();
+    let mut words = vec![];
+    let mut start = 0;
+    for end in 0..=graphemes.len() {
+        let at_space = graphemes
+            .get(end)
+            .is_none_or(|(_, g)| g.chars().all(char::is_whitespace));
+        if !at_space {
+            continue;
+        }
+        let (mut from, mut to) = (start, end);
+        while from < to && AROUND.contains(&graphemes[from].1) {
+            from += 1;
+        }
+        while to > from && AROUND.contains(&graphemes[to - 1].1) {
+            to -= 1;
+        }
+        if from < to {
+            words.push(from..to);
+        }
+        start = end + 1;
+    }
+    words.into_iter().map(move |range| {
+        let from = graphemes[range.start].0;
+        let to = graphemes.get(range.end).map_or(line.len(), |(at, _)| *at);
+        (range, &line[from..to])
+    })
+}
+
+/// Every string one deletion, swap, replacement or insertion from `word`.
+fn edits(word: &str) -> Vec<String> {
+    let chars: Vec<char> = word.chars().collect();
+    let letters = 'a'..='z';
+    let mut edits = vec![];
+    for i in 0..chars.len() {
+        let mut deleted = chars.clone();
+        deleted.remove(i);
+        edits.push(deleted.into_iter().collect());
+    }
+    for i in 1..chars.len() {
+        let mut swapped = chars.clone();
+        swapped.swap(i - 1, i);
+        edits.push(swapped.into_iter().collect());
+    }
+    for i in 0..chars.len() {
+        for c in letters.clone() {
+            let mut replaced = chars.clone();
+            replaced[i] = c;
+            edits.push(replaced.into_iter().collect());
+        }
+    }
+    for i in 0..=chars.len() {
+        for c in letters.clone() {
+            let mut inserted = chars.clone();
+            inserted.insert(i, c);
+            edits.push(inserted.into_iter().collect());
+        }
+    }
+    edits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_misspellings_and_suggests_fixes() {
+        let dictionary = Dictionary::parse("6\nthe/S\ncat\nsat\non\nmat\nhappy\n");
+        assert!(dictionary.check("The"));
+        assert!(dictionary.check("cats") && dictionary.check("happiest"));
+        assert!(dictionary.check("42") && dictionary.check("a"));
+        assert_eq!(
+            dictionary.misspelled("teh cat sat on \"teh\" mat!"),
+            vec![0..3, 16..19]
+        );
+        assert!(dictionary.misspelled("@bob https://example.com").is_empty());
+        assert_eq!(dictionary.suggest("Teh"), vec!["The"]);
+        assert_eq!(dictionary.suggest("mta"), vec!["mat"]);
+    }
+}
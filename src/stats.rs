@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+
+use chrono::Timelike;
+
+use crate::{
+    chat_log::Log,
+    event_log::{kind_of, EventKind},
+    i18n::tr,
+};
+
+/// Widest bar in a chart, in characters.
+const BAR_WIDTH: usize = 30;
+/// Rows shown in the per user and per room charts.
+const TOP_N: usize = 10;
+
+/// Messages sent per room this session, for the room chart.
+#[derive(Debug, Default)]
+pub struct RoomCounts(HashMap<String, usize>);
+
+impl RoomCounts {
+    pub fn count(&mut self, room: &str) {
+        *self.0.entry(room.to_string()).or_insert(0) += 1;
+    }
+}
+
+fn bar_chart(title: &str, rows: &[(String, usize)]) -> String {
+    let max = rows.iter().map(|(_, n)| *n).max().unwrap_or(0).max(1);
+    let label_width = rows
+        .iter()
+        .map(|(l, _)| l.chars().count())
+        .max()
+        .unwrap_or(0);
+    let mut out = format!("{title}\n");
+    for (label, n) in rows {
+        let bar = "█".repeat((n * BAR_WIDTH).div_ceil(max));
+        out += &format!("{label:>label_width$} {bar} {n}\n");
+    }
+    out
+}
+
+fn top(counts: HashMap<String, usize>) -> Vec<(String, usize)> {
+    let mut rows: Vec<_> = counts.into_iter().collect();
+    rows.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    rows.truncate(TOP_N);
+    rows
+}
+
+/// Renders the statistics popup from the logs in memory.
+pub fn render<'a>(logs: impl Iterator<Item = &'a Log>, rooms: &RoomCounts) -> String {
+    let mut per_user: HashMap<String, usize> = HashMap::new();
+    let mut per_hour = [0; 24];
+    for log in logs.filter(|l| l.should_render(false, true) && kind_of(l) == EventKind::Chat) {
+        *per_user.entry(log.from.clone()).or_insert(0) += 1;
+        per_hour[log.ts.hour() as usize] += 1;
+    }
+    let hours: Vec<_> = per_hour
+        .iter()
+        .enumerate()
+        .filter(|(_, n)| **n > 0)
+        .map(|(h, n)| (format!("{h:02}:00"), *n))
+        .collect();
+
+    [
+        bar_chart(&tr("stats.users"), &top(per_user)),
+        bar_chart(&tr("stats.hours"), &hours),
+        bar_chart(&tr("stats.rooms"), &top(rooms.0.clone())),
+    ]
+    .join("\n")
+}
@@ -0,0 +1,349 @@
+use std::collections::HashMap;
+
+use ratatui::{
+    style::{Color, Modifier, Style, Stylize},
+    symbols::{border, scrollbar},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app::Mode,
+    chat_log::{KindStyle, LogKind, LogStyle},
+};
+
+/// Pane borders drawn without box drawing characters.
+const ASCII_BORDERS: border::Set = border::Set {
+    top_left: "+",
+    top_right: "+",
+    bottom_left: "+",
+    bottom_right: "+",
+    vertical_left: "|",
+    vertical_right: "|",
+    horizontal_top: "-",
+    horizontal_bottom: "-",
+};
+
+const ASCII_SCROLLBAR: scrollbar::Set = scrollbar::Set {
+    track: "|",
+    thumb: "#",
+    begin: "^",
+    end: "v",
+};
+
+/// The `accessibility` section of the user config, for anyone the
+/// blinking caret and dense box drawing get in the way of.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+#[serde(default)]
+pub struct AccessibilityConfig {
+    /// Nothing blinks, whatever the theme asks for.
+    pub reduced_motion: bool,
+    /// The caret is a plain reversed block in the input's colors.
+    pub block_caret: bool,
+    /// The high contrast preset, like `accessible` or `--accessible`.
+    pub high_contrast: bool,
+    /// Borders and scrollbars in ASCII rather than box drawing characters.
+    pub ascii_borders: bool,
+    /// A line above the status bar holding only the newest message as
+    /// plain text, which screen readers follow better than the log.
+    pub live_region: bool,
+}
+
+/// Built-in palettes a theme starts from.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ThemePreset {
+    #[default]
+    Dark,
+    Light,
+    HighContrast,
+}
+
+/// Changes to one style of the preset. Colors are names like `"red"` or
+/// `"lightblue"`, hex like `"#ff8800"` or a 256 color index.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct StyleSpec {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underlined: bool,
+    pub reversed: bool,
+    /// Any other modifiers by name, like `["dim", "crossed_out"]`.
+    pub modifiers: Vec<ModifierName>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ModifierName {
+    Bold,
+    Dim,
+    Italic,
+    Underlined,
+    SlowBlink,
+    RapidBlink,
+    Reversed,
+    Hidden,
+    CrossedOut,
+}
+
+impl From<ModifierName> for Modifier {
+    fn from(name: ModifierName) -> Self {
+        match name {
+            ModifierName::Bold => Modifier::BOLD,
+            ModifierName::Dim => Modifier::DIM,
+            ModifierName::Italic => Modifier::ITALIC,
+            ModifierName::Underlined => Modifier::UNDERLINED,
+            ModifierName::SlowBlink => Modifier::SLOW_BLINK,
+            ModifierName::RapidBlink => Modifier::RAPID_BLINK,
+            ModifierName::Reversed => Modifier::REVERSED,
+            ModifierName::Hidden => Modifier::HIDDEN,
+            ModifierName::CrossedOut => Modifier::CROSSED_OUT,
+        }
+    }
+}
+
+/// Overrides for the chat log lines of one kind, on top of the log styles
+/// every kind shares.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct LogKindSpec {
+    pub time: Option<StyleSpec>,
+    pub username: Option<StyleSpec>,
+    pub message: Option<StyleSpec>,
+    pub delims: Option<StyleSpec>,
+    /// strftime format for these lines' timestamps.
+    pub time_format: Option<String>,
+}
+
+impl StyleSpec {
+    pub fn apply(&self, mut style: Style) -> Style {
+        if let Some(fg) = self.fg {
+            style = style.fg(fg);
+        }
+        if let Some(bg) = self.bg {
+            style = style.bg(bg);
+        }
+        for (set, modifier) in [
+            (self.bold, Modifier::BOLD),
+            (self.italic, Modifier::ITALIC),
+            (self.underlined, Modifier::UNDERLINED),
+            (self.reversed, Modifier::REVERSED),
+        ] {
+            if set {
+                style = style.add_modifier(modifier);
+            }
+        }
+        for &name in &self.modifiers {
+            style = style.add_modifier(name.into());
+        }
+        style
+    }
+}
+
+fn patch(style: Style, spec: &Option<StyleSpec>) -> Style {
+    match spec {
+        Some(spec) => spec.apply(style),
+        None => style,
+    }
+}
+
+/// The `theme` section of the user config: a preset plus overrides for
+/// any of its styles.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ThemeConfig {
+    pub preset: ThemePreset,
+    pub border: Option<StyleSpec>,
+    pub title: Option<StyleSpec>,
+    pub input: Option<StyleSpec>,
+    pub caret: Option<StyleSpec>,
+    pub log_time: Option<StyleSpec>,
+    pub log_username: Option<StyleSpec>,
+    pub log_message: Option<StyleSpec>,
+    pub log_delims: Option<StyleSpec>,
+    pub log_highlight: Option<StyleSpec>,
+    /// Colors to pick usernames' colors from, replacing the preset's.
+    pub username_palette: Option<Vec<Color>>,
+    /// Overrides by kind of log: `chat`, `notification` (from the server),
+    /// `system` (from the client) or `debug`.
+    pub log_kinds: HashMap<LogKind, LogKindSpec>,
+    pub mode_navigate: Option<StyleSpec>,
+    pub mode_insert: Option<StyleSpec>,
+    pub mode_command: Option<StyleSpec>,
+    pub mode_disconnected: Option<StyleSpec>,
+}
+
+/// Styles used across the whole UI.
+#[derive(Debug, Clone)]
+pub struct Theme {
+    /// Pane borders and their default text.
+    pub border: Style,
+    pub title: Style,
+    /// Text typed into the input area.
+    pub input: Style,
+    pub caret: Style,
+    pub log: LogStyle,
+    /// The mode indicator, per mode.
+    pub mode_navigate: Style,
+    pub mode_insert: Style,
+    pub mode_command: Style,
+    pub mode_disconnected: Style,
+    pub borders: border::Set,
+    pub scrollbar: scrollbar::Set,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        let title = Style::new().white().on_black();
+        Self {
+            border: Style::new().green().on_black(),
+            title,
+            input: Style::new().white().on_black(),
+            caret: Style::new()
+                .bg(Color::Green)
+                .fg(Color::Black)
+                .add_modifier(Modifier::SLOW_BLINK),
+            log: LogStyle::default(),
+            mode_navigate: title,
+            mode_insert: Style::new().light_green().on_black(),
+            mode_command: Style::new().light_cyan().on_black(),
+            mode_disconnected: Style::new().light_red().on_black(),
+            borders: border::PLAIN,
+            scrollbar: scrollbar::DOUBLE_VERTICAL,
+        }
+    }
+
+    pub fn light() -> Self {
+        let base = Style::new().fg(Color::Black).bg(Color::White);
+        Self {
+            border: Style::new().blue().on_white(),
+            title: base.bold(),
+            input: base,
+            caret: Style::new()
+                .bg(Color::Blue)
+                .fg(Color::White)
+                .add_modifier(Modifier::SLOW_BLINK),
+            log: LogStyle::new(
+                Style::new().fg(Color::DarkGray).bg(Color::White).italic(),
+                Style::new().fg(Color::Magenta).bg(Color::White).bold(),
+                base,
+                Style::new().fg(Color::Blue).bg(Color::White),
+                Style::new().fg(Color::Black).bg(Color::LightYellow),
+            )
+            .with_palette(vec![
+                Color::Red,
+                Color::Green,
+                Color::Blue,
+                Color::Magenta,
+                Color::Cyan,
+                Color::DarkGray,
+            ]),
+            mode_navigate: base.bold(),
+            mode_insert: Style::new().green().on_white().bold(),
+            mode_command: Style::new().cyan().on_white().bold(),
+            mode_disconnected: Style::new().red().on_white().bold(),
+            borders: border::PLAIN,
+            scrollbar: scrollbar::DOUBLE_VERTICAL,
+        }
+    }
+
+    /// High contrast white on black, no blinking, and state is always
+    /// conveyed by text or modifiers rather than by color alone.
+    pub fn accessible() -> Self {
+        let plain = Style::new().fg(Color::White).bg(Color::Black);
+        Self {
+            border: plain,
+            title: plain.bold(),
+            input: plain,
+            caret: plain.add_modifier(Modifier::REVERSED),
+            log: LogStyle::new(
+                plain,
+                Style::new().fg(Color::LightYellow).bg(Color::Black).bold(),
+                plain,
+                plain,
+                plain.add_modifier(Modifier::REVERSED),
+            )
+            // one color for everyone, names are told apart by reading them
+            .with_palette(vec![]),
+            mode_navigate: plain.bold(),
+            mode_insert: plain.bold(),
+            mode_command: plain.bold(),
+            mode_disconnected: plain.bold(),
+            borders: border::PLAIN,
+            scrollbar: scrollbar::DOUBLE_VERTICAL,
+        }
+    }
+
+    /// Builds the configured theme. High contrast forces its preset but
+    /// still applies the overrides.
+    pub fn from_config(conf: &ThemeConfig, accessibility: &AccessibilityConfig) -> Self {
+        let preset = match accessibility.high_contrast {
+            true => ThemePreset::HighContrast,
+            false => conf.preset,
+        };
+        let base = match preset {
+            ThemePreset::Dark => Self::dark(),
+            ThemePreset::Light => Self::light(),
+            ThemePreset::HighContrast => Self::accessible(),
+        };
+        let mut log = LogStyle::new(
+            patch(base.log.time(), &conf.log_time),
+            patch(base.log.uname(), &conf.log_username),
+            patch(base.log.msg(), &conf.log_message),
+            patch(base.log.delims(), &conf.log_delims),
+            patch(base.log.highlight(), &conf.log_highlight),
+        )
+        .with_palette(
+            conf.username_palette
+                .clone()
+                .unwrap_or_else(|| base.log.palette().to_vec()),
+        );
+        for (&kind, spec) in &conf.log_kinds {
+            let style = KindStyle {
+                time: spec.time.as_ref().map(|s| s.apply(log.time())),
+                uname: spec.username.as_ref().map(|s| s.apply(log.uname())),
+                msg: spec.message.as_ref().map(|s| s.apply(log.msg_for(kind))),
+                delims: spec.delims.as_ref().map(|s| s.apply(log.delims())),
+                time_fmt: spec.time_format.clone(),
+            };
+            log = log.with_kind(kind, style);
+        }
+        let input = patch(base.input, &conf.input);
+        let (borders, scrollbar) = match accessibility.ascii_borders {
+            true => (ASCII_BORDERS, ASCII_SCROLLBAR),
+            false => (base.borders, base.scrollbar),
+        };
+        Self {
+            border: patch(base.border, &conf.border),
+            title: patch(base.title, &conf.title),
+            input,
+            caret: match accessibility.block_caret {
+                true => input.add_modifier(Modifier::REVERSED),
+                false => patch(base.caret, &conf.caret),
+            },
+            log,
+            mode_navigate: patch(base.mode_navigate, &conf.mode_navigate),
+            mode_insert: patch(base.mode_insert, &conf.mode_insert),
+            mode_command: patch(base.mode_command, &conf.mode_command),
+            mode_disconnected: patch(base.mode_disconnected, &conf.mode_disconnected),
+            borders,
+            scrollbar,
+        }
+    }
+
+    pub fn mode(&self, mode: &Mode) -> Style {
+        match mode {
+            Mode::Navigate | Mode::RoomList | Mode::Copy => self.mode_navigate,
+            Mode::Insert => self.mode_insert,
+            Mode::InsertCommand | Mode::Search => self.mode_command,
+            Mode::Disconnected => self.mode_disconnected,
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// How many toasts are on screen at once, the rest wait their turn.
+const MAX_SHOWN: usize = 3;
+
+/// How many can wait, past this the oldest waiting are dropped.
+const MAX_QUEUED: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Info,
+    /// Something asked for went through, like an export.
+    Success,
+    Error,
+}
+
+impl Severity {
+    /// Errors stay up long enough to be read in full.
+    fn lifetime(self) -> Duration {
+        match self {
+            Severity::Error => Duration::from_secs(8),
+            Severity::Info | Severity::Success => Duration::from_secs(4),
+        }
+    }
+
+    /// The key of the word shown over the toast, so severity isn't told by
+    /// color alone.
+    pub fn label(self) -> &'static str {
+        match self {
+            Severity::Info => "toast.info",
+            Severity::Success => "toast.success",
+            Severity::Error => "toast.error",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Toast {
+    pub text: String,
+    pub severity: Severity,
+    /// When it made it on screen, its time only runs from then.
+    shown: Option<Instant>,
+}
+
+/// Short lived notices drawn in a corner of the chat log, oldest on top.
+#[derive(Debug, Default)]
+pub struct Toasts {
+    queue: VecDeque<Toast>,
+}
+
+impl Toasts {
+    pub fn push(&mut self, severity: Severity, text: String) {
+        self.queue.push_back(Toast {
+            text,
+            severity,
+            shown: None,
+        });
+        while self.queue.len() > MAX_QUEUED {
+            // never drop one being shown for one nobody has seen
+            self.queue.remove(MAX_SHOWN);
+        }
+        self.start_shown();
+    }
+
+    /// Drops the toasts that have been up long enough, starting the clock
+    /// on those taking their place. Returns whether any went.
+    pub fn expire(&mut self) -> bool {
+        let before = self.queue.len();
+        self.queue.retain(|toast| {
+            toast
+                .shown
+                .is_none_or(|shown| shown.elapsed() < toast.severity.lifetime())
+        });
+        self.start_shown();
+        self.queue.len() != before
+    }
+
+    fn start_shown(&mut self) {
+        for toast in self.queue.iter_mut().take(MAX_SHOWN) {
+            toast.shown.get_or_insert_with(Instant::now);
+        }
+    }
+
+    /// What's on screen now, oldest first.
+    pub fn shown(&self) -> impl Iterator<Item = &Toast> {
+        self.queue.iter().take(MAX_SHOWN)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn waiting_toasts_take_the_place_of_expired_ones() {
+        let mut toasts = Toasts::default();
+        for n in 0..5 {
+            toasts.push(Severity::Info, n.to_string());
+        }
+        let shown = |toasts: &Toasts| toasts.shown().map(|t| t.text.clone()).collect::<Vec<_>>();
+        assert_eq!(shown(&toasts), ["0", "1", "2"]);
+        assert!(!toasts.expire());
+
+        // as if the first had been up a while
+        toasts.queue[0].shown = Instant::now().checked_sub(Duration::from_secs(5));
+        assert!(toasts.expire());
+        assert_eq!(shown(&toasts), ["1", "2", "3"]);
+        assert!(toasts.queue[2].shown.is_some());
+        assert!(toasts.queue[3].shown.is_none());
+    }
+}
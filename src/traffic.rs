@@ -0,0 +1,120 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crate::i18n::{tr, trf};
+
+/// How far back the per second rates look.
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Messages going one way over the socket.
+#[derive(Debug, Clone, Default)]
+pub struct Flow {
+    pub messages: u64,
+    pub bytes: u64,
+    /// When the messages of the last second went.
+    recent: VecDeque<Instant>,
+}
+
+impl Flow {
+    pub fn record(&mut self, bytes: usize) {
+        let now = Instant::now();
+        self.messages += 1;
+        self.bytes += bytes as u64;
+        self.recent.push_back(now);
+        while self
+            .recent
+            .front()
+            .is_some_and(|t| now.duration_since(*t) > RATE_WINDOW)
+        {
+            self.recent.pop_front();
+        }
+    }
+
+    pub fn per_sec(&self) -> usize {
+        let now = Instant::now();
+        self.recent
+            .iter()
+            .filter(|t| now.duration_since(**t) <= RATE_WINDOW)
+            .count()
+    }
+}
+
+/// What has gone over the connection this session, for the traffic overlay.
+#[derive(Debug, Clone, Default)]
+pub struct Traffic {
+    pub sent: Flow,
+    pub received: Flow,
+    pub last_decrypt_error: Option<String>,
+    pub reconnects: usize,
+    /// Terminal and socket events the app has yet to take.
+    pub queued_events: usize,
+    /// Messages waiting for a connection.
+    pub queued_offline: usize,
+    /// Messages held back by the rate limit.
+    pub throttled: usize,
+    /// Ticks and renders left out because one was already waiting.
+    pub coalesced: u64,
+    /// Times a message had to wait for room in the socket's buffer.
+    pub outbound_waits: u64,
+    /// Debug pane entries dropped to make room for newer ones.
+    pub dropped_debug_logs: u64,
+}
+
+impl Traffic {
+    /// The overlay's rows.
+    pub fn lines(&self) -> Vec<String> {
+        let flow = |key, flow: &Flow| trf!(key, flow.per_sec(), flow.messages, size(flow.bytes));
+        vec![
+            flow("traffic.sent", &self.sent),
+            flow("traffic.received", &self.received),
+            trf!("traffic.reconnects", self.reconnects),
+            trf!(
+                "traffic.queues",
+                self.queued_events,
+                self.queued_offline,
+                self.throttled
+            ),
+            trf!(
+                "traffic.dropped",
+                self.coalesced,
+                self.outbound_waits,
+                self.dropped_debug_logs
+            ),
+            trf!(
+                "traffic.decrypt_error",
+                self.last_decrypt_error
+                    .clone()
+                    .unwrap_or_else(|| tr("traffic.none"))
+            ),
+        ]
+    }
+}
+
+/// A byte count in the largest unit that keeps it above one.
+pub fn size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{bytes} B"),
+        _ => format!("{value:.1} {}", UNITS[unit]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_pick_a_readable_unit() {
+        assert_eq!(size(512), "512 B");
+        assert_eq!(size(1536), "1.5 KiB");
+        assert_eq!(size(3 * 1024 * 1024), "3.0 MiB");
+    }
+}
@@ -0,0 +1,153 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+use crate::{chat_log::Log, user_config::data_dir};
+
+/// The `transcripts` section of the user config.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct TranscriptConfig {
+    /// Write every chat message to a file per room and day.
+    pub enabled: bool,
+    /// Defaults to `transcripts` in the data directory.
+    pub dir: Option<String>,
+    /// Size in bytes at which a day's file is rotated, 0 never rotates.
+    pub max_size: u64,
+    /// Days a transcript is kept after it was last written, 0 keeps them
+    /// all.
+    pub max_age_days: u64,
+}
+
+impl Default for TranscriptConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: None,
+            max_size: 5 * 1024 * 1024,
+            max_age_days: 90,
+        }
+    }
+}
+
+impl TranscriptConfig {
+    pub fn dir(&self) -> PathBuf {
+        match &self.dir {
+            Some(dir) => PathBuf::from(dir),
+            None => data_dir().join("transcripts"),
+        }
+    }
+}
+
+/// Writes chat messages to `<dir>/<room>/<date>.log`, one line each. A file
+/// that grows past the size limit is moved aside to `<date>.<n>.log`.
+#[derive(Debug)]
+pub struct Transcripts {
+    dir: PathBuf,
+    max_size: u64,
+}
+
+impl Transcripts {
+    /// Starts writing under the configured directory, first removing the
+    /// transcripts that are too old to keep.
+    pub fn open(config: &TranscriptConfig) -> Self {
+        let transcripts = Self {
+            dir: config.dir(),
+            max_size: config.max_size,
+        };
+        if config.max_age_days > 0 {
+            let max_age = Duration::from_secs(config.max_age_days * 24 * 60 * 60);
+            if let Err(e) = transcripts.prune(max_age) {
+                tracing::error!("Could not remove old transcripts: {e}");
+            }
+        }
+
+        transcripts
+    }
+
+    pub fn write(&mut self, room: &str, log: &Log) {
+        let ts = log.ts.with_timezone(&Local);
+        let line = format!("[{}] <{}> {}", ts.format("%H:%M:%S"), log.from, log.msg);
+        let dir = self.dir.join(file_name(room));
+        let path = dir.join(format!("{}.log", ts.format("%Y-%m-%d")));
+        let result = fs::create_dir_all(&dir)
+            .and_then(|_| self.rotate(&path))
+            .and_then(|_| OpenOptions::new().create(true).append(true).open(&path))
+            .and_then(|mut file| writeln!(file, "{line}"));
+        if let Err(e) = result {
+            tracing::error!("Could not write to transcript {}: {e}", path.display());
+        }
+    }
+
+    /// Moves `path` aside if it has reached the size limit.
+    fn rotate(&self, path: &Path) -> io::Result<()> {
+        let full =
+            self.max_size > 0 && fs::metadata(path).is_ok_and(|meta| meta.len() >= self.max_size);
+        if !full {
+            return Ok(());
+        }
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let rotated = (1..)
+            .map(|n| path.with_file_name(format!("{stem}.{n}.log")))
+            .find(|p| !p.exists())
+            .expect("there is always a free name");
+        fs::rename(path, rotated)
+    }
+
+    /// Removes transcripts last written longer than `max_age` ago.
+    fn prune(&self, max_age: Duration) -> io::Result<()> {
+        let Ok(rooms) = fs::read_dir(&self.dir) else {
+            return Ok(());
+        };
+        let now = SystemTime::now();
+        for room in rooms.filter_map(Result::ok) {
+            if !room.file_type()?.is_dir() {
+                continue;
+            }
+            for file in fs::read_dir(room.path())?.filter_map(Result::ok) {
+                let modified = file.metadata()?.modified()?;
+                if now.duration_since(modified).unwrap_or_default() > max_age {
+                    fs::remove_file(file.path())?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The room name as a directory name, characters that could escape the
+/// transcript directory or upset a file system are replaced.
+fn file_name(room: &str) -> String {
+    let safe = |c: char| c.is_alphanumeric() || matches!(c, '-' | '_' | ' ');
+    let name: String = room
+        .chars()
+        .map(|c| match safe(c) {
+            true => c,
+            false => '_',
+        })
+        .collect();
+    match name.trim().is_empty() {
+        true => "_".into(),
+        false => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn room_names_stay_in_the_transcript_directory() {
+        assert_eq!(file_name("lobby"), "lobby");
+        assert_eq!(file_name("../etc"), "___etc");
+        assert_eq!(file_name("a/b\\c"), "a_b_c");
+        assert_eq!(file_name(""), "_");
+    }
+}
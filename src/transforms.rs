@@ -0,0 +1,86 @@
+use std::fmt::Debug;
+
+use serde::{Deserialize, Serialize};
+
+/// Rewrites the content of incoming messages before they become logs.
+pub trait Transform: Debug {
+    fn apply(&self, sender: &str, content: String) -> String;
+}
+
+/// Built-in transforms as they are configured in the user config, applied in
+/// the order they are listed.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TransformConfig {
+    /// Replaces every occurrence of the words, ignoring case, with asterisks.
+    Mask { words: Vec<String> },
+    /// Replaces every occurrence of `from` with `to`, e.g. custom emoji.
+    Replace { from: String, to: String },
+}
+
+#[derive(Debug)]
+struct Mask {
+    words: Vec<String>,
+}
+
+impl Transform for Mask {
+    fn apply(&self, _sender: &str, mut content: String) -> String {
+        for word in &self.words {
+            let lower = content.to_lowercase();
+            let word = word.to_lowercase();
+            if word.is_empty() || lower.len() != content.len() {
+                // byte offsets from the lowercased copy only line up when
+                // lowercasing didn't change the length
+                continue;
+            }
+            let mut from = 0;
+            while let Some(pos) = lower[from..].find(&word) {
+                let start = from + pos;
+                content.replace_range(start..start + word.len(), &"*".repeat(word.len()));
+                from = start + word.len();
+            }
+        }
+        content
+    }
+}
+
+#[derive(Debug)]
+struct Replace {
+    from: String,
+    to: String,
+}
+
+impl Transform for Replace {
+    fn apply(&self, _sender: &str, content: String) -> String {
+        content.replace(&self.from, &self.to)
+    }
+}
+
+/// The ordered list of registered transforms.
+#[derive(Debug, Default)]
+pub struct TransformChain {
+    transforms: Vec<Box<dyn Transform>>,
+}
+
+impl TransformChain {
+    pub fn from_config(conf: &[TransformConfig]) -> Self {
+        let mut chain = Self::default();
+        for t in conf {
+            match t.clone() {
+                TransformConfig::Mask { words } => chain.register(Mask { words }),
+                TransformConfig::Replace { from, to } => chain.register(Replace { from, to }),
+            }
+        }
+        chain
+    }
+
+    pub fn register(&mut self, transform: impl Transform + 'static) {
+        self.transforms.push(Box::new(transform));
+    }
+
+    pub fn apply(&self, sender: &str, content: String) -> String {
+        self.transforms
+            .iter()
+            .fold(content, |content, t| t.apply(sender, content))
+    }
+}
@@ -0,0 +1,19 @@
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::error::ClientError;
+
+/// What messages to and from the server go over: the websocket, or a
+/// session daemon's channels, in the app, and a scripted server in tests.
+/// Login and resuming only ever talk through this.
+pub trait Transport {
+    async fn send(&mut self, msg: Message) -> Result<(), ClientError>;
+
+    /// The next message from the server, an error once there won't be any.
+    async fn recv(&mut self) -> Result<Message, ClientError>;
+
+    /// Whether what's sent is encrypted on the way, which a password has
+    /// to be on top of the session's own encryption.
+    fn is_secure(&self) -> bool {
+        false
+    }
+}
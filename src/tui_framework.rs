@@ -1,37 +1,71 @@
-use std::{io, panic};
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+    panic,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
 
 use chrono::{DateTime, Utc};
 use color_eyre::Result;
 use crossterm::{
     event::{
-        DisableMouseCapture, EnableMouseCapture, Event as CrosstermEvent, KeyCode, KeyEvent,
+        DisableBracketedPaste, DisableFocusChange, DisableMouseCapture, EnableBracketedPaste,
+        EnableFocusChange, EnableMouseCapture, Event as CrosstermEvent, KeyCode, KeyEvent,
         KeyEventKind, MouseEvent,
     },
-    terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
+    terminal::{self, EnterAlternateScreen, LeaveAlternateScreen, SetTitle},
 };
-use futures::{stream::StreamExt, FutureExt};
-use log2 as log;
-use marain_api::prelude::{ClientMsg, ClientMsgBody, Key, ServerMsg, ServerMsgBody, Status};
+use futures::{stream::StreamExt, FutureExt, SinkExt};
+use marain_api::prelude::{ClientMsg, ClientMsgBody, ServerMsg, Timestamp};
+use ratatui::prelude::Rect;
+use serde::{Deserialize, Serialize};
 use tokio::{
-    sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Notify,
+    },
     task::JoinHandle,
 };
 use tokio_tungstenite::tungstenite::Message;
-use x25519_dalek::PublicKey;
-
-use sphinx::prelude::{cbc_decode, cbc_encode, get_rng};
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
 
 pub type CrosstermTerminal = ratatui::Terminal<ratatui::backend::CrosstermBackend<std::io::Stdout>>;
 
 use crate::{
     app::App,
+    error::ClientError,
+    graphics::{self, Protocol},
+    links,
+    rate_limit::{Overflow, RateLimitConfig, TokenBucket},
+    recording::Recorder,
+    session::Session,
+    shared_secret,
     socket_client::{SocketClient, SocketConf},
+    traffic::{Flow, Traffic},
     ui,
 };
 
+/// How long to wait on shutdown for the server to close the socket.
+const DRAIN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+/// Quiet time after which ticks and renders slow down to `IDLE_FREQ`.
+const IDLE_AFTER: std::time::Duration = std::time::Duration::from_secs(10);
+/// Ticks and renders per second while idle.
+const IDLE_FREQ: f64 = 2.0;
+/// Terminal and socket events that can wait for the app. Once full the
+/// worker stops reading until there's room.
+const EVENT_BUFFER: usize = 256;
+/// Events from the app to itself: messages to send and results of the
+/// work it started.
+const COMMAND_BUFFER: usize = 64;
+
 /// Terminal events.
 #[allow(dead_code)]
-#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Event {
     /// App Initialization
     Init,
@@ -41,22 +75,24 @@ pub enum Event {
     Closed,
     /// Render Event
     Render,
-    /// Indicates window focus lost
-    FocusGained,
     /// Indicates window focus gained
+    FocusGained,
+    /// Indicates window focus lost
     FocusLost,
-    /// Error Event
-    Error,
+    /// Something went wrong that the app should know about
+    Error(ClientError),
     /// Terminal tick.
     Tick,
     /// Key press.
     Key(KeyEvent),
     /// Mouse click/scroll.
     Mouse(MouseEvent),
+    /// Text pasted into the terminal, all of it at once.
+    Paste(String),
     /// Terminal resize.
     Resize(u16, u16),
-    /// Inbound Message.
-    Recv(Vec<u8>),
+    /// Inbound message, already opened by the socket worker.
+    ServerMsg(ServerMsg),
     /// Outbound Message.
     Send {
         token: String,
@@ -73,6 +109,44 @@ pub enum Event {
     },
     /// Server closed the socket connection
     ServerClose,
+    /// Nothing heard from the server within the keepalive timeout, or the
+    /// socket went away without a close
+    ConnectionLost,
+    /// Link to an uploaded paste, or why the upload failed
+    PasteResult(Result<String, String>),
+    /// Captured stdout of a `/run` command, or why it failed
+    RunResult(Result<String, String>),
+    /// Log in again after the connection went away
+    Reconnect,
+}
+
+impl Event {
+    /// The variant alone, for logs, which mustn't carry what was typed or
+    /// said.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::Init => "init",
+            Event::Quit => "quit",
+            Event::Closed => "closed",
+            Event::Render => "render",
+            Event::FocusGained => "focus_gained",
+            Event::FocusLost => "focus_lost",
+            Event::Error(_) => "error",
+            Event::Tick => "tick",
+            Event::Key(_) => "key",
+            Event::Mouse(_) => "mouse",
+            Event::Paste(_) => "paste",
+            Event::Resize(..) => "resize",
+            Event::ServerMsg(_) => "server_msg",
+            Event::Send { .. } => "send",
+            Event::ServerCommand { .. } => "server_command",
+            Event::ServerClose => "server_close",
+            Event::ConnectionLost => "connection_lost",
+            Event::PasteResult(_) => "paste_result",
+            Event::RunResult(_) => "run_result",
+            Event::Reconnect => "reconnect",
+        }
+    }
 }
 
 impl From<char> for Event {
@@ -87,6 +161,46 @@ impl From<KeyCode> for Event {
     }
 }
 
+/// What the worker has put in the event channel that the app has yet to
+/// take.
+#[derive(Debug, Default)]
+struct Backlog {
+    queued: AtomicUsize,
+    tick_queued: AtomicBool,
+    render_queued: AtomicBool,
+    /// Ticks and renders left out because one was already waiting.
+    coalesced: AtomicU64,
+}
+
+impl Backlog {
+    /// Whether `event` should go in the channel, one tick and one render
+    /// waiting are as good as any number of them.
+    fn admit(&self, event: &Event) -> bool {
+        let queued = match event {
+            Event::Tick => self.tick_queued.swap(true, Ordering::Relaxed),
+            Event::Render => self.render_queued.swap(true, Ordering::Relaxed),
+            _ => false,
+        };
+        if queued {
+            self.coalesced.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.queued.fetch_add(1, Ordering::Relaxed);
+        }
+        !queued
+    }
+
+    fn taken(&self, event: &Event) {
+        match event {
+            Event::Tick => self.tick_queued.store(false, Ordering::Relaxed),
+            Event::Render => self.render_queued.store(false, Ordering::Relaxed),
+            _ => {}
+        }
+        _ = self
+            .queued
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1));
+    }
+}
+
 pub struct TuiConf {
     pub update_freq: f64,
     pub render_freq: f64,
@@ -108,26 +222,52 @@ impl Default for TuiConf {
 /// It is responsible for setting up the terminal,
 /// initializing the interface and handling the draw events.
 pub struct Tui {
-    /// Interface to the Terminal.
-    pub terminal: CrosstermTerminal,
+    /// Interface to the Terminal, none when running headless.
+    pub terminal: Option<CrosstermTerminal>,
     pub task: Option<JoinHandle<()>>,
     pub socket_conf: SocketConf,
 
-    pub receiver: UnboundedReceiver<Event>,
+    pub receiver: Receiver<Event>,
 
-    pub sender: UnboundedSender<Event>,
-    pub socket_sender: Option<futures::channel::mpsc::UnboundedSender<Message>>,
+    pub sender: Sender<Event>,
+    /// Terminal and socket events, kept apart so a flood of them can't
+    /// crowd out the app's own.
+    worker_events: Receiver<Event>,
+    worker_sender: Sender<Event>,
+    pub socket_sender: Option<futures::channel::mpsc::Sender<Message>>,
 
     pub frame_rate: f64,
 
     pub update_rate: f64,
-    shared_secret: Option<[u8; 32]>,
+    session: Option<Session>,
+    /// Messages sent while disconnected, flushed in order on reconnect.
+    queue: VecDeque<ClientMsg>,
+    /// Thumbnails on screen after the last draw.
+    placed: Vec<(Rect, PathBuf)>,
+    /// Stops the event loop worker started last.
+    shutdown: CancellationToken,
+    rate_limit: RateLimitConfig,
+    bucket: TokenBucket,
+    /// Encrypted messages held back by the rate limit, oldest first.
+    throttled: VecDeque<Vec<u8>>,
+    pub traffic: Traffic,
+    /// What came from the server, counted by the worker as it opens it.
+    received: Arc<Mutex<Flow>>,
+    backlog: Arc<Backlog>,
+    /// Wakes the worker from idle when a message goes out.
+    activity: Arc<Notify>,
+    /// Where events go for `--record`.
+    pub recorder: Option<Recorder>,
+    /// Last title given to the terminal, so it's only set when it changes.
+    title: String,
 }
 
 impl Tui {
-    /// Constructs a new instance of [`Tui`].
-    pub fn new(terminal: CrosstermTerminal) -> Self {
-        let (sender, receiver) = unbounded_channel::<Event>();
+    /// Constructs a new instance of [`Tui`]. Without a terminal nothing is
+    /// drawn and no input is read, events only come from the socket.
+    pub fn new(terminal: Option<CrosstermTerminal>) -> Self {
+        let (sender, receiver) = channel::<Event>(COMMAND_BUFFER);
+        let (worker_sender, worker_events) = channel::<Event>(EVENT_BUFFER);
         Self {
             terminal,
             task: None,
@@ -135,20 +275,38 @@ impl Tui {
             socket_sender: None,
             sender,
             receiver,
+            worker_events,
+            worker_sender,
             frame_rate: 60.0,
             update_rate: 60.0,
-            shared_secret: None,
+            session: None,
+            queue: VecDeque::new(),
+            placed: vec![],
+            shutdown: CancellationToken::new(),
+            rate_limit: RateLimitConfig::default(),
+            bucket: TokenBucket::new(&RateLimitConfig::default()),
+            throttled: VecDeque::new(),
+            traffic: Traffic::default(),
+            received: Arc::default(),
+            backlog: Arc::new(Backlog::default()),
+            activity: Arc::new(Notify::new()),
+            recorder: None,
+            title: String::new(),
         }
     }
 
-    pub fn from_conf(terminal: CrosstermTerminal, config: TuiConf) -> Self {
+    pub fn from_conf(terminal: Option<CrosstermTerminal>, config: TuiConf) -> Self {
         Self::new(terminal)
             .set_render_freq(config.render_freq)
             .set_update_freq(config.update_freq)
     }
 
-    pub fn set_shared_secret(&mut self, shared_secret: Key) {
-        self.shared_secret = Some(shared_secret);
+    pub fn set_session(&mut self, session: Session) {
+        self.session = Some(session);
+    }
+
+    pub fn session(&self) -> Option<&Session> {
+        self.session.as_ref()
     }
 
     /// Fluent setter for the render frequency.
@@ -173,8 +331,19 @@ impl Tui {
         self
     }
 
-    pub fn default_client(self) -> Self {
-        self.configure_client(SocketConf::default())
+    /// Fluent setter for how fast messages may go to the server.
+    pub fn with_rate_limit(mut self, rate_limit: &RateLimitConfig) -> Self {
+        self.bucket = TokenBucket::new(rate_limit);
+        self.rate_limit = rate_limit.clone();
+
+        self
+    }
+
+    /// Fluent setter for the recording every handled event is written to.
+    pub fn with_recorder(mut self, recorder: Option<Recorder>) -> Self {
+        self.recorder = recorder;
+
+        self
     }
 
     /// Initializes the terminal interface.
@@ -182,18 +351,28 @@ impl Tui {
     /// It enables the raw mode and sets terminal properties.
     pub async fn enter(&mut self, client: SocketClient) -> Result<()> {
         terminal::enable_raw_mode()?;
-        crossterm::execute!(io::stderr(), EnterAlternateScreen, EnableMouseCapture)?;
+        crossterm::execute!(
+            io::stderr(),
+            EnterAlternateScreen,
+            EnableMouseCapture,
+            EnableFocusChange,
+            EnableBracketedPaste
+        )?;
 
         // Define a custom panic hook to reset the terminal properties.
         // This way, you won't have your terminal messed up if an unexpected error happens.
         let panic_hook = panic::take_hook();
         panic::set_hook(Box::new(move |panic| {
-            Self::reset().expect("failed to reset the terminal");
+            if let Err(e) = Self::reset() {
+                eprintln!("failed to reset the terminal: {e}");
+            }
             panic_hook(panic);
         }));
 
-        self.terminal.hide_cursor()?;
-        self.terminal.clear()?;
+        if let Some(terminal) = self.terminal.as_mut() {
+            terminal.hide_cursor()?;
+            terminal.clear()?;
+        }
         self.start(client).await;
 
         Ok(())
@@ -204,7 +383,60 @@ impl Tui {
     /// [`Draw`]: tui::Terminal::draw
     /// [`rendering`]: crate::ui:render
     pub fn draw(&mut self, app: &mut App) -> Result<()> {
-        self.terminal.draw(|frame| ui::render(app, frame))?;
+        let Some(terminal) = self.terminal.as_mut() else {
+            return Ok(());
+        };
+        terminal.draw(|frame| ui::render(app, frame))?;
+        if let Some(protocol) = app.graphics {
+            self.draw_thumbnails(protocol, app)?;
+        }
+        // last, redrawing for the thumbnails would write over them
+        links::write_hyperlinks(&app.link_placements, &mut io::stdout())?;
+        if app.config.window_title {
+            self.set_title(app.window_title())?;
+        }
+        Ok(())
+    }
+
+    fn set_title(&mut self, title: String) -> Result<()> {
+        if title != self.title {
+            crossterm::execute!(io::stdout(), SetTitle(&title))?;
+            self.title = title;
+        }
+        Ok(())
+    }
+
+    /// Rings the terminal bell, not when headless where stdout is the
+    /// script's report.
+    pub fn bell(&mut self) -> Result<()> {
+        if self.terminal.is_some() {
+            let mut out = io::stdout();
+            out.write_all(b"\x07")?;
+            out.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Thumbnails only need drawing again when they've moved.
+    fn draw_thumbnails(&mut self, protocol: Protocol, app: &mut App) -> Result<()> {
+        let Some(terminal) = self.terminal.as_mut() else {
+            return Ok(());
+        };
+        if app.image_placements != self.placed {
+            if protocol != Protocol::Kitty && !self.placed.is_empty() {
+                // the old ones are painted into the cells, clearing is the
+                // only way to get rid of them
+                terminal.clear()?;
+                terminal.draw(|frame| ui::render(app, frame))?;
+            }
+            graphics::draw(
+                protocol,
+                &app.image_placements,
+                &app.thumbnails,
+                &mut io::stdout(),
+            )?;
+            self.placed = app.image_placements.clone();
+        }
         Ok(())
     }
 
@@ -214,54 +446,54 @@ impl Tui {
     /// the terminal properties if unexpected errors occur.
     fn reset() -> Result<()> {
         terminal::disable_raw_mode()?;
-        crossterm::execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture)?;
+        crossterm::execute!(
+            io::stdout(),
+            LeaveAlternateScreen,
+            DisableMouseCapture,
+            DisableFocusChange,
+            DisableBracketedPaste
+        )?;
         Ok(())
     }
 
     /// Exits the terminal interface.
     ///
-    /// It disables the raw mode and reverts back the terminal properties.
-    pub fn exit(&mut self) -> Result<()> {
-        Self::reset()?;
-        self.terminal.show_cursor()?;
+    /// It stops the event loop, disables the raw mode and reverts back the
+    /// terminal properties.
+    pub async fn exit(&mut self) -> Result<()> {
+        self.log_out();
+        self.stop().await;
+        if let Some(terminal) = self.terminal.as_mut() {
+            Self::reset()?;
+            terminal.show_cursor()?;
+        }
         Ok(())
     }
 
-    pub fn get_sender(&self) -> UnboundedSender<Event> {
+    pub fn get_sender(&self) -> Sender<Event> {
         self.sender.clone()
     }
 
-    pub async fn connect(
-        &mut self,
-        on_connect: ClientMsg,
-    ) -> Option<(SocketClient, String, PublicKey)> {
-        let mut client: SocketClient = self.socket_conf.spawn_client().await;
-        let socket_sender = client.out_sink.clone();
-        socket_sender
-            .unbounded_send(Message::Binary(
-                bincode::serialize(&on_connect).expect("The api code is broken"),
-            ))
-            .expect("Could not connect to the marain server.");
-
-        match client.next().await {
-            Ok(msg) => match msg.clone() {
-                Message::Binary(data) => match bincode::deserialize::<ServerMsg>(&data[..]) {
-                    Ok(ServerMsg {
-                        status: Status::Yes,
-                        body: ServerMsgBody::LoginSuccess { token, public_key },
-                        ..
-                    }) => Some((client, token, PublicKey::from(public_key))),
-                    _ => {
-                        log::error!("Login failed, could not deserialize server message: {msg:?}");
-                        None
-                    }
-                },
-                _ => {
-                    log::error!("Unexpected message format from server {msg:?}");
-                    None
-                }
-            },
-            _ => None,
+    /// Sends what the rate limit is still holding back, there's no later to
+    /// send it in. The protocol has no logout message, the server ends the
+    /// session when the socket closes, which `stop` then waits for.
+    fn log_out(&mut self) {
+        while let Some(encoded) = self.throttled.pop_front() {
+            if !matches!(self.send_encoded(encoded), Ok(true)) {
+                tracing::error!("Quit with {} messages unsent", self.throttled.len());
+                break;
+            }
+        }
+    }
+
+    /// Stops the event loop worker and waits for it to close the socket.
+    pub async fn stop(&mut self) {
+        self.shutdown.cancel();
+        let Some(task) = self.task.take() else {
+            return;
+        };
+        if let Err(e) = task.await {
+            tracing::error!("The event loop worker failed: {e}");
         }
     }
 
@@ -270,137 +502,306 @@ impl Tui {
         let update_delay = std::time::Duration::from_secs_f64(1.0 / self.update_rate);
         let render_delay = std::time::Duration::from_secs_f64(1.0 / self.frame_rate);
 
-        let socket_sender = client.out_sink.clone();
+        let mut socket_sender = client.out_sink.clone();
         self.socket_sender = Some(socket_sender.clone());
 
-        let update_sender = self.sender.clone();
+        let update_sender = self.worker_sender.clone();
+        let read_input = self.terminal.is_some();
+        let backlog = self.backlog.clone();
+        let activity = self.activity.clone();
+        // messages are opened here, keeping crypto off the UI's thread
+        let session = self.session.clone();
+        let received = self.received.clone();
+        let idle_delay = std::time::Duration::from_secs_f64(1.0 / IDLE_FREQ);
+        let shutdown = CancellationToken::new();
+        self.shutdown = shutdown.clone();
 
         // worker code -----
-        let task = tokio::spawn(async move {
+        let span = tracing::info_span!("events");
+        let work = async move {
             let mut reader = crossterm::event::EventStream::new();
             let mut update_interval = tokio::time::interval(update_delay);
             let mut render_interval = tokio::time::interval(render_delay);
             let mut client = client;
+            // stop polling the socket once it's gone, it would error forever
+            let mut connected = true;
+            // nothing is happening, ticks and renders are slowed down
+            let mut idle = false;
+            let mut active_at = std::time::Instant::now();
 
             loop {
-                let update_delay = update_interval.tick();
-                let render_delay = render_interval.tick();
+                let update_tick = update_interval.tick();
+                let render_tick = render_interval.tick();
                 let input_event = reader.next().fuse();
                 let server_event = client.next().fuse();
 
-                tokio::select! {
-                    maybe_recv = server_event => {
-                        match maybe_recv {
-                            Ok(message) => {
-                                match message {
-                                    Message::Binary(data) => {
-                                        update_sender.send(Event::Recv(data)).unwrap();
-                                    }
-                                    Message::Close(_) => {
-                                        update_sender.send(Event::ServerClose).unwrap();
-                                    }
-                                    _ => {
-                                        panic!("No implementation for message:\n {message:#?}");
-                                    }
-                                }
-                            },
-                            Err(e) => {
-                                panic!("Failed to receive message over receiver: {e}");
-                            },
+                let event = tokio::select! {
+                    _ = shutdown.cancelled() => break,
+                    _ = activity.notified() => None,
+                    maybe_recv = server_event, if connected => match maybe_recv {
+                        Ok(Message::Binary(data)) => {
+                            if let Ok(mut received) = received.lock() {
+                                received.record(data.len());
+                            }
+                            Some(match session {
+                                Some(ref session) => session
+                                    .decode(data)
+                                    .map_or_else(Event::Error, Event::ServerMsg),
+                                None => Event::Error(ClientError::NoSharedSecret),
+                            })
                         }
-                    }
-                    maybe_input = input_event => {
-                        // user events
-                        match maybe_input {
-                            Some(Ok(evt)) => match evt {
-                                CrosstermEvent::Key(key) => {
-                                    if key.kind == KeyEventKind::Press {
-                                        update_sender.send(Event::Key(key)).unwrap();
-                                    }
-                                }
-                                CrosstermEvent::Mouse(e) => {
-                                    update_sender.send(Event::Mouse(e)).unwrap();
-                                }
-                                CrosstermEvent::Resize(w, h) => {
-                                    update_sender.send(Event::Resize(w, h)).unwrap();
-                                }
-                                _ => log::info!("Handler not implemented for: {:?}", evt),
-                            },
-                            Some(Err(_)) => {
-                                update_sender.send(Event::Error).unwrap();
-                            },
-                            None => {},
+                        Ok(Message::Close(_)) => {
+                            connected = false;
+                            Some(Event::ServerClose)
+                        }
+                        Ok(message) => Some(Event::Error(ClientError::UnexpectedMessage(
+                            format!("{message:?}"),
+                        ))),
+                        Err(e) => {
+                            tracing::error!("Lost the socket connection: {e}");
+                            connected = false;
+                            Some(Event::ConnectionLost)
                         }
                     },
-                    // backend/app update trigger
-                    _update_tick = update_delay => {
-                        update_sender.send(Event::Tick).unwrap();
+                    // user events
+                    maybe_input = input_event, if read_input => match maybe_input {
+                        Some(Ok(evt)) => match evt {
+                            CrosstermEvent::Key(key) if key.kind == KeyEventKind::Press => {
+                                Some(Event::Key(key))
+                            }
+                            CrosstermEvent::Key(_) => None,
+                            CrosstermEvent::Mouse(e) => Some(Event::Mouse(e)),
+                            CrosstermEvent::Paste(text) => Some(Event::Paste(text)),
+                            CrosstermEvent::Resize(w, h) => Some(Event::Resize(w, h)),
+                            CrosstermEvent::FocusGained => Some(Event::FocusGained),
+                            CrosstermEvent::FocusLost => Some(Event::FocusLost),
+                        },
+                        Some(Err(e)) => Some(Event::Error(ClientError::Input(e.to_string()))),
+                        None => None,
                     },
+                    // backend/app update trigger
+                    _update_tick = update_tick => Some(Event::Tick),
                     // render trigger
-                    _frame_tick = render_delay => {
-                        update_sender.send(Event::Render).unwrap();
+                    _frame_tick = render_tick => Some(Event::Render),
+                };
+
+                if !matches!(event, Some(Event::Tick | Event::Render)) {
+                    active_at = std::time::Instant::now();
+                }
+                if idle != (active_at.elapsed() >= IDLE_AFTER) {
+                    idle = !idle;
+                    // a fresh interval ticks right away, so waking up
+                    // redraws without waiting out the slow one
+                    let (update, render) = match idle {
+                        true => (idle_delay, idle_delay),
+                        false => (update_delay, render_delay),
+                    };
+                    update_interval = tokio::time::interval(update);
+                    render_interval = tokio::time::interval(render);
+                }
+
+                let Some(event) = event.filter(|e| backlog.admit(e)) else {
+                    continue;
+                };
+                // waits for the app to catch up, unless it's shutting down
+                tokio::select! {
+                    _ = shutdown.cancelled() => break,
+                    sent = update_sender.send(event) => {
+                        // the app has gone away, nothing left to do
+                        if sent.is_err() {
+                            break;
+                        }
                     }
                 }
             }
-        });
-        // end worker code --
 
-        self.task = Some(task);
-    }
-
-    fn encrypt_outgoing_msg(&self, serialized: Vec<u8>) -> Vec<u8> {
-        let rng = get_rng();
-        match self.shared_secret {
-            Some(k) => match cbc_encode(k.to_vec(), serialized, rng) {
-                Ok(enc) => enc,
-                Err(e) => {
-                    panic!("Failed to encrypt outgoing message with error: {e}");
+            // say goodbye once everything before it has gone out, and read
+            // whatever the server still had in flight, nobody is listening
+            // for it any more
+            let close =
+                tokio::time::timeout(DRAIN_TIMEOUT, socket_sender.send(Message::Close(None)));
+            if connected && matches!(close.await, Ok(Ok(()))) {
+                let drain = async { while client.next().await.is_ok() {} };
+                if tokio::time::timeout(DRAIN_TIMEOUT, drain).await.is_err() {
+                    tracing::error!("The server did not close the socket in time");
                 }
-            },
-            None => panic!("No key for encryption of outgoing message."),
-        }
-    }
+            }
+        };
+        let task = tokio::spawn(work.instrument(span));
+        // end worker code --
 
-    pub fn decrypt_incoming_msg(&self, enc: Vec<u8>) -> Vec<u8> {
-        match self.shared_secret {
-            Some(k) => match cbc_decode(k.to_vec(), enc) {
-                Ok(dec) => dec,
-                Err(e) => {
-                    panic!("Failed to decrypt incoming message with error: {e}");
-                }
-            },
-            None => panic!("No key for decryption of incoming message."),
-        }
+        self.task = Some(task);
     }
 
     fn serialize_outgoing_msg(outgoing_msg: ClientMsg) -> Option<Vec<u8>> {
         let serialized = match bincode::serialize(&outgoing_msg) {
             Ok(s) => s.to_owned(),
             Err(e) => {
-                log::error!("Could not serialize chat message {e}");
+                tracing::error!("Could not serialize chat message {e}");
                 return None;
             }
         };
         Some(serialized)
     }
 
-    pub fn push_binary_msg_to_server(&self, outgoing_msg: ClientMsg) {
+    /// Sends the message, or holds it back if it would go over the rate
+    /// limit. Fails with `RateLimited` if it had to be dropped instead.
+    pub fn push_binary_msg_to_server(
+        &mut self,
+        outgoing_msg: ClientMsg,
+    ) -> Result<(), ClientError> {
         let serialized = match Self::serialize_outgoing_msg(outgoing_msg) {
             Some(value) => value,
-            None => return,
+            None => return Ok(()),
+        };
+
+        let encoded = match self.session {
+            Some(ref session) => session.seal(serialized)?,
+            None => return Err(ClientError::NoSharedSecret),
+        };
+        // nothing overtakes what is already waiting
+        if self.throttled.is_empty() && self.bucket.try_take() {
+            return self.send_encoded(encoded).map(|_| ());
+        }
+        if self.throttled.len() >= self.rate_limit.max_queued {
+            match self.rate_limit.overflow {
+                Overflow::DropNewest => return Err(ClientError::RateLimited),
+                Overflow::DropOldest => {
+                    tracing::error!("Too many messages held back, dropped the oldest");
+                    self.throttled.pop_front();
+                }
+            }
+        }
+        self.throttled.push_back(encoded);
+        Ok(())
+    }
+
+    /// Sends held back messages as far as the rate limit allows.
+    pub fn flush_throttled(&mut self) -> Result<(), ClientError> {
+        while !self.throttled.is_empty() && self.bucket.try_take() {
+            if let Some(encoded) = self.throttled.pop_front() {
+                if !self.send_encoded(encoded)? {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Messages held back by the rate limit.
+    pub fn throttled(&self) -> usize {
+        self.throttled.len()
+    }
+
+    /// Hands the message to the socket. If its buffer is full the message
+    /// waits at the front of the held back ones and false is returned.
+    fn send_encoded(&mut self, encoded: Vec<u8>) -> Result<bool, ClientError> {
+        let Some(ref mut sender) = self.socket_sender else {
+            return Ok(true);
         };
+        let size = encoded.len();
+        match sender.try_send(Message::Binary(encoded)) {
+            Ok(()) => {
+                self.traffic.sent.record(size);
+                self.activity.notify_one();
+                Ok(true)
+            }
+            Err(e) if e.is_full() => {
+                if let Message::Binary(encoded) = e.into_inner() {
+                    self.throttled.push_front(encoded);
+                }
+                self.traffic.outbound_waits += 1;
+                Ok(false)
+            }
+            Err(e) => Err(ClientError::Socket(e.to_string())),
+        }
+    }
 
-        let encoded = self.encrypt_outgoing_msg(serialized);
+    /// The traffic so far, with how much is waiting in each queue now.
+    pub fn traffic(&self) -> Traffic {
+        Traffic {
+            queued_events: self.backlog.queued.load(Ordering::Relaxed),
+            coalesced: self.backlog.coalesced.load(Ordering::Relaxed),
+            queued_offline: self.queue.len(),
+            throttled: self.throttled.len(),
+            received: self.received.lock().map(|r| r.clone()).unwrap_or_default(),
+            ..self.traffic.clone()
+        }
+    }
+
+    /// Whether the socket worker is still there to take messages.
+    pub fn is_connected(&self) -> bool {
+        self.socket_sender
+            .as_ref()
+            .is_some_and(|sender| !sender.is_closed())
+    }
 
-        if let Some(ref sender) = self.socket_sender.clone() {
-            sender.unbounded_send(Message::Binary(encoded)).unwrap();
+    /// Holds on to a message until the next reconnect. Time requests would
+    /// be stale by then so they're dropped.
+    pub fn queue_msg(&mut self, msg: ClientMsg) {
+        if msg.body != ClientMsgBody::GetTime {
+            self.queue.push_back(msg);
         }
     }
 
+    /// Logs in again with a new connection, returns to `room` and sends
+    /// everything queued while offline. Returns the number of queued
+    /// messages sent.
+    pub async fn reconnect(
+        &mut self,
+        app: &mut App,
+        room: Option<String>,
+    ) -> Result<usize, ClientError> {
+        let client = shared_secret::handle_login_success(self, app).await?;
+        // the old loop is still reading the terminal, swap it out only now
+        self.stop().await;
+        self.start(client).await;
+        self.traffic.reconnects += 1;
+
+        let token = app.token.clone();
+        if let Some(target) = room {
+            self.push_binary_msg_to_server(ClientMsg {
+                token: token.clone(),
+                body: ClientMsgBody::Move { target },
+                timestamp: Timestamp::from(Utc::now()),
+            })?;
+        }
+        let queued = self.queue.len();
+        while let Some(mut msg) = self.queue.pop_front() {
+            // the old session's token is no good any more
+            msg.token = token.clone();
+            self.push_binary_msg_to_server(msg)?;
+        }
+        Ok(queued)
+    }
+
     pub async fn next(&mut self) -> Result<Event> {
-        self.receiver
-            .recv()
-            .await
-            .ok_or(color_eyre::eyre::eyre!("Unable to get event"))
+        tokio::select! {
+            // the app's own events first, they're what the user is waiting on
+            biased;
+            Some(event) = self.receiver.recv() => Ok(event),
+            Some(event) = self.worker_events.recv() => {
+                self.backlog.taken(&event);
+                Ok(event)
+            }
+            else => Err(color_eyre::eyre::eyre!("Unable to get event")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_one_tick_waits_at_a_time() {
+        let backlog = Backlog::default();
+        assert!(backlog.admit(&Event::Tick));
+        assert!(!backlog.admit(&Event::Tick));
+        assert!(backlog.admit(&Event::Resize(1, 1)));
+        backlog.taken(&Event::Tick);
+        assert!(backlog.admit(&Event::Tick));
+        assert_eq!(backlog.coalesced.load(Ordering::Relaxed), 1);
+        assert_eq!(backlog.queued.load(Ordering::Relaxed), 2);
     }
 }
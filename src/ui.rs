@@ -1,116 +1,929 @@
-use crate::{app::App, chat_log::LogStyle};
+use std::{collections::HashMap, path::PathBuf};
+
+use crate::{
+    app::{image_placeholder, App, ConnectionState, Mode},
+    capabilities,
+    chat_log::LogKind,
+    directory::Directory,
+    emoji, graphemes,
+    graphics::{THUMBNAIL_COLS, THUMBNAIL_ROWS},
+    i18n::{tr, trf},
+    links,
+    modal::Modal,
+    toasts::Severity,
+    user_config::{LayoutConfig, MIN_INPUT_ROWS},
+};
 use ratatui::{
-    layout::Offset,
-    prelude::{Frame, Rect, Stylize},
-    style::{Color, Style},
-    text::Span,
-    widgets::{Block, Padding, Paragraph, Wrap},
+    layout::{Alignment, Constraint, Layout, Margin},
+    prelude::{Frame, Rect},
+    style::Modifier,
+    text::{Line, Span},
+    widgets::{
+        block::{Position, Title},
+        Block, Clear, Gauge, List, ListItem, ListState, Padding, Paragraph, Scrollbar,
+        ScrollbarOrientation, ScrollbarState, Tabs, Wrap,
+    },
 };
 
-fn h_split(frame: &Rect, rows: usize) -> [Rect; 2] {
-    let h = frame.height as i32;
+/// Where each pane was last drawn. Kept on the app so mouse events can be
+/// matched to the pane under the pointer.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Panes {
+    /// Empty while there's only one tab.
+    pub tabs: Rect,
+    pub logs: Rect,
+    pub help: Rect,
+    pub room: Rect,
+    pub rooms: Rect,
+    pub input: Rect,
+    /// Empty unless the accessibility live region is on.
+    pub live: Rect,
+    pub status: Rect,
+    /// Empty while the pins pane is hidden.
+    pub pins: Rect,
+    /// Empty while the direct message pane is hidden.
+    pub dms: Rect,
+    /// Empty while the debug pane is hidden.
+    pub debug: Rect,
+}
 
-    let top_area = frame
-        .offset(Offset {
-            x: 0,
-            y: rows as i32,
-        })
-        .intersection(frame.clone())
-        .offset(Offset {
-            x: 0,
-            y: -(rows as i32),
-        });
+/// Narrowest terminal the panes are laid out in.
+const MIN_WIDTH: u16 = 40;
 
-    let bottom_area = frame
-        .offset(Offset {
-            x: 0,
-            y: h - (rows as i32),
-        })
-        .intersection(frame.clone());
+/// Most pinned messages shown before the pins pane scrolls them off.
+const MAX_PIN_ROWS: usize = 5;
+
+/// Rows the chat log and sidebar need on top of the input and status bar.
+const MIN_TOP_ROWS: u16 = 6;
 
-    [top_area, bottom_area]
+/// Which of the panes that come and go are there.
+#[derive(Debug, Clone, Copy)]
+struct Shown {
+    tabs: bool,
+    /// The input area, left out when monitoring.
+    input: bool,
+    sidebar: bool,
+    dms: bool,
+    debug: bool,
+    live: bool,
 }
 
-fn v_split(rect: Rect) -> [Rect; 2] {
-    let w = rect.width as i32;
-    let left_area = rect
-        .offset(Offset { x: w / 3, y: 0 })
-        .intersection(rect.clone())
-        .offset(Offset { x: -w / 3, y: 0 });
-    let right_area = rect
-        .offset(Offset {
-            x: w - (w / 3),
-            y: 0,
+impl Panes {
+    /// Lays the panes out in `area`, or `None` when it's too small to fit
+    /// them all.
+    fn new(
+        area: Rect,
+        conf: &LayoutConfig,
+        input_rows: u16,
+        pin_rows: u16,
+        shown: Shown,
+    ) -> Option<Self> {
+        let tab_rows = shown.tabs as u16;
+        let bottom_rows = 1 + shown.live as u16;
+        // the input gives up rows it grew by before the panes don't fit
+        let spare = area
+            .height
+            .saturating_sub(tab_rows + bottom_rows + MIN_TOP_ROWS);
+        let input_rows = match shown.input {
+            true => input_rows.min(spare).max(MIN_INPUT_ROWS),
+            false => 0,
+        };
+        if area.width < MIN_WIDTH
+            || area.height < tab_rows + input_rows + bottom_rows + MIN_TOP_ROWS
+        {
+            return None;
+        }
+        let [tabs, top, input, live, status] = Layout::vertical([
+            Constraint::Length(tab_rows),
+            Constraint::Min(MIN_TOP_ROWS),
+            Constraint::Length(input_rows),
+            Constraint::Length(shown.live as u16),
+            Constraint::Length(1),
+        ])
+        .areas(area);
+        let [logs, sidebar] = match shown.sidebar {
+            true => Layout::horizontal([
+                Constraint::Percentage(conf.log_percent()),
+                Constraint::Fill(1),
+            ])
+            .areas(top),
+            false => [top, Rect::default()],
+        };
+        // hidden panes get no rows
+        let share = |shown: bool, percent: u16| match shown {
+            true => Constraint::Percentage(percent),
+            false => Constraint::Length(0),
+        };
+        let [pins, logs, dms, debug] = Layout::vertical([
+            Constraint::Max(pin_rows),
+            Constraint::Fill(1),
+            share(shown.dms, conf.dm_percent()),
+            share(shown.debug, conf.debug_percent()),
+        ])
+        .areas(logs);
+        let [help, room, rooms] = Layout::vertical([
+            Constraint::Ratio(2, 4),
+            Constraint::Ratio(1, 4),
+            Constraint::Ratio(1, 4),
+        ])
+        .areas(sidebar);
+
+        Some(Self {
+            tabs,
+            logs,
+            help,
+            room,
+            rooms,
+            input,
+            live,
+            status,
+            pins,
+            dms,
+            debug,
         })
-        .intersection(rect.clone());
+    }
+}
+
+/// Whether the screen cell at `column`, `row` falls inside `area`.
+pub fn contains(area: Rect, column: u16, row: u16) -> bool {
+    area.intersects(Rect::new(column, row, 1, 1))
+}
+
+/// A bordered block drawn with the theme's border characters.
+fn bordered<'a>(app: &App) -> Block<'a> {
+    Block::bordered().border_set(app.theme.borders)
+}
 
-    [left_area, right_area]
+fn server_clock(app: &App) -> Title {
+    let time = match app.server_time() {
+        Some(t) => app.theme.log.format_time(t),
+        None => "--:--:--".to_string(),
+    };
+    Title::from(Span::styled(
+        trf!("pane.server_clock", time),
+        app.theme.title,
+    ))
+    .alignment(Alignment::Right)
 }
 
 fn top_help_widget(app: &App) -> Paragraph {
     Paragraph::new(app.render_keymap())
         .block(
-            Block::bordered()
+            bordered(app)
                 .title(Span::styled(
-                    format!("INFO: {}", app.show_current_mode()),
-                    Style::new().white().on_black(),
+                    trf!("pane.info", app.show_current_mode()),
+                    app.theme.mode(&app.mode),
                 ))
+                .title(server_clock(app))
                 .padding(Padding::left(1)),
         )
-        .green()
-        .on_black()
+        .style(app.theme.border)
 }
 
 fn room_info_widget(app: &App) -> Paragraph {
-    let block = Block::bordered().title(Span::styled(
-        format!("ROOM: {}", app.room_state.room_name),
-        Style::new().fg(Color::White),
-    ));
+    let mut title = trf!("pane.room", app.room_state.room_name);
+    if app.current_room_unread() > 0 {
+        title += &trf!("room.unread_badge", app.current_room_unread());
+    }
+    if app.room_keys.is_encrypted(&app.room_state.room_name) {
+        title += &trf!("room.encrypted", capabilities::symbol("\u{f023}", "[e2e]"));
+    }
+    let block = bordered(app).title(Span::styled(title, app.theme.title));
 
+    let mut occupants: Vec<&String> = app.room_state.occupants.iter().collect();
+    occupants.sort_by_key(|name| name.to_lowercase());
     let mut text = "".to_string();
     let mut prefix: String = "".into();
-    for username in &app.room_state.occupants {
-        text = text + &(prefix + username);
+    for username in occupants {
+        text = match app.bio_of(username) {
+            Some(bio) => text + prefix.as_str() + username.as_str() + " - " + bio,
+            None => text + prefix.as_str() + username.as_str(),
+        };
+        if let Some(presence) = app.presence_of(username) {
+            text += &trf!("room.presence", presence);
+        }
+        if let Some(since) = app.occupants_since.get(username) {
+            text += &trf!("room.since", app.theme.log.format_time(*since));
+        }
         prefix = "\n".into();
     }
 
     Paragraph::new(text)
         .block(block)
-        .green()
-        .on_black()
+        .style(app.theme.border)
         .wrap(Wrap { trim: false })
 }
 
+/// The open rooms, numbered for Ctrl+number.
+fn tabs_widget(app: &App) -> Tabs {
+    let titles: Vec<String> = app
+        .tabs
+        .iter()
+        .enumerate()
+        .map(|(i, tab)| {
+            let mut title = format!("{} {}", i + 1, tab.name);
+            let unread = app
+                .rooms
+                .iter()
+                .find(|r| r.name == tab.name)
+                .map_or(0, |r| r.unread);
+            if unread > 0 {
+                title += &trf!("room.unread_badge", unread);
+            }
+            title
+        })
+        .collect();
+
+    Tabs::new(titles)
+        .select(app.active_tab().unwrap_or(usize::MAX))
+        .style(app.theme.border)
+        .highlight_style(app.theme.title.add_modifier(Modifier::REVERSED))
+}
+
+fn rooms_widget(app: &App) -> Paragraph {
+    let block = bordered(app).title(Span::styled(tr("pane.rooms"), app.theme.title));
+    let picking = app.mode == Mode::RoomList;
+    // favorites first, by the keys that move to them
+    let favorites = app.config.favorite_rooms.iter().take(9).enumerate();
+    let mut lines: Vec<Line> = favorites
+        .map(|(i, name)| {
+            let style = match *name == app.room_state.room_name {
+                true => app.theme.title,
+                false => app.theme.border,
+            };
+            Line::styled(trf!("room.favorite", i + 1, name), style)
+        })
+        .collect();
+    lines.extend(app.rooms.iter().enumerate().map(|(i, room)| {
+        let marker = match room.name == app.room_state.room_name {
+            true => "* ",
+            false => "  ",
+        };
+        let mut text = format!("{marker}{} ({})", room.name, room.occupants);
+        if app.has_draft_in(&room.name) {
+            text += &tr("room.draft_badge");
+        }
+        if room.unread > 0 {
+            text += &trf!("room.unread_badge", room.unread);
+        }
+        match picking && i == app.selected_room {
+            true => Line::styled(text, app.theme.caret),
+            false => Line::raw(text),
+        }
+    }));
+
+    Paragraph::new(lines).block(block).style(app.theme.border)
+}
+
 fn chat_log_widget(app: &App, area: Rect) -> Paragraph {
-    let block = Block::bordered().title(Span::styled("LOGS", Style::new().fg(Color::White)));
-    let text = app.render_logs(
-        (area.height as usize).checked_sub(2).unwrap_or(0),
-        &LogStyle::default(),
-    );
+    let mut block = bordered(app).title(Span::styled(app.logs_title(), app.theme.title));
+    if app.scroll_offset > 0 && app.new_below > 0 {
+        let below = Span::styled(trf!("pane.new_below", app.new_below), app.theme.caret);
+        block = block.title(
+            Title::from(below)
+                .position(Position::Bottom)
+                .alignment(Alignment::Right),
+        );
+    }
+    let inner = area.inner(&Margin::new(1, 1));
+    let text = app.render_logs(inner.height as usize, inner.width as usize, &app.theme.log);
+    let logs = Paragraph::new(text).wrap(Wrap { trim: false });
+    // the window can run past the top, keep the newest rows in view
+    let over = logs
+        .line_count(inner.width)
+        .saturating_sub(inner.height as usize);
+    logs.block(block)
+        .style(app.theme.border)
+        .scroll((over as u16, 0))
+}
+
+/// Rows the pins pane takes, with its border, none while it's hidden.
+fn pin_rows(app: &App) -> u16 {
+    match app.show_pins {
+        true => app.pins.len().min(MAX_PIN_ROWS) as u16 + 2,
+        false => 0,
+    }
+}
+
+/// Messages pinned in the room, newest pin at the bottom.
+fn pins_widget(app: &App, area: Rect) -> Paragraph {
+    let title = trf!("pane.pins", app.pins.len());
+    let block = bordered(app).title(Span::styled(title, app.theme.title));
+    let rows = (area.height as usize).saturating_sub(2);
+    let skip = app.pins.len().saturating_sub(rows);
+    let lines: Vec<Line> = app.pins[skip..]
+        .iter()
+        .map(|pin| pin.render(&app.theme.log))
+        .collect();
+    Paragraph::new(lines)
+        .block(block)
+        .style(app.theme.border)
+        .wrap(Wrap { trim: false })
+}
+
+/// Direct messages, kept apart from the room's chat.
+fn dm_widget(app: &App, area: Rect) -> Paragraph {
+    let title = trf!("pane.dms", app.dm_logs.len());
+    let block = bordered(app).title(Span::styled(title, app.theme.title));
+    let text = app.render_dm_logs((area.height as usize).saturating_sub(2), &app.theme.log);
     Paragraph::new(text)
         .block(block)
-        .green()
-        .on_black()
+        .style(app.theme.border)
+        .wrap(Wrap { trim: false })
+}
+
+/// Raw server messages, kept apart from the chat log.
+fn debug_widget(app: &App, area: Rect) -> Paragraph {
+    let mut title = trf!("pane.debug", app.debug_logs.len());
+    if app.debug_scroll > 0 {
+        title += &trf!("pane.logs_scrolled", app.debug_scroll);
+    }
+    let perf = Title::from(Span::styled(
+        app.perf.summary(app.logs.len(), app.logs_size()),
+        app.theme.title,
+    ))
+    .position(Position::Bottom)
+    .alignment(Alignment::Right);
+    let block = bordered(app)
+        .title(Span::styled(title, app.theme.title))
+        .title(perf);
+    let text = app.render_debug_logs((area.height as usize).saturating_sub(2), &app.theme.log);
+    Paragraph::new(text)
+        .block(block)
+        .style(app.theme.border)
         .wrap(Wrap { trim: false })
 }
 
 fn textarea_widget(app: &App) -> Paragraph {
+    let mut block = bordered(app)
+        .style(app.theme.border)
+        .title(Span::styled(app.input_area_name(), app.theme.title));
+    if let Some((counter, warn)) = app.message_counter() {
+        let style = match warn {
+            true => app.theme.mode_disconnected,
+            false => app.theme.title,
+        };
+        block = block.title(Title::from(Span::styled(counter, style)).alignment(Alignment::Right));
+    }
     Paragraph::new(app.render_buf_styled())
-        .block(Block::bordered().green().on_black().title(Span::styled(
-            app.input_area_name(),
-            Style::new().fg(Color::White),
-        )))
-        .white()
-        .on_black()
-}
-
-pub fn render(app: &App, frame: &mut Frame) {
-    let [top_area, bottom_area] = h_split(&frame.size(), 6);
-    let [top_left, top_right] = v_split(top_area);
-    let [top_top_right, btm_top_right] = h_split(&top_right, (top_right.height / 2) as usize);
-
-    frame.render_widget(top_help_widget(app), top_top_right);
-    frame.render_widget(room_info_widget(app), btm_top_right);
-    frame.render_widget(chat_log_widget(app, top_left.clone()), top_left);
-    frame.render_widget(textarea_widget(app), bottom_area);
+        .block(block)
+        .style(app.theme.input)
+        .scroll((app.input_scroll(), 0))
+}
+
+/// Puts the terminal's cursor on the caret while typing, for input methods
+/// to show what's being composed where it will go.
+fn place_cursor(app: &App, frame: &mut Frame, input: Rect) {
+    if !matches!(app.mode, Mode::Insert | Mode::InsertCommand | Mode::Search) {
+        return;
+    }
+    let (at, col) = app.caret_on_screen();
+    let row = at.saturating_sub(app.input_scroll() as usize) as u16;
+    let x = (input.x + 1 + col as u16).min(input.right().saturating_sub(2));
+    let y = (input.y + 1 + row).min(input.bottom().saturating_sub(2));
+    frame.set_cursor(x, y);
+}
+
+/// The newest chat message alone as plain text, for screen readers to
+/// pick up without reading the whole log again.
+fn live_region_widget(app: &App) -> Paragraph {
+    let newest = app
+        .logs
+        .iter()
+        .find(|log| log.kind == LogKind::Chat)
+        .map(|log| format!("{}: {}", log.from, log.get_msg_body()))
+        .unwrap_or_default();
+    Paragraph::new(newest).style(app.theme.input)
+}
+
+/// One line of session details under the input area.
+fn status_bar_widget(app: &App) -> Paragraph {
+    let state_style = match app.connection {
+        ConnectionState::Connected => app.theme.mode_navigate,
+        _ => app.theme.mode_disconnected,
+    };
+    let (row, col) = app.get_caret_2d();
+    let sep = Span::styled(" | ", app.theme.border);
+    let mut spans = vec![];
+    match app.bell_lit() {
+        Some(true) => spans.push(Span::styled(
+            tr("status.bell"),
+            app.theme.title.add_modifier(Modifier::REVERSED),
+        )),
+        Some(false) => spans.push(Span::styled(tr("status.bell"), app.theme.input)),
+        None => {}
+    }
+    spans.extend([
+        Span::styled(app.connection.to_string(), state_style),
+        sep.clone(),
+        Span::raw(app.server.clone()),
+        sep.clone(),
+        Span::raw(app.room_state.room_name.clone()),
+        sep.clone(),
+        Span::raw(app.username.clone()),
+        sep.clone(),
+        Span::raw(trf!("status.caret", row, col)),
+    ]);
+    if let Some(rtt) = app.latency.average() {
+        spans.push(sep.clone());
+        spans.push(Span::raw(trf!("status.rtt", rtt.as_millis())));
+    }
+    if app.throttled > 0 {
+        spans.push(sep.clone());
+        spans.push(Span::styled(
+            trf!("status.throttled", app.throttled),
+            app.theme.mode_disconnected,
+        ));
+    }
+    let failed = app.failed_outbox().count();
+    if failed > 0 {
+        spans.push(sep.clone());
+        spans.push(Span::styled(
+            trf!("status.failed", failed),
+            app.theme.mode_disconnected,
+        ));
+    }
+    if app.has_draft_in(&app.room_state.room_name) {
+        spans.push(sep);
+        spans.push(Span::styled(tr("status.draft"), app.theme.title));
+    }
+    Paragraph::new(Line::from(spans)).style(app.theme.input)
+}
+
+/// Finds where the thumbnails of the visible images go by looking for their
+/// placeholders in what was rendered, the wrapping of the lines above them
+/// isn't known until then.
+fn find_thumbnails(app: &App, frame: &mut Frame, area: Rect) -> Vec<(Rect, PathBuf)> {
+    let inner = area.inner(&Margin::new(1, 1));
+    let images: HashMap<String, &PathBuf> = app
+        .logs
+        .iter()
+        .filter_map(|l| l.image.as_ref())
+        .filter(|path| app.shows_thumbnail(path))
+        .map(|path| (image_placeholder(path).trim().to_string(), path))
+        .collect();
+    let buf = frame.buffer_mut();
+    let mut placements = vec![];
+    for y in inner.top()..inner.bottom() {
+        let row: String = (inner.left()..inner.right())
+            .map(|x| buf.get(x, y).symbol())
+            .collect();
+        let Some(path) = images.get(row.trim()) else {
+            continue;
+        };
+        // only whole thumbnails, cut off ones would spill over the border
+        if y + THUMBNAIL_ROWS < inner.bottom() {
+            let width = THUMBNAIL_COLS.min(inner.width.saturating_sub(2));
+            let rect = Rect::new(inner.x + 2, y + 1, width, THUMBNAIL_ROWS);
+            placements.push((rect, (*path).clone()));
+        }
+    }
+
+    placements
+}
+
+/// Width of the transfer progress bar at the end of the status bar.
+const TRANSFER_BAR_WIDTH: u16 = 40;
+
+/// Progress of the running upload or download.
+fn transfer_widget(app: &App) -> Option<Gauge> {
+    let (label, percent) = app.transfer_progress()?;
+    let gauge = Gauge::default()
+        .gauge_style(app.theme.title)
+        .label(format!("{label} {percent}%"))
+        .percent(percent.min(100) as u16);
+    Some(gauge)
+}
+
+/// A rect of the given size centred in `area`, clamped to fit.
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
+
+fn popup_widget(title: &str, body: &str, footer: String, app: &App) -> Paragraph<'static> {
+    Paragraph::new(body.to_string())
+        .block(
+            bordered(app)
+                .title(Span::styled(title.to_string(), app.theme.title))
+                .title(
+                    Title::from(Span::styled(footer, app.theme.title))
+                        .position(Position::Bottom)
+                        .alignment(Alignment::Right),
+                ),
+        )
+        .style(app.theme.border)
+}
+
+/// Renders a popup centred over the whole frame, sized to its body.
+fn render_popup(frame: &mut Frame, popup: Paragraph, body: &str) {
+    let width = body.lines().map(graphemes::width).max().unwrap_or(0);
+    let height = body.lines().count();
+    let area = centered(frame.size(), width as u16 + 2, height as u16 + 2);
+    frame.render_widget(Clear, area);
+    frame.render_widget(popup, area);
+}
+
+/// A modal's question with its buttons under it, the focused one reversed
+/// and in brackets.
+fn render_modal(frame: &mut Frame, modal: &Modal, app: &App) {
+    let mut buttons = vec![];
+    for &choice in modal.choices() {
+        let label = tr(choice.label());
+        buttons.push(match choice == modal.focus {
+            true => Span::styled(
+                format!("[{label}]"),
+                app.theme.title.add_modifier(Modifier::REVERSED),
+            ),
+            false => Span::styled(format!(" {label} "), app.theme.border),
+        });
+        buttons.push(Span::raw("  "));
+    }
+    buttons.pop();
+    let buttons = Line::from(buttons).alignment(Alignment::Center);
+    let mut lines: Vec<Line> = modal.body.lines().map(Line::raw).collect();
+    lines.push(Line::raw(""));
+    lines.push(buttons);
+    let width = lines.iter().map(Line::width).max().unwrap_or(0);
+    let area = centered(frame.size(), width as u16 + 2, lines.len() as u16 + 2);
+    let widget = Paragraph::new(lines)
+        .block(
+            bordered(app)
+                .title(Span::styled(modal.title.clone(), app.theme.title))
+                .title(
+                    Title::from(Span::styled(tr("popup.confirm"), app.theme.title))
+                        .position(Position::Bottom)
+                        .alignment(Alignment::Right),
+                ),
+        )
+        .style(app.theme.border);
+    frame.render_widget(Clear, area);
+    frame.render_widget(widget, area);
+}
+
+/// Connection stats in the top right corner of the chat log, over the
+/// messages there.
+fn render_traffic(frame: &mut Frame, logs: Rect, app: &App) {
+    let lines = app.traffic.lines();
+    let width = lines.iter().map(|l| graphemes::width(l)).max().unwrap_or(0) as u16 + 2;
+    let inner = logs.inner(&Margin::new(1, 1));
+    let width = width.min(inner.width);
+    let area = Rect {
+        x: inner.right() - width,
+        y: inner.y,
+        width,
+        height: (lines.len() as u16 + 2).min(inner.height),
+    };
+    let block = bordered(app).title(Span::styled(tr("pane.traffic"), app.theme.title));
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(lines.join("\n"))
+            .block(block)
+            .style(app.theme.border),
+        area,
+    );
+}
+
+/// Widest a toast gets before its text wraps.
+const TOAST_WIDTH: u16 = 48;
+
+/// The toasts stacked in the bottom right corner of the chat log, oldest
+/// on top, until there's no more room.
+fn render_toasts(frame: &mut Frame, logs: Rect, app: &App) {
+    let inner = logs.inner(&Margin::new(1, 1));
+    let mut bottom = inner.bottom();
+    for toast in app.toasts.shown().collect::<Vec<_>>().into_iter().rev() {
+        let label = tr(toast.severity.label());
+        let text_width = graphemes::width(&toast.text).max(graphemes::width(&label)) as u16;
+        let width = (text_width + 2).min(TOAST_WIDTH).min(inner.width);
+        let rows = text_width.div_ceil(width.saturating_sub(2).max(1)).max(1);
+        let height = rows + 2;
+        if width < 3 || bottom < inner.y + height {
+            break;
+        }
+        bottom -= height;
+        let area = Rect {
+            x: inner.right() - width,
+            y: bottom,
+            width,
+            height,
+        };
+        let style = match toast.severity {
+            Severity::Info => app.theme.title,
+            Severity::Success => app.theme.mode_insert,
+            Severity::Error => app.theme.mode_disconnected,
+        };
+        frame.render_widget(Clear, area);
+        frame.render_widget(
+            Paragraph::new(toast.text.clone())
+                .wrap(Wrap { trim: true })
+                .block(bordered(app).title(Span::styled(label, style.add_modifier(Modifier::BOLD))))
+                .style(style),
+            area,
+        );
+    }
+}
+
+/// The help overlay, over everything but a prompt.
+fn render_help(frame: &mut Frame, scroll: usize, app: &App) {
+    let block = bordered(app)
+        .title(Span::styled(tr("popup.help"), app.theme.title))
+        .title(
+            Title::from(Span::styled(tr("popup.help_footer"), app.theme.title))
+                .position(Position::Bottom)
+                .alignment(Alignment::Right),
+        );
+    let help = Paragraph::new(app.help_text())
+        .block(block)
+        .style(app.theme.border)
+        .scroll((scroll as u16, 0));
+    frame.render_widget(Clear, frame.size());
+    frame.render_widget(help, frame.size());
+}
+
+/// The room directory, scrolled to keep the selected room in view.
+fn render_directory(frame: &mut Frame, directory: &Directory, app: &App) {
+    let items: Vec<ListItem> = directory
+        .entries
+        .iter()
+        .map(|entry| {
+            let occupants = match entry.occupants {
+                Some(n) => n.to_string(),
+                None => "?".into(),
+            };
+            let marker = match entry.name == app.room_state.room_name {
+                true => "* ",
+                false => "  ",
+            };
+            ListItem::new(format!("{marker}{} ({occupants})", entry.name))
+        })
+        .collect();
+    let empty = items.is_empty();
+    let width = directory
+        .entries
+        .iter()
+        .map(|e| e.name.chars().count() + 8)
+        .chain([tr("popup.directory").chars().count() + 2])
+        .max()
+        .unwrap_or(0) as u16;
+    let height = (directory.entries.len().max(1) + 2) as u16;
+    let area = centered(
+        frame.size(),
+        width.min(frame.size().width),
+        height.min(frame.size().height.saturating_sub(4)),
+    );
+    let block = bordered(app)
+        .title(Span::styled(tr("popup.directory_title"), app.theme.title))
+        .title(
+            Title::from(Span::styled(tr("popup.directory"), app.theme.title))
+                .position(Position::Bottom)
+                .alignment(Alignment::Right),
+        );
+    frame.render_widget(Clear, area);
+    if empty {
+        let text = Paragraph::new(tr("popup.directory_empty")).block(block);
+        frame.render_widget(text.style(app.theme.border), area);
+        return;
+    }
+    let list = List::new(items)
+        .block(block)
+        .style(app.theme.border)
+        .highlight_style(app.theme.caret);
+    let mut state = ListState::default().with_selected(Some(directory.selected));
+    frame.render_stateful_widget(list, area, &mut state);
+}
+
+/// Completions for the buffer, shown just above the input area. While
+/// cycling with Tab the selected one is highlighted.
+fn completion_widget(app: &App, input_area: Rect) -> Option<(List, Rect)> {
+    let (candidates, selected) = match app.completion {
+        Some(ref completion) => (completion.candidates.clone(), Some(completion.selected)),
+        None => (app.completions(), None),
+    };
+    if candidates.is_empty() {
+        return None;
+    }
+    // show what a shortcode stands for
+    let labels: Vec<String> = candidates
+        .into_iter()
+        .map(|c| match emoji::lookup(c.trim_matches(':')) {
+            Some(emoji) if c.starts_with(':') => format!("{c} {emoji}"),
+            _ => c,
+        })
+        .collect();
+    let width = labels
+        .iter()
+        .map(|c| graphemes::width(c))
+        .max()
+        .unwrap_or(0)
+        + 2;
+    let height = (labels.len() + 2).min(10).min(input_area.y as usize);
+    let area = Rect {
+        x: input_area.x + 1,
+        y: input_area.y - height as u16,
+        width: (width as u16).min(input_area.width),
+        height: height as u16,
+    };
+    let items: Vec<ListItem> = labels
+        .into_iter()
+        .enumerate()
+        .map(|(i, label)| match Some(i) == selected {
+            true => ListItem::new(label).style(app.theme.caret),
+            false => ListItem::new(label),
+        })
+        .collect();
+    let list = List::new(items)
+        .block(bordered(app).style(app.theme.border))
+        .style(app.theme.input);
+    Some((list, area))
+}
+
+/// Shown instead of the panes when the terminal is too small for them.
+fn too_small_widget(app: &App, area: Rect) -> Paragraph {
+    let needed = MIN_INPUT_ROWS + 1 + MIN_TOP_ROWS;
+    Paragraph::new(trf!(
+        "ui.too_small",
+        area.width,
+        area.height,
+        MIN_WIDTH,
+        needed
+    ))
+    .style(app.theme.border)
+    .wrap(Wrap { trim: true })
+}
+
+/// Why the app has to stop, across the whole terminal.
+fn fatal_widget<'a>(app: &App, reason: &'a str) -> Paragraph<'a> {
+    let block = bordered(app)
+        .title(Span::styled(tr("err.fatal_title"), app.theme.title))
+        .title(
+            Title::from(Span::styled(tr("err.fatal_footer"), app.theme.title))
+                .position(Position::Bottom)
+                .alignment(Alignment::Right),
+        )
+        .padding(Padding::uniform(1));
+    Paragraph::new(reason)
+        .block(block)
+        .style(app.theme.mode_disconnected)
+        .wrap(Wrap { trim: true })
+}
+
+pub fn render(app: &mut App, frame: &mut Frame) {
+    draw(app, frame);
+    capabilities::fit_colors(frame.buffer_mut());
+    if app.config.accessibility.reduced_motion {
+        for cell in &mut frame.buffer_mut().content {
+            cell.modifier
+                .remove(Modifier::SLOW_BLINK | Modifier::RAPID_BLINK);
+        }
+    }
+}
+
+fn draw(app: &mut App, frame: &mut Frame) {
+    if let Some(ref reason) = app.fatal {
+        frame.render_widget(fatal_widget(app, reason), frame.size());
+        app.image_placements.clear();
+        app.link_placements.clear();
+        return;
+    }
+    // the input runs the full width, so it wraps inside that less its border
+    let lines = app.wrapped_rows(frame.size().width.saturating_sub(2) as usize);
+    let Some(panes) = Panes::new(
+        frame.size(),
+        &app.config.layout,
+        app.config.layout.input_rows(lines),
+        pin_rows(app),
+        Shown {
+            tabs: app.tabs.len() > 1,
+            input: !app.monitor,
+            sidebar: !app.monitor || app.config.monitor.sidebar,
+            dms: app.show_dms,
+            debug: app.show_debug,
+            live: app.config.accessibility.live_region,
+        },
+    ) else {
+        app.panes = Panes::default();
+        frame.render_widget(too_small_widget(app, frame.size()), frame.size());
+        return;
+    };
+    app.panes = panes;
+    frame.render_widget(chat_log_widget(app, panes.logs), panes.logs);
+    let (count, position) = app.log_position();
+    if count > panes.logs.height.saturating_sub(2) as usize {
+        let mut state = ScrollbarState::new(count).position(position);
+        frame.render_stateful_widget(
+            Scrollbar::new(ScrollbarOrientation::VerticalRight)
+                .symbols(app.theme.scrollbar.clone())
+                .begin_symbol(None)
+                .end_symbol(None)
+                .style(app.theme.border),
+            panes.logs.inner(&Margin::new(0, 1)),
+            &mut state,
+        );
+    }
+    // the help overlay covers the log entirely
+    let covered = app.help_scroll.is_some();
+    app.image_placements = match app.graphics {
+        Some(_) if !covered => find_thumbnails(app, frame, panes.logs),
+        _ => vec![],
+    };
+    app.link_placements = match app.hyperlinks {
+        true if !covered => {
+            links::find_hyperlinks(frame.buffer_mut(), panes.logs.inner(&Margin::new(1, 1)))
+        }
+        _ => vec![],
+    };
+    let app = &*app;
+
+    if !panes.tabs.is_empty() {
+        frame.render_widget(tabs_widget(app), panes.tabs);
+    }
+    if !panes.help.is_empty() {
+        frame.render_widget(top_help_widget(app), panes.help);
+        frame.render_widget(room_info_widget(app), panes.room);
+        frame.render_widget(rooms_widget(app), panes.rooms);
+    }
+    if !panes.pins.is_empty() {
+        frame.render_widget(pins_widget(app, panes.pins), panes.pins);
+    }
+    if app.show_dms {
+        frame.render_widget(dm_widget(app, panes.dms), panes.dms);
+    }
+    if app.show_debug {
+        frame.render_widget(debug_widget(app, panes.debug), panes.debug);
+    }
+    if !panes.input.is_empty() {
+        frame.render_widget(textarea_widget(app), panes.input);
+        if app.config.input_method.cursor {
+            place_cursor(app, frame, panes.input);
+        }
+    }
+    if !panes.live.is_empty() {
+        frame.render_widget(live_region_widget(app), panes.live);
+    }
+    match transfer_widget(app) {
+        Some(gauge) => {
+            let [status, transfer] =
+                Layout::horizontal([Constraint::Fill(1), Constraint::Max(TRANSFER_BAR_WIDTH)])
+                    .areas(panes.status);
+            frame.render_widget(status_bar_widget(app), status);
+            frame.render_widget(gauge, transfer);
+        }
+        None => frame.render_widget(status_bar_widget(app), panes.status),
+    }
+
+    if app.show_traffic {
+        render_traffic(frame, panes.logs, app);
+    }
+    render_toasts(frame, panes.logs, app);
+
+    if let Some((list, area)) = completion_widget(app, panes.input) {
+        frame.render_widget(Clear, area);
+        frame.render_widget(list, area);
+    }
+
+    if let Some(ref popup) = app.popup {
+        let widget = popup_widget(&popup.title, &popup.body, tr("popup.dismiss"), app);
+        render_popup(frame, widget, &popup.body);
+    }
+    if let Some(ref modal) = app.modal {
+        render_modal(frame, modal, app);
+    }
+    if let Some(ref directory) = app.directory {
+        render_directory(frame, directory, app);
+    }
+    if let Some(body) = app.recent_rooms_body() {
+        let widget = popup_widget(
+            &tr("popup.recent_rooms"),
+            &body,
+            tr("popup.recent_footer"),
+            app,
+        );
+        render_popup(frame, widget, &body);
+    }
+    if let Some(body) = app.link_picker_body() {
+        let widget = popup_widget(&tr("popup.links"), &body, tr("popup.links_footer"), app);
+        render_popup(frame, widget, &body);
+    }
+    if let Some(scroll) = app.help_scroll {
+        render_help(frame, scroll, app);
+    }
+    if let Some(ref prompt) = app.prompt {
+        let input = match prompt.masked {
+            true => "•".repeat(prompt.input.chars().count()),
+            false => prompt.input.clone(),
+        };
+        let mut body = format!("{}\n\n> {input}", prompt.body);
+        if let Some(ref error) = prompt.error {
+            body += &format!("\n{error}");
+        }
+        let widget = popup_widget(&prompt.title, &body, tr("popup.prompt"), app);
+        render_popup(frame, widget, &body);
+    }
 }
@@ -1,114 +1,717 @@
-use crate::app::{App, Mode};
+use crate::alerts::{Alert, AlertEvent, Bell};
+use crate::app::{App, ConnectionState, Mode};
 use crate::chat_log::Log;
+use crate::error::ClientError;
+use crate::handlers;
+use crate::i18n::tr;
+use crate::toasts::Severity;
 use crate::tui_framework::Event;
-use crate::Tui;
-use chrono::{DateTime, Utc};
 use crossterm::event::KeyEvent;
-use marain_api::prelude::{ChatMsg, ServerMsg, ServerMsgBody, Status, Timestamp};
+use marain_api::prelude::{ClientMsg, ClientMsgBody, Status, Timestamp};
 
-fn translate_ts(ts: Timestamp) -> DateTime<Utc> {
-    Into::<Option<DateTime<Utc>>>::into(ts).unwrap_or(Utc::now())
+/// Side effects asked for by [`reduce`], carried out by the executor in
+/// [`crate::event_bus`] which owns the socket and the terminal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Effect {
+    /// Redraw the terminal.
+    Draw,
+    /// Send a message to the server, queued while disconnected. The delivery
+    /// of chat messages is tracked in the log.
+    Send { msg: ClientMsg, chat: bool },
+    /// Play the sound and run the hooks configured for an alert.
+    Alert(Alert),
+    /// Ring the terminal bell.
+    Bell,
+    /// Show a desktop notification for a chat message.
+    Notify {
+        room: String,
+        sender: String,
+        message: String,
+    },
 }
 
-pub fn update(app: &mut App, tui: &mut Tui, event: Event) {
+/// Applies an event to the app and returns the effects it calls for. This
+/// never touches the socket or the terminal, messages from the server
+/// come already opened by the socket worker.
+pub fn reduce(app: &mut App, event: Event) -> Vec<Effect> {
+    let mut effects = vec![];
+    // ticks mostly change nothing on screen, the idle redraw covers them
+    if !matches!(event, Event::Render | Event::Tick) {
+        app.dirty = true;
+    }
     match event {
-        Event::Tick => {}
+        Event::Render if app.needs_redraw() => effects.push(Effect::Draw),
+        Event::Render => app.perf.skipped += 1,
+
+        Event::Tick => {
+            app.perf.tick();
+            app.check_idle();
+            app.progress_upload();
+            app.offer_download();
+            app.expire_logs();
+            app.expire_toasts();
+            app.expire_pending();
+            app.probe_latency();
+            app.check_welcome(false);
+            app.autosave_if_due();
+        }
+
+        // Terminal focus tracking
+        Event::FocusGained => app.set_focus(true),
+        Event::FocusLost => app.set_focus(false),
 
         // User input event handling
-        Event::Key(KeyEvent { code: key, .. }) => {
-            if let Some(cmd) = app.map_key(key) {
+        Event::Key(KeyEvent {
+            code: key,
+            modifiers,
+            ..
+        }) => {
+            if app.fatal.is_some() {
+                app.should_quit = true;
+                return effects;
+            }
+            app.mark_read();
+            app.register_input();
+            // any key dismisses a popup without doing anything else
+            if app.answer_prompt(key)
+                || app.answer_help(key)
+                || app.answer_modal(key)
+                || app.answer_directory(key)
+                || app.answer_link_picker(key)
+                || app.answer_recent_rooms(key)
+                || app.answer_own_selection(key)
+                || app.answer_failed_selection(key)
+                || app.answer_log_selection(key)
+                || app.close_popup()
+            {
+                return effects;
+            }
+            if let Some(cmd) = app.map_key(key, modifiers) {
                 app.handle(cmd);
             }
         }
 
+        // a modal takes all input until it's answered
+        Event::Mouse(mouse) if app.modal.is_none() => app.handle_mouse(mouse),
+
+        Event::Paste(text) if app.fatal.is_none() && app.modal.is_none() => {
+            app.mark_read();
+            app.register_input();
+            app.paste(&text);
+        }
+
         // Socket closed by server
         Event::ServerClose => {
-            app.push_log(Log::new(
-                "SERVER".into(),
-                "Connection closed by server".into(),
-            ));
-            app.switch_mode(Mode::Disconnected);
+            app.push_log(Log::new("SERVER".into(), tr("sys.connection_closed")));
+            app.toasts
+                .push(Severity::Error, tr("sys.connection_closed"));
+            app.disconnect(ConnectionState::Closed);
+            let alert = Alert::new(AlertEvent::Disconnect, "SERVER", "Connection closed");
+            raise_alert(app, alert, &mut effects);
+        }
+
+        // Socket worker gave up on a silent connection
+        Event::ConnectionLost => {
+            app.notify(Severity::Error, tr("sys.connection_lost"));
+            app.disconnect(ConnectionState::Lost);
+            let alert = Alert::new(AlertEvent::Disconnect, "CLIENT", "Connection lost");
+            raise_alert(app, alert, &mut effects);
+        }
+
+        // Socket, crypto or terminal trouble
+        Event::Error(e) => {
+            tracing::error!("{e:?}");
+            app.push_log(Log::new("CLIENT".into(), e.to_string()));
+            if e.is_fatal() {
+                app.disconnect(ConnectionState::Lost);
+            }
+            // there's no carrying on with a server we can't understand
+            if let ClientError::ProtocolMismatch(_) = e {
+                app.fatal = Some(e.to_string());
+            }
+        }
+
+        // Large code block uploaded to the paste service
+        Event::PasteResult(Ok(link)) => {
+            app.send_chat(link);
+        }
+        Event::PasteResult(Err(e)) => app.push_log(Log::new("CLIENT".into(), e)),
+
+        // Output of /run, loaded into the composer for review
+        Event::RunResult(Ok(output)) => {
+            app.load_buffer(&format!("```\n{}\n```", output.trim_end()));
+            app.switch_mode(Mode::Insert);
         }
+        Event::RunResult(Err(e)) => app.push_log(Log::new("CLIENT".into(), e)),
+
+        // Outgoing chat and commands
+        Event::Send {
+            token,
+            timestamp,
+            contents,
+            ..
+        } => effects.push(Effect::Send {
+            msg: ClientMsg {
+                token: Some(token),
+                body: ClientMsgBody::SendToRoom { contents },
+                timestamp: Timestamp::from(timestamp),
+            },
+            chat: true,
+        }),
+        Event::ServerCommand {
+            token,
+            timestamp,
+            message_body,
+            ..
+        } => effects.push(Effect::Send {
+            msg: ClientMsg {
+                token: Some(token),
+                timestamp: Timestamp::from(timestamp),
+                body: message_body,
+            },
+            chat: false,
+        }),
 
         // Websocket event handling
-        Event::Recv(msg) => {
-            let decrypted_msg = tui.decrypt_incoming_msg(msg);
-            match bincode::deserialize::<ServerMsg>(&decrypted_msg[..]) {
-                Ok(deserialized) => {
-                    app.push_debug_log(deserialized.clone());
-
-                    // Handle any errors
-                    match deserialized.status {
-                        // Happy path!
-                        Status::Yes => handle_server_msg(app, deserialized),
-                        // sadger
-                        Status::No(error_msg) => {
-                            app.push_log(Log::new("SERVER".into(), error_msg.clone()));
-                            log::error!("The computer said no: {error_msg}");
-                        }
-                        // sadgest
-                        Status::JustNo => {
-                            app.push_log(Log::new("CLIENT".into(), "Failed to login".into()));
-                        }
-                    }
+        Event::ServerMsg(msg) => {
+            app.push_debug_log(msg.clone());
+
+            // Handle any errors
+            match msg.status {
+                // Happy path!
+                Status::Yes => handlers::registry().dispatch(app, msg, &mut effects),
+                // sadger
+                Status::No(error_msg) => {
+                    // whatever was refused, a room being created won't come
+                    app.creating_room = None;
+                    app.push_log(Log::new("SERVER".into(), error_msg.clone()));
+                    tracing::error!("The computer said no: {error_msg}");
                 }
-                Err(deserialization_err) => {
-                    app.push_log(Log::new(
-                        "CLIENT".into(),
-                        format!("Could not deserialize inbound message: {deserialization_err}"),
-                    ));
+                // sadgest
+                Status::JustNo => {
+                    app.push_log(Log::new("CLIENT".into(), tr("sys.login_failed")));
                 }
             }
         }
         _ => {}
     }
+    effects
 }
 
-fn handle_server_msg(app: &mut App, deserialized: ServerMsg) {
-    let dt = translate_ts(deserialized.timestamp.clone());
-    // These are all success responses from the server
-    match deserialized.body {
-        ServerMsgBody::LoginSuccess { .. } => {
-            panic!("Received a second LoginSuccess message from the server.")
-        }
+/// Raises the alert and rings the bell configured for its event.
+pub fn raise_alert(app: &mut App, alert: Alert, effects: &mut Vec<Effect>) {
+    ring(app, alert.event, effects);
+    effects.push(Effect::Alert(alert));
+}
+
+/// Beeps, flashes the status bar, or both, as configured for `event`.
+pub fn ring(app: &mut App, event: AlertEvent, effects: &mut Vec<Effect>) {
+    let Some(&bell) = app.config.notifications.bells.get(&event) else {
+        return;
+    };
+    if matches!(bell, Bell::Audible | Bell::Both) {
+        effects.push(Effect::Bell);
+    }
+    if matches!(bell, Bell::Visual | Bell::Both) {
+        app.flash();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::Command;
+    use crate::session::Session;
+    use crate::spelling::{self, Dictionary};
+    use crate::user_config::UserConfig;
+    use chrono::Utc;
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use marain_api::prelude::{ChatMsg, ServerMsg, ServerMsgBody};
+    use tokio::sync::mpsc::{channel, Receiver};
+
+    fn app() -> App {
+        App::for_test(UserConfig::default())
+    }
+
+    /// An app that is logged in, with the channel its sends go out on.
+    fn connected_app() -> (App, Receiver<Event>) {
+        let mut app = app();
+        let (sender, receiver) = channel(16);
+        app.set_send_chan(sender);
+        app.store_token("token".into());
+        (app, receiver)
+    }
+
+    fn press(app: &mut App, keys: &str) -> Vec<Effect> {
+        keys.chars()
+            .flat_map(|c| reduce(app, Event::from(c)))
+            .collect()
+    }
+
+    fn recv(body: ServerMsgBody) -> Event {
+        recv_with_status(Status::Yes, body)
+    }
+
+    fn recv_with_status(status: Status, body: ServerMsgBody) -> Event {
+        let msg = ServerMsg {
+            status,
+            timestamp: Timestamp::from(Utc::now()),
+            body,
+        };
+        Event::ServerMsg(msg)
+    }
+
+    fn chat(sender: &str, content: &str) -> ServerMsgBody {
         ServerMsgBody::ChatRecv {
+            direct: false,
             chat_msg: ChatMsg {
-                sender, content, ..
+                sender: sender.into(),
+                timestamp: Timestamp::from(Utc::now()),
+                content: content.into(),
             },
-            ..
-        } => {
-            app.push_log(Log::new(sender, content).at(dt));
         }
-        ServerMsgBody::Empty => app.push_log(Log::new(
-            "SERVER".into(),
-            "The time is: ".to_string() + &dt.format("%Y-%m-%D %H:%M:%S").to_string(),
-        )),
-        ServerMsgBody::RoomData {
-            logs,
-            notifications,
-            occupants,
-            room_name,
-            ..
-        } => {
-            let chat_logs: Vec<Log> = logs
-                .iter()
-                .map(|cm| {
-                    Log::new(cm.sender.clone(), cm.content.clone())
-                        .at(translate_ts(cm.timestamp.clone()))
-                })
-                .collect();
-            let notifications: Vec<Log> = notifications
+    }
+
+    fn newest(app: &App) -> &Log {
+        app.logs.front().expect("no logs")
+    }
+
+    #[test]
+    fn render_asks_for_a_draw() {
+        assert_eq!(reduce(&mut app(), Event::Render), vec![Effect::Draw]);
+    }
+
+    #[test]
+    fn render_skips_the_draw_until_something_changes() {
+        let mut app = app();
+        app.drawn();
+        assert!(reduce(&mut app, Event::Render).is_empty());
+        assert!(reduce(&mut app, Event::Tick).is_empty());
+        assert!(reduce(&mut app, Event::Render).is_empty());
+        press(&mut app, "i");
+        assert_eq!(reduce(&mut app, Event::Render), vec![Effect::Draw]);
+    }
+
+    #[test]
+    fn i_and_esc_switch_between_navigate_and_insert() {
+        let mut app = app();
+        assert_eq!(app.mode, Mode::Navigate);
+        press(&mut app, "i");
+        assert_eq!(app.mode, Mode::Insert);
+        reduce(&mut app, KeyCode::Esc.into());
+        assert_eq!(app.mode, Mode::Navigate);
+    }
+
+    #[test]
+    fn r_opens_the_room_list() {
+        let mut app = app();
+        press(&mut app, "R");
+        assert_eq!(app.mode, Mode::RoomList);
+        reduce(&mut app, KeyCode::Esc.into());
+        assert_eq!(app.mode, Mode::Navigate);
+    }
+
+    #[test]
+    fn keys_only_edit_the_buffer_in_insert_mode() {
+        let mut app = app();
+        press(&mut app, "xyz");
+        assert_eq!(app.buffer, vec![""]);
+        press(&mut app, "ihello");
+        assert_eq!(app.buffer, vec!["hello"]);
+        reduce(&mut app, KeyCode::Backspace.into());
+        assert_eq!(app.buffer, vec!["hell"]);
+    }
+
+    #[test]
+    fn a_paste_is_one_edit_with_its_lines() {
+        let mut app = app();
+        press(&mut app, "iab");
+        reduce(&mut app, KeyCode::Left.into());
+        reduce(&mut app, Event::Paste("one\r\ntwo\n".into()));
+        assert_eq!(app.buffer, vec!["aone", "two", "b"]);
+        assert_eq!(app.get_caret_2d(), (3, 1));
+        app.handle(Command::Undo);
+        assert_eq!(app.buffer, vec!["ab"]);
+    }
+
+    #[test]
+    fn number_keys_move_to_favorite_rooms() {
+        let mut config = UserConfig::default();
+        config.favorite_rooms = vec!["lobby".into(), "rust".into()];
+        let mut app = App::for_test(config);
+        let (sender, mut sent) = channel(16);
+        app.set_send_chan(sender);
+        app.store_token("token".into());
+        press(&mut app, "2");
+        assert!(matches!(
+            sent.try_recv().expect("nothing sent"),
+            Event::ServerCommand {
+                message_body: ClientMsgBody::Move { target },
+                ..
+            } if target == "rust"
+        ));
+    }
+
+    #[test]
+    fn dash_goes_back_to_the_previous_room() {
+        let (mut app, mut sent) = connected_app();
+        for room in ["lobby", "rust", "lobby", "music"] {
+            app.update_room(vec![], vec![], vec![], Utc::now(), room.into());
+        }
+        assert_eq!(app.recent_rooms, ["lobby", "rust"]);
+        press(&mut app, "-");
+        let event = sent.try_recv().expect("nothing sent");
+        assert!(matches!(
+            event,
+            Event::ServerCommand {
+                message_body: ClientMsgBody::Move { target },
+                ..
+            } if target == "lobby"
+        ));
+    }
+
+    #[test]
+    fn capital_i_shows_the_server_info() {
+        let (mut app, _sent) = connected_app();
+        app.server = "chat.example:8080".into();
+        press(&mut app, "I");
+        let popup = app.popup.expect("no popup");
+        assert!(popup.body.contains("Host: chat.example:8080"));
+        assert!(popup.body.contains("Latency: not measured yet"));
+        assert!(!popup.body.contains("Logged in: unknown"));
+    }
+
+    #[test]
+    fn quitting_with_a_draft_asks_first() {
+        let (mut app, _sent) = connected_app();
+        press(&mut app, "ihalf a thought");
+        reduce(&mut app, KeyCode::Esc.into());
+        press(&mut app, "q");
+        assert!(!app.should_quit);
+        assert!(app.modal.is_some());
+        press(&mut app, "n");
+        assert!(app.modal.is_none());
+        assert!(!app.should_quit);
+
+        press(&mut app, "q");
+        reduce(&mut app, KeyCode::Left.into());
+        reduce(&mut app, KeyCode::Enter.into());
+        assert!(app.should_quit);
+    }
+
+    #[test]
+    fn encrypted_rooms_only_send_ciphertext() {
+        let (mut app, mut sent) = connected_app();
+        app.handle(Command::SetRoomKey);
+        press(&mut app, "hunter2");
+        reduce(&mut app, KeyCode::Enter.into());
+        assert!(app.room_keys.is_encrypted(&app.room_state.room_name));
+
+        press(&mut app, "ihi there");
+        reduce(&mut app, KeyCode::Enter.into());
+        let Ok(Event::Send { contents, .. }) = sent.try_recv() else {
+            panic!("nothing sent");
+        };
+        assert!(!contents.contains("hi there"));
+        let room = &app.room_state.room_name;
+        let opened = app
+            .room_keys
+            .open(room, &app.username, Utc::now(), contents);
+        assert_eq!(opened, "hi there");
+    }
+
+    #[test]
+    fn encrypted_rooms_never_use_the_paste_service() {
+        let (mut app, mut sent) = connected_app();
+        app.config.paste.url = Some("https://paste.invalid".into());
+        app.config.paste.threshold = 0;
+        app.handle(Command::SetRoomKey);
+        press(&mut app, "hunter2");
+        reduce(&mut app, KeyCode::Enter.into());
+
+        app.switch_mode(Mode::Insert);
+        app.load_buffer("```\nlet secret = 1;\n```");
+        reduce(&mut app, KeyCode::Enter.into());
+        let Ok(Event::Send { contents, .. }) = sent.try_recv() else {
+            panic!("nothing sent");
+        };
+        assert!(!contents.contains("secret"));
+    }
+
+    #[test]
+    fn enter_sends_the_buffer() {
+        let (mut app, mut sent) = connected_app();
+        press(&mut app, "ihi there");
+        reduce(&mut app, KeyCode::Enter.into());
+        assert_eq!(app.buffer, vec![""]);
+
+        let event = sent.try_recv().expect("nothing sent");
+        assert!(matches!(&event, Event::Send { contents, .. } if contents == "hi there"));
+        let effects = reduce(&mut app, event);
+        assert!(matches!(
+            &effects[..],
+            [Effect::Send {
+                msg: ClientMsg {
+                    body: ClientMsgBody::SendToRoom { contents },
+                    ..
+                },
+                chat: true,
+            }] if contents == "hi there"
+        ));
+    }
+
+    #[test]
+    fn server_commands_are_not_tracked_as_chat() {
+        let (mut app, _sent) = connected_app();
+        let effects = reduce(
+            &mut app,
+            Event::ServerCommand {
+                token: "token".into(),
+                username: "me".into(),
+                timestamp: Utc::now(),
+                message_body: ClientMsgBody::GetTime,
+            },
+        );
+        assert!(matches!(&effects[..], [Effect::Send { chat: false, .. }]));
+    }
+
+    #[test]
+    fn chat_from_others_is_logged() {
+        let mut app = app();
+        let effects = reduce(&mut app, recv(chat("alice", "good morning")));
+        assert!(effects.is_empty());
+        assert_eq!(newest(&app).from, "alice");
+        assert_eq!(newest(&app).msg, "good morning");
+    }
+
+    #[test]
+    fn configured_bells_ring_for_messages() {
+        let mut app = app();
+        let bells = &mut app.config.notifications.bells;
+        bells.insert(AlertEvent::Message, Bell::Both);
+        let effects = reduce(&mut app, recv(chat("alice", "ping")));
+        assert!(matches!(&effects[..], [Effect::Bell]));
+        assert_eq!(app.bell_lit(), Some(true));
+    }
+
+    #[test]
+    fn mentions_raise_an_alert() {
+        let mut app = app();
+        let effects = reduce(&mut app, recv(chat("alice", "hey me, look")));
+        assert!(matches!(
+            &effects[..],
+            [Effect::Alert(Alert {
+                event: AlertEvent::Mention,
+                ..
+            })]
+        ));
+        assert_eq!(newest(&app).from, "MENTION");
+    }
+
+    #[test]
+    fn chat_while_unfocused_notifies_the_desktop() {
+        let mut app = app();
+        reduce(&mut app, Event::FocusLost);
+        let effects = reduce(&mut app, recv(chat("alice", "anyone?")));
+        assert!(matches!(
+            &effects[..],
+            [Effect::Notify { sender, message, .. }] if sender == "alice" && message == "anyone?"
+        ));
+        assert_eq!(app.unread_count, 1);
+    }
+
+    #[test]
+    fn regaining_focus_scrolls_to_the_first_unread() {
+        let mut app = app();
+        reduce(&mut app, recv(chat("alice", "before")));
+        reduce(&mut app, Event::FocusLost);
+        for text in ["one", "two", "three"] {
+            reduce(&mut app, recv(chat("alice", text)));
+        }
+        reduce(&mut app, Event::FocusGained);
+        assert_eq!(app.scroll_offset, 2);
+    }
+
+    #[test]
+    fn misspellings_are_underlined_and_fixed_on_request() {
+        let mut app = app();
+        app.dictionary = Some(Dictionary::parse("the\ncat\ncoat\n"));
+        press(&mut app, "ithe caat teh");
+        let underlined = |app: &App| -> Vec<String> {
+            let text = app.render_buf_styled();
+            text.lines[0]
+                .spans
                 .iter()
-                .map(|n| {
-                    Log::new(n.sender.clone(), n.content.clone())
-                        .at(translate_ts(n.timestamp.clone()))
-                })
-                .collect();
-            app.update_room(chat_logs, notifications, occupants, dt, room_name);
+                .filter(|span| span.style == spelling::style())
+                .map(|span| span.content.to_string())
+                .collect()
+        };
+        // not the word still being typed
+        assert_eq!(underlined(&app), vec!["caat"]);
+        let alt = KeyEvent::new(KeyCode::Char('$'), KeyModifiers::ALT);
+        reduce(&mut app, Event::Key(alt));
+        assert_eq!(app.buffer, vec!["the caat the"]);
+        reduce(&mut app, KeyCode::Left.into());
+        reduce(&mut app, KeyCode::Left.into());
+        reduce(&mut app, KeyCode::Left.into());
+        reduce(&mut app, KeyCode::Left.into());
+        reduce(&mut app, Event::Key(alt));
+        assert_eq!(app.buffer, vec!["the cat the"]);
+        let Some(ref completion) = app.completion else {
+            panic!("no suggestions to cycle through");
+        };
+        assert_eq!(completion.candidates, vec!["cat", "coat"]);
+        assert!(underlined(&app).is_empty());
+    }
+
+    #[test]
+    fn dead_keys_compose_with_the_next_letter() {
+        let mut app = app();
+        press(&mut app, "i´e");
+        assert_eq!(app.buffer, vec!["´e"]);
+
+        app.config.input_method.dead_keys = true;
+        app.handle(Command::Reset);
+        app.switch_mode(Mode::Insert);
+        press(&mut app, "caf´e ^ ~1");
+        assert_eq!(app.buffer, vec!["café ^~1"]);
+        assert_eq!(app.get_caret_2d(), (1, 9));
+        // moving away leaves the accent as it is
+        press(&mut app, "´");
+        reduce(&mut app, KeyCode::Left.into());
+        press(&mut app, "a");
+        assert_eq!(app.buffer, vec!["café ^~1a´"]);
+    }
+
+    #[test]
+    fn enter_shows_the_details_of_the_picked_message() {
+        let mut app = app();
+        reduce(&mut app, recv(chat("alice", "older news")));
+        reduce(&mut app, recv(chat("bob", "héllo")));
+        reduce(&mut app, KeyCode::Enter.into());
+        assert_eq!(app.selected_log, Some(0));
+        press(&mut app, "k");
+        press(&mut app, "k");
+        assert_eq!(app.selected_log, Some(1));
+        reduce(&mut app, KeyCode::Enter.into());
+        assert_eq!(app.selected_log, None);
+        let popup = app.popup.as_ref().expect("no details shown");
+        assert!(popup.body.contains("alice"));
+        assert!(popup.body.ends_with("older news"));
+        assert!(popup.body.contains(" UTC"));
+        assert!(popup.body.contains("10 characters, 10 bytes"));
+    }
+
+    #[test]
+    fn the_window_title_counts_what_came_in_the_background() {
+        let mut app = app();
+        app.room_state.room_name = "rust".into();
+        assert_eq!(app.window_title(), "rust - marain");
+        reduce(&mut app, Event::FocusLost);
+        reduce(&mut app, recv(chat("alice", "anyone?")));
+        assert_eq!(app.window_title(), "(1) rust - marain");
+        let mention = format!("{} are you there?", app.username);
+        reduce(&mut app, recv(chat("alice", &mention)));
+        assert_eq!(app.window_title(), "(2, 1 @) rust - marain");
+        reduce(&mut app, Event::FocusGained);
+        assert_eq!(app.window_title(), "rust - marain");
+    }
+
+    #[test]
+    fn refusals_are_logged() {
+        let mut app = app();
+        let event = recv_with_status(Status::No("not allowed".into()), ServerMsgBody::Empty);
+        reduce(&mut app, event);
+        assert_eq!(newest(&app).from, "SERVER");
+        assert_eq!(newest(&app).msg, "not allowed");
+    }
+
+    #[test]
+    fn garbage_from_the_server_is_reported() {
+        let mut app = app();
+        let session = Session::new("token".into(), [7; 32]);
+        let garbage = session.seal(vec![0xff; 3]).unwrap();
+        let error = session.decode(garbage).unwrap_err();
+        assert!(matches!(error, ClientError::Deserialize(_)));
+        reduce(&mut app, Event::Error(error));
+        assert_eq!(newest(&app).from, "CLIENT");
+    }
+
+    #[test]
+    fn server_messages_go_to_the_debug_pane() {
+        let mut app = app();
+        reduce(&mut app, recv(ServerMsgBody::Empty));
+        assert_eq!(app.debug_logs.len(), 1);
+    }
+
+    #[test]
+    fn closing_the_socket_disconnects_and_alerts() {
+        let (mut app, _sent) = connected_app();
+        let effects = reduce(&mut app, Event::ServerClose);
+        assert_eq!(app.connection, ConnectionState::Closed);
+        assert_eq!(app.mode, Mode::Disconnected);
+        assert!(matches!(
+            &effects[..],
+            [Effect::Alert(Alert {
+                event: AlertEvent::Disconnect,
+                ..
+            })]
+        ));
+    }
+
+    #[test]
+    fn a_protocol_mismatch_stops_the_app() {
+        let (mut app, _sent) = connected_app();
+        reduce(&mut app, Event::Error(ClientError::ProtocolMismatch(99)));
+        assert!(app.fatal.is_some());
+        press(&mut app, "i");
+        assert!(app.should_quit);
+    }
+
+    #[test]
+    fn direct_messages_are_not_sent_to_the_room() {
+        let (mut app, mut sent) = connected_app();
+        app.handle(Command::SendDirect("bob".into(), "psst".into()));
+        assert!(sent.try_recv().is_err());
+        assert!(app.dm_logs.is_empty());
+        assert_eq!(app.logs.front().map(|l| l.from.as_str()), Some("CLIENT"));
+    }
+
+    #[test]
+    fn hidden_logs_count_towards_retention() {
+        let mut app = app();
+        app.config.retention.max_messages = 3;
+        for n in 0..4 {
+            app.push_log(Log::new("DEBUG".into(), n.to_string()).as_debug());
         }
+        app.push_log(Log::new("bob".into(), "newest".into()));
+        let kept: Vec<_> = app.logs.iter().map(|l| l.msg.as_str()).collect();
+        assert_eq!(kept, ["newest", "3", "2"]);
 
-        ServerMsgBody::Notification { body } => {
-            app.push_log(Log::new("SERVER".to_owned(), body).at(dt))
+        app.config.retention.max_bytes = 1;
+        app.push_log(Log::new("bob".into(), "alone".into()));
+        assert_eq!(app.logs.len(), 1);
+    }
+
+    #[test]
+    fn only_the_logs_in_view_are_rendered() {
+        let mut app = app();
+        app.config.retention.max_messages = 1000;
+        for n in 0..1000 {
+            app.push_log(Log::new("bob".into(), format!("message {n}")));
         }
+        let text = app.render_logs(10, 80, &app.theme.log);
+        assert!(text.lines.len() < 20);
+        let newest = text.lines.last().unwrap().to_string();
+        assert!(newest.ends_with("message 999"), "{newest}");
+
+        app.scroll_offset = 500;
+        let text = app.render_logs(10, 80, &app.theme.log);
+        assert!(text
+            .lines
+            .last()
+            .unwrap()
+            .to_string()
+            .ends_with("message 499"));
     }
 }
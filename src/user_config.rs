@@ -1,20 +1,695 @@
 use chrono::Utc;
-use homedir::get_my_home;
+use directories::{BaseDirs, ProjectDirs};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::env::current_dir;
 use std::fs::{read_to_string, File};
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tokio::fs::create_dir_all;
 
+use crate::alerts::{AlertEvent, Bell, DesktopConfig, Hook, MentionConfig, NotificationRule};
+use crate::capabilities::TerminalConfig;
+use crate::chat_log::{TimeZoneSetting, DEFAULT_TIME_FORMAT};
+use crate::default_keybinds::KeyPreset;
+use crate::endpoint::Endpoint;
+use crate::graphics::Protocol;
+use crate::highlights::HighlightRule;
+use crate::keymap_file::KeymapFile;
+use crate::profiles::Profile;
+use crate::rate_limit::RateLimitConfig;
+use crate::secrets::{self, SecretStorage};
+use crate::signing::Identity;
+use crate::theme::{AccessibilityConfig, ThemeConfig};
+use crate::transcripts::TranscriptConfig;
+use crate::transforms::TransformConfig;
+use crate::wizard;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct NotificationConfig {
+    /// Shell command used to play a sound, `{sound}` is replaced with the
+    /// sound file configured for the event, e.g. `"paplay {sound}"`.
+    pub sound_command: Option<String>,
+    /// Sound file to play per alert event. Events without an entry are silent.
+    #[serde(default)]
+    pub sounds: HashMap<AlertEvent, String>,
+    /// Words that raise a `keyword` alert when they appear in a message.
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    /// Extra words per room name that are highlighted and notified like a
+    /// mention of your username.
+    #[serde(default)]
+    pub room_keywords: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    pub mentions: MentionConfig,
+    #[serde(default)]
+    pub desktop: DesktopConfig,
+    #[serde(default)]
+    pub hooks: Vec<Hook>,
+    /// Bell to ring per alert event, e.g. `{"mention": "both", "message": "visual"}`.
+    #[serde(default)]
+    pub bells: HashMap<AlertEvent, Bell>,
+    /// Overrule the above per message, the first rule that matches decides.
+    #[serde(default)]
+    pub rules: Vec<NotificationRule>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct AwayConfig {
+    /// Seconds without keyboard input before going away, `None` disables auto-away.
+    pub after_secs: Option<u64>,
+    pub message: String,
+    /// Post the away/back message to the current room.
+    pub announce: bool,
+    /// Seconds the terminal can be in the background before going away,
+    /// `None` ignores focus.
+    pub unfocused_secs: Option<u64>,
+    /// Tell other clients when going away and coming back, so they can mark
+    /// you in the room pane. Sent as a chat message, the server has no
+    /// presence updates.
+    pub notify: bool,
+}
+
+impl Default for AwayConfig {
+    fn default() -> Self {
+        Self {
+            after_secs: None,
+            message: "away from keyboard".into(),
+            announce: false,
+            unfocused_secs: None,
+            notify: false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct PasteConfig {
+    /// Paste service to upload large code blocks to, disabled when `None`.
+    pub url: Option<String>,
+    /// Size in bytes above which a code block is pasted instead of sent.
+    pub threshold: usize,
+}
+
+impl Default for PasteConfig {
+    fn default() -> Self {
+        Self {
+            url: None,
+            threshold: 2048,
+        }
+    }
+}
+
+/// Port used when neither the command line nor the config names one.
+pub const DEFAULT_PORT: &str = "1337";
+
+/// Height of the input area when empty, one line inside its border.
+pub const MIN_INPUT_ROWS: u16 = 3;
+
+/// Server to connect to when none is given on the command line.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ServerConfig {
+    pub host: Option<String>,
+    pub port: Option<String>,
+}
+
+/// Settings for one server that go over the global ones, under
+/// `[hosts."chat.example.com"]`. What's left out stays as it is globally.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct HostConfig {
+    pub username: Option<String>,
+    pub theme: Option<ThemeConfig>,
+    pub key_preset: Option<KeyPreset>,
+    /// Go over the global bindings, a key bound in both does what it does
+    /// here.
+    pub keybindings: KeymapFile,
+    pub notifications: Option<NotificationConfig>,
+    pub default_room: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct TlsConfig {
+    /// Connect over `wss://`, same as passing `--tls`.
+    pub enabled: bool,
+    /// Trust the certificate authorities installed on the system.
+    pub system_roots: bool,
+    /// PEM file with extra certificates to trust. With `system_roots` off
+    /// this pins the server to exactly these certificates.
+    pub pinned_cert: Option<String>,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            system_roots: true,
+            pinned_cert: None,
+        }
+    }
+}
+
+/// Thumbnails of image files in the log pane.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ImageConfig {
+    /// Draw thumbnails when the terminal supports it, otherwise images
+    /// are only named.
+    pub enabled: bool,
+    /// `"kitty"`, `"iterm"` or `"sixel"`, guessed from the terminal if unset.
+    pub protocol: Option<Protocol>,
+}
+
+impl Default for ImageConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            protocol: None,
+        }
+    }
+}
+
+impl ImageConfig {
+    /// The protocol to draw thumbnails with, `None` when they're off.
+    pub fn protocol(&self) -> Option<Protocol> {
+        match self.enabled {
+            true => self.protocol.or_else(Protocol::detect),
+            false => None,
+        }
+    }
+}
+
+/// Proxy for the websocket connection.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ProxyConfig {
+    /// `socks5://host:port` or `http://host:port`, no proxy if unset.
+    pub url: Option<String>,
+    /// Credentials for the proxy, these can also go in the url. With the
+    /// keyring as secret storage a password found here is moved into it.
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct KeepaliveConfig {
+    /// Seconds between websocket pings, keeps NAT mappings alive.
+    pub ping_secs: u64,
+    /// Seconds without any traffic from the server before the connection
+    /// is considered dead.
+    pub timeout_secs: u64,
+}
+
+impl Default for KeepaliveConfig {
+    fn default() -> Self {
+        Self {
+            ping_secs: 20,
+            timeout_secs: 60,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct LoggingConfig {
+    /// Write a log file at all.
+    pub enabled: bool,
+    /// Defaults to `marain.log` in the state directory.
+    pub path: Option<String>,
+    /// One of `off`, `error`, `warn`, `info`, `debug` or `trace`, or
+    /// filter directives like `info,marain_client::socket_client=trace`.
+    /// `MARAIN_LOG` overrides it.
+    pub level: String,
+    /// Size in bytes at which the log file is rotated.
+    pub max_size: u64,
+    /// Number of rotated files to keep.
+    pub keep: usize,
+    /// Write JSON lines, with the spans each happened in, instead of text.
+    pub json: bool,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            path: None,
+            level: "info".into(),
+            max_size: 10 * 1024 * 1024,
+            keep: 3,
+            json: false,
+        }
+    }
+}
+
+impl LoggingConfig {
+    pub fn path(&self) -> PathBuf {
+        match &self.path {
+            Some(path) => PathBuf::from(path),
+            None => state_dir().join("marain.log"),
+        }
+    }
+}
+
+/// How much of the chat log is kept in memory. Whichever limit is reached
+/// first drops the oldest messages, hidden ones included.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct RetentionConfig {
+    /// Messages kept, and loaded from the history store on joining a room.
+    pub max_messages: usize,
+    /// Approximate bytes the messages may take up.
+    pub max_bytes: usize,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            max_messages: 100,
+            max_bytes: 4 * 1024 * 1024,
+        }
+    }
+}
+
+/// Typing through input methods and dead keys.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct InputMethodConfig {
+    /// Keep the terminal's cursor at the caret while typing, which is
+    /// where input methods show the text being composed and the
+    /// candidates for it.
+    pub cursor: bool,
+    /// Put accents typed with a dead key on the letter that follows, for
+    /// terminals that pass the accent on by itself. Typing one for real
+    /// then takes a space after it.
+    pub dead_keys: bool,
+}
+
+impl Default for InputMethodConfig {
+    fn default() -> Self {
+        Self {
+            cursor: true,
+            dead_keys: false,
+        }
+    }
+}
+
+/// Spell checking of the input. Dictionaries are hunspell `.dic` files, or
+/// plain word lists, named after the language, e.g. `en_US.dic`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct SpellingConfig {
+    /// Language to check in, spell checking is off if unset.
+    pub language: Option<String>,
+    /// Directories to look for the dictionary in, in order.
+    pub dictionaries: Vec<String>,
+}
+
+impl Default for SpellingConfig {
+    fn default() -> Self {
+        Self {
+            language: None,
+            dictionaries: vec![
+                "/usr/share/hunspell".into(),
+                "/usr/share/myspell".into(),
+                "/usr/share/myspell/dicts".into(),
+            ],
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct LayoutConfig {
+    /// Share of the width given to the chat log, the sidebar gets the rest.
+    pub log_percent: u16,
+    pub density: Density,
+    /// Tallest the input area grows to as the buffer gains lines, including
+    /// its border.
+    pub input_height: u16,
+    /// Share of the chat log's height taken by the debug pane when shown.
+    pub debug_percent: u16,
+    /// Share of the chat log's height taken by direct messages when shown.
+    pub dm_percent: u16,
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        Self {
+            log_percent: 67,
+            density: Density::default(),
+            input_height: 8,
+            debug_percent: 35,
+            dm_percent: 30,
+        }
+    }
+}
+
+/// How `--monitor` shows the room it watches.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct MonitorConfig {
+    /// Jump to each new message even while scrolled back.
+    pub auto_scroll: bool,
+    /// Keep the sidebar, otherwise the chat log takes the full width.
+    pub sidebar: bool,
+}
+
+impl Default for MonitorConfig {
+    fn default() -> Self {
+        Self {
+            auto_scroll: true,
+            sidebar: false,
+        }
+    }
+}
+
+/// How closely the chat log is packed.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Density {
+    /// One line per message with its time and sender.
+    #[default]
+    Compact,
+    /// Runs of messages from one sender under a single heading, with a
+    /// blank line between runs.
+    Comfortable,
+}
+
+impl Density {
+    pub fn toggled(self) -> Self {
+        match self {
+            Self::Compact => Self::Comfortable,
+            Self::Comfortable => Self::Compact,
+        }
+    }
+}
+
+impl LayoutConfig {
+    /// `log_percent` kept to a range that leaves both panes usable.
+    pub fn log_percent(&self) -> u16 {
+        self.log_percent.clamp(20, 90)
+    }
+
+    /// `input_height` with room for at least one line inside the border.
+    pub fn max_input_rows(&self) -> u16 {
+        self.input_height.max(MIN_INPUT_ROWS)
+    }
+
+    /// Height of the input area for a buffer of `lines` rows on screen.
+    pub fn input_rows(&self, lines: usize) -> u16 {
+        let rows = u16::try_from(lines).unwrap_or(u16::MAX).saturating_add(2);
+        rows.clamp(MIN_INPUT_ROWS, self.max_input_rows())
+    }
+
+    pub fn debug_percent(&self) -> u16 {
+        self.debug_percent.clamp(10, 80)
+    }
+
+    pub fn dm_percent(&self) -> u16 {
+        self.dm_percent.clamp(10, 80)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct UserConfig {
     username: Option<String>,
+    #[serde(default)]
+    pub notifications: NotificationConfig,
+    #[serde(default)]
+    pub away: AwayConfig,
+    #[serde(default)]
+    pub paste: PasteConfig,
+    /// Allows `/run` to execute local shell commands.
+    #[serde(default)]
+    pub allow_run: bool,
+    /// Sign outgoing messages with the persistent identity key.
+    #[serde(default)]
+    pub signing: bool,
+    /// Name of the locale file in `<config dir>/locales` to translate the UI with.
+    #[serde(default)]
+    pub locale: Option<String>,
+    /// Use the high contrast, reduced decoration theme, same as
+    /// `accessibility.high_contrast`.
+    #[serde(default)]
+    pub accessible: bool,
+    /// Rewrites applied to incoming messages, in order.
+    #[serde(default)]
+    pub transforms: Vec<TransformConfig>,
+    /// Status message last set with `/bio`.
+    #[serde(default)]
+    pub bio: Option<String>,
+    /// Room moved to once logged in, unless a resumed session was in
+    /// another.
+    #[serde(default)]
+    pub default_room: Option<String>,
+    /// Users whose messages are hidden, set with `/ignore`.
+    #[serde(default)]
+    pub ignored: Vec<String>,
+    /// Show a line where messages from ignored users were left out.
+    #[serde(default)]
+    pub collapse_ignored: bool,
+    /// Patterns styled wherever they appear in messages, in order.
+    #[serde(default)]
+    pub highlights: Vec<HighlightRule>,
+    /// File to mirror chat and system events to, one JSON object per line.
+    #[serde(default)]
+    pub event_log: Option<String>,
+    #[serde(default)]
+    pub transcripts: TranscriptConfig,
+    /// Seconds before a message sent with `/shh` disappears, at most a week.
+    #[serde(default = "default_ephemeral_ttl")]
+    pub ephemeral_ttl_secs: u64,
+    #[serde(default)]
+    pub tls: TlsConfig,
+    #[serde(default)]
+    pub keepalive: KeepaliveConfig,
+    #[serde(default)]
+    pub proxy: ProxyConfig,
+    /// The server wants a password or pre-shared secret, asked for at startup.
+    /// It is only sent over TLS, sealed once the session key is agreed.
+    #[serde(default)]
+    pub ask_password: bool,
+    /// Key bindings per mode, e.g. `{"Insert": [{"key": "Ctrl+s", "command": "SendBuffer"}]}`.
+    /// These replace the default binding of the same key, all other defaults stay.
+    #[serde(default)]
+    pub keybindings: KeymapFile,
+    /// Rooms moved to with the number keys in Navigate mode, the first on
+    /// 1 and up to nine of them. `keybindings` can take the keys back.
+    #[serde(default)]
+    pub favorite_rooms: Vec<String>,
+    /// The built-in bindings `keybindings` go on top of: `vim`, `emacs` or
+    /// `plain`.
+    #[serde(default)]
+    pub key_preset: KeyPreset,
+    #[serde(default)]
+    pub theme: ThemeConfig,
+    /// Keep chat history on disk under the data directory, so scrollback
+    /// survives restarts.
+    #[serde(default = "default_persist_history")]
+    pub persist_history: bool,
+    /// Encrypt the history store with a passphrase asked for at startup.
+    /// History from before is encrypted the first time it's unlocked,
+    /// anything written in the clear after that is dropped.
+    #[serde(default)]
+    pub encrypt_history: bool,
+    /// Save the session token and key on exit and try to pick the session
+    /// back up on the next start instead of logging in again.
+    #[serde(default)]
+    pub resume_session: bool,
+    /// Where the saved session and the proxy password are kept: `keyring`
+    /// for the system keychain, or `file` on headless systems without one.
+    #[serde(default)]
+    pub secret_storage: SecretStorage,
+    /// strftime format for message timestamps and the server clock.
+    #[serde(default = "default_time_format")]
+    pub time_format: String,
+    /// `"local"`, `"utc"` or `{"offset": <minutes east of UTC>}`.
+    #[serde(default)]
+    pub timezone: TimeZoneSetting,
+    #[serde(default)]
+    pub layout: LayoutConfig,
+    #[serde(default)]
+    pub monitor: MonitorConfig,
+    #[serde(default)]
+    pub accessibility: AccessibilityConfig,
+    #[serde(default)]
+    pub terminal: TerminalConfig,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    #[serde(default)]
+    pub retention: RetentionConfig,
+    #[serde(default)]
+    pub spelling: SpellingConfig,
+    #[serde(default)]
+    pub input_method: InputMethodConfig,
+    /// Expand `:shortcode:`s to emoji when sending, and complete them with Tab.
+    #[serde(default = "default_emoji_shortcodes")]
+    pub emoji_shortcodes: bool,
+    /// Show what the server sends right after login, and the room landed
+    /// in, over the chat until a key is pressed.
+    #[serde(default = "default_welcome_screen")]
+    pub welcome_screen: bool,
+    /// Where accepted files are saved, `<data dir>/downloads` if unset.
+    #[serde(default)]
+    pub download_dir: Option<String>,
+    #[serde(default)]
+    pub images: ImageConfig,
+    /// Show `*bold*`, `_italic_` and `` `code` `` in messages as such,
+    /// rather than the raw text.
+    #[serde(default = "default_format_messages")]
+    pub format_messages: bool,
+    /// Make links clickable with OSC 8 escapes, guessed from the terminal
+    /// if unset.
+    #[serde(default)]
+    pub hyperlinks: Option<bool>,
+    #[serde(default)]
+    pub server: ServerConfig,
+    /// Identities to choose between at startup, each going in place of
+    /// the username, server and `identity_key` here.
+    #[serde(default)]
+    pub profiles: Vec<Profile>,
+    /// File of the signing key, `<config dir>/identity.key` if unset.
+    #[serde(default)]
+    pub identity_key: Option<String>,
+    /// Overrides for particular servers by hostname, applied when that's
+    /// the server connected to at startup.
+    #[serde(default)]
+    pub hosts: HashMap<String, HostConfig>,
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
+    /// Largest message, in bytes as sent to the server, the client will
+    /// send in one go. Longer ones are split into up to 16 parts, the
+    /// counter over the input turns to a warning near that.
+    #[serde(default = "default_max_message_bytes")]
+    pub max_message_bytes: usize,
+    /// Offer the server compressed messages when announcing the protocol.
+    /// Either way large ones are only sent compressed once the server has
+    /// shown it can read them by sending some itself.
+    #[serde(default = "default_compression")]
+    pub compression: bool,
+    /// Put the protocol version and the features above on a line after the
+    /// username at login. Only for servers that read them there, others
+    /// would take them for part of the name.
+    #[serde(default)]
+    pub announce_protocol: bool,
+    /// Show notifications such as joins and leaves, and the client's own
+    /// messages, in the chat log. They can be toggled while running.
+    #[serde(default = "default_show_system_messages")]
+    pub show_system_messages: bool,
+    /// Show how long ago messages were sent, like `5m`, rather than the
+    /// time. Can be toggled while running.
+    #[serde(default)]
+    pub relative_times: bool,
+    /// Show the room, and how many messages came while the terminal was in
+    /// the background, in the terminal window or tab title.
+    #[serde(default = "default_window_title")]
+    pub window_title: bool,
+}
+
+fn default_ephemeral_ttl() -> u64 {
+    60
+}
+
+fn default_persist_history() -> bool {
+    true
+}
+
+fn default_emoji_shortcodes() -> bool {
+    true
+}
+
+fn default_welcome_screen() -> bool {
+    true
+}
+
+fn default_format_messages() -> bool {
+    true
+}
+
+fn default_show_system_messages() -> bool {
+    true
+}
+
+fn default_compression() -> bool {
+    true
+}
+
+fn default_window_title() -> bool {
+    true
+}
+
+fn default_max_message_bytes() -> usize {
+    4096
+}
+
+fn default_time_format() -> String {
+    DEFAULT_TIME_FORMAT.to_string()
 }
 
 impl Default for UserConfig {
     fn default() -> Self {
-        Self { username: None }
+        Self {
+            username: None,
+            notifications: NotificationConfig::default(),
+            away: AwayConfig::default(),
+            paste: PasteConfig::default(),
+            allow_run: false,
+            signing: false,
+            locale: None,
+            accessible: false,
+            transforms: vec![],
+            bio: None,
+            default_room: None,
+            ignored: vec![],
+            collapse_ignored: false,
+            highlights: vec![],
+            event_log: None,
+            transcripts: TranscriptConfig::default(),
+            ephemeral_ttl_secs: default_ephemeral_ttl(),
+            tls: TlsConfig::default(),
+            keepalive: KeepaliveConfig::default(),
+            proxy: ProxyConfig::default(),
+            ask_password: false,
+            keybindings: KeymapFile::default(),
+            favorite_rooms: vec![],
+            key_preset: KeyPreset::default(),
+            theme: ThemeConfig::default(),
+            persist_history: default_persist_history(),
+            encrypt_history: false,
+            resume_session: false,
+            secret_storage: SecretStorage::default(),
+            time_format: default_time_format(),
+            timezone: TimeZoneSetting::default(),
+            layout: LayoutConfig::default(),
+            monitor: MonitorConfig::default(),
+            accessibility: AccessibilityConfig::default(),
+            terminal: TerminalConfig::default(),
+            logging: LoggingConfig::default(),
+            retention: RetentionConfig::default(),
+            spelling: SpellingConfig::default(),
+            input_method: InputMethodConfig::default(),
+            emoji_shortcodes: default_emoji_shortcodes(),
+            welcome_screen: default_welcome_screen(),
+            download_dir: None,
+            images: ImageConfig::default(),
+            format_messages: default_format_messages(),
+            hyperlinks: None,
+            server: ServerConfig::default(),
+            profiles: vec![],
+            identity_key: None,
+            hosts: HashMap::new(),
+            rate_limit: RateLimitConfig::default(),
+            max_message_bytes: default_max_message_bytes(),
+            compression: default_compression(),
+            announce_protocol: false,
+            show_system_messages: default_show_system_messages(),
+            relative_times: false,
+            window_title: default_window_title(),
+        }
     }
 }
 
@@ -25,33 +700,259 @@ impl UserConfig {
             None => format!("User {}", Utc::now().timestamp_micros() % 1024,),
         }
     }
+
+    /// Fluent setter for the name to log in as, a random one if `None`.
+    pub fn with_username(mut self, username: Option<String>) -> Self {
+        self.username = username;
+
+        self
+    }
+
+    /// Fluent setter for the server to connect to.
+    pub fn with_server(mut self, server: ServerConfig) -> Self {
+        self.server = server;
+
+        self
+    }
+
+    /// The config with the overrides for the server being connected to, the
+    /// one on the command line or else the configured one, merged in.
+    pub fn with_host_overrides(self) -> Self {
+        let spec = crate::cli::args().host.clone().or(self.server.host.clone());
+        let host = spec.map(|spec| match Endpoint::parse(&spec, "1337", false) {
+            Ok(endpoint) => endpoint.host().to_string(),
+            Err(_) => spec,
+        });
+        match host {
+            Some(host) => self.for_host(&host),
+            None => self,
+        }
+    }
+
+    fn for_host(mut self, host: &str) -> Self {
+        let Some(overrides) = self
+            .hosts
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(host))
+            .map(|(_, overrides)| overrides.clone())
+        else {
+            return self;
+        };
+        self.username = overrides.username.or(self.username);
+        self.theme = overrides.theme.unwrap_or(self.theme);
+        self.key_preset = overrides.key_preset.unwrap_or(self.key_preset);
+        self.notifications = overrides.notifications.unwrap_or(self.notifications);
+        self.default_room = overrides.default_room.or(self.default_room);
+        for (mode, entries) in overrides.keybindings {
+            // earlier bindings of a key win
+            self.keybindings
+                .entry(mode)
+                .or_default()
+                .splice(0..0, entries);
+        }
+
+        self
+    }
+
+    /// Fluent setter for the identity to log in as. A profile without a
+    /// server connects to the configured one.
+    pub fn with_profile(mut self, profile: Profile) -> Self {
+        self.username = profile.username.or(self.username);
+        if profile.server.host.is_some() {
+            self.server = profile.server;
+        }
+        self.identity_key = profile.identity_key.or(self.identity_key);
+
+        self
+    }
+
+    pub fn identity_path(&self) -> PathBuf {
+        match self.identity_key {
+            Some(ref path) => PathBuf::from(path),
+            None => Identity::default_path(),
+        }
+    }
+
+    pub fn download_dir(&self) -> PathBuf {
+        match self.download_dir {
+            Some(ref dir) => PathBuf::from(dir),
+            None => data_dir().join("downloads"),
+        }
+    }
 }
 
 const CLIENT_CONF_PATH_ENV_VAR: &str = "MARAIN_CONFIG_PATH";
 
+/// The platform's directories for the client, `$XDG_CONFIG_HOME/marain`
+/// and friends on Linux.
+fn project_dirs() -> Option<ProjectDirs> {
+    ProjectDirs::from("", "", "marain")
+}
+
+fn fallback_dir() -> PathBuf {
+    current_dir().expect("Falling back to current directory for user config failed")
+}
+
 pub fn config_path() -> PathBuf {
     match std::env::var(CLIENT_CONF_PATH_ENV_VAR) {
         // if the env var is set
         Ok(p) => p.into(),
 
         // fallback
-        _ => match get_my_home() {
-            Ok(Some(p)) => p
-                .join(PathBuf::from(".config"))
-                .join(PathBuf::from("marain_config.json")),
-            _ => current_dir().expect("Falling back to current directory for user config failed"),
+        _ => match project_dirs() {
+            Some(dirs) => {
+                let json = dirs.config_dir().join("config.json");
+                // one from before TOML is read until it's migrated
+                match json.exists() {
+                    true => json,
+                    false => dirs.config_dir().join("config.toml"),
+                }
+            }
+            None => fallback_dir().join("marain_config.toml"),
         },
     }
 }
 
+/// Directory holding the config file, keys and other settings.
+pub fn config_dir() -> PathBuf {
+    let path = config_path();
+    match path.parent() {
+        Some(dir) if path.is_file() || !path.is_dir() => dir.to_path_buf(),
+        _ => path,
+    }
+}
+
+/// Directory for files kept between sessions that aren't settings,
+/// `$XDG_DATA_HOME/marain`.
+pub fn data_dir() -> PathBuf {
+    match project_dirs() {
+        Some(dirs) => dirs.data_dir().to_path_buf(),
+        None => config_dir(),
+    }
+}
+
+/// Directory for logs, `$XDG_STATE_HOME/marain`. Platforms without a state
+/// directory use the data one.
+pub fn state_dir() -> PathBuf {
+    match project_dirs() {
+        Some(dirs) => dirs.state_dir().unwrap_or(dirs.data_dir()).to_path_buf(),
+        None => config_dir(),
+    }
+}
+
+/// Moves what older versions kept straight in `~/.config` to where it goes
+/// now. Only done when the old config file is there and no new one is, so
+/// the other files are known to be ours. `plugins` and `locales` are too
+/// generic to claim and are only pointed out.
+fn migrate_legacy_paths() {
+    if std::env::var_os(CLIENT_CONF_PATH_ENV_VAR).is_some() {
+        return;
+    }
+    let (Some(base), Some(dirs)) = (BaseDirs::new(), project_dirs()) else {
+        return;
+    };
+    let old = base.home_dir().join(".config");
+    let config = dirs.config_dir();
+    let old_configs = [
+        ("marain_config.json", "config.json"),
+        ("marain_config.toml", "config.toml"),
+    ];
+    if !old_configs.iter().any(|(name, _)| old.join(name).is_file())
+        || config.join("config.json").exists()
+        || config.join("config.toml").exists()
+    {
+        return;
+    }
+
+    let moves = old_configs
+        .into_iter()
+        .chain([
+            ("marain_config.json.bak", "config.json.bak"),
+            ("identity.key", "identity.key"),
+            ("known_servers.json", "known_servers.json"),
+            ("known_signers.json", "known_signers.json"),
+            ("session.json", "session.json"),
+        ])
+        .map(|(from, to)| (old.join(from), config.join(to)))
+        .chain([
+            (old.join("history"), data_dir().join("history")),
+            (old.join("downloads"), data_dir().join("downloads")),
+            (old.join("marain.log"), state_dir().join("marain.log")),
+        ]);
+    for (from, to) in moves.filter(|(from, to)| from.exists() && !to.exists()) {
+        let moved = to
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::rename(&from, &to));
+        if let Err(e) = moved {
+            eprintln!("Could not move {} to {}: {e}", from.display(), to.display());
+        }
+    }
+    for name in ["plugins", "locales"] {
+        if old.join(name).is_dir() {
+            eprintln!(
+                "If {} is marain's, move it to {}",
+                old.join(name).display(),
+                config.join(name).display()
+            );
+        }
+    }
+}
+
+/// How a config file is written, going by its extension. Anything not
+/// ending in `.json` is TOML.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Json,
+    Toml,
+}
+
+impl Format {
+    fn of(path: &Path) -> Self {
+        match path.extension().is_some_and(|ext| ext == "json") {
+            true => Format::Json,
+            false => Format::Toml,
+        }
+    }
+
+    fn parse(self, contents: &str) -> Result<UserConfig, String> {
+        match self {
+            Format::Json => serde_json::from_str(contents).map_err(|e| e.to_string()),
+            Format::Toml => toml::from_str(contents).map_err(|e| e.to_string()),
+        }
+    }
+
+    fn render(self, config: &UserConfig) -> Result<String, String> {
+        match self {
+            Format::Json => serde_json::to_string_pretty(config).map_err(|e| e.to_string()),
+            Format::Toml => toml::to_string_pretty(config).map_err(|e| e.to_string()),
+        }
+    }
+}
+
 pub async fn load_config() -> UserConfig {
+    migrate_legacy_paths();
     let conf_path = config_path();
-    return if conf_path.exists() {
-        read_config(&conf_path)
-    } else {
-        write_default_config(&conf_path).await;
-        UserConfig::default()
+    if conf_path.exists() {
+        let config = read_config(&conf_path);
+        if Format::of(&conf_path) == Format::Json
+            && std::env::var_os(CLIENT_CONF_PATH_ENV_VAR).is_none()
+        {
+            migrate_to_toml(&conf_path, &config);
+        }
+        return config;
+    }
+    // first run, ask rather than leave everything to editing the file
+    let config = match wizard::interactive() {
+        true => wizard::run(&conf_path.display().to_string()).unwrap_or_else(|e| {
+            eprintln!("{e}");
+            UserConfig::default()
+        }),
+        false => UserConfig::default(),
     };
+    write_config(&conf_path, &config).await;
+
+    config
 }
 
 fn read_config(conf_path: &PathBuf) -> UserConfig {
@@ -59,13 +960,82 @@ fn read_config(conf_path: &PathBuf) -> UserConfig {
         "Failed to read config at path: {}",
         conf_path.display()
     ));
-    serde_json::from_str(&contents).expect(&format!(
+    let config = Format::of(conf_path).parse(&contents).expect(&format!(
         "Config file at {} schema was not valid",
         conf_path.display()
-    ))
+    ));
+    with_secrets(config)
+}
+
+/// Writes a JSON config found in the default place out again as TOML next
+/// to it, keeping the original as `config.json.bak`. If that fails
+/// the JSON one stays in use.
+fn migrate_to_toml(json_path: &Path, config: &UserConfig) {
+    let toml_path = json_path.with_extension("toml");
+    let migrated = write_file(&toml_path, config).and_then(|_| {
+        std::fs::rename(json_path, json_path.with_extension("json.bak")).map_err(|e| e.to_string())
+    });
+    match migrated {
+        Ok(()) => tracing::info!("Migrated the config to {}", toml_path.display()),
+        Err(e) => eprintln!("Could not migrate the config to TOML: {e}"),
+    }
 }
 
-async fn write_default_config(conf_path: &PathBuf) {
+/// Name of the proxy password among the secrets.
+const PROXY_SECRET: &str = "proxy_password";
+
+/// Fills in the proxy password from the keyring, or moves it there if it's
+/// still in the file.
+fn with_secrets(mut config: UserConfig) -> UserConfig {
+    if config.secret_storage != SecretStorage::Keyring {
+        return config;
+    }
+    match config.proxy.password {
+        Some(ref password) => {
+            let moved = secrets::store(SecretStorage::Keyring, PROXY_SECRET, password)
+                .map_err(|e| e.to_string())
+                .and_then(|_| save_config(&config));
+            if let Err(e) = moved {
+                tracing::error!("Could not move the proxy password to the keyring: {e}");
+            }
+        }
+        None => config.proxy.password = secrets::load(SecretStorage::Keyring, PROXY_SECRET),
+    }
+
+    config
+}
+
+/// Reads the config file again while running. Unlike at startup, a broken
+/// file is reported rather than fatal, the session carries on as it was.
+pub fn reload_config() -> Result<UserConfig, String> {
+    let conf_path = config_path();
+    let contents =
+        read_to_string(&conf_path).map_err(|e| format!("{}: {e}", conf_path.display()))?;
+    Format::of(&conf_path)
+        .parse(&contents)
+        .map(with_secrets)
+        .map_err(|e| format!("{}: {e}", conf_path.display()))
+}
+
+/// Writes the config file while running, for settings changed in the UI.
+pub fn save_config(config: &UserConfig) -> Result<(), String> {
+    write_file(&config_path(), config)
+}
+
+fn write_file(conf_path: &Path, config: &UserConfig) -> Result<(), String> {
+    let mut config = config.clone();
+    // only left out once it's safely in the keyring
+    if config.secret_storage == SecretStorage::Keyring
+        && config.proxy.password.is_some()
+        && secrets::load(SecretStorage::Keyring, PROXY_SECRET) == config.proxy.password
+    {
+        config.proxy.password = None;
+    }
+    let contents = Format::of(conf_path).render(&config)?;
+    std::fs::write(conf_path, contents).map_err(|e| format!("{}: {e}", conf_path.display()))
+}
+
+async fn write_config(conf_path: &PathBuf, config: &UserConfig) {
     create_dir_all(
         &conf_path
             .parent()
@@ -82,12 +1052,105 @@ async fn write_default_config(conf_path: &PathBuf) {
         conf_path.display()
     ));
     file.write_all(
-        serde_json::to_string_pretty(&UserConfig::default())
-            .expect("Could not serialize default config json")
+        Format::of(conf_path)
+            .render(config)
+            .expect("Could not serialize config")
             .as_bytes(),
     )
     .expect(&format!(
-        "Could not write default config to path: {}",
+        "Could not write config to path: {}",
         conf_path.display()
     ));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        app::{Command, Mode},
+        keymap_file::KeyBindEntry,
+    };
+
+    #[test]
+    fn toml_round_trips_the_config() {
+        let mut config = UserConfig::default();
+        config.keybindings.insert(
+            Mode::Insert,
+            vec![KeyBindEntry {
+                key: "Ctrl+s".into(),
+                command: Command::SendBuffer,
+            }],
+        );
+        config.proxy.url = Some("socks5://localhost:1080".into());
+        let toml = Format::Toml.render(&config).unwrap();
+        let back = Format::Toml.parse(&toml).unwrap();
+        assert_eq!(
+            serde_json::to_value(&back).unwrap(),
+            serde_json::to_value(&config).unwrap()
+        );
+        assert_eq!(Format::of(Path::new("marain_config.json")), Format::Json);
+        assert_eq!(Format::of(Path::new("marain_config.toml")), Format::Toml);
+    }
+
+    #[test]
+    fn a_partial_section_keeps_the_other_defaults() {
+        let config = Format::Toml
+            .parse(
+                r#"
+            [away]
+            after_secs = 300
+            [paste]
+            url = "https://paste.example.com"
+            [tls]
+            enabled = true
+            [notifications.mentions]
+            case_sensitive = true
+            [notifications.desktop]
+            enabled = true
+            [keepalive]
+            ping_secs = 5
+            "#,
+            )
+            .unwrap();
+        assert_eq!(config.away.after_secs, Some(300));
+        assert_eq!(config.away.message, AwayConfig::default().message);
+        assert_eq!(config.paste.threshold, PasteConfig::default().threshold);
+        assert!(config.tls.enabled && config.tls.system_roots);
+        let mentions = &config.notifications.mentions;
+        assert!(mentions.case_sensitive && mentions.whole_word);
+        let desktop = &config.notifications.desktop;
+        assert!(desktop.enabled && desktop.max_len == DesktopConfig::default().max_len);
+        assert_eq!(config.keepalive.ping_secs, 5);
+        assert_eq!(
+            config.keepalive.timeout_secs,
+            KeepaliveConfig::default().timeout_secs
+        );
+    }
+
+    #[test]
+    fn host_overrides_go_over_the_global_settings() {
+        let bind = |key: &str, command| KeyBindEntry {
+            key: key.into(),
+            command,
+        };
+        let mut config = UserConfig::default().with_username(Some("me".into()));
+        config
+            .keybindings
+            .insert(Mode::Insert, vec![bind("Ctrl+s", Command::SendBuffer)]);
+        let toml = r#"
+            [hosts."chat.example.com"]
+            username = "work me"
+            key_preset = "emacs"
+            keybindings = { Insert = [{ key = "Ctrl+s", command = "Quit" }] }
+        "#;
+        config.hosts = Format::Toml.parse(toml).unwrap().hosts;
+
+        let other = config.clone().for_host("localhost");
+        assert_eq!(other.get_username(), "me");
+        let work = config.for_host("Chat.Example.com");
+        assert_eq!(work.get_username(), "work me");
+        assert_eq!(work.key_preset, KeyPreset::Emacs);
+        assert_eq!(work.keybindings[&Mode::Insert][0].command, Command::Quit);
+        assert_eq!(work.keybindings[&Mode::Insert].len(), 2);
+    }
+}
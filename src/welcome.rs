@@ -0,0 +1,71 @@
+use std::time::{Duration, Instant};
+
+use crate::i18n::trf;
+
+/// How long after login what the server sends still counts as its welcome.
+const WELCOME_WINDOW: Duration = Duration::from_secs(3);
+
+/// What the server says right after login, gathered for the welcome screen
+/// shown over the chat until a key is pressed.
+#[derive(Debug)]
+pub struct Welcome {
+    started: Instant,
+    motd: Vec<String>,
+}
+
+impl Welcome {
+    pub fn start() -> Self {
+        Self {
+            started: Instant::now(),
+            motd: vec![],
+        }
+    }
+
+    /// Whether messages arriving now still belong to the welcome.
+    pub fn open(&self) -> bool {
+        self.started.elapsed() < WELCOME_WINDOW
+    }
+
+    pub fn add_motd(&mut self, line: String) {
+        self.motd.push(line);
+    }
+
+    /// Whether there's anything to show once the window has passed with no
+    /// room data.
+    pub fn has_motd(&self) -> bool {
+        !self.motd.is_empty()
+    }
+
+    /// The message of the day, then the room landed in and who is there.
+    pub fn body(&self, room: Option<(&str, &[String])>) -> String {
+        let mut lines = self.motd.clone();
+        if let Some((name, occupants)) = room {
+            if !lines.is_empty() {
+                lines.push(String::new());
+            }
+            lines.push(trf!("welcome.room", name, occupants.len()));
+            if !occupants.is_empty() {
+                lines.push(occupants.join(", "));
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_motd_goes_above_the_room() {
+        let mut welcome = Welcome::start();
+        assert!(welcome.open());
+        welcome.add_motd("Be nice".into());
+        let occupants = ["ann".to_string(), "bob".to_string()];
+        assert_eq!(
+            welcome.body(Some(("lobby", &occupants))),
+            "Be nice\n\nlobby: 2 here\nann, bob"
+        );
+        assert_eq!(welcome.body(None), "Be nice");
+    }
+}
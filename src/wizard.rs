@@ -0,0 +1,69 @@
+use std::io::{self, stdin, stdout, BufRead, IsTerminal, Write};
+
+use crate::{
+    cli,
+    i18n::{tr, trf},
+    theme::ThemePreset,
+    user_config::{ServerConfig, UserConfig, DEFAULT_PORT},
+};
+
+/// Whether there is someone at the terminal to answer the questions.
+pub fn interactive() -> bool {
+    stdin().is_terminal() && stdout().is_terminal() && cli::args().headless.is_none()
+}
+
+/// Asks for the settings a new user has to make on the plain terminal,
+/// before the UI starts. Empty answers keep the default shown in brackets.
+pub fn run(path: &str) -> io::Result<UserConfig> {
+    let mut out = stdout();
+    writeln!(out, "{}", trf!("wizard.welcome", path))?;
+
+    let username = ask(&tr("wizard.username"), "")?;
+    let host = ask(
+        &tr("wizard.host"),
+        cli::args().host.as_deref().unwrap_or("localhost"),
+    )?;
+    let port = ask(
+        &tr("wizard.port"),
+        cli::args().port.as_deref().unwrap_or(DEFAULT_PORT),
+    )?;
+    let preset = loop {
+        let answer = ask(&tr("wizard.theme"), "dark")?;
+        match serde_json::from_value::<ThemePreset>(answer.into()) {
+            Ok(preset) => break preset,
+            Err(_) => writeln!(out, "{}", tr("wizard.unknown_theme"))?,
+        }
+    };
+
+    let mut config = UserConfig::default()
+        .with_username((!username.is_empty()).then_some(username))
+        .with_server(ServerConfig {
+            host: Some(host),
+            port: Some(port),
+        });
+    config.theme.preset = preset;
+
+    Ok(config)
+}
+
+/// One answer, or `default` if there was none.
+pub fn ask(question: &str, default: &str) -> io::Result<String> {
+    let mut out = stdout();
+    match default.is_empty() {
+        true => write!(out, "{question}: ")?,
+        false => write!(out, "{question} [{default}]: ")?,
+    }
+    out.flush()?;
+    let mut answer = String::new();
+    if stdin().lock().read_line(&mut answer)? == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            tr("err.wizard_cancelled"),
+        ));
+    }
+
+    Ok(match answer.trim() {
+        "" => default.to_string(),
+        answer => answer.to_string(),
+    })
+}